@@ -0,0 +1,50 @@
+package com.voicecast.app
+
+import android.content.Context
+import android.media.AudioAttributes
+import android.media.AudioFocusRequest
+import android.media.AudioManager
+import android.os.Handler
+import android.os.Looper
+
+// Holds VoiceCast's audio focus while streaming and forwards focus changes
+// (navigation prompts, ringing calls, other media apps) to the Rust side
+object AudioFocusHandler {
+
+    private var audioManager: AudioManager? = null
+    private var focusRequest: AudioFocusRequest? = null
+
+    private val listener = AudioManager.OnAudioFocusChangeListener { focusChange ->
+        nativeOnAudioFocusChange(focusChange)
+    }
+
+    @JvmStatic
+    fun request(context: Context): Boolean {
+        val manager = context.getSystemService(Context.AUDIO_SERVICE) as AudioManager
+        audioManager = manager
+
+        val attributes = AudioAttributes.Builder()
+            .setUsage(AudioAttributes.USAGE_VOICE_COMMUNICATION)
+            .setContentType(AudioAttributes.CONTENT_TYPE_SPEECH)
+            .build()
+
+        val request = AudioFocusRequest.Builder(AudioManager.AUDIOFOCUS_GAIN)
+            .setAudioAttributes(attributes)
+            .setWillPauseWhenDucked(false)
+            .setOnAudioFocusChangeListener(listener, Handler(Looper.getMainLooper()))
+            .build()
+        focusRequest = request
+
+        return manager.requestAudioFocus(request) == AudioManager.AUDIOFOCUS_REQUEST_GRANTED
+    }
+
+    @JvmStatic
+    fun abandon() {
+        val request = focusRequest ?: return
+        audioManager?.abandonAudioFocusRequest(request)
+        focusRequest = null
+    }
+
+    @JvmStatic
+    private external fun nativeOnAudioFocusChange(focusChange: Int)
+}
@@ -0,0 +1,61 @@
+package com.voicecast.app
+
+import android.content.Context
+import android.media.AudioDeviceCallback
+import android.media.AudioDeviceInfo
+import android.media.AudioManager
+import android.os.Handler
+import android.os.Looper
+
+// Watches input devices coming and going (Bluetooth headsets, wired mics,
+// USB interfaces) and reports each change to the Rust side
+object AudioRouteMonitor {
+
+    private var registered = false
+
+    // Ids of the inputs already connected. Registering the callback fires
+    // onAudioDevicesAdded for every one of them, which is not a route change
+    // and must not restart capture.
+    private val knownInputs = mutableSetOf<Int>()
+
+    private val callback = object : AudioDeviceCallback() {
+        override fun onAudioDevicesAdded(addedDevices: Array<out AudioDeviceInfo>) {
+            addedDevices
+                .filter { it.isSource && knownInputs.add(it.id) }
+                .forEach { report("device_added", it) }
+        }
+
+        override fun onAudioDevicesRemoved(removedDevices: Array<out AudioDeviceInfo>) {
+            removedDevices
+                .filter { it.isSource && knownInputs.remove(it.id) }
+                .forEach { report("device_removed", it) }
+        }
+    }
+
+    @JvmStatic
+    fun register(context: Context) {
+        if (registered) return
+        val audioManager = context.getSystemService(Context.AUDIO_SERVICE) as AudioManager
+        audioManager.getDevices(AudioManager.GET_DEVICES_INPUTS).forEach { knownInputs.add(it.id) }
+        audioManager.registerAudioDeviceCallback(callback, Handler(Looper.getMainLooper()))
+        registered = true
+    }
+
+    private fun report(reason: String, device: AudioDeviceInfo) {
+        val bluetooth = device.type == AudioDeviceInfo.TYPE_BLUETOOTH_SCO ||
+            device.type == AudioDeviceInfo.TYPE_BLUETOOTH_A2DP ||
+            device.type == AudioDeviceInfo.TYPE_BLE_HEADSET
+        // An empty list means the device accepts any rate
+        val sampleRate = device.sampleRates.maxOrNull() ?: 0
+
+        nativeOnRouteChanged(reason, device.productName?.toString(), bluetooth, sampleRate)
+    }
+
+    @JvmStatic
+    private external fun nativeOnRouteChanged(
+        reason: String,
+        input: String?,
+        bluetooth: Boolean,
+        sampleRate: Int,
+    )
+}
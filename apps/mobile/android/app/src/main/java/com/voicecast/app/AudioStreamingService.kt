@@ -0,0 +1,212 @@
+package com.voicecast.app
+
+import android.app.Notification
+import android.app.NotificationChannel
+import android.app.NotificationManager
+import android.app.PendingIntent
+import android.app.Service
+import android.content.Intent
+import android.content.pm.ServiceInfo
+import android.os.Build
+import android.os.IBinder
+import android.os.PowerManager
+import android.support.v4.media.MediaMetadataCompat
+import android.support.v4.media.session.MediaSessionCompat
+import android.support.v4.media.session.PlaybackStateCompat
+import android.util.Log
+import androidx.core.app.NotificationCompat
+import androidx.core.app.NotificationManagerCompat
+import androidx.core.app.ServiceCompat
+import androidx.media.app.NotificationCompat.MediaStyle
+import androidx.media.session.MediaButtonReceiver
+
+// Foreground service that keeps the process and microphone alive while
+// streaming in the background. Capture itself runs in the Rust engine; this
+// holds the ongoing notification, the media session behind its transport
+// controls, and a partial wake lock.
+class AudioStreamingService : Service() {
+
+    private var wakeLock: PowerManager.WakeLock? = null
+    private var mediaSession: MediaSessionCompat? = null
+
+    override fun onCreate() {
+        super.onCreate()
+        instance = this
+        mediaSession = MediaSessionCompat(this, TAG).apply {
+            setCallback(object : MediaSessionCompat.Callback() {
+                override fun onPlay() = nativeOnMediaAction("play")
+                override fun onPause() = nativeOnMediaAction("pause")
+                override fun onStop() = nativeOnMediaAction("stop")
+            })
+            isActive = true
+        }
+    }
+
+    override fun onBind(intent: Intent?): IBinder? = null
+
+    override fun onStartCommand(intent: Intent?, flags: Int, startId: Int): Int {
+        // Notification buttons arrive as media button intents
+        mediaSession?.let { MediaButtonReceiver.handleIntent(it, intent) }
+
+        when (intent?.action) {
+            ACTION_START_STREAM -> startStreaming()
+            ACTION_STOP_STREAM -> stopStreaming()
+        }
+        return START_NOT_STICKY
+    }
+
+    override fun onDestroy() {
+        releaseWakeLock()
+        mediaSession?.release()
+        mediaSession = null
+        instance = null
+        super.onDestroy()
+    }
+
+    private fun startStreaming() {
+        createNotificationChannel()
+
+        // Android 14 requires the type to be passed explicitly and to match
+        // the manifest; it also throws if RECORD_AUDIO isn't granted or the
+        // app wasn't in the foreground when the service was started
+        val type = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.R) {
+            ServiceInfo.FOREGROUND_SERVICE_TYPE_MICROPHONE
+        } else {
+            0
+        }
+
+        try {
+            ServiceCompat.startForeground(this, NOTIFICATION_ID, buildNotification(), type)
+        } catch (e: Exception) {
+            Log.e(TAG, "Could not enter the foreground", e)
+            stopSelf()
+            return
+        }
+
+        acquireWakeLock()
+    }
+
+    private fun stopStreaming() {
+        releaseWakeLock()
+        ServiceCompat.stopForeground(this, ServiceCompat.STOP_FOREGROUND_REMOVE)
+        stopSelf()
+    }
+
+    private fun buildNotification(): Notification {
+        val openApp = PendingIntent.getActivity(
+            this,
+            0,
+            packageManager.getLaunchIntentForPackage(packageName),
+            PendingIntent.FLAG_IMMUTABLE or PendingIntent.FLAG_UPDATE_CURRENT,
+        )
+
+        val playPause = if (playing) {
+            NotificationCompat.Action(
+                android.R.drawable.ic_media_pause,
+                "Pause",
+                MediaButtonReceiver.buildMediaButtonPendingIntent(this, PlaybackStateCompat.ACTION_PAUSE),
+            )
+        } else {
+            NotificationCompat.Action(
+                android.R.drawable.ic_media_play,
+                "Resume",
+                MediaButtonReceiver.buildMediaButtonPendingIntent(this, PlaybackStateCompat.ACTION_PLAY),
+            )
+        }
+        val stop = NotificationCompat.Action(
+            android.R.drawable.ic_menu_close_clear_cancel,
+            "Stop",
+            MediaButtonReceiver.buildMediaButtonPendingIntent(this, PlaybackStateCompat.ACTION_STOP),
+        )
+
+        return NotificationCompat.Builder(this, CHANNEL_ID)
+            .setContentTitle(title)
+            .setContentText(if (playing) "Live" else "Paused")
+            .setSmallIcon(android.R.drawable.ic_btn_speak_now)
+            .setContentIntent(openApp)
+            .setOngoing(true)
+            .setCategory(NotificationCompat.CATEGORY_SERVICE)
+            .setForegroundServiceBehavior(NotificationCompat.FOREGROUND_SERVICE_IMMEDIATE)
+            .addAction(playPause)
+            .addAction(stop)
+            .setStyle(
+                MediaStyle()
+                    .setMediaSession(mediaSession?.sessionToken)
+                    .setShowActionsInCompactView(0, 1),
+            )
+            .build()
+    }
+
+    private fun applyNowPlaying(elapsedMs: Long) {
+        val session = mediaSession ?: return
+
+        session.setMetadata(
+            MediaMetadataCompat.Builder()
+                .putString(MediaMetadataCompat.METADATA_KEY_TITLE, title)
+                .putLong(MediaMetadataCompat.METADATA_KEY_DURATION, -1)
+                .build(),
+        )
+        val state = if (playing) PlaybackStateCompat.STATE_PLAYING else PlaybackStateCompat.STATE_PAUSED
+        session.setPlaybackState(
+            PlaybackStateCompat.Builder()
+                .setActions(
+                    PlaybackStateCompat.ACTION_PLAY or
+                        PlaybackStateCompat.ACTION_PAUSE or
+                        PlaybackStateCompat.ACTION_STOP,
+                )
+                .setState(state, elapsedMs.coerceAtLeast(0), if (playing) 1f else 0f)
+                .build(),
+        )
+
+        NotificationManagerCompat.from(this).notify(NOTIFICATION_ID, buildNotification())
+    }
+
+    private fun createNotificationChannel() {
+        if (Build.VERSION.SDK_INT < Build.VERSION_CODES.O) return
+
+        val channel = NotificationChannel(
+            CHANNEL_ID,
+            "Live streaming",
+            NotificationManager.IMPORTANCE_LOW,
+        )
+        getSystemService(NotificationManager::class.java).createNotificationChannel(channel)
+    }
+
+    private fun acquireWakeLock() {
+        if (wakeLock?.isHeld == true) return
+        val powerManager = getSystemService(PowerManager::class.java)
+        wakeLock = powerManager.newWakeLock(PowerManager.PARTIAL_WAKE_LOCK, "VoiceCast::Streaming")
+            .apply { acquire() }
+    }
+
+    private fun releaseWakeLock() {
+        wakeLock?.takeIf { it.isHeld }?.release()
+        wakeLock = null
+    }
+
+    companion object {
+        private const val TAG = "AudioStreamingService"
+        private const val CHANNEL_ID = "voicecast_streaming"
+        private const val NOTIFICATION_ID = 1001
+
+        const val ACTION_START_STREAM = "com.voicecast.ACTION_START_STREAM"
+        const val ACTION_STOP_STREAM = "com.voicecast.ACTION_STOP_STREAM"
+
+        private var instance: AudioStreamingService? = null
+        private var title = "VoiceCast is streaming"
+        private var playing = true
+
+        // Called from Rust; a negative elapsed time clears the entry
+        @JvmStatic
+        fun updateNowPlaying(newTitle: String, elapsedMs: Long, isPlaying: Boolean) {
+            if (elapsedMs >= 0) {
+                title = newTitle
+                playing = isPlaying
+            }
+            instance?.applyNowPlaying(elapsedMs)
+        }
+
+        @JvmStatic
+        private external fun nativeOnMediaAction(action: String)
+    }
+}
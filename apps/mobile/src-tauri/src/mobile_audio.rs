@@ -1,5 +1,13 @@
-use tauri::api::notification::Notification;
+use tauri::{AppHandle, Manager};
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::audio::AudioEngine;
+use crate::commands::StreamConfig;
+
+#[cfg(target_os = "android")]
+use std::sync::OnceLock;
 
 #[cfg(target_os = "android")]
 use jni::{JNIEnv, JavaVM, objects::{JClass, JString}};
@@ -7,10 +15,145 @@ use jni::{JNIEnv, JavaVM, objects::{JClass, JString}};
 #[cfg(target_os = "ios")]
 use objc::{msg_send, sel, sel_impl, class};
 
+#[cfg(target_os = "ios")]
+use block::ConcreteBlock;
+
+// AVAudioSessionInterruptionType / AVAudioSessionInterruptionOptions values
+#[cfg(target_os = "ios")]
+const INTERRUPTION_TYPE_BEGAN: u64 = 1;
+#[cfg(target_os = "ios")]
+const INTERRUPTION_OPTION_SHOULD_RESUME: u64 = 1;
+
+// AVAudioSessionRouteChangeReason values that mean the input hardware changed
+#[cfg(target_os = "ios")]
+const ROUTE_NEW_DEVICE_AVAILABLE: u64 = 1;
+#[cfg(target_os = "ios")]
+const ROUTE_OLD_DEVICE_UNAVAILABLE: u64 = 2;
+#[cfg(target_os = "ios")]
+const ROUTE_CONFIGURATION_CHANGE: u64 = 8;
+
+// Android delivers route and media-button callbacks on a Java thread with
+// no Tauri context; set by the first mobile_start_stream
+#[cfg(target_os = "android")]
+static JNI_APP: OnceLock<AppHandle> = OnceLock::new();
+
+// AudioManager.AUDIOFOCUS_* values passed to OnAudioFocusChangeListener
+#[cfg(target_os = "android")]
+const AUDIOFOCUS_GAIN: i32 = 1;
+#[cfg(target_os = "android")]
+const AUDIOFOCUS_LOSS: i32 = -1;
+#[cfg(target_os = "android")]
+const AUDIOFOCUS_LOSS_TRANSIENT: i32 = -2;
+#[cfg(target_os = "android")]
+const AUDIOFOCUS_LOSS_TRANSIENT_CAN_DUCK: i32 = -3;
+
+// MPRemoteCommandHandlerStatus.success
+#[cfg(target_os = "ios")]
+const REMOTE_COMMAND_SUCCESS: i64 = 0;
+
+// Payload of the `audio-interruption` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AudioInterruption {
+    pub phase: InterruptionPhase,
+    // Only meaningful when the interruption ends
+    pub should_resume: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InterruptionPhase {
+    Began,
+    Ended,
+}
+
+// Payload of the `audio-route-changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioRouteChange {
+    pub reason: String,
+    pub input: Option<String>,
+    pub bluetooth: bool,
+    // Hardware rate of the new route; Bluetooth headsets often drop to 16 kHz
+    pub sample_rate: Option<f64>,
+}
+
+// Payload of the `audio-focus-changed` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFocusState {
+    Gained,
+    Lost,
+    LostTransient,
+    LostTransientCanDuck,
+}
+
+// Lock-screen / notification transport controls, also the payload of the
+// `media-control` event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaAction {
+    Play,
+    Pause,
+    Stop,
+}
+
+// What the lock screen shows for the live stream
+#[derive(Debug, Clone)]
+struct NowPlaying {
+    title: String,
+    started_at: Instant,
+    playing: bool,
+}
+
+// The desktop stream config plus mobile-only options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MobileStreamConfig {
+    #[serde(flatten)]
+    pub stream: StreamConfig,
+    // Keep streaming with the screen locked / app in the background
+    #[serde(default)]
+    pub background: bool,
+    // Shown on the lock screen and in the notification
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+// What the OS audio session was last configured with
+#[derive(Debug, Clone, Default)]
+pub struct AudioSession {
+    pub active: bool,
+    pub sample_rate: Option<f64>,
+}
+
+// NSNotificationCenter observer token. It is only ever handed back to the
+// notification center, never dereferenced, so moving it between threads is fine.
+#[cfg(target_os = "ios")]
+#[derive(Clone, Copy)]
+struct Observer(*mut objc::runtime::Object);
+
+#[cfg(target_os = "ios")]
+unsafe impl Send for Observer {}
+#[cfg(target_os = "ios")]
+unsafe impl Sync for Observer {}
+
+// Managed as Tauri state and shared by every mobile command. All fields are
+// shared handles, so a clone (e.g. one moved into a notification handler)
+// sees and changes the same streaming/background flags as the original.
+#[derive(Clone)]
 pub struct MobileAudioService {
     is_streaming: Arc<Mutex<bool>>,
     is_background: Arc<Mutex<bool>>,
-    audio_session: Option<AudioSession>,
+    audio_session: Arc<Mutex<Option<AudioSession>>>,
+    now_playing: Arc<Mutex<Option<NowPlaying>>>,
+    // Set when a transient focus loss paused the stream, so regaining focus
+    // resumes it without overriding a pause the user asked for
+    paused_for_focus: Arc<Mutex<bool>>,
+    #[cfg(target_os = "ios")]
+    interruption_observer: Arc<Mutex<Option<Observer>>>,
+    #[cfg(target_os = "ios")]
+    route_observer: Arc<Mutex<Option<Observer>>>,
+    #[cfg(target_os = "ios")]
+    remote_commands_registered: Arc<Mutex<bool>>,
 }
 
 impl MobileAudioService {
@@ -18,12 +161,112 @@ impl MobileAudioService {
         Self {
             is_streaming: Arc::new(Mutex::new(false)),
             is_background: Arc::new(Mutex::new(false)),
-            audio_session: None,
+            audio_session: Arc::new(Mutex::new(None)),
+            now_playing: Arc::new(Mutex::new(None)),
+            paused_for_focus: Arc::new(Mutex::new(false)),
+            #[cfg(target_os = "ios")]
+            interruption_observer: Arc::new(Mutex::new(None)),
+            #[cfg(target_os = "ios")]
+            route_observer: Arc::new(Mutex::new(None)),
+            #[cfg(target_os = "ios")]
+            remote_commands_registered: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        *self.is_streaming.lock().unwrap()
+    }
+
+    pub fn audio_session(&self) -> Option<AudioSession> {
+        self.audio_session.lock().unwrap().clone()
+    }
+
+    // Starts (or, with `None`, clears) the lock-screen entry for the stream
+    pub fn set_now_playing(&self, title: Option<String>) -> Result<(), String> {
+        *self.now_playing.lock().unwrap() = title.map(|title| NowPlaying {
+            title,
+            started_at: Instant::now(),
+            playing: true,
+        });
+        self.publish_now_playing()
+    }
+
+    pub fn set_now_playing_state(&self, playing: bool) -> Result<(), String> {
+        if let Some(now_playing) = self.now_playing.lock().unwrap().as_mut() {
+            now_playing.playing = playing;
+        }
+        self.publish_now_playing()
+    }
+
+    // Pushes title, elapsed time and play state to the OS media controls
+    fn publish_now_playing(&self) -> Result<(), String> {
+        let now_playing = self.now_playing.lock().unwrap().clone();
+
+        #[cfg(target_os = "ios")]
+        unsafe {
+            set_ios_now_playing(now_playing.as_ref());
+        }
+
+        #[cfg(target_os = "android")]
+        {
+            let ctx = tauri::android::current_activity();
+            let env = ctx.env;
+            let (title, elapsed_ms, playing) = match &now_playing {
+                Some(np) => (np.title.as_str(), np.started_at.elapsed().as_millis() as i64, np.playing),
+                None => ("", -1, false),
+            };
+            let title = env.new_string(title).map_err(|e| e.to_string())?;
+            env.call_static_method(
+                "com/voicecast/app/AudioStreamingService",
+                "updateNowPlaying",
+                "(Ljava/lang/String;JZ)V",
+                &[title.into(), elapsed_ms.into(), playing.into()],
+            ).map_err(|e| e.to_string())?;
+        }
+
+        #[cfg(not(any(target_os = "ios", target_os = "android")))]
+        let _ = now_playing;
+
+        Ok(())
+    }
+
+    // Play/pause/stop on the lock screen, Control Center and headphones
+    #[cfg(target_os = "ios")]
+    pub fn register_ios_remote_commands(&self, app: AppHandle) -> Result<(), String> {
+        let mut registered = self.remote_commands_registered.lock().unwrap();
+        if *registered {
+            return Ok(());
         }
+
+        unsafe {
+            let center: *mut objc::runtime::Object =
+                msg_send![class!(MPRemoteCommandCenter), sharedCommandCenter];
+            let play: *mut objc::runtime::Object = msg_send![center, playCommand];
+            let pause: *mut objc::runtime::Object = msg_send![center, pauseCommand];
+            let stop: *mut objc::runtime::Object = msg_send![center, stopCommand];
+
+            for (command, action) in [
+                (play, MediaAction::Play),
+                (pause, MediaAction::Pause),
+                (stop, MediaAction::Stop),
+            ] {
+                let app = app.clone();
+                let handler = ConcreteBlock::new(move |_event: *mut objc::runtime::Object| -> i64 {
+                    handle_media_action(app.clone(), action);
+                    REMOTE_COMMAND_SUCCESS
+                })
+                .copy();
+                let _: *mut objc::runtime::Object = msg_send![command, addTargetWithHandler: &*handler];
+                let _: () = msg_send![command, setEnabled: true];
+            }
+        }
+
+        *registered = true;
+        Ok(())
     }
 
     #[cfg(target_os = "ios")]
-    pub fn configure_ios_audio_session(&mut self) -> Result<(), String> {
+    pub fn configure_ios_audio_session(&self) -> Result<(), String> {
         unsafe {
             let audio_session: *mut objc::runtime::Object = msg_send![
                 class!(AVAudioSession),
@@ -47,56 +290,190 @@ impl MobileAudioService {
             // Activate session
             let _: () = msg_send![audio_session, setActive: true error: 0];
 
+            let sample_rate: f64 = msg_send![audio_session, sampleRate];
+            *self.audio_session.lock().unwrap() = Some(AudioSession {
+                active: true,
+                sample_rate: Some(sample_rate),
+            });
+
             Ok(())
         }
     }
 
+    // Phone calls, Siri and alarms deactivate the session. Tell the UI when
+    // that starts and ends, and bring capture back if iOS says we may.
+    #[cfg(target_os = "ios")]
+    pub fn observe_ios_interruptions(&self, app: AppHandle) -> Result<(), String> {
+        let mut registered = self.interruption_observer.lock().unwrap();
+        if registered.is_some() {
+            return Ok(());
+        }
+
+        let is_streaming = self.is_streaming.clone();
+
+        unsafe {
+            let center: *mut objc::runtime::Object =
+                msg_send![class!(NSNotificationCenter), defaultCenter];
+            let name = ns_string("AVAudioSessionInterruptionNotification");
+
+            let handler = ConcreteBlock::new(move |notification: *mut objc::runtime::Object| {
+                let user_info: *mut objc::runtime::Object = msg_send![notification, userInfo];
+                let kind = user_info_integer(user_info, "AVAudioSessionInterruptionTypeKey");
+
+                if kind == INTERRUPTION_TYPE_BEGAN {
+                    let _ = app.emit_all("audio-interruption", AudioInterruption {
+                        phase: InterruptionPhase::Began,
+                        should_resume: false,
+                    });
+                    return;
+                }
+
+                let options = user_info_integer(user_info, "AVAudioSessionInterruptionOptionKey");
+                let should_resume = options & INTERRUPTION_OPTION_SHOULD_RESUME != 0;
+                let _ = app.emit_all("audio-interruption", AudioInterruption {
+                    phase: InterruptionPhase::Ended,
+                    should_resume,
+                });
+
+                if should_resume && *is_streaming.lock().unwrap() {
+                    let session: *mut objc::runtime::Object =
+                        msg_send![class!(AVAudioSession), sharedInstance];
+                    let _: () = msg_send![session, setActive: true error: 0];
+                    restart_capture(app.clone());
+                }
+            })
+            .copy();
+
+            let observer: *mut objc::runtime::Object = msg_send![
+                center,
+                addObserverForName: name
+                object: std::ptr::null_mut::<objc::runtime::Object>()
+                queue: std::ptr::null_mut::<objc::runtime::Object>()
+                usingBlock: &*handler
+            ];
+            *registered = Some(Observer(observer));
+        }
+
+        Ok(())
+    }
+
+    // AirPods connecting or a headset being unplugged swaps the input under
+    // us; rebuild capture on the new route and let the UI know
+    #[cfg(target_os = "ios")]
+    pub fn observe_ios_route_changes(&self, app: AppHandle) -> Result<(), String> {
+        let mut registered = self.route_observer.lock().unwrap();
+        if registered.is_some() {
+            return Ok(());
+        }
+
+        let is_streaming = self.is_streaming.clone();
+
+        unsafe {
+            let center: *mut objc::runtime::Object =
+                msg_send![class!(NSNotificationCenter), defaultCenter];
+            let name = ns_string("AVAudioSessionRouteChangeNotification");
+
+            let handler = ConcreteBlock::new(move |notification: *mut objc::runtime::Object| {
+                let user_info: *mut objc::runtime::Object = msg_send![notification, userInfo];
+                let reason = user_info_integer(user_info, "AVAudioSessionRouteChangeReasonKey");
+                let input_changed = matches!(
+                    reason,
+                    ROUTE_NEW_DEVICE_AVAILABLE | ROUTE_OLD_DEVICE_UNAVAILABLE | ROUTE_CONFIGURATION_CHANGE
+                );
+
+                let session: *mut objc::runtime::Object =
+                    msg_send![class!(AVAudioSession), sharedInstance];
+                let route: *mut objc::runtime::Object = msg_send![session, currentRoute];
+                let inputs: *mut objc::runtime::Object = msg_send![route, inputs];
+                let port: *mut objc::runtime::Object = msg_send![inputs, firstObject];
+                let sample_rate: f64 = msg_send![session, sampleRate];
+
+                let (input, bluetooth) = if port.is_null() {
+                    (None, false)
+                } else {
+                    let port_name: *mut objc::runtime::Object = msg_send![port, portName];
+                    let port_type: *mut objc::runtime::Object = msg_send![port, portType];
+                    let port_type = from_ns_string(port_type);
+                    (Some(from_ns_string(port_name)), port_type.starts_with("Bluetooth"))
+                };
+
+                let _ = app.emit_all("audio-route-changed", AudioRouteChange {
+                    reason: ios_route_reason(reason).to_string(),
+                    input,
+                    bluetooth,
+                    sample_rate: Some(sample_rate),
+                });
+
+                if input_changed && *is_streaming.lock().unwrap() {
+                    restart_capture(app.clone());
+                }
+            })
+            .copy();
+
+            let observer: *mut objc::runtime::Object = msg_send![
+                center,
+                addObserverForName: name
+                object: std::ptr::null_mut::<objc::runtime::Object>()
+                queue: std::ptr::null_mut::<objc::runtime::Object>()
+                usingBlock: &*handler
+            ];
+            *registered = Some(Observer(observer));
+        }
+
+        Ok(())
+    }
+
+    // Registers an AudioDeviceCallback (AudioRouteMonitor.kt) that reports
+    // input devices appearing or disappearing back through JNI
     #[cfg(target_os = "android")]
-    pub fn configure_android_audio(&mut self, env: JNIEnv) -> Result<(), String> {
-        // Get AudioManager
-        let audio_manager = env
-            .call_static_method(
-                "android/media/AudioManager",
-                "getSystemService",
-                "(Ljava/lang/String;)Ljava/lang/Object;",
-                &[env.new_string("audio").unwrap().into()],
-            )
-            .map_err(|e| e.to_string())?;
+    pub fn start_android_route_monitor(&self, env: JNIEnv) -> Result<(), String> {
+        // Registration is idempotent on the Kotlin side
+        let ctx = tauri::android::current_activity();
+        env.call_static_method(
+            "com/voicecast/app/AudioRouteMonitor",
+            "register",
+            "(Landroid/content/Context;)V",
+            &[ctx.activity.into()],
+        ).map_err(|e| e.to_string())?;
 
-        // Request audio focus
-        let audio_focus_request = env
-            .new_object(
-                "android/media/AudioFocusRequest$Builder",
-                "(I)V",
-                &[1.into()], // AUDIOFOCUS_GAIN
-            )
-            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
 
-        // Set audio attributes for voice communication
-        let audio_attributes = env
-            .new_object(
-                "android/media/AudioAttributes$Builder",
-                "()V",
-                &[],
+    #[cfg(target_os = "android")]
+    pub fn configure_android_audio(&self, env: JNIEnv) -> Result<(), String> {
+        // AudioFocusHandler.kt builds the focus request (AUDIOFOCUS_GAIN,
+        // USAGE_VOICE_COMMUNICATION) with a listener that reports back
+        // through nativeOnAudioFocusChange
+        let ctx = tauri::android::current_activity();
+        let granted = env
+            .call_static_method(
+                "com/voicecast/app/AudioFocusHandler",
+                "request",
+                "(Landroid/content/Context;)Z",
+                &[ctx.activity.into()],
             )
+            .and_then(|value| value.z())
             .map_err(|e| e.to_string())?;
 
-        env.call_method(
-            audio_attributes,
-            "setUsage",
-            "(I)Landroid/media/AudioAttributes$Builder;",
-            &[2.into()], // USAGE_VOICE_COMMUNICATION
-        ).map_err(|e| e.to_string())?;
+        if !granted {
+            return Err("Audio focus was not granted".to_string());
+        }
+
+        *self.audio_session.lock().unwrap() = Some(AudioSession {
+            active: true,
+            sample_rate: None,
+        });
 
         Ok(())
     }
 
-    pub async fn start_background_streaming(&mut self) -> Result<(), String> {
+    pub async fn start_background_streaming(&self) -> Result<(), String> {
         *self.is_background.lock().unwrap() = true;
 
-        // Show persistent notification
+        // Foreground service owning the persistent notification, so Android
+        // keeps the process (and the microphone) alive when backgrounded
         #[cfg(target_os = "android")]
-        self.show_streaming_notification()?;
+        send_streaming_service_action(ACTION_START_STREAM)?;
 
         // Configure audio for background
         #[cfg(target_os = "ios")]
@@ -105,37 +482,399 @@ impl MobileAudioService {
         Ok(())
     }
 
-    #[cfg(target_os = "android")]
-    fn show_streaming_notification(&self) -> Result<(), String> {
-        Notification::new("com.voicecast.app")
-            .title("VoiceCast is streaming")
-            .body("Tap to return to the app")
-            .icon("microphone")
-            .persistent(true)
-            .show()
-            .map_err(|e| e.to_string())
+    // iOS keeps a recording app running in the background as long as the
+    // session stays active and the `audio` background mode is declared
+    #[cfg(target_os = "ios")]
+    fn enable_background_audio(&self) -> Result<(), String> {
+        unsafe {
+            let session: *mut objc::runtime::Object =
+                msg_send![class!(AVAudioSession), sharedInstance];
+            let _: () = msg_send![session, setActive: true error: 0];
+        }
+        Ok(())
+    }
+
+    pub async fn stop_background_streaming(&self) -> Result<(), String> {
+        *self.is_background.lock().unwrap() = false;
+
+        #[cfg(target_os = "android")]
+        send_streaming_service_action(ACTION_STOP_STREAM)?;
+
+        Ok(())
     }
 }
 
+// Intent actions understood by AudioStreamingService (see AndroidManifest.xml)
+#[cfg(target_os = "android")]
+const ACTION_START_STREAM: &str = "com.voicecast.ACTION_START_STREAM";
+#[cfg(target_os = "android")]
+const ACTION_STOP_STREAM: &str = "com.voicecast.ACTION_STOP_STREAM";
+
+// Starts or stops the foreground service. Android 14 only lets a
+// microphone-type service start while the app is visible, so this must run
+// from a UI-initiated command, never from the background.
+#[cfg(target_os = "android")]
+fn send_streaming_service_action(action: &str) -> Result<(), String> {
+    let ctx = tauri::android::current_activity();
+    let env = ctx.env;
+
+    let service_class = env
+        .find_class("com/voicecast/app/AudioStreamingService")
+        .map_err(|e| e.to_string())?;
+    let intent = env
+        .new_object(
+            "android/content/Intent",
+            "(Landroid/content/Context;Ljava/lang/Class;)V",
+            &[ctx.activity.into(), service_class.into()],
+        )
+        .map_err(|e| e.to_string())?;
+
+    let action_name = env.new_string(action).map_err(|e| e.to_string())?;
+    env.call_method(
+        intent,
+        "setAction",
+        "(Ljava/lang/String;)Landroid/content/Intent;",
+        &[action_name.into()],
+    ).map_err(|e| e.to_string())?;
+
+    // The service calls startForeground() itself within the 5 second window
+    let method = if action == ACTION_START_STREAM {
+        "startForegroundService"
+    } else {
+        "startService"
+    };
+    env.call_method(
+        ctx.activity,
+        method,
+        "(Landroid/content/Intent;)Landroid/content/ComponentName;",
+        &[intent.into()],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// The cpal stream dies with the session or route; build a fresh one on the
+// current default input, re-negotiating its rate and channel count
+fn restart_capture(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let engine = app.state::<Arc<tokio::sync::Mutex<AudioEngine>>>();
+        let mut engine = engine.lock().await;
+        engine.refresh_default_devices();
+        if let Err(e) = engine.start_capture().await {
+            log::error!("Failed to restart capture: {}", e);
+        }
+    });
+}
+
+// Called from AudioRouteMonitor.onAudioDevicesAdded/Removed
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_voicecast_app_AudioRouteMonitor_nativeOnRouteChanged(
+    mut env: JNIEnv,
+    _class: JClass,
+    reason: JString,
+    input: JString,
+    bluetooth: jni::sys::jboolean,
+    sample_rate: jni::sys::jint,
+) {
+    let Some(app) = JNI_APP.get() else {
+        return;
+    };
+
+    let reason: String = env.get_string(&reason).map(Into::into).unwrap_or_default();
+    let input: Option<String> = if input.is_null() {
+        None
+    } else {
+        env.get_string(&input).ok().map(Into::into)
+    };
+
+    let _ = app.emit_all("audio-route-changed", AudioRouteChange {
+        reason,
+        input,
+        bluetooth: bluetooth != 0,
+        sample_rate: (sample_rate > 0).then_some(sample_rate as f64),
+    });
+
+    if app.state::<MobileAudioService>().is_streaming() {
+        restart_capture(app.clone());
+    }
+}
+
+// Maps a lock-screen/notification control onto the same engine calls as
+// pause_streaming / resume_streaming / stop_streaming, then tells the UI
+fn handle_media_action(app: AppHandle, action: MediaAction) {
+    tauri::async_runtime::spawn(async move {
+        let engine = app.state::<Arc<tokio::sync::Mutex<AudioEngine>>>();
+        let service = app.state::<MobileAudioService>();
+        // The user took over, so regaining audio focus must not undo it
+        *service.paused_for_focus.lock().unwrap() = false;
+
+        let result = match action {
+            MediaAction::Play | MediaAction::Pause => {
+                let playing = action == MediaAction::Play;
+                engine.lock().await.set_paused(!playing);
+                service.set_now_playing_state(playing)
+            }
+            MediaAction::Stop => {
+                if let Err(e) = engine.lock().await.stop_capture().await {
+                    log::error!("Failed to stop capture: {}", e);
+                }
+                *service.is_streaming.lock().unwrap() = false;
+                let cleared = service.set_now_playing(None);
+                service.stop_background_streaming().await.and(cleared)
+            }
+        };
+
+        if let Err(e) = result {
+            log::warn!("Media control {:?} failed: {}", action, e);
+        }
+        let _ = app.emit_all("media-control", action);
+    });
+}
+
+// Called from AudioFocusHandler's OnAudioFocusChangeListener. Another app
+// speaking over us (navigation, a call ringing) pauses the stream and
+// regaining focus resumes it. VoiceCast plays nothing locally, so there is
+// nothing to duck on CAN_DUCK; the UI is told and capture carries on.
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_voicecast_app_AudioFocusHandler_nativeOnAudioFocusChange(
+    _env: JNIEnv,
+    _class: JClass,
+    focus_change: jni::sys::jint,
+) {
+    let Some(app) = JNI_APP.get() else {
+        return;
+    };
+
+    let state = match focus_change {
+        AUDIOFOCUS_GAIN => AudioFocusState::Gained,
+        AUDIOFOCUS_LOSS => AudioFocusState::Lost,
+        AUDIOFOCUS_LOSS_TRANSIENT => AudioFocusState::LostTransient,
+        AUDIOFOCUS_LOSS_TRANSIENT_CAN_DUCK => AudioFocusState::LostTransientCanDuck,
+        _ => return,
+    };
+    let _ = app.emit_all("audio-focus-changed", state);
+
+    if app.state::<MobileAudioService>().is_streaming() {
+        handle_focus_change(app.clone(), state);
+    }
+}
+
+// Pauses on a focus loss and resumes on regaining it, but only a pause the
+// focus loss caused: a stream the user had already paused stays paused
+#[cfg(target_os = "android")]
+fn handle_focus_change(app: AppHandle, state: AudioFocusState) {
+    tauri::async_runtime::spawn(async move {
+        let engine = app.state::<Arc<tokio::sync::Mutex<AudioEngine>>>();
+        let service = app.state::<MobileAudioService>();
+        let mut engine = engine.lock().await;
+
+        let action = match state {
+            AudioFocusState::Lost | AudioFocusState::LostTransient => {
+                if engine.is_paused() {
+                    return;
+                }
+                // A permanent loss stays paused until the user resumes
+                *service.paused_for_focus.lock().unwrap() = state == AudioFocusState::LostTransient;
+                MediaAction::Pause
+            }
+            AudioFocusState::Gained => {
+                if !std::mem::take(&mut *service.paused_for_focus.lock().unwrap()) {
+                    return;
+                }
+                MediaAction::Play
+            }
+            AudioFocusState::LostTransientCanDuck => return,
+        };
+
+        let playing = action == MediaAction::Play;
+        engine.set_paused(!playing);
+        drop(engine);
+        if let Err(e) = service.set_now_playing_state(playing) {
+            log::warn!("Media control {:?} failed: {}", action, e);
+        }
+        let _ = app.emit_all("media-control", action);
+    });
+}
+
+// Called from the MediaSession callbacks in AudioStreamingService
+#[cfg(target_os = "android")]
+#[no_mangle]
+pub extern "system" fn Java_com_voicecast_app_AudioStreamingService_nativeOnMediaAction(
+    mut env: JNIEnv,
+    _class: JClass,
+    action: JString,
+) {
+    let Some(app) = JNI_APP.get() else {
+        return;
+    };
+
+    let action: String = env.get_string(&action).map(Into::into).unwrap_or_default();
+    let action = match action.as_str() {
+        "play" => MediaAction::Play,
+        "pause" => MediaAction::Pause,
+        "stop" => MediaAction::Stop,
+        other => {
+            log::warn!("Unknown media action: {}", other);
+            return;
+        }
+    };
+    handle_media_action(app.clone(), action);
+}
+
+#[cfg(target_os = "ios")]
+unsafe fn set_ios_now_playing(now_playing: Option<&NowPlaying>) {
+    let center: *mut objc::runtime::Object =
+        msg_send![class!(MPNowPlayingInfoCenter), defaultCenter];
+
+    let Some(now_playing) = now_playing else {
+        let _: () = msg_send![center, setNowPlayingInfo: std::ptr::null_mut::<objc::runtime::Object>()];
+        return;
+    };
+
+    let info: *mut objc::runtime::Object = msg_send![class!(NSMutableDictionary), dictionary];
+    let rate = if now_playing.playing { 1.0f64 } else { 0.0 };
+    let elapsed = now_playing.started_at.elapsed().as_secs_f64();
+    let title = ns_string(&now_playing.title);
+    let elapsed: *mut objc::runtime::Object = msg_send![class!(NSNumber), numberWithDouble: elapsed];
+    let rate: *mut objc::runtime::Object = msg_send![class!(NSNumber), numberWithDouble: rate];
+    let live: *mut objc::runtime::Object = msg_send![class!(NSNumber), numberWithBool: true];
+
+    let _: () = msg_send![info, setObject: title forKey: ns_string("title")];
+    let _: () = msg_send![info, setObject: elapsed forKey: ns_string("MPNowPlayingInfoPropertyElapsedPlaybackTime")];
+    let _: () = msg_send![info, setObject: rate forKey: ns_string("MPNowPlayingInfoPropertyPlaybackRate")];
+    let _: () = msg_send![info, setObject: live forKey: ns_string("MPNowPlayingInfoPropertyIsLiveStream")];
+    let _: () = msg_send![center, setNowPlayingInfo: info];
+}
+
+#[cfg(target_os = "ios")]
+fn ios_route_reason(reason: u64) -> &'static str {
+    match reason {
+        1 => "new_device_available",
+        2 => "old_device_unavailable",
+        3 => "category_change",
+        4 => "override",
+        6 => "wake_from_sleep",
+        7 => "no_suitable_route",
+        8 => "route_configuration_change",
+        _ => "unknown",
+    }
+}
+
+#[cfg(target_os = "ios")]
+unsafe fn ns_string(value: &str) -> *mut objc::runtime::Object {
+    let value = std::ffi::CString::new(value).unwrap_or_default();
+    msg_send![class!(NSString), stringWithUTF8String: value.as_ptr()]
+}
+
+#[cfg(target_os = "ios")]
+unsafe fn from_ns_string(value: *mut objc::runtime::Object) -> String {
+    if value.is_null() {
+        return String::new();
+    }
+    let bytes: *const std::os::raw::c_char = msg_send![value, UTF8String];
+    std::ffi::CStr::from_ptr(bytes).to_string_lossy().into_owned()
+}
+
+#[cfg(target_os = "ios")]
+unsafe fn user_info_integer(user_info: *mut objc::runtime::Object, key: &str) -> u64 {
+    let value: *mut objc::runtime::Object = msg_send![user_info, objectForKey: ns_string(key)];
+    if value.is_null() {
+        return 0;
+    }
+    msg_send![value, unsignedIntegerValue]
+}
+
 // Tauri commands for mobile
 #[tauri::command]
 pub async fn mobile_start_stream(
-    config: StreamConfig,
+    app: AppHandle,
+    config: MobileStreamConfig,
     state: tauri::State<'_, MobileAudioService>,
+    audio_engine: tauri::State<'_, Arc<tokio::sync::Mutex<AudioEngine>>>,
 ) -> Result<(), String> {
-    let mut service = state.inner().clone();
+    // Operate on the managed instance so every command sees the same flags
+    let service = state.inner();
 
     #[cfg(target_os = "ios")]
-    service.configure_ios_audio_session()?;
+    {
+        service.configure_ios_audio_session()?;
+        service.observe_ios_interruptions(app.clone())?;
+        service.observe_ios_route_changes(app.clone())?;
+        service.register_ios_remote_commands(app.clone())?;
+    }
 
     #[cfg(target_os = "android")]
     {
         // Get JNI environment
         let ctx = tauri::android::current_activity();
+        // Before anything that can call back from Java
+        let _ = JNI_APP.set(app.clone());
         service.configure_android_audio(ctx.env)?;
+        service.start_android_route_monitor(ctx.env)?;
+    }
+
+    // The session must be active before cpal can open the microphone. Same
+    // call as the desktop start_streaming, so the codec, bitrate and frame
+    // size picked in the UI are applied before capture starts.
+    let stream = &config.stream;
+    audio_engine
+        .lock()
+        .await
+        .start_stream(stream.source.clone(), stream.codec, stream.bitrate, stream.frame_size_ms)
+        .await
+        .map_err(|e| e.to_string())?;
+    *service.is_streaming.lock().unwrap() = true;
+
+    if config.background {
+        service.start_background_streaming().await?;
     }
 
-    service.start_background_streaming().await?;
+    let title = config.title.clone().unwrap_or_else(|| "VoiceCast Live".to_string());
+    service.set_now_playing(Some(title))?;
+
+    log::info!(
+        "Mobile stream started ({}, {} bps)",
+        config.stream.quality,
+        config.stream.bitrate
+    );
 
     Ok(())
 }
+
+#[tauri::command]
+pub async fn mobile_stop_stream(
+    state: tauri::State<'_, MobileAudioService>,
+    audio_engine: tauri::State<'_, Arc<tokio::sync::Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let service = state.inner();
+    *service.is_streaming.lock().unwrap() = false;
+    *service.paused_for_focus.lock().unwrap() = false;
+
+    // Tear everything down even when a step fails, so a capture error can't
+    // leave the foreground service running or audio focus held; the first
+    // error is reported once the rest is done
+    let captured = audio_engine
+        .lock()
+        .await
+        .stop_capture()
+        .await
+        .map_err(|e| e.to_string());
+    let now_playing = service.set_now_playing(None);
+
+    #[cfg(target_os = "android")]
+    let focus = {
+        let ctx = tauri::android::current_activity();
+        ctx.env
+            .call_static_method("com/voicecast/app/AudioFocusHandler", "abandon", "()V", &[])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    #[cfg(not(target_os = "android"))]
+    let focus = Ok(());
+
+    let background = service.stop_background_streaming().await;
+
+    captured.and(now_playing).and(focus).and(background)
+}
@@ -0,0 +1,203 @@
+// AAC-LC through libfdk-aac, for destinations that only take AAC. Packets go
+// out as ADTS, so every frame carries its own header and a listener can join
+// mid-stream. AAC codes fixed 1024-frame frames whatever the block size; the
+// encoder holds on to what a block leaves over, so one block can produce no
+// frame or several back to back.
+use super::codec::{Codec, CodecError, Encoder};
+use super::ContentType;
+use fdk_aac::enc as aac;
+
+// Frames per AAC-LC frame
+pub const AAC_FRAME_FRAMES: usize = 1024;
+
+// Largest AAC frame: 6144 bits per channel (ISO/IEC 14496-3, 4.5.3)
+const MAX_AAC_FRAME_BYTES_PER_CHANNEL: usize = 768;
+
+// ADTS header without CRC
+const ADTS_HEADER_BYTES: usize = 7;
+
+// What "maximum" means for AAC, which has no open-ended setting: already
+// transparent for AAC-LC
+const MAX_AAC_BITRATE_PER_CHANNEL: u32 = 160_000;
+
+pub struct AacEncoder {
+    encoder: aac::Encoder,
+    sample_rate: u32,
+    channels: u16,
+    bitrate: u32,
+    // The block being encoded, as 16-bit PCM
+    pcm: Vec<i16>,
+}
+
+// The fdk-aac handle has no thread affinity; access is serialized by the
+// Mutex it lives in
+unsafe impl Send for AacEncoder {}
+
+impl AacEncoder {
+    // Constant bitrate; None uses the maximum
+    pub fn new(sample_rate: u32, channels: u16, bitrate: Option<u32>) -> Result<Self, CodecError> {
+        let bitrate = bitrate.unwrap_or(MAX_AAC_BITRATE_PER_CHANNEL * channels as u32);
+        Ok(Self {
+            encoder: open(sample_rate, channels, bitrate)?,
+            sample_rate,
+            channels,
+            bitrate,
+            pcm: Vec::new(),
+        })
+    }
+}
+
+fn open(sample_rate: u32, channels: u16, bitrate: u32) -> Result<aac::Encoder, CodecError> {
+    let channel_mode = match channels {
+        1 => aac::ChannelMode::Mono,
+        2 => aac::ChannelMode::Stereo,
+        _ => return Err(CodecError::UnsupportedAacChannels(channels)),
+    };
+    aac::Encoder::new(aac::EncoderParams {
+        bit_rate: aac::BitRate::Cbr(bitrate),
+        sample_rate,
+        transport: aac::Transport::Adts,
+        channels: channel_mode,
+    })
+    .map_err(|e| CodecError::Aac(format!("{:?}", e)))
+}
+
+impl Encoder for AacEncoder {
+    fn codec(&self) -> Codec {
+        Codec::Aac
+    }
+
+    fn max_packet_bytes(&self, frames: usize, _sample_rate: u32) -> usize {
+        // What the previous block left over can complete one more frame
+        let aac_frames = frames / AAC_FRAME_FRAMES + 1;
+        aac_frames * (MAX_AAC_FRAME_BYTES_PER_CHANNEL * self.channels as usize + ADTS_HEADER_BYTES)
+    }
+
+    // fdk-aac can't retune a running encoder, so a change reopens it and the
+    // part-filled frame it was holding (under 25 ms) is lost
+    fn set_target_bitrate(&mut self, bitrate: Option<u32>) -> Result<(), CodecError> {
+        let bitrate = bitrate.unwrap_or(MAX_AAC_BITRATE_PER_CHANNEL * self.channels as u32);
+        if bitrate != self.bitrate {
+            self.encoder = open(self.sample_rate, self.channels, bitrate)?;
+            self.bitrate = bitrate;
+        }
+        Ok(())
+    }
+
+    // AAC-LC has neither setting
+    fn set_complexity(&mut self, _complexity: i32) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn set_content(&mut self, _content: ContentType) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn encode(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, CodecError> {
+        self.pcm.clear();
+        self.pcm.extend(
+            input
+                .iter()
+                .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16),
+        );
+
+        // Each call takes in at most what completes the pending frame and
+        // writes at most that one frame
+        let (mut consumed, mut written) = (0, 0);
+        while consumed < self.pcm.len() {
+            let info = self
+                .encoder
+                .encode(&self.pcm[consumed..], &mut output[written..])
+                .map_err(|e| CodecError::Aac(format!("{:?}", e)))?;
+            if info.input_consumed == 0 && info.output_size == 0 {
+                break;
+            }
+            consumed += info.input_consumed;
+            written += info.output_size;
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits a packet into ADTS frames by the 13-bit length in each header,
+    // failing on anything that doesn't start with the 0xFFF syncword
+    fn adts_frames(packet: &[u8]) -> usize {
+        let mut pos = 0;
+        let mut frames = 0;
+        while pos < packet.len() {
+            let header = &packet[pos..];
+            assert!(header.len() >= ADTS_HEADER_BYTES, "truncated ADTS header");
+            assert_eq!(header[0], 0xFF, "missing ADTS syncword");
+            assert_eq!(header[1] & 0xF0, 0xF0, "missing ADTS syncword");
+            let len = ((header[3] as usize & 0x03) << 11)
+                | ((header[4] as usize) << 3)
+                | (header[5] as usize >> 5);
+            assert!(
+                len > ADTS_HEADER_BYTES && pos + len <= packet.len(),
+                "bad ADTS frame length {}",
+                len
+            );
+            pos += len;
+            frames += 1;
+        }
+        frames
+    }
+
+    // Encodes `blocks` 1024-frame blocks of a stereo 440 Hz tone at `bitrate`
+    // and returns the ADTS frames and bytes that came out
+    fn encode_tone(bitrate: u32, blocks: usize) -> (usize, usize) {
+        let sample_rate = 48_000;
+        let mut encoder = AacEncoder::new(sample_rate, 2, Some(bitrate)).unwrap();
+        let mut output = vec![0u8; encoder.max_packet_bytes(AAC_FRAME_FRAMES, sample_rate)];
+
+        let (mut frames, mut bytes) = (0, 0);
+        for block in 0..blocks {
+            let input: Vec<f32> = (0..AAC_FRAME_FRAMES)
+                .flat_map(|i| {
+                    let t = (block * AAC_FRAME_FRAMES + i) as f32 / sample_rate as f32;
+                    let s = 0.5 * (2.0 * std::f32::consts::PI * 440.0 * t).sin();
+                    [s, s]
+                })
+                .collect();
+            let written = encoder.encode(&input, &mut output).unwrap();
+            frames += adts_frames(&output[..written]);
+            bytes += written;
+        }
+        (frames, bytes)
+    }
+
+    #[test]
+    fn encodes_1024_frame_blocks_as_adts() {
+        let (frames, _) = encode_tone(128_000, 20);
+        // The encoder's lookahead holds back the first few frames
+        assert!(frames >= 15, "only {} ADTS frames from 20 blocks", frames);
+    }
+
+    #[test]
+    fn frames_come_out_at_the_configured_bitrate() {
+        for bitrate in [64_000, 128_000] {
+            // About five seconds
+            let (frames, bytes) = encode_tone(bitrate, 235);
+            let seconds = (frames * AAC_FRAME_FRAMES) as f32 / 48_000.0;
+            let measured = bytes as f32 * 8.0 / seconds;
+            assert!(
+                (measured / bitrate as f32 - 1.0).abs() < 0.15,
+                "{} bps at {} bps",
+                measured,
+                bitrate
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_more_than_two_channels() {
+        assert!(matches!(
+            AacEncoder::new(48_000, 6, None),
+            Err(CodecError::UnsupportedAacChannels(6))
+        ));
+    }
+}
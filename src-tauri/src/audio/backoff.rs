@@ -0,0 +1,113 @@
+// Reconnect schedule for network sinks: exponential backoff with random
+// jitter, bounded by a total retry window rather than an attempt count.
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Up to this fraction of each delay is randomly taken off, so sinks that
+// dropped together (one network outage) don't all retry in lockstep
+const JITTER_FRACTION: f64 = 0.25;
+
+// The delay starts at `initial_delay_ms` and doubles each retry up to
+// `max_delay_ms`; no retry starts more than `max_retry_secs` after the
+// connection dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub max_retry_secs: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+            max_retry_secs: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    policy: ReconnectPolicy,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            attempts: 0,
+        }
+    }
+
+    // Retries handed out so far
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // Delay before retry number `attempt` (counting from 1), before jitter
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        let millis = self
+            .policy
+            .initial_delay_ms
+            .saturating_mul(factor)
+            .min(self.policy.max_delay_ms);
+        Duration::from_millis(millis)
+    }
+
+    // Delay before the next retry, or None once that retry would start past
+    // the window. `elapsed` is the time since the connection dropped.
+    pub fn next_delay(&mut self, elapsed: Duration) -> Option<Duration> {
+        let jitter = rand::thread_rng().gen_range(0.0..=JITTER_FRACTION);
+        let delay = self.base_delay(self.attempts + 1).mul_f64(1.0 - jitter);
+        if elapsed + delay > Duration::from_secs(self.policy.max_retry_secs) {
+            return None;
+        }
+        self.attempts += 1;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        let backoff = Backoff::new(ReconnectPolicy::default());
+        let delays: Vec<u128> = (1..=9)
+            .map(|attempt| backoff.base_delay(attempt).as_millis())
+            .collect();
+        assert_eq!(
+            delays,
+            [500, 1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000, 30_000]
+        );
+        assert_eq!(backoff.base_delay(200), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn jittered_retries_stop_at_the_window() {
+        let mut backoff = Backoff::new(ReconnectPolicy::default());
+        let mut elapsed = Duration::ZERO;
+        while let Some(delay) = backoff.next_delay(elapsed) {
+            let base = backoff.base_delay(backoff.attempts());
+            assert!(
+                delay <= base && delay >= base.mul_f64(1.0 - JITTER_FRACTION),
+                "{:?} from {:?}",
+                delay,
+                base
+            );
+            elapsed += delay;
+        }
+        assert!(elapsed <= Duration::from_secs(300));
+        // Past the window every later call keeps refusing
+        let attempts = backoff.attempts();
+        assert!(backoff.next_delay(elapsed).is_none());
+        assert_eq!(backoff.attempts(), attempts);
+        assert!(attempts >= 10, "gave up after {} attempts", attempts);
+    }
+}
@@ -0,0 +1,171 @@
+// Adaptive bitrate. Once a second the engine hands each live stream's stats to
+// its controller, which treats new lagged or dropped packets as congestion:
+// a sink or subscriber that can't keep up skips packets (`lagged_packets`), or
+// the channel refuses them (`dropped_frames`). Congestion cuts the bitrate
+// quickly; a run of clean intervals lets it creep back up. Both moves are
+// limited per interval so the stream never jumps audibly.
+use super::StreamStats;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// What the Opus encoder accepts, roughly: 6 kbps is already narrowband speech
+pub const MIN_ADAPTIVE_BITRATE: u32 = 6_000;
+pub const MAX_ADAPTIVE_BITRATE: u32 = 510_000;
+
+// How often the engine runs the controllers
+pub const BITRATE_ADAPT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Largest cut per congested interval, and largest raise per clean one
+const MAX_STEP_DOWN: f32 = 0.25;
+const MAX_STEP_UP: f32 = 0.05;
+
+// Clean intervals needed after congestion before the bitrate starts to rise
+const RECOVERY_INTERVALS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdaptiveBitrateConfig {
+    pub min_bitrate: u32,
+    pub max_bitrate: u32,
+}
+
+impl AdaptiveBitrateConfig {
+    // Both bounds clamped to what the encoder accepts, min never above max
+    pub fn new(min_bitrate: u32, max_bitrate: u32) -> Self {
+        let max_bitrate = max_bitrate.clamp(MIN_ADAPTIVE_BITRATE, MAX_ADAPTIVE_BITRATE);
+        let min_bitrate = min_bitrate.clamp(MIN_ADAPTIVE_BITRATE, max_bitrate);
+        Self {
+            min_bitrate,
+            max_bitrate,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BitrateController {
+    config: AdaptiveBitrateConfig,
+    // Lagged plus dropped packets at the last update; None until the first
+    last_losses: Option<u64>,
+    clean_intervals: u32,
+}
+
+impl BitrateController {
+    pub fn new(config: AdaptiveBitrateConfig) -> Self {
+        Self {
+            config,
+            last_losses: None,
+            clean_intervals: 0,
+        }
+    }
+
+    pub fn config(&self) -> AdaptiveBitrateConfig {
+        self.config
+    }
+
+    // Called once per interval with the stream's running stats and its current
+    // bitrate (None while the encoder runs at its maximum). Returns the new
+    // bitrate when it should change.
+    pub fn update(&mut self, stats: &StreamStats, current: Option<u32>) -> Option<u32> {
+        let losses = stats.lagged_packets + stats.dropped_frames;
+        // The counters restart with the stream; take the first reading as a baseline
+        let new_losses = match self.last_losses {
+            Some(last) if losses >= last => losses - last,
+            _ => 0,
+        };
+        self.last_losses = Some(losses);
+
+        let previous = current;
+        let current = current
+            .unwrap_or(self.config.max_bitrate)
+            .clamp(self.config.min_bitrate, self.config.max_bitrate);
+        let target = if new_losses > 0 {
+            self.clean_intervals = 0;
+            (current as f32 * (1.0 - MAX_STEP_DOWN)) as u32
+        } else {
+            self.clean_intervals = self.clean_intervals.saturating_add(1);
+            if self.clean_intervals < RECOVERY_INTERVALS {
+                current
+            } else {
+                // At least 1 bps so a low bitrate can still climb
+                current + ((current as f32 * MAX_STEP_UP) as u32).max(1)
+            }
+        };
+
+        // Also pins a stream left at the encoder maximum, or outside new bounds
+        let target = target.clamp(self.config.min_bitrate, self.config.max_bitrate);
+        (previous != Some(target)).then_some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs one interval and applies whatever the controller decides
+    fn step(controller: &mut BitrateController, stats: &StreamStats, bitrate: &mut Option<u32>) {
+        if let Some(new) = controller.update(stats, *bitrate) {
+            *bitrate = Some(new);
+        }
+    }
+
+    #[test]
+    fn congestion_lowers_the_bitrate_and_recovery_raises_it() {
+        let mut controller = BitrateController::new(AdaptiveBitrateConfig::new(24_000, 128_000));
+        let mut stats = StreamStats::default();
+        let mut bitrate = None;
+        step(&mut controller, &stats, &mut bitrate);
+        assert_eq!(bitrate, Some(128_000));
+
+        let mut previous = 128_000;
+        for _ in 0..10 {
+            stats.lagged_packets += 20;
+            step(&mut controller, &stats, &mut bitrate);
+            let current = bitrate.unwrap();
+            assert!(
+                current < previous || current == 24_000,
+                "{} -> {}",
+                previous,
+                current
+            );
+            assert!(current as f32 >= previous as f32 * (1.0 - MAX_STEP_DOWN) - 1.0);
+            previous = current;
+        }
+        assert_eq!(bitrate, Some(24_000));
+
+        for interval in 1..=60 {
+            step(&mut controller, &stats, &mut bitrate);
+            let current = bitrate.unwrap();
+            if interval < RECOVERY_INTERVALS {
+                assert_eq!(current, 24_000);
+            }
+            assert!(
+                current >= previous
+                    && current as f32 <= previous as f32 * (1.0 + MAX_STEP_UP) + 1.0
+            );
+            previous = current;
+        }
+        assert_eq!(bitrate, Some(128_000));
+    }
+
+    #[test]
+    fn restarted_counters_are_not_congestion() {
+        let mut controller = BitrateController::new(AdaptiveBitrateConfig::new(24_000, 128_000));
+        let stats = StreamStats {
+            lagged_packets: 500,
+            ..StreamStats::default()
+        };
+        assert_eq!(controller.update(&stats, Some(64_000)), None);
+        assert_eq!(
+            controller.update(&StreamStats::default(), Some(64_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn bounds_are_clamped_to_the_encoder_range() {
+        let config = AdaptiveBitrateConfig::new(200_000, 1_000);
+        assert_eq!(
+            (config.min_bitrate, config.max_bitrate),
+            (MIN_ADAPTIVE_BITRATE, MIN_ADAPTIVE_BITRATE)
+        );
+    }
+}
@@ -0,0 +1,975 @@
+use super::versioning::{check_version, unversioned, PRESET_VERSION};
+use super::{
+    AudioEffect, AudioError, DelayLine, EffectParams, EffectRegistry, EffectType, ParamSmoother,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+// Quietest gate threshold a version 1 preset upgrades to (-80 dBFS, the
+// parameter's minimum)
+const V1_GATE_THRESHOLD_FLOOR: f32 = 1e-4;
+
+// Likewise for a version 2 compressor (-60 dBFS)
+const V2_COMPRESSOR_THRESHOLD_FLOOR: f32 = 1e-3;
+
+// Weight of each new block in the per-effect timing average (~20 blocks)
+const CPU_AVERAGE_WEIGHT: f32 = 0.05;
+
+// Overrun protection: share of each block the chain may take on average,
+// leaving the rest for metering, encoding and the device callback itself
+const OVERRUN_BUDGET_SHARE: f32 = 0.75;
+
+// How long the average has to stay over budget before an effect is bypassed
+const OVERRUN_TRIP_SECS: f32 = 0.5;
+
+// Per-effect bypass. Toggling fades between the effect's output and its
+// input instead of switching, so the change doesn't click.
+#[derive(Debug, Clone, Copy)]
+struct Bypass {
+    bypassed: bool,
+    // 1 is all effect, 0 all input
+    wet: f32,
+    step: f32,
+}
+
+impl Default for Bypass {
+    fn default() -> Self {
+        Self {
+            bypassed: false,
+            wet: 1.0,
+            step: 1.0,
+        }
+    }
+}
+
+impl Bypass {
+    fn target(&self) -> f32 {
+        if self.bypassed {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    // Bypassed and faded out, so the effect needn't run at all
+    fn is_settled_dry(&self) -> bool {
+        self.bypassed && self.wet == 0.0
+    }
+
+    // Mixes the effect's output with its input while a fade is under way
+    fn blend(&mut self, processed: &mut [f32], input: &[f32], channels: usize) {
+        let target = self.target();
+        if self.wet == target {
+            return;
+        }
+        for (frame, dry_frame) in processed.chunks_mut(channels).zip(input.chunks(channels)) {
+            self.wet = if self.wet < target {
+                (self.wet + self.step).min(target)
+            } else {
+                (self.wet - self.step).max(target)
+            };
+            for (sample, dry) in frame.iter_mut().zip(dry_frame) {
+                *sample = dry + (*sample - dry) * self.wet;
+            }
+        }
+    }
+}
+
+// Dry/wet balance of one effect, for parallel processing such as New York
+// compression. The dry path is held back by the effect's latency so the two
+// line up instead of combing.
+struct DryMix {
+    // 1 is all effect, 0 all input
+    wet: ParamSmoother,
+    dry_delay: DelayLine,
+    // The delay only runs while some dry signal is mixed in
+    active: bool,
+}
+
+impl Default for DryMix {
+    fn default() -> Self {
+        Self {
+            wet: ParamSmoother::new(1.0, 0),
+            dry_delay: DelayLine::new(0, 1),
+            active: false,
+        }
+    }
+}
+
+impl DryMix {
+    fn blend(&mut self, processed: &mut [f32], input: &[f32], channels: usize, latency: usize) {
+        if self.wet.target() >= 1.0 && self.wet.is_settled() {
+            self.active = false;
+            return;
+        }
+        if self.dry_delay.channels() != channels {
+            self.dry_delay = DelayLine::new(latency, channels);
+        }
+        self.dry_delay.set_delay(latency);
+        if !self.active {
+            // Don't replay whatever was left from the last time
+            self.dry_delay.reset();
+            self.active = true;
+        }
+
+        let mut dry = input.to_vec();
+        self.dry_delay.process(&mut dry);
+        for (frame, dry_frame) in processed.chunks_mut(channels).zip(dry.chunks(channels)) {
+            let wet = self.wet.tick();
+            for (sample, dry) in frame.iter_mut().zip(dry_frame) {
+                *sample = dry + (*sample - dry) * wet;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.dry_delay.reset();
+    }
+}
+
+// One effect in the chain plus the registry key it was built from, so the
+// chain can be serialized and rebuilt
+pub struct EffectSlot {
+    pub effect_type: String,
+    pub effect: Box<dyn AudioEffect>,
+    // Moving average of process() time, only updated while profiling
+    avg_micros: f32,
+    bypass: Bypass,
+    mix: DryMix,
+}
+
+impl EffectSlot {
+    pub fn new(effect_type: &str, effect: Box<dyn AudioEffect>) -> Self {
+        Self {
+            effect_type: effect_type.to_string(),
+            effect,
+            avg_micros: 0.0,
+            bypass: Bypass::default(),
+            mix: DryMix::default(),
+        }
+    }
+
+    pub fn is_bypassed(&self) -> bool {
+        self.bypass.bypassed
+    }
+
+    // Fades the effect out of (or back into) the signal over `fade_frames`;
+    // 0 switches at once
+    pub fn set_bypassed(&mut self, bypassed: bool, fade_frames: usize) {
+        self.bypass.bypassed = bypassed;
+        self.bypass.step = if fade_frames == 0 {
+            1.0
+        } else {
+            1.0 / fade_frames as f32
+        };
+    }
+
+    // Share of the effect in the slot's output, 0-1
+    pub fn mix(&self) -> f32 {
+        self.mix.wet.target()
+    }
+
+    // Blends the effect's output with its input, the input delayed by the
+    // effect's latency. The change ramps over `fade_frames`; 0 is instant.
+    pub fn set_mix(&mut self, mix: f32, fade_frames: usize) {
+        self.mix.wet.set_ramp_frames(fade_frames);
+        self.mix.wet.set_target(mix.clamp(0.0, 1.0));
+    }
+
+    // Clears the effect's internal state and the dry delay
+    pub fn reset(&mut self) {
+        self.effect.reset();
+        self.mix.reset();
+    }
+
+    pub fn to_preset(&self) -> EffectPreset {
+        let mut params = EffectParams::new();
+        for parameter in self.effect.get_parameters() {
+            params.set(parameter.name, parameter.value);
+        }
+        EffectPreset {
+            effect_type: self.effect_type.clone(),
+            params,
+            mix: self.mix(),
+        }
+    }
+}
+
+// Serialized form of one effect: its registry key and every parameter value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EffectPreset {
+    pub effect_type: String,
+    pub params: EffectParams,
+    // Dry/wet balance of the slot; presets saved without one are all effect
+    #[serde(default = "full_mix")]
+    pub mix: f32,
+}
+
+fn full_mix() -> f32 {
+    1.0
+}
+
+impl EffectPreset {
+    // None if `effect_type` isn't registered
+    pub fn build(&self, registry: &EffectRegistry, sample_rate: u32) -> Option<EffectSlot> {
+        let mut params = self.params.clone();
+        params.set("sample_rate".to_string(), sample_rate as f32);
+        let mut effect = registry.create(&self.effect_type, params)?;
+
+        // Not every constructor reads every parameter (the EQ bands, for one),
+        // so apply them all again to land on exactly the saved values
+        for (name, value) in self.params.params.iter() {
+            effect.set_parameter(name, *value);
+        }
+        let mut slot = EffectSlot::new(&self.effect_type, effect);
+        slot.set_mix(self.mix, 0);
+        Some(slot)
+    }
+
+    // Adds the default value of every parameter the preset doesn't name.
+    // Unknown effect types are left for build() to report.
+    // Gates up to version 1 and compressors up to version 2 read `threshold`
+    // as |value| / 100 of full scale, so the gate's default -40 opened at 0.4
+    // (about -8 dBFS). It is dBFS now; rewrite the value as the level the
+    // preset actually worked at, no lower than `floor`.
+    fn upgrade_linear_threshold(&mut self, effect_type: EffectType, floor: f32) {
+        if self.effect_type != effect_type.key() {
+            return;
+        }
+        if let Some(threshold) = self.params.get("threshold") {
+            let linear = (threshold.abs() / 100.0).max(floor);
+            self.params
+                .set("threshold".to_string(), 20.0 * linear.log10());
+        }
+    }
+
+    fn fill_defaults(&mut self, registry: &EffectRegistry) {
+        let Some(effect) = registry.create(&self.effect_type, EffectParams::new()) else {
+            return;
+        };
+        for parameter in effect.get_parameters() {
+            self.params
+                .params
+                .entry(parameter.name)
+                .or_insert(parameter.value);
+        }
+    }
+}
+
+// Time one effect takes per block, as returned by get_effect_cpu_usage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectCpuUsage {
+    pub index: usize,
+    pub effect_type: String,
+    pub avg_micros: f32,
+    // Share of the real-time budget (one block's duration); the whole chain
+    // has to stay well under 100% to avoid dropouts
+    pub percent_of_block: f32,
+}
+
+// An effect overrun protection bypassed, reported once per trip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectOverrun {
+    pub index: usize,
+    pub effect_type: String,
+    // Average chain time per block when it tripped, against the budget
+    pub chain_micros: f32,
+    pub budget_micros: f32,
+}
+
+// Watches the chain's own processing time and bypasses the most expensive
+// effect when it stays over budget, so an overloaded CPU costs one effect
+// instead of continuous dropouts
+struct OverrunGuard {
+    sample_rate: f32,
+    avg_micros: f32,
+    // Time the average has been over budget without a break
+    over_secs: f32,
+    tripped: Option<EffectOverrun>,
+}
+
+impl OverrunGuard {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1) as f32,
+            avg_micros: 0.0,
+            over_secs: 0.0,
+            tripped: None,
+        }
+    }
+}
+
+// Serialized form of a whole chain, in processing order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainPreset {
+    #[serde(default = "unversioned")]
+    pub version: u32,
+    pub effects: Vec<EffectPreset>,
+}
+
+impl Default for ChainPreset {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl ChainPreset {
+    // A preset in the current schema
+    pub fn new(effects: Vec<EffectPreset>) -> Self {
+        Self {
+            version: PRESET_VERSION,
+            effects,
+        }
+    }
+
+    // This preset in the current schema, upgraded step by step from whatever
+    // version it was saved with
+    pub fn upgrade(&self, registry: &EffectRegistry) -> Result<ChainPreset, AudioError> {
+        check_version("preset", self.version, PRESET_VERSION)?;
+        let mut preset = self.clone();
+        if preset.version < 2 {
+            // Version 1 saved whatever parameters an effect had at the time;
+            // write in the defaults of the ones added since
+            for effect in preset.effects.iter_mut() {
+                effect.upgrade_linear_threshold(EffectType::NoiseGate, V1_GATE_THRESHOLD_FLOOR);
+                effect.fill_defaults(registry);
+            }
+            preset.version = 2;
+        }
+        if preset.version < 3 {
+            for effect in preset.effects.iter_mut() {
+                effect.upgrade_linear_threshold(
+                    EffectType::Compressor,
+                    V2_COMPRESSOR_THRESHOLD_FLOOR,
+                );
+            }
+            preset.version = 3;
+        }
+        Ok(preset)
+    }
+}
+
+// One entry of a parameter batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterChange {
+    pub effect_index: usize,
+    pub name: String,
+    pub value: f32,
+}
+
+// Outcome of one change, in the same order as the batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParameterChangeResult {
+    pub applied: bool,
+    pub error: Option<String>,
+}
+
+// Previous chain kept running while the output fades over to its replacement
+struct Outgoing {
+    slots: Vec<EffectSlot>,
+    mix: f32,
+    step: f32,
+}
+
+#[derive(Default)]
+pub struct EffectsChain {
+    slots: Vec<EffectSlot>,
+    outgoing: Option<Outgoing>,
+    // Off by default so the audio path doesn't pay for the clock reads
+    profiling: bool,
+    // Ramp for smoothable parameters once set; until then each effect keeps
+    // its own default
+    smoothing_frames: Option<usize>,
+    // While set, only this slot runs; the others are skipped with their
+    // state left as it was
+    solo: Option<usize>,
+    // Set while overrun protection is on; times every block like profiling
+    overrun_guard: Option<OverrunGuard>,
+}
+
+impl EffectsChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, mut slot: EffectSlot) {
+        if let Some(frames) = self.smoothing_frames {
+            slot.effect.set_smoothing_frames(frames);
+        }
+        self.slots.push(slot);
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.outgoing = None;
+        self.solo = None;
+    }
+
+    // Clears every effect's internal state; a chain still fading out is dropped
+    pub fn reset(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.reset();
+        }
+        self.outgoing = None;
+    }
+
+    pub fn slots(&self) -> &[EffectSlot] {
+        &self.slots
+    }
+
+    pub fn slot_mut(&mut self, index: usize) -> Option<&mut EffectSlot> {
+        self.slots.get_mut(index)
+    }
+
+    // Applies every change that names an existing effect and parameter; the
+    // rest are reported and skipped without affecting the others
+    pub fn apply_parameter_batch(
+        &mut self,
+        changes: &[ParameterChange],
+    ) -> Vec<ParameterChangeResult> {
+        changes
+            .iter()
+            .map(|change| {
+                let result = self.apply_parameter(change);
+                ParameterChangeResult {
+                    applied: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                }
+            })
+            .collect()
+    }
+
+    fn apply_parameter(&mut self, change: &ParameterChange) -> Result<(), AudioError> {
+        let index = change.effect_index;
+        let slot = self
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        if !slot
+            .effect
+            .get_parameters()
+            .iter()
+            .any(|p| p.name == change.name)
+        {
+            return Err(AudioError::NoSuchParameter(index, change.name.clone()));
+        }
+        slot.effect.set_parameter(&change.name, change.value);
+        Ok(())
+    }
+
+    pub fn to_preset(&self) -> ChainPreset {
+        ChainPreset::new(self.slots.iter().map(EffectSlot::to_preset).collect())
+    }
+
+    // Swaps in a new chain; for the next `fade_frames` frames both chains run
+    // and the output crossfades from the old one to the new one
+    pub fn replace(&mut self, mut slots: Vec<EffectSlot>, fade_frames: usize) {
+        if let Some(frames) = self.smoothing_frames {
+            slots
+                .iter_mut()
+                .for_each(|slot| slot.effect.set_smoothing_frames(frames));
+        }
+        let previous = std::mem::replace(&mut self.slots, slots);
+        // The indices now point at different effects
+        self.solo = None;
+        self.outgoing = (fade_frames > 0).then(|| Outgoing {
+            slots: previous,
+            mix: 0.0,
+            step: 1.0 / fade_frames as f32,
+        });
+    }
+
+    // Auditions one effect on its own; soloing another replaces it
+    pub fn set_solo(&mut self, index: usize) -> Result<(), AudioError> {
+        if index >= self.slots.len() {
+            return Err(AudioError::NoSuchEffect(index));
+        }
+        self.solo = Some(index);
+        Ok(())
+    }
+
+    pub fn clear_solo(&mut self) {
+        self.solo = None;
+    }
+
+    pub fn solo(&self) -> Option<usize> {
+        self.solo
+    }
+
+    // The slots that run: just the soloed one while a solo is active
+    fn active_slots(&mut self) -> &mut [EffectSlot] {
+        match self.solo {
+            Some(index) => &mut self.slots[index..=index],
+            None => &mut self.slots,
+        }
+    }
+
+    pub fn set_smoothing_frames(&mut self, frames: usize) {
+        self.smoothing_frames = Some(frames);
+        self.slots
+            .iter_mut()
+            .for_each(|slot| slot.effect.set_smoothing_frames(frames));
+    }
+
+    // Starts (with fresh averages) or stops per-effect timing
+    pub fn set_profiling(&mut self, enabled: bool) {
+        if enabled && !self.profiling {
+            self.slots.iter_mut().for_each(|slot| slot.avg_micros = 0.0);
+        }
+        self.profiling = enabled;
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiling
+    }
+
+    // Turns overrun protection on or off. It needs the per-effect timings, so
+    // while it is on every block is timed whether or not profiling is.
+    pub fn set_overrun_protection(&mut self, enabled: bool, sample_rate: u32) {
+        match (enabled, &self.overrun_guard) {
+            (true, None) => {
+                self.slots.iter_mut().for_each(|slot| slot.avg_micros = 0.0);
+                self.overrun_guard = Some(OverrunGuard::new(sample_rate));
+            }
+            (false, _) => self.overrun_guard = None,
+            (true, Some(_)) => {}
+        }
+    }
+
+    pub fn has_overrun_protection(&self) -> bool {
+        self.overrun_guard.is_some()
+    }
+
+    // The effect overrun protection bypassed since the last call, if any
+    pub fn take_overrun(&mut self) -> Option<EffectOverrun> {
+        self.overrun_guard
+            .as_mut()
+            .and_then(|guard| guard.tripped.take())
+    }
+
+    // Per-effect averages against a block lasting `block_secs`
+    pub fn cpu_usage(&self, block_secs: f32) -> Vec<EffectCpuUsage> {
+        let budget_micros = block_secs * 1_000_000.0;
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| EffectCpuUsage {
+                index,
+                effect_type: slot.effect_type.clone(),
+                avg_micros: slot.avg_micros,
+                percent_of_block: if budget_micros > 0.0 {
+                    slot.avg_micros / budget_micros * 100.0
+                } else {
+                    0.0
+                },
+            })
+            .collect()
+    }
+
+    pub fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let channels = channels.max(1);
+        let profiling = self.profiling || self.overrun_guard.is_some();
+        let started = self.overrun_guard.is_some().then(Instant::now);
+        let mut output = run(self.active_slots(), input, channels, profiling);
+
+        // The outgoing chain only runs for a crossfade and isn't profiled
+        if let Some(outgoing) = self.outgoing.as_mut() {
+            let old = run(&mut outgoing.slots, input, channels, false);
+            for (frame, old_frame) in output.chunks_mut(channels).zip(old.chunks(channels)) {
+                outgoing.mix = (outgoing.mix + outgoing.step).min(1.0);
+                for (sample, old_sample) in frame.iter_mut().zip(old_frame) {
+                    *sample = *old_sample + (*sample - *old_sample) * outgoing.mix;
+                }
+            }
+            if outgoing.mix >= 1.0 {
+                self.outgoing = None;
+            }
+        }
+
+        if let Some(started) = started {
+            self.check_overrun(
+                started.elapsed().as_secs_f32() * 1_000_000.0,
+                input.len() / channels,
+            );
+        }
+        output
+    }
+
+    // Folds one block's time into the guard's average and, once it has been
+    // over budget for OVERRUN_TRIP_SECS, bypasses the running effect with the
+    // highest average. The bypass fades over the next block.
+    fn check_overrun(&mut self, micros: f32, frames: usize) {
+        let Some(guard) = self.overrun_guard.as_mut() else {
+            return;
+        };
+        let block_secs = frames as f32 / guard.sample_rate;
+        let budget_micros = block_secs * OVERRUN_BUDGET_SHARE * 1_000_000.0;
+        guard.avg_micros += (micros - guard.avg_micros) * CPU_AVERAGE_WEIGHT;
+        if guard.avg_micros <= budget_micros {
+            guard.over_secs = 0.0;
+            return;
+        }
+        guard.over_secs += block_secs;
+        if guard.over_secs < OVERRUN_TRIP_SECS {
+            return;
+        }
+
+        let chain_micros = guard.avg_micros;
+        // Start over, so the next trip needs another sustained overrun with
+        // the cheaper chain
+        guard.avg_micros = 0.0;
+        guard.over_secs = 0.0;
+        let costliest = self
+            .active_slot_indices()
+            .filter(|&index| !self.slots[index].is_bypassed())
+            .max_by(|&a, &b| {
+                self.slots[a]
+                    .avg_micros
+                    .total_cmp(&self.slots[b].avg_micros)
+            });
+        let Some(index) = costliest else {
+            return;
+        };
+        let slot = &mut self.slots[index];
+        slot.set_bypassed(true, frames);
+        let overrun = EffectOverrun {
+            index,
+            effect_type: slot.effect_type.clone(),
+            chain_micros,
+            budget_micros,
+        };
+        if let Some(guard) = self.overrun_guard.as_mut() {
+            guard.tripped = Some(overrun);
+        }
+    }
+
+    fn active_slot_indices(&self) -> std::ops::Range<usize> {
+        match self.solo {
+            Some(index) => index..index + 1,
+            None => 0..self.slots.len(),
+        }
+    }
+
+    // Sum of the latency every running effect reports; bypassed effects add none
+    pub fn latency_samples(&self) -> usize {
+        let latency = |slot: &EffectSlot| {
+            if slot.is_bypassed() {
+                0
+            } else {
+                slot.effect.latency_samples()
+            }
+        };
+        match self.solo {
+            Some(index) => latency(&self.slots[index]),
+            None => self.slots.iter().map(latency).sum(),
+        }
+    }
+}
+
+fn run(slots: &mut [EffectSlot], input: &[f32], channels: usize, profile: bool) -> Vec<f32> {
+    let mut output = input.to_vec();
+    for slot in slots.iter_mut() {
+        if slot.bypass.is_settled_dry() {
+            continue;
+        }
+        let mut processed = if profile {
+            let started = Instant::now();
+            let processed = slot.effect.process(&output, channels);
+            let micros = started.elapsed().as_secs_f32() * 1_000_000.0;
+            slot.avg_micros += (micros - slot.avg_micros) * CPU_AVERAGE_WEIGHT;
+            processed
+        } else {
+            slot.effect.process(&output, channels)
+        };
+        let latency = slot.effect.latency_samples();
+        slot.mix.blend(&mut processed, &output, channels, latency);
+        slot.bypass.blend(&mut processed, &output, channels);
+        output = processed;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::EffectParameter;
+
+    fn chain_of(keys: &[&str]) -> EffectsChain {
+        let registry = EffectRegistry::with_builtin_effects();
+        let mut chain = EffectsChain::new();
+        for key in keys {
+            chain.push(EffectSlot::new(
+                key,
+                registry.create(key, EffectParams::new()).unwrap(),
+            ));
+        }
+        chain
+    }
+
+    fn threshold(chain: &EffectsChain, index: usize) -> f32 {
+        chain.to_preset().effects[index]
+            .params
+            .get("threshold")
+            .unwrap()
+    }
+
+    // Both compressors' thresholds set to `value` in one batch
+    fn both_thresholds(value: f32) -> Vec<ParameterChange> {
+        (0..2)
+            .map(|effect_index| ParameterChange {
+                effect_index,
+                name: "threshold".to_string(),
+                value,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_reports_each_change_and_applies_the_valid_ones() {
+        let mut chain = chain_of(&["compressor", "compressor"]);
+        let mut changes = both_thresholds(-30.0);
+        changes.push(ParameterChange {
+            effect_index: 5,
+            name: "threshold".to_string(),
+            value: -10.0,
+        });
+        changes.push(ParameterChange {
+            effect_index: 0,
+            name: "bogus".to_string(),
+            value: 1.0,
+        });
+
+        let results = chain.apply_parameter_batch(&changes);
+        let applied: Vec<bool> = results.iter().map(|r| r.applied).collect();
+        assert_eq!(applied, [true, true, false, false]);
+        assert!(results[2].error.is_some() && results[3].error.is_some());
+        assert_eq!((threshold(&chain, 0), threshold(&chain, 1)), (-30.0, -30.0));
+    }
+
+    #[test]
+    fn soloed_effect_sounds_like_a_chain_of_just_that_effect() {
+        let mut full = chain_of(&["compressor", "saturation", "reverb"]);
+        full.set_solo(2).unwrap();
+        let mut alone = chain_of(&["reverb"]);
+
+        // Several blocks, so the reverb's tail carries state from one to the next
+        let input: Vec<f32> = (0..1_920).map(|i| (i as f32 * 0.03).sin() * 0.8).collect();
+        for _ in 0..5 {
+            assert_eq!(full.process(&input, 2), alone.process(&input, 2));
+        }
+
+        full.clear_solo();
+        assert_ne!(full.process(&input, 2), alone.process(&input, 2));
+        assert!(full.set_solo(3).is_err());
+    }
+
+    // Fixed -6 dB, so dry and processed differ by a known step
+    struct HalfGain;
+
+    impl AudioEffect for HalfGain {
+        fn process(&mut self, input: &[f32], _channels: usize) -> Vec<f32> {
+            input.iter().map(|s| s * 0.5).collect()
+        }
+
+        fn get_name(&self) -> &str {
+            "Half Gain"
+        }
+
+        fn get_parameters(&self) -> Vec<EffectParameter> {
+            Vec::new()
+        }
+
+        fn set_parameter(&mut self, _name: &str, _value: f32) {}
+    }
+
+    #[test]
+    fn bypass_toggle_ramps_between_the_levels() {
+        let mut chain = EffectsChain::new();
+        chain.push(EffectSlot::new("half_gain", Box::new(HalfGain)));
+        assert_eq!(chain.process(&[1.0; 4], 1), [0.5; 4]);
+
+        // 480 frames is 10 ms at 48 kHz
+        chain.slot_mut(0).unwrap().set_bypassed(true, 480);
+        let fading_out = chain.process(&[1.0; 960], 1);
+        assert!(fading_out[0] < 0.51);
+        for pair in fading_out.windows(2) {
+            assert!(
+                pair[1] >= pair[0] && pair[1] - pair[0] < 0.002,
+                "step from {} to {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        assert_eq!(fading_out[959], 1.0);
+        assert_eq!(chain.process(&[1.0; 4], 1), [1.0; 4]);
+
+        chain.slot_mut(0).unwrap().set_bypassed(false, 480);
+        let fading_in = chain.process(&[1.0; 960], 1);
+        assert!(fading_in[0] > 0.99);
+        for pair in fading_in.windows(2) {
+            assert!(
+                pair[1] <= pair[0] && pair[0] - pair[1] < 0.002,
+                "step from {} to {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        assert_eq!(fading_in[959], 0.5);
+    }
+
+    #[test]
+    fn v1_preset_loads_with_defaults_filled_in() {
+        // As saved before versioning: no version, only the parameters of the time
+        let json = r#"{"effects":[
+            {"effect_type":"compressor","params":{"params":{"threshold":-18.0}}},
+            {"effect_type":"noise_gate","params":{"params":{"threshold":-40.0}}}
+        ]}"#;
+        let saved: ChainPreset = serde_json::from_str(json).unwrap();
+        assert_eq!(saved.version, 1);
+
+        let registry = EffectRegistry::with_builtin_effects();
+        let preset = saved.upgrade(&registry).unwrap();
+        assert_eq!(preset.version, PRESET_VERSION);
+        let compressor = &preset.effects[0].params;
+        // 0.18 of full scale, as compressors read it before version 3
+        assert!((compressor.get("threshold").unwrap() - -14.89).abs() < 0.01);
+        assert_eq!(compressor.get("detector"), Some(0.0));
+        let gate = &preset.effects[1].params;
+        assert_eq!(gate.get("lookahead_ms"), Some(0.0));
+        // 0.4 of full scale, where the v1 gate opened
+        assert!((gate.get("threshold").unwrap() - -7.96).abs() < 0.01);
+
+        let mut chain = EffectsChain::new();
+        for effect in &preset.effects {
+            chain.push(effect.build(&registry, 48_000).unwrap());
+        }
+        let output = chain.process(&vec![0.25; 960], 1);
+        assert!(output.iter().all(|s| s.is_finite()));
+    }
+
+    #[test]
+    fn v2_preset_moves_only_the_compressor_threshold_to_dbfs() {
+        let json = r#"{"version":2,"effects":[
+            {"effect_type":"compressor","params":{"params":{"threshold":-10.0}}},
+            {"effect_type":"noise_gate","params":{"params":{"threshold":-40.0}}}
+        ]}"#;
+        let saved: ChainPreset = serde_json::from_str(json).unwrap();
+
+        let registry = EffectRegistry::with_builtin_effects();
+        let preset = saved.upgrade(&registry).unwrap();
+        assert_eq!(preset.version, PRESET_VERSION);
+        // 0.1 of full scale is -20 dBFS
+        let compressor = preset.effects[0].params.get("threshold").unwrap();
+        assert!((compressor - -20.0).abs() < 1e-4, "{} dBFS", compressor);
+        // The gate was already dBFS
+        assert_eq!(preset.effects[1].params.get("threshold"), Some(-40.0));
+    }
+
+    #[test]
+    fn preset_from_a_newer_app_is_refused() {
+        let newer = ChainPreset {
+            version: PRESET_VERSION + 1,
+            effects: Vec::new(),
+        };
+        let registry = EffectRegistry::with_builtin_effects();
+        assert!(matches!(
+            newer.upgrade(&registry),
+            Err(AudioError::UnsupportedVersion { kind: "preset", .. })
+        ));
+    }
+
+    // Nothing but latency, which it reports
+    struct PureDelay(DelayLine);
+
+    impl AudioEffect for PureDelay {
+        fn process(&mut self, input: &[f32], _channels: usize) -> Vec<f32> {
+            let mut output = input.to_vec();
+            self.0.process(&mut output);
+            output
+        }
+
+        fn get_name(&self) -> &str {
+            "Pure Delay"
+        }
+
+        fn get_parameters(&self) -> Vec<EffectParameter> {
+            Vec::new()
+        }
+
+        fn set_parameter(&mut self, _name: &str, _value: f32) {}
+
+        fn latency_samples(&self) -> usize {
+            self.0.delay()
+        }
+    }
+
+    #[test]
+    fn parallel_mix_of_a_delay_reconstructs_the_input() {
+        let mut chain = EffectsChain::new();
+        chain.push(EffectSlot::new(
+            "pure_delay",
+            Box::new(PureDelay(DelayLine::new(37, 2))),
+        ));
+        chain.slot_mut(0).unwrap().set_mix(0.5, 0);
+        let input: Vec<f32> = (0..9_600).map(|n| ((n / 2) as f32 * 0.05).sin()).collect();
+        let output: Vec<f32> = input
+            .chunks(960)
+            .flat_map(|block| chain.process(block, 2))
+            .collect();
+
+        // Half dry, half wet, both 37 frames late: the input again, not a comb
+        let offset = 37 * 2;
+        assert_eq!(chain.latency_samples(), 37);
+        assert!(output[..offset].iter().all(|&s| s == 0.0));
+        for (out, expected) in output[offset..].iter().zip(&input) {
+            assert!(
+                (out - expected).abs() < 1e-5,
+                "{} against {}",
+                out,
+                expected
+            );
+        }
+    }
+
+    // Feeds the overrun guard `blocks` 10 ms blocks that each took `micros`
+    fn run_for(
+        chain: &mut EffectsChain,
+        micros: impl Fn(usize) -> f32,
+        blocks: usize,
+    ) -> Option<(usize, EffectOverrun)> {
+        (0..blocks).find_map(|block| {
+            chain.check_overrun(micros(block), 480);
+            chain.take_overrun().map(|overrun| (block, overrun))
+        })
+    }
+
+    #[test]
+    fn sustained_overrun_bypasses_the_costliest_effect() {
+        let mut chain = chain_of(&["compressor", "reverb"]);
+        assert!(run_for(&mut chain, |_| 11_000.0, 100).is_none());
+
+        chain.set_overrun_protection(true, 48_000);
+        chain.slots[0].avg_micros = 500.0;
+        chain.slots[1].avg_micros = 10_500.0;
+        let (block, overrun) =
+            run_for(&mut chain, |_| 11_000.0, 200).expect("protection never tripped");
+
+        // Half a second of 10 ms blocks, against 75% of each block
+        assert!(block >= 50, "tripped after {} blocks", block);
+        assert_eq!((overrun.index, overrun.effect_type.as_str()), (1, "reverb"));
+        assert_eq!(overrun.budget_micros, 7_500.0);
+        assert!(overrun.chain_micros > overrun.budget_micros);
+        assert!(chain.slots[1].is_bypassed() && !chain.slots[0].is_bypassed());
+
+        // The cheaper chain stays under budget
+        assert!(run_for(&mut chain, |_| 600.0, 200).is_none());
+    }
+
+    #[test]
+    fn brief_spikes_dont_trip_the_protection() {
+        let mut chain = chain_of(&["reverb"]);
+        chain.set_overrun_protection(true, 48_000);
+        let spiky = |block: usize| if block % 10 == 0 { 20_000.0 } else { 100.0 };
+        assert!(run_for(&mut chain, spiky, 200).is_none());
+        assert!(!chain.slots[0].is_bypassed());
+    }
+}
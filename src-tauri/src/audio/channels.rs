@@ -0,0 +1,81 @@
+// Channel-count adaptation between the capture device and the encoder
+
+// Averages each interleaved frame into one sample
+pub fn downmix_to_mono(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+    let scale = 1.0 / channels as f32;
+    samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() * scale)
+        .collect()
+}
+
+// Duplicates a mono signal into both sides of an interleaved stereo pair
+pub fn upmix_to_stereo(samples: &[f32]) -> Vec<f32> {
+    samples.iter().flat_map(|&s| [s, s]).collect()
+}
+
+// Keeps only the `selected` channels (0-based) of each interleaved frame, in
+// the order given, e.g. inputs 3-4 of a multi-input interface
+pub fn extract_channels(samples: &[f32], channels: usize, selected: &[u16]) -> Vec<f32> {
+    samples
+        .chunks_exact(channels.max(1))
+        .flat_map(|frame| selected.iter().map(move |&c| frame[c as usize]))
+        .collect()
+}
+
+// Converts interleaved audio between channel counts. Mono is averaged down or
+// copied up; otherwise the leading channels are kept (front L/R for surround
+// layouts) and the last one repeated if more are needed.
+pub fn convert_channels(samples: &[f32], from: usize, to: usize) -> Vec<f32> {
+    let (from, to) = (from.max(1), to.max(1));
+    match (from, to) {
+        _ if from == to => samples.to_vec(),
+        (_, 1) => downmix_to_mono(samples, from),
+        (1, 2) => upmix_to_stereo(samples),
+        _ => samples
+            .chunks_exact(from)
+            .flat_map(|frame| (0..to).map(move |c| frame[c.min(from - 1)]))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_averages_a_hard_panned_pair() {
+        // Full scale on the left only comes out at half level
+        let stereo: Vec<f32> = (0..4).flat_map(|_| [0.8, 0.0]).collect();
+        assert_eq!(downmix_to_mono(&stereo, 2), vec![0.4; 4]);
+        assert_eq!(convert_channels(&stereo, 2, 1), vec![0.4; 4]);
+    }
+
+    #[test]
+    fn upmix_copies_mono_to_both_sides() {
+        assert_eq!(upmix_to_stereo(&[0.1, -0.2]), vec![0.1, 0.1, -0.2, -0.2]);
+        assert_eq!(
+            convert_channels(&[0.1, -0.2], 1, 2),
+            vec![0.1, 0.1, -0.2, -0.2]
+        );
+    }
+
+    #[test]
+    fn extracts_the_first_pair_of_four_channels() {
+        // Each sample holds frame * 10 + channel
+        let samples: Vec<f32> = (0..3)
+            .flat_map(|frame| (0..4).map(move |c| (frame * 10 + c) as f32))
+            .collect();
+        assert_eq!(
+            extract_channels(&samples, 4, &[0, 1]),
+            [0.0, 1.0, 10.0, 11.0, 20.0, 21.0]
+        );
+        assert_eq!(
+            extract_channels(&samples, 4, &[3, 2]),
+            [3.0, 2.0, 13.0, 12.0, 23.0, 22.0]
+        );
+    }
+}
@@ -0,0 +1,318 @@
+// Voice/music detection for the encoder's signal hint. Opus tunes its mode
+// decisions differently for speech and music, so a show that moves between
+// talk and songs encodes better when the hint follows the content.
+//
+// The outgoing audio is downmixed and cut into ~21 ms frames, each measured
+// for spectral flatness, zero-crossing rate and energy. Once per window the
+// frames vote: speech has syllabic pauses (many frames well below the mean
+// energy), alternates voiced and unvoiced sounds (a zero-crossing rate that
+// swings), and its fricatives are noise-like (flat spectra). Sustained music
+// is steadier and more tonal on all three. A change only sticks after several
+// windows agree, so a drum break or a pause doesn't flip the encoder back and forth.
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// ~21 ms at 48 kHz, short enough to resolve syllables
+const FRAME_SIZE: usize = 1024;
+
+// Length of one vote
+pub const CLASSIFY_WINDOW_SECS: f32 = 1.0;
+
+// Consecutive windows that must disagree with the current decision before it
+// changes
+const SWITCH_WINDOWS: u32 = 3;
+
+// Windows scoring between these are undecided and keep the current decision
+const VOICE_SCORE: f32 = 0.6;
+const MUSIC_SCORE: f32 = 0.4;
+
+// Windows quieter than this don't vote, so silence keeps the current decision
+const SILENT_RMS: f32 = 0.001;
+
+// A frame under half the window's mean energy counts as a pause
+const LOW_ENERGY_RATIO: f32 = 0.5;
+
+// Flatness is measured over the band where voice and instruments both live
+const FLATNESS_LOW_HZ: f32 = 100.0;
+const FLATNESS_HIGH_HZ: f32 = 8000.0;
+
+// Each feature maps linearly from its music-like end (score 0) to its
+// speech-like end (score 1)
+const LOW_ENERGY_RANGE: (f32, f32) = (0.1, 0.4);
+const ZCR_VARIATION_RANGE: (f32, f32) = (0.3, 0.9);
+const FLATNESS_RANGE: (f32, f32) = (0.05, 0.3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentType {
+    Voice,
+    Music,
+}
+
+// One analysis frame's features
+#[derive(Debug, Clone, Copy)]
+struct FrameFeatures {
+    rms: f32,
+    zero_crossing_rate: f32,
+    flatness: f32,
+}
+
+pub struct ContentClassifier {
+    channels: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    // Bins the flatness is measured over
+    flatness_bins: std::ops::Range<usize>,
+    frames_per_window: usize,
+    pending: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    frames: Vec<FrameFeatures>,
+    content: ContentType,
+    // Windows in a row that voted against `content`
+    disagreeing: u32,
+}
+
+impl ContentClassifier {
+    // Starts out at `initial`, the hint the encoder already has
+    pub fn new(sample_rate: u32, channels: usize, initial: ContentType) -> Self {
+        let bin_hz = sample_rate as f32 / FRAME_SIZE as f32;
+        let low = ((FLATNESS_LOW_HZ / bin_hz) as usize).max(1);
+        let high = ((FLATNESS_HIGH_HZ / bin_hz) as usize).clamp(low + 1, FRAME_SIZE / 2);
+        let frames_per_window =
+            ((CLASSIFY_WINDOW_SECS * sample_rate as f32) as usize / FRAME_SIZE).max(1);
+        Self {
+            channels: channels.max(1),
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            window: (0..FRAME_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME_SIZE as f32).cos())
+                .collect(),
+            flatness_bins: low..high,
+            frames_per_window,
+            pending: Vec::with_capacity(FRAME_SIZE * 2),
+            buffer: vec![Complex::default(); FRAME_SIZE],
+            frames: Vec::with_capacity(frames_per_window),
+            content: initial,
+            disagreeing: 0,
+        }
+    }
+
+    pub fn content(&self) -> ContentType {
+        self.content
+    }
+
+    // Takes an interleaved block; returns the new decision when it changes
+    pub fn process(&mut self, samples: &[f32]) -> Option<ContentType> {
+        let scale = 1.0 / self.channels as f32;
+        self.pending.extend(
+            samples
+                .chunks_exact(self.channels)
+                .map(|frame| frame.iter().sum::<f32>() * scale),
+        );
+
+        let mut changed = None;
+        let mut start = 0;
+        while self.pending.len() - start >= FRAME_SIZE {
+            let features = self.analyze(start);
+            self.frames.push(features);
+            start += FRAME_SIZE;
+            if self.frames.len() >= self.frames_per_window {
+                changed = self.vote().or(changed);
+                self.frames.clear();
+            }
+        }
+        self.pending.drain(..start);
+        changed
+    }
+
+    fn analyze(&mut self, start: usize) -> FrameFeatures {
+        let frame = &self.pending[start..start + FRAME_SIZE];
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / FRAME_SIZE as f32).sqrt();
+        let crossings = frame
+            .windows(2)
+            .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+            .count();
+
+        for ((bin, &sample), &window) in self.buffer.iter_mut().zip(frame).zip(&self.window) {
+            *bin = Complex::new(sample * window, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        // Geometric over arithmetic mean of the power spectrum: near 0 for a
+        // few strong partials, 1 for white noise
+        let bins = &self.buffer[self.flatness_bins.clone()];
+        let powers = bins.iter().map(|bin| bin.norm_sqr() + 1e-12);
+        let (log_sum, sum) = powers.fold((0.0f64, 0.0f64), |(log_sum, sum), power| {
+            (log_sum + (power as f64).ln(), sum + power as f64)
+        });
+        let count = bins.len() as f64;
+        let flatness = ((log_sum / count).exp() / (sum / count)) as f32;
+
+        FrameFeatures {
+            rms,
+            zero_crossing_rate: crossings as f32 / FRAME_SIZE as f32,
+            flatness,
+        }
+    }
+
+    // Scores the window's frames and applies the hysteresis; returns the new
+    // decision when it changes
+    fn vote(&mut self) -> Option<ContentType> {
+        let vote = match window_score(&self.frames) {
+            Some(score) if score >= VOICE_SCORE => ContentType::Voice,
+            Some(score) if score <= MUSIC_SCORE => ContentType::Music,
+            // Silent or undecided: neither side gains ground
+            _ => return None,
+        };
+        if vote == self.content {
+            self.disagreeing = 0;
+            return None;
+        }
+        self.disagreeing += 1;
+        if self.disagreeing < SWITCH_WINDOWS {
+            return None;
+        }
+        self.disagreeing = 0;
+        self.content = vote;
+        Some(vote)
+    }
+}
+
+// 0 for clearly music, 1 for clearly speech; None for a silent window
+fn window_score(frames: &[FrameFeatures]) -> Option<f32> {
+    if frames.is_empty() {
+        return None;
+    }
+    let count = frames.len() as f32;
+    let mean_rms = frames.iter().map(|f| f.rms).sum::<f32>() / count;
+    if mean_rms < SILENT_RMS {
+        return None;
+    }
+
+    let low_energy = frames
+        .iter()
+        .filter(|f| f.rms < mean_rms * LOW_ENERGY_RATIO)
+        .count() as f32
+        / count;
+
+    // Only sounding frames count for the spectral features; a pause has
+    // whatever zero-crossing rate and flatness its noise floor gives it
+    let sounding: Vec<&FrameFeatures> = frames
+        .iter()
+        .filter(|f| f.rms >= mean_rms * LOW_ENERGY_RATIO)
+        .collect();
+    let sounding_count = sounding.len() as f32;
+    let mean_zcr = sounding.iter().map(|f| f.zero_crossing_rate).sum::<f32>() / sounding_count;
+    let zcr_deviation = (sounding
+        .iter()
+        .map(|f| (f.zero_crossing_rate - mean_zcr).powi(2))
+        .sum::<f32>()
+        / sounding_count)
+        .sqrt();
+    let zcr_variation = if mean_zcr > 0.0 {
+        zcr_deviation / mean_zcr
+    } else {
+        0.0
+    };
+    let flatness = sounding.iter().map(|f| f.flatness).sum::<f32>() / sounding_count;
+
+    let scores = [
+        ramp(low_energy, LOW_ENERGY_RANGE),
+        ramp(zcr_variation, ZCR_VARIATION_RANGE),
+        ramp(flatness, FLATNESS_RANGE),
+    ];
+    Some(scores.iter().sum::<f32>() / scores.len() as f32)
+}
+
+fn ramp(value: f32, (from, to): (f32, f32)) -> f32 {
+    ((value - from) / (to - from)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // Deterministic white noise in -1..1
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self) -> f32 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (self.0 >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+        }
+    }
+
+    // A sustained chord with slow vibrato over a faint noise floor
+    fn music(secs: f32) -> Vec<f32> {
+        let mut noise = Noise(3);
+        (0..(secs * 48_000.0) as usize)
+            .map(|n| {
+                let t = n as f32 / 48_000.0;
+                let vibrato = 0.3 * (2.0 * PI * 5.0 * t).sin();
+                let chord: f32 = [220.0, 277.2, 329.6, 440.0, 659.3]
+                    .iter()
+                    .enumerate()
+                    .map(|(k, freq)| (2.0 * PI * freq * t + vibrato).sin() * 0.1 / (k as f32 + 1.0))
+                    .sum();
+                chord + noise.next() * 0.002
+            })
+            .collect()
+    }
+
+    // Syllables every 300 ms: a voiced harmonic burst, a fricative, then a pause
+    fn speech(secs: f32) -> Vec<f32> {
+        let mut noise = Noise(9);
+        let mut phase = 0.0f32;
+        (0..(secs * 48_000.0) as usize)
+            .map(|n| {
+                let t = n as f32 / 48_000.0;
+                let syllable = t % 0.3;
+                phase += 2.0 * PI * (120.0 + 20.0 * (2.0 * PI * 1.3 * t).sin()) / 48_000.0;
+                let voice = if syllable < 0.14 {
+                    let envelope = (PI * syllable / 0.14).sin();
+                    (1..20)
+                        .map(|h| (phase * h as f32).sin() / h as f32)
+                        .sum::<f32>()
+                        * 0.15
+                        * envelope
+                } else if syllable < 0.2 {
+                    noise.next() * 0.08
+                } else {
+                    0.0
+                };
+                voice + noise.next() * 0.003
+            })
+            .collect()
+    }
+
+    // Decisions the classifier changes to over `audio`, fed as stereo 20 ms blocks
+    fn changes(classifier: &mut ContentClassifier, audio: &[f32]) -> Vec<ContentType> {
+        let stereo: Vec<f32> = audio.iter().flat_map(|&s| [s, s]).collect();
+        stereo
+            .chunks(1_920)
+            .filter_map(|block| classifier.process(block))
+            .collect()
+    }
+
+    #[test]
+    fn speech_and_music_drive_the_expected_decision() {
+        let mut classifier = ContentClassifier::new(48_000, 2, ContentType::Music);
+        assert!(changes(&mut classifier, &music(6.0)).is_empty());
+        assert_eq!(changes(&mut classifier, &speech(6.0)), [ContentType::Voice]);
+        assert_eq!(changes(&mut classifier, &music(6.0)), [ContentType::Music]);
+        assert_eq!(classifier.content(), ContentType::Music);
+    }
+
+    #[test]
+    fn short_bursts_and_silence_keep_the_decision() {
+        let mut classifier = ContentClassifier::new(48_000, 2, ContentType::Music);
+        let mut interruption = speech(1.5);
+        interruption.extend(music(3.0));
+        assert!(changes(&mut classifier, &interruption).is_empty());
+        assert!(changes(&mut classifier, &vec![0.0; 48_000 * 5]).is_empty());
+        assert_eq!(classifier.content(), ContentType::Music);
+    }
+}
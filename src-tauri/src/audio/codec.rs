@@ -0,0 +1,570 @@
+// Opus encoder/decoder covering 1-8 channels. A single Opus stream only holds
+// mono or stereo, so wider layouts go through libopus' multistream API, which
+// splits them into coupled stereo pairs plus mono streams. The opus crate
+// doesn't wrap that API, so it's called through audiopus_sys directly.
+#[cfg(feature = "aac")]
+use super::aac::AacEncoder;
+use super::classifier::ContentType;
+use super::ogg::ChannelMapping;
+use audiopus_sys as ffi;
+use serde::{Deserialize, Serialize};
+use std::ffi::CStr;
+use std::os::raw::c_int;
+
+pub const MAX_CHANNELS: u16 = 8;
+
+// Vorbis channel order; what players expect for 3-8 channel Ogg Opus
+const SURROUND_MAPPING_FAMILY: c_int = 1;
+
+// Largest compressed Opus frame (RFC 6716 3.2.1)
+pub const MAX_OPUS_FRAME_BYTES: usize = 1275;
+
+// Longest frame Opus codes; a longer packet carries several frames
+const MAX_OPUS_FRAME_MS: f64 = 20.0;
+
+// Packet durations Opus can encode in one call. Shorter packets cut latency
+// but raise the packet rate and the share of bytes spent on headers.
+pub const OPUS_FRAME_SIZES_MS: [f32; 6] = [2.5, 5.0, 10.0, 20.0, 40.0, 60.0];
+
+// Framing around the compressed frames: the TOC and frame count bytes, and up
+// to two length bytes per frame (also covers the self-delimiting length of
+// each multistream sub-packet)
+const PACKET_HEADER_BYTES: usize = 2;
+const FRAME_LENGTH_BYTES: usize = 2;
+
+// libopus error code for an output buffer that can't hold the packet
+const OPUS_BUFFER_TOO_SMALL: c_int = -2;
+
+// libopus CTL requests and special values (opus_defines.h)
+const OPUS_SET_BITRATE_REQUEST: c_int = 4002;
+const OPUS_SET_COMPLEXITY_REQUEST: c_int = 4010;
+const OPUS_SET_SIGNAL_REQUEST: c_int = 4024;
+const OPUS_RESET_STATE: c_int = 4028;
+const OPUS_AUTO: c_int = -1000;
+const OPUS_BITRATE_MAX: c_int = -1;
+const OPUS_OK: c_int = 0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    #[error("Opus error: {0}")]
+    Opus(#[from] opus::Error),
+    #[error("Opus {0} failed: {1}")]
+    Multistream(&'static str, String),
+    #[error("Unsupported channel count: {0} (Opus takes 1 to {MAX_CHANNELS})")]
+    UnsupportedChannels(u16),
+    #[error("Encoded packet does not fit the {0}-byte encode buffer")]
+    BufferTooSmall(usize),
+    #[error("AAC error: {0}")]
+    Aac(String),
+    #[error("Unsupported channel count: {0} (AAC takes 1 or 2)")]
+    UnsupportedAacChannels(u16),
+    #[error("AAC is not available in this build (enable the aac feature)")]
+    AacUnavailable,
+}
+
+// What a stream's main encoder produces. Opus is what every sink speaks; AAC
+// is for destinations that only take AAC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Codec {
+    #[default]
+    Opus,
+    Aac,
+}
+
+// A stream's main encoder, whatever the codec; the pipeline only goes
+// through this
+pub trait Encoder: Send {
+    fn codec(&self) -> Codec;
+
+    // Output buffer size that fits everything one `frames`-frame block can
+    // produce at `sample_rate`
+    fn max_packet_bytes(&self, frames: usize, sample_rate: u32) -> usize;
+
+    // None runs at the codec's maximum
+    fn set_target_bitrate(&mut self, bitrate: Option<u32>) -> Result<(), CodecError>;
+
+    fn set_complexity(&mut self, complexity: i32) -> Result<(), CodecError>;
+
+    fn set_content(&mut self, content: ContentType) -> Result<(), CodecError>;
+
+    // Encodes one interleaved block and returns the bytes written, which is
+    // zero while a codec with fixed-length frames is still filling one
+    fn encode(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, CodecError>;
+
+    // Stream layout for Ogg Opus sinks; None for other codecs
+    fn opus_mapping(&self) -> Option<&ChannelMapping> {
+        None
+    }
+}
+
+// A main encoder for `codec` at its maximum bitrate, tuned for music
+pub fn new_encoder(
+    codec: Codec,
+    sample_rate: u32,
+    channels: u16,
+    complexity: i32,
+) -> Result<Box<dyn Encoder>, CodecError> {
+    let mut encoder: Box<dyn Encoder> = match codec {
+        // Mono and stereo use a single Opus stream; 3-8 channels go multistream
+        Codec::Opus => Box::new(OpusEncoder::new(
+            sample_rate,
+            channels,
+            opus::Application::Audio,
+        )?),
+        #[cfg(feature = "aac")]
+        Codec::Aac => Box::new(AacEncoder::new(sample_rate, channels, None)?),
+        #[cfg(not(feature = "aac"))]
+        Codec::Aac => return Err(CodecError::AacUnavailable),
+    };
+    encoder.set_target_bitrate(None)?;
+    encoder.set_complexity(complexity)?;
+    encoder.set_content(ContentType::Music)?;
+    Ok(encoder)
+}
+
+// Room for the largest packet `streams` Opus streams can produce from
+// `frames` frames at `sample_rate`: 1275 bytes per 20 ms (or shorter) Opus
+// frame, plus framing. Any bitrate fits.
+pub fn max_packet_bytes(streams: u8, frames: usize, sample_rate: u32) -> usize {
+    let packet_ms = frames as f64 * 1000.0 / sample_rate.max(1) as f64;
+    let opus_frames = (packet_ms / MAX_OPUS_FRAME_MS).ceil().max(1.0) as usize;
+    let per_stream =
+        opus_frames * (MAX_OPUS_FRAME_BYTES + FRAME_LENGTH_BYTES) + PACKET_HEADER_BYTES;
+    per_stream * streams.max(1) as usize
+}
+
+// Frames in one `frame_size_ms` packet at `sample_rate`; None unless the
+// duration is one of OPUS_FRAME_SIZES_MS
+pub fn opus_frame_frames(frame_size_ms: f32, sample_rate: u32) -> Option<usize> {
+    OPUS_FRAME_SIZES_MS
+        .contains(&frame_size_ms)
+        .then(|| (frame_size_ms as f64 * sample_rate as f64 / 1000.0).round() as usize)
+}
+
+// The encoder hint for detected content
+pub fn content_signal(content: ContentType) -> opus::Signal {
+    match content {
+        ContentType::Voice => opus::Signal::Voice,
+        ContentType::Music => opus::Signal::Music,
+    }
+}
+
+fn check(function: &'static str, code: c_int) -> Result<c_int, CodecError> {
+    if code >= OPUS_OK {
+        return Ok(code);
+    }
+    // opus_strerror returns a static string for every code
+    let message = unsafe { CStr::from_ptr(ffi::opus_strerror(code)) };
+    Err(CodecError::Multistream(
+        function,
+        message.to_string_lossy().into_owned(),
+    ))
+}
+
+fn bitrate_value(bitrate: opus::Bitrate) -> c_int {
+    match bitrate {
+        opus::Bitrate::Bits(bits) => bits,
+        opus::Bitrate::Max => OPUS_BITRATE_MAX,
+        opus::Bitrate::Auto => OPUS_AUTO,
+    }
+}
+
+pub struct MultistreamEncoder {
+    ptr: *mut ffi::OpusMSEncoder,
+    channels: usize,
+    mapping: ChannelMapping,
+}
+
+// The encoder state is plain memory with no thread affinity; access is
+// serialized by the Mutex it lives in
+unsafe impl Send for MultistreamEncoder {}
+
+impl MultistreamEncoder {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        application: opus::Application,
+    ) -> Result<Self, CodecError> {
+        let mut streams: c_int = 0;
+        let mut coupled_streams: c_int = 0;
+        let mut mapping = vec![0u8; channels as usize];
+        let mut error: c_int = 0;
+        let ptr = unsafe {
+            ffi::opus_multistream_surround_encoder_create(
+                sample_rate as i32,
+                channels as c_int,
+                SURROUND_MAPPING_FAMILY,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                application as c_int,
+                &mut error,
+            )
+        };
+        check("opus_multistream_surround_encoder_create", error)?;
+        if ptr.is_null() {
+            return Err(CodecError::UnsupportedChannels(channels));
+        }
+
+        Ok(Self {
+            ptr,
+            channels: channels as usize,
+            mapping: ChannelMapping {
+                family: SURROUND_MAPPING_FAMILY as u8,
+                streams: streams as u8,
+                coupled_streams: coupled_streams as u8,
+                mapping,
+            },
+        })
+    }
+
+    fn ctl(&mut self, request: c_int, value: c_int) -> Result<(), CodecError> {
+        let code = unsafe { ffi::opus_multistream_encoder_ctl(self.ptr, request, value) };
+        check("opus_multistream_encoder_ctl", code).map(|_| ())
+    }
+
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, CodecError> {
+        let frames = input.len() / self.channels;
+        let len = unsafe {
+            ffi::opus_multistream_encode_float(
+                self.ptr,
+                input.as_ptr(),
+                frames as c_int,
+                output.as_mut_ptr(),
+                output.len().min(i32::MAX as usize) as i32,
+            )
+        };
+        if len == OPUS_BUFFER_TOO_SMALL {
+            return Err(CodecError::BufferTooSmall(output.len()));
+        }
+        check("opus_multistream_encode_float", len).map(|len| len as usize)
+    }
+}
+
+impl Drop for MultistreamEncoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_encoder_destroy(self.ptr) }
+    }
+}
+
+pub struct MultistreamDecoder {
+    ptr: *mut ffi::OpusMSDecoder,
+    channels: usize,
+}
+
+unsafe impl Send for MultistreamDecoder {}
+
+impl MultistreamDecoder {
+    pub fn new(sample_rate: u32, mapping: &ChannelMapping) -> Result<Self, CodecError> {
+        let channels = mapping.channels();
+        let mut error: c_int = 0;
+        let ptr = unsafe {
+            ffi::opus_multistream_decoder_create(
+                sample_rate as i32,
+                channels as c_int,
+                mapping.streams as c_int,
+                mapping.coupled_streams as c_int,
+                mapping.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+        check("opus_multistream_decoder_create", error)?;
+        if ptr.is_null() {
+            return Err(CodecError::UnsupportedChannels(channels as u16));
+        }
+        Ok(Self {
+            ptr,
+            channels: channels as usize,
+        })
+    }
+
+    pub fn reset(&mut self) -> Result<(), CodecError> {
+        let code = unsafe { ffi::opus_multistream_decoder_ctl(self.ptr, OPUS_RESET_STATE) };
+        check("opus_multistream_decoder_ctl", code).map(|_| ())
+    }
+
+    // Returns the number of frames written to `output`
+    pub fn decode_float(
+        &mut self,
+        input: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, CodecError> {
+        let frames = unsafe {
+            ffi::opus_multistream_decode_float(
+                self.ptr,
+                input.as_ptr(),
+                input.len() as i32,
+                output.as_mut_ptr(),
+                (output.len() / self.channels) as c_int,
+                fec as c_int,
+            )
+        };
+        check("opus_multistream_decode_float", frames).map(|frames| frames as usize)
+    }
+}
+
+impl Drop for MultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_decoder_destroy(self.ptr) }
+    }
+}
+
+// What the engine encodes with: the plain encoder for mono/stereo, the
+// multistream one above that
+pub enum OpusEncoder {
+    Single(opus::Encoder, ChannelMapping),
+    Multistream(MultistreamEncoder),
+}
+
+impl OpusEncoder {
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        application: opus::Application,
+    ) -> Result<Self, CodecError> {
+        match channels {
+            1 | 2 => {
+                let layout = if channels == 1 {
+                    opus::Channels::Mono
+                } else {
+                    opus::Channels::Stereo
+                };
+                let encoder = opus::Encoder::new(sample_rate, layout, application)?;
+                Ok(OpusEncoder::Single(
+                    encoder,
+                    ChannelMapping::single_stream(channels as u8),
+                ))
+            }
+            3..=MAX_CHANNELS => Ok(OpusEncoder::Multistream(MultistreamEncoder::new(
+                sample_rate,
+                channels,
+                application,
+            )?)),
+            _ => Err(CodecError::UnsupportedChannels(channels)),
+        }
+    }
+
+    pub fn mapping(&self) -> &ChannelMapping {
+        match self {
+            OpusEncoder::Single(_, mapping) => mapping,
+            OpusEncoder::Multistream(encoder) => &encoder.mapping,
+        }
+    }
+
+    // Output buffer size that fits any packet this encoder can produce from
+    // `frames`-frame blocks at `sample_rate`
+    pub fn max_packet_bytes(&self, frames: usize, sample_rate: u32) -> usize {
+        max_packet_bytes(self.mapping().streams, frames, sample_rate)
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: opus::Bitrate) -> Result<(), CodecError> {
+        match self {
+            OpusEncoder::Single(encoder, _) => Ok(encoder.set_bitrate(bitrate)?),
+            OpusEncoder::Multistream(encoder) => {
+                encoder.ctl(OPUS_SET_BITRATE_REQUEST, bitrate_value(bitrate))
+            }
+        }
+    }
+
+    pub fn set_complexity(&mut self, complexity: i32) -> Result<(), CodecError> {
+        match self {
+            OpusEncoder::Single(encoder, _) => Ok(encoder.set_complexity(complexity)?),
+            OpusEncoder::Multistream(encoder) => {
+                encoder.ctl(OPUS_SET_COMPLEXITY_REQUEST, complexity)
+            }
+        }
+    }
+
+    pub fn set_signal(&mut self, signal: opus::Signal) -> Result<(), CodecError> {
+        match self {
+            OpusEncoder::Single(encoder, _) => Ok(encoder.set_signal(signal)?),
+            OpusEncoder::Multistream(encoder) => {
+                encoder.ctl(OPUS_SET_SIGNAL_REQUEST, signal as c_int)
+            }
+        }
+    }
+
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, CodecError> {
+        match self {
+            OpusEncoder::Single(encoder, _) => encoder.encode_float(input, output).map_err(|e| {
+                if e.code() == opus::ErrorCode::BufferTooSmall {
+                    CodecError::BufferTooSmall(output.len())
+                } else {
+                    e.into()
+                }
+            }),
+            OpusEncoder::Multistream(encoder) => encoder.encode_float(input, output),
+        }
+    }
+}
+
+impl Encoder for OpusEncoder {
+    fn codec(&self) -> Codec {
+        Codec::Opus
+    }
+
+    fn max_packet_bytes(&self, frames: usize, sample_rate: u32) -> usize {
+        OpusEncoder::max_packet_bytes(self, frames, sample_rate)
+    }
+
+    fn set_target_bitrate(&mut self, bitrate: Option<u32>) -> Result<(), CodecError> {
+        self.set_bitrate(
+            bitrate.map_or(opus::Bitrate::Max, |bits| opus::Bitrate::Bits(bits as i32)),
+        )
+    }
+
+    fn set_complexity(&mut self, complexity: i32) -> Result<(), CodecError> {
+        OpusEncoder::set_complexity(self, complexity)
+    }
+
+    fn set_content(&mut self, content: ContentType) -> Result<(), CodecError> {
+        self.set_signal(content_signal(content))
+    }
+
+    fn encode(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize, CodecError> {
+        self.encode_float(input, output)
+    }
+
+    fn opus_mapping(&self) -> Option<&ChannelMapping> {
+        Some(self.mapping())
+    }
+}
+
+// Decoder matching an OpusEncoder (or an OpusHead read from a file)
+pub enum OpusDecoder {
+    Single(opus::Decoder),
+    Multistream(MultistreamDecoder),
+}
+
+impl OpusDecoder {
+    pub fn new(sample_rate: u32, mapping: &ChannelMapping) -> Result<Self, CodecError> {
+        match (mapping.family, mapping.channels()) {
+            (0, 1) => Ok(OpusDecoder::Single(opus::Decoder::new(
+                sample_rate,
+                opus::Channels::Mono,
+            )?)),
+            (0, 2) => Ok(OpusDecoder::Single(opus::Decoder::new(
+                sample_rate,
+                opus::Channels::Stereo,
+            )?)),
+            (0, channels) => Err(CodecError::UnsupportedChannels(channels as u16)),
+            _ => Ok(OpusDecoder::Multistream(MultistreamDecoder::new(
+                sample_rate,
+                mapping,
+            )?)),
+        }
+    }
+
+    // Returns the number of frames written to `output`
+    pub fn decode_float(
+        &mut self,
+        input: &[u8],
+        output: &mut [f32],
+        fec: bool,
+    ) -> Result<usize, CodecError> {
+        match self {
+            OpusDecoder::Single(decoder) => Ok(decoder.decode_float(input, output, fec)?),
+            OpusDecoder::Multistream(decoder) => decoder.decode_float(input, output, fec),
+        }
+    }
+
+    // Forgets previous packets, as if freshly created
+    pub fn reset(&mut self) -> Result<(), CodecError> {
+        match self {
+            OpusDecoder::Single(decoder) => Ok(decoder.reset_state()?),
+            OpusDecoder::Multistream(decoder) => decoder.reset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms(samples: impl Iterator<Item = f32>) -> f32 {
+        let (sum, count) = samples.fold((0.0, 0usize), |(sum, count), s| (sum + s * s, count + 1));
+        (sum / count as f32).sqrt()
+    }
+
+    #[test]
+    fn four_channels_round_trip_through_multistream() {
+        const CHANNELS: usize = 4;
+        const FRAMES: usize = 960;
+        // A different level on each channel, one of them silent, so a channel
+        // that came back in the wrong place would show up
+        let levels = [0.5, 0.2, 0.0, 0.35];
+
+        let mut encoder =
+            OpusEncoder::new(48_000, CHANNELS as u16, opus::Application::Audio).unwrap();
+        encoder.set_bitrate(opus::Bitrate::Bits(256_000)).unwrap();
+        assert!(matches!(encoder, OpusEncoder::Multistream(_)));
+        let mut decoder = OpusDecoder::new(48_000, encoder.mapping()).unwrap();
+
+        let mut packet = vec![0u8; encoder.max_packet_bytes(FRAMES, 48_000)];
+        let mut decoded = Vec::new();
+        for block in 0..50 {
+            let input: Vec<f32> = (0..FRAMES)
+                .flat_map(|i| {
+                    let phase =
+                        (block * FRAMES + i) as f32 * 2.0 * std::f32::consts::PI * 440.0 / 48_000.0;
+                    levels.map(|level| level * phase.sin())
+                })
+                .collect();
+            let len = encoder.encode_float(&input, &mut packet).unwrap();
+            let mut output = vec![0.0; FRAMES * CHANNELS];
+            assert_eq!(
+                decoder
+                    .decode_float(&packet[..len], &mut output, false)
+                    .unwrap(),
+                FRAMES
+            );
+            decoded.extend(output);
+        }
+
+        // Skip the first blocks while the codec's delay fills with signal
+        let settled = &decoded[10 * FRAMES * CHANNELS..];
+        for (channel, level) in levels.iter().enumerate() {
+            let level_out = rms(settled.iter().skip(channel).step_by(CHANNELS).copied());
+            let expected = level / std::f32::consts::SQRT_2;
+            assert!(
+                (level_out - expected).abs() < 0.02,
+                "channel {} came back at {} instead of {}",
+                channel,
+                level_out,
+                expected
+            );
+        }
+    }
+
+    // Deterministic full-scale white noise, the hardest input to compress
+    fn noise(samples: usize) -> Vec<f32> {
+        let mut seed = 0x1234_5678u32;
+        (0..samples)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packet_buffer_fits_max_bitrate_60_ms_frames() {
+        const FRAMES: usize = 2_880;
+        assert_eq!(opus_frame_frames(60.0, 48_000), Some(FRAMES));
+        // Three 20 ms frames of up to 1275 bytes each, plus framing
+        assert_eq!(max_packet_bytes(1, FRAMES, 48_000), 3_833);
+
+        for channels in [1u16, 2, 6, 8] {
+            let mut encoder = OpusEncoder::new(48_000, channels, opus::Application::Audio).unwrap();
+            encoder.set_bitrate(opus::Bitrate::Max).unwrap();
+            let mut packet = vec![0u8; encoder.max_packet_bytes(FRAMES, 48_000)];
+            let input = noise(FRAMES * channels as usize);
+            for _ in 0..10 {
+                let len = encoder.encode_float(&input, &mut packet).unwrap();
+                assert!(len <= packet.len());
+            }
+        }
+    }
+}
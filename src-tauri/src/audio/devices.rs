@@ -0,0 +1,80 @@
+// What a device can be opened with, as reported by cpal, so the UI only offers
+// configs that will actually start
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigRange {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+// Ranges are empty for a direction the device doesn't have. A device that
+// fails to report still returns whatever could be read, with the failures
+// listed in `errors`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCapabilities {
+    pub name: String,
+    pub inputs: Vec<ConfigRange>,
+    pub outputs: Vec<ConfigRange>,
+    pub errors: Vec<String>,
+}
+
+fn config_range(config: cpal::SupportedStreamConfigRange) -> ConfigRange {
+    ConfigRange {
+        channels: config.channels(),
+        min_sample_rate: config.min_sample_rate().0,
+        max_sample_rate: config.max_sample_rate().0,
+        sample_format: format!("{:?}", config.sample_format()),
+    }
+}
+
+// None if no input or output device on `host` has this name
+pub fn device_capabilities(host: &cpal::Host, name: &str) -> Option<DeviceCapabilities> {
+    let mut errors = Vec::new();
+    let named = |device: &cpal::Device| device.name().map(|n| n == name).unwrap_or(false);
+
+    let input = match host.input_devices() {
+        Ok(mut devices) => devices.find(named),
+        Err(e) => {
+            errors.push(format!("Cannot list input devices: {}", e));
+            None
+        }
+    };
+    let output = match host.output_devices() {
+        Ok(mut devices) => devices.find(named),
+        Err(e) => {
+            errors.push(format!("Cannot list output devices: {}", e));
+            None
+        }
+    };
+    if input.is_none() && output.is_none() {
+        return None;
+    }
+
+    let inputs = match input.map(|d| d.supported_input_configs()) {
+        Some(Ok(configs)) => configs.map(config_range).collect(),
+        Some(Err(e)) => {
+            errors.push(format!("Cannot read input configs: {}", e));
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    let outputs = match output.map(|d| d.supported_output_configs()) {
+        Some(Ok(configs)) => configs.map(config_range).collect(),
+        Some(Err(e)) => {
+            errors.push(format!("Cannot read output configs: {}", e));
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+
+    Some(DeviceCapabilities {
+        name: name.to_string(),
+        inputs,
+        outputs,
+        errors,
+    })
+}
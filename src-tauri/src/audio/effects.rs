@@ -1,8 +1,32 @@
-use super::{AudioEffect, EffectParameter, EQBand};
+use super::{AudioEffect, BandLevel, EQBand, EffectParameter, ParamScale, METER_FLOOR_DB};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Sample rate assumed when an effect is constructed without a "sample_rate" param
+pub const DEFAULT_SAMPLE_RATE: f32 = 48000.0;
+
+// Ramp for smoothable parameters until the chain asks for another length
+pub const DEFAULT_PARAM_SMOOTHING_SECS: f32 = 0.02;
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+pub fn linear_to_db(linear: f32) -> f32 {
+    20.0 * linear.max(1e-10).log10()
+}
+
+// One-pole smoothing coefficient reaching ~63% of a step after `seconds`
+pub fn time_coefficient(seconds: f32, sample_rate: f32) -> f32 {
+    if seconds <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / (seconds * sample_rate)).exp()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EffectParams {
     pub params: HashMap<String, f32>,
 }
@@ -29,314 +53,998 @@ impl Default for EffectParams {
     }
 }
 
-// Equalizer Effect
-pub struct EqualizerEffect {
-    bands: Vec<EQBand>,
+// Largest magnitude across one interleaved frame, for linked level detection
+fn frame_peak(frame: &[f32]) -> f32 {
+    frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
 }
 
-impl EqualizerEffect {
-    pub fn new(_params: EffectParams) -> Self {
-        // Initialize 10-band EQ with standard frequencies
-        Self {
-            bands: vec![
-                EQBand::new(32.0, 1.0, 0.0),     // Sub-bass
-                EQBand::new(64.0, 1.0, 0.0),     // Bass
-                EQBand::new(125.0, 1.0, 0.0),    // Low
-                EQBand::new(250.0, 1.0, 0.0),    // Low-mid
-                EQBand::new(500.0, 1.0, 0.0),    // Mid
-                EQBand::new(1000.0, 1.0, 0.0),   // High-mid
-                EQBand::new(2000.0, 1.0, 0.0),   // Presence
-                EQBand::new(4000.0, 1.0, 0.0),   // Brilliance
-                EQBand::new(8000.0, 1.0, 0.0),   // Air
-                EQBand::new(16000.0, 1.0, 0.0),  // Sparkle
-            ]
+// Window of the RMS detector: long enough to ride over a single transient,
+// short enough to follow syllables
+pub const RMS_DETECTOR_WINDOW_SECS: f32 = 0.01;
+
+// What a dynamics detector follows. Peak reacts to every transient; RMS
+// averages the last RMS_DETECTOR_WINDOW_SECS and responds to loudness
+// instead, which sounds smoother on vocals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectorMode {
+    Peak,
+    Rms,
+}
+
+impl DetectorMode {
+    fn from_param(value: f32) -> Self {
+        if value >= 0.5 {
+            DetectorMode::Rms
+        } else {
+            DetectorMode::Peak
+        }
+    }
+
+    fn as_param(self) -> f32 {
+        match self {
+            DetectorMode::Peak => 0.0,
+            DetectorMode::Rms => 1.0,
         }
     }
 }
 
-impl AudioEffect for EqualizerEffect {
-    fn process(&self, input: &[f32]) -> Vec<f32> {
-        let mut output = input.to_vec();
+// Which channels a stereo effect works on. Mid/side encodes L/R into
+// M = (L + R) / 2 and S = (L - R) / 2 before the effect and decodes after, so
+// per-channel processing shapes the centre and the width separately. Streams
+// that aren't stereo always process as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessingMode {
+    Stereo,
+    MidSide,
+}
 
-        for band in &self.bands {
-            output = band.apply(&output);
+impl ProcessingMode {
+    fn from_param(value: f32) -> Self {
+        if value >= 0.5 {
+            ProcessingMode::MidSide
+        } else {
+            ProcessingMode::Stereo
         }
+    }
 
-        output
+    fn as_param(self) -> f32 {
+        match self {
+            ProcessingMode::Stereo => 0.0,
+            ProcessingMode::MidSide => 1.0,
+        }
     }
 
-    fn get_name(&self) -> &str {
-        "Equalizer"
+    fn applies(self, channels: usize) -> bool {
+        self == ProcessingMode::MidSide && channels == 2
     }
+}
 
-    fn get_parameters(&self) -> Vec<EffectParameter> {
-        self.bands.iter().enumerate().map(|(i, band)| {
-            EffectParameter {
-                name: format!("band_{}", i),
-                value: band.gain,
-                min: -12.0,
-                max: 12.0,
-                step: 0.1,
-            }
-        }).collect()
+// Interleaved stereo L/R to M/S, in place
+pub fn encode_mid_side(samples: &mut [f32]) {
+    for frame in samples.chunks_exact_mut(2) {
+        let (left, right) = (frame[0], frame[1]);
+        frame[0] = (left + right) * 0.5;
+        frame[1] = (left - right) * 0.5;
     }
+}
 
-    fn set_parameter(&mut self, name: &str, value: f32) {
-        if let Some(band_idx) = name.strip_prefix("band_").and_then(|s| s.parse::<usize>().ok()) {
-            if band_idx < self.bands.len() {
-                self.bands[band_idx].gain = value;
-            }
-        }
+// Interleaved stereo M/S back to L/R, in place; exactly undoes encode_mid_side
+pub fn decode_mid_side(samples: &mut [f32]) {
+    for frame in samples.chunks_exact_mut(2) {
+        let (mid, side) = (frame[0], frame[1]);
+        frame[0] = mid + side;
+        frame[1] = mid - side;
     }
 }
 
-// Compressor Effect
-pub struct CompressorEffect {
-    threshold: f32,
-    ratio: f32,
-    attack: f32,
-    release: f32,
-    makeup_gain: f32,
+// Runs `process` on the M/S encoding of an interleaved stereo block and
+// returns its output decoded back to L/R
+pub fn process_mid_side(input: &[f32], process: impl FnOnce(&[f32]) -> Vec<f32>) -> Vec<f32> {
+    let mut mid_side = input.to_vec();
+    encode_mid_side(&mut mid_side);
+    let mut output = process(&mid_side);
+    decode_mid_side(&mut output);
+    output
 }
 
-impl CompressorEffect {
-    pub fn new(params: EffectParams) -> Self {
+// Level of each interleaved frame for a compressor or gate envelope, linked
+// across channels. The RMS window is kept between blocks, so block
+// boundaries don't show up in the level.
+pub struct LevelDetector {
+    mode: DetectorMode,
+    // Squared frame peaks over the window, written round-robin
+    window: Vec<f32>,
+    pos: usize,
+    // Running total of `window`; f64 so adding and removing doesn't drift
+    sum: f64,
+}
+
+impl LevelDetector {
+    pub fn new(mode: DetectorMode, sample_rate: f32) -> Self {
+        let length = ((RMS_DETECTOR_WINDOW_SECS * sample_rate) as usize).max(1);
         Self {
-            threshold: params.get("threshold").unwrap_or(-20.0),
-            ratio: params.get("ratio").unwrap_or(4.0),
-            attack: params.get("attack").unwrap_or(0.01),
-            release: params.get("release").unwrap_or(0.1),
-            makeup_gain: params.get("makeup").unwrap_or(1.0),
+            mode,
+            window: vec![0.0; length],
+            pos: 0,
+            sum: 0.0,
+        }
+    }
+
+    pub fn mode(&self) -> DetectorMode {
+        self.mode
+    }
+
+    // Switching to RMS starts from an empty window
+    pub fn set_mode(&mut self, mode: DetectorMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.reset();
+        }
+    }
+
+    pub fn level(&mut self, frame: &[f32]) -> f32 {
+        let peak = frame_peak(frame);
+        match self.mode {
+            DetectorMode::Peak => peak,
+            DetectorMode::Rms => {
+                let square = peak * peak;
+                self.sum += (square - self.window[self.pos]) as f64;
+                self.window[self.pos] = square;
+                self.pos = (self.pos + 1) % self.window.len();
+                (self.sum.max(0.0) / self.window.len() as f64).sqrt() as f32
+            }
         }
     }
+
+    pub fn reset(&mut self) {
+        self.window.fill(0.0);
+        self.pos = 0;
+        self.sum = 0.0;
+    }
 }
 
-impl AudioEffect for CompressorEffect {
-    fn process(&self, input: &[f32]) -> Vec<f32> {
-        let mut output = Vec::with_capacity(input.len());
-        let mut envelope = 0.0f32;
+// Linear per-frame fade toward a target gain, for click-free mute and gating
+#[derive(Debug, Clone, Copy)]
+pub struct GainRamp {
+    gain: f32,
+    step: f32,
+}
 
-        for &sample in input {
-            let input_level = sample.abs();
+impl GainRamp {
+    pub fn new(initial: f32, ramp_secs: f32, sample_rate: f32) -> Self {
+        Self {
+            gain: initial,
+            step: Self::step_for(ramp_secs, sample_rate),
+        }
+    }
 
-            // Update envelope
-            let target = input_level;
+    fn step_for(ramp_secs: f32, sample_rate: f32) -> f32 {
+        let frames = ramp_secs * sample_rate;
+        if frames <= 1.0 {
+            1.0
+        } else {
+            1.0 / frames
+        }
+    }
 
-            let rate = if target > envelope {
-                self.attack
-            } else {
-                self.release
-            };
+    pub fn set_ramp_time(&mut self, ramp_secs: f32, sample_rate: f32) {
+        self.step = Self::step_for(ramp_secs, sample_rate);
+    }
 
-            envelope += (target - envelope) * rate;
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
 
-            // Apply compression
-            let threshold_linear = self.threshold.abs() / 100.0;
-            let mut gain = 1.0;
+    // Scales interleaved frames in place while moving the gain toward `target`
+    pub fn apply(&mut self, samples: &mut [f32], channels: usize, target: f32) {
+        if self.gain == target && target == 1.0 {
+            return;
+        }
 
-            if envelope > threshold_linear {
-                let over = envelope - threshold_linear;
-                let compressed = over / self.ratio;
-                gain = (threshold_linear + compressed) / envelope.max(0.001);
+        for frame in samples.chunks_mut(channels.max(1)) {
+            if self.gain < target {
+                self.gain = (self.gain + self.step).min(target);
+            } else if self.gain > target {
+                self.gain = (self.gain - self.step).max(target);
             }
+            frame.iter_mut().for_each(|s| *s *= self.gain);
+        }
+    }
+}
+
+// Moves a parameter to a new value in a straight line, one step per frame,
+// so a change made mid-stream doesn't jump (zipper noise on gains and mixes)
+#[derive(Debug, Clone)]
+pub struct ParamSmoother {
+    current: f32,
+    target: f32,
+    step: f32,
+    remaining: usize,
+    ramp_frames: usize,
+}
 
-            output.push(sample * gain * self.makeup_gain);
+impl ParamSmoother {
+    pub fn new(value: f32, ramp_frames: usize) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+            ramp_frames,
         }
+    }
 
-        output
+    // DEFAULT_PARAM_SMOOTHING_SECS at `sample_rate`
+    pub fn with_default_ramp(value: f32, sample_rate: f32) -> Self {
+        Self::new(value, (DEFAULT_PARAM_SMOOTHING_SECS * sample_rate) as usize)
     }
 
-    fn get_name(&self) -> &str {
-        "Compressor"
+    // Applies to the next change; 0 makes changes instant
+    pub fn set_ramp_frames(&mut self, frames: usize) {
+        self.ramp_frames = frames;
     }
 
-    fn get_parameters(&self) -> Vec<EffectParameter> {
-        vec![
-            EffectParameter {
-                name: "threshold".to_string(),
-                value: self.threshold,
-                min: -60.0,
-                max: 0.0,
-                step: 0.1,
-            },
-            EffectParameter {
-                name: "ratio".to_string(),
-                value: self.ratio,
-                min: 1.0,
-                max: 20.0,
-                step: 0.1,
-            },
-            EffectParameter {
-                name: "attack".to_string(),
-                value: self.attack,
-                min: 0.001,
-                max: 0.1,
-                step: 0.001,
-            },
-            EffectParameter {
-                name: "release".to_string(),
-                value: self.release,
-                min: 0.01,
-                max: 1.0,
-                step: 0.01,
-            },
-            EffectParameter {
-                name: "makeup".to_string(),
-                value: self.makeup_gain,
-                min: 0.0,
-                max: 24.0,
-                step: 0.1,
-            },
-        ]
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+        if self.ramp_frames == 0 {
+            self.current = target;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.current) / self.ramp_frames as f32;
+            self.remaining = self.ramp_frames;
+        }
     }
 
-    fn set_parameter(&mut self, name: &str, value: f32) {
-        match name {
-            "threshold" => self.threshold = value,
-            "ratio" => self.ratio = value,
-            "attack" => self.attack = value,
-            "release" => self.release = value,
-            "makeup" => self.makeup_gain = value,
-            _ => {}
+    // The value being ramped to, which is what the parameter reads as
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    // True once the ramp has reached its target
+    pub fn is_settled(&self) -> bool {
+        self.remaining == 0
+    }
+
+    // Value for the next frame
+    pub fn tick(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
         }
+        self.current
     }
 }
 
-// Reverb Effect
-pub struct ReverbEffect {
-    room_size: f32,
-    damping: f32,
-    wet_level: f32,
-    dry_level: f32,
+// First-order DC blocker, y[n] = x[n] - x[n-1] + r*y[n-1], with the pole set
+// for a cutoff a few Hz above DC. One state pair per channel.
+pub struct DcBlocker {
+    r: f32,
+    x1: Vec<f32>,
+    y1: Vec<f32>,
 }
 
-impl ReverbEffect {
-    pub fn new(params: EffectParams) -> Self {
+impl DcBlocker {
+    pub fn new(cutoff_hz: f32, sample_rate: f32, channels: usize) -> Self {
+        let channels = channels.max(1);
         Self {
-            room_size: params.get("room_size").unwrap_or(0.5),
-            damping: params.get("damping").unwrap_or(0.5),
-            wet_level: params.get("wet_level").unwrap_or(0.3),
-            dry_level: params.get("dry_level").unwrap_or(0.7),
+            r: (-2.0 * PI * cutoff_hz / sample_rate.max(1.0)).exp(),
+            x1: vec![0.0; channels],
+            y1: vec![0.0; channels],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.x1.fill(0.0);
+        self.y1.fill(0.0);
+    }
+
+    // Filters interleaved frames in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let channels = self.x1.len();
+        for frame in samples.chunks_mut(channels) {
+            for (c, sample) in frame.iter_mut().enumerate() {
+                let y = *sample - self.x1[c] + self.r * self.y1[c];
+                self.x1[c] = *sample;
+                self.y1[c] = y;
+                *sample = y;
+            }
         }
     }
 }
 
-impl AudioEffect for ReverbEffect {
-    fn process(&self, input: &[f32]) -> Vec<f32> {
-        // Simple reverb implementation (placeholder)
-        // In production, use a proper reverb algorithm like Freeverb
-        let mut output = Vec::with_capacity(input.len());
+// Whole-frame delay for interleaved audio, e.g. to hold a dry signal back by
+// the latency of the processed one so the two line up
+pub struct DelayLine {
+    channels: usize,
+    delay: usize,
+    // delay + 1 frames; the slot after the write position is the oldest
+    buffer: Vec<f32>,
+    pos: usize,
+}
 
-        for &sample in input {
-            let wet = sample * self.wet_level * self.room_size;
-            let dry = sample * self.dry_level;
-            output.push(wet + dry);
+impl DelayLine {
+    pub fn new(delay_frames: usize, channels: usize) -> Self {
+        let channels = channels.max(1);
+        Self {
+            channels,
+            delay: delay_frames,
+            buffer: vec![0.0; (delay_frames + 1) * channels],
+            pos: 0,
         }
+    }
 
-        output
+    pub fn delay(&self) -> usize {
+        self.delay
     }
 
-    fn get_name(&self) -> &str {
-        "Reverb"
+    pub fn channels(&self) -> usize {
+        self.channels
     }
 
-    fn get_parameters(&self) -> Vec<EffectParameter> {
-        vec![
-            EffectParameter {
-                name: "room_size".to_string(),
-                value: self.room_size,
-                min: 0.0,
-                max: 1.0,
-                step: 0.01,
-            },
-            EffectParameter {
-                name: "damping".to_string(),
-                value: self.damping,
-                min: 0.0,
-                max: 1.0,
-                step: 0.01,
-            },
-            EffectParameter {
-                name: "wet_level".to_string(),
-                value: self.wet_level,
-                min: 0.0,
-                max: 1.0,
-                step: 0.01,
-            },
-            EffectParameter {
-                name: "dry_level".to_string(),
-                value: self.dry_level,
-                min: 0.0,
-                max: 1.0,
-                step: 0.01,
-            },
-        ]
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
     }
 
-    fn set_parameter(&mut self, name: &str, value: f32) {
-        match name {
-            "room_size" => self.room_size = value,
-            "damping" => self.damping = value,
-            "wet_level" => self.wet_level = value,
-            "dry_level" => self.dry_level = value,
-            _ => {}
+    // Changing the length starts over from silence
+    pub fn set_delay(&mut self, delay_frames: usize) {
+        if delay_frames != self.delay {
+            *self = Self::new(delay_frames, self.channels);
+        }
+    }
+
+    // Delays interleaved frames in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if self.delay == 0 {
+            return;
+        }
+        let frames = self.delay + 1;
+        for frame in samples.chunks_mut(self.channels) {
+            let write = self.pos * self.channels;
+            self.buffer[write..write + frame.len()].copy_from_slice(frame);
+            self.pos = (self.pos + 1) % frames;
+            let read = self.pos * self.channels;
+            frame.copy_from_slice(&self.buffer[read..read + frame.len()]);
         }
     }
 }
 
-// Noise Gate Effect
-pub struct NoiseGateEffect {
-    threshold: f32,
-    ratio: f32,
-    attack: f32,
-    release: f32,
+// Recovery of the brickwall limiter's gain once a peak has passed
+const LIMITER_RELEASE_SECS: f32 = 0.1;
+
+// Zero-latency peak limiter. The gain drops at once to whatever keeps the
+// loudest channel of a frame at the ceiling and recovers over the release.
+// With no lookahead it squares off hard transients rather than shaping them,
+// but nothing ever passes the ceiling and it adds no delay.
+pub struct BrickwallLimiter {
+    ceiling: f32,
+    gain: f32,
+    release_coeff: f32,
 }
 
-impl NoiseGateEffect {
-    pub fn new(params: EffectParams) -> Self {
+impl BrickwallLimiter {
+    pub fn new(ceiling_db: f32, sample_rate: f32) -> Self {
         Self {
-            threshold: params.get("threshold").unwrap_or(-40.0),
-            ratio: params.get("ratio").unwrap_or(10.0),
-            attack: params.get("attack").unwrap_or(0.001),
-            release: params.get("release").unwrap_or(0.1),
+            ceiling: db_to_linear(ceiling_db),
+            gain: 1.0,
+            release_coeff: 1.0 - (-1.0 / (LIMITER_RELEASE_SECS * sample_rate.max(1.0))).exp(),
         }
     }
-}
-
-impl AudioEffect for NoiseGateEffect {
-    fn process(&self, input: &[f32]) -> Vec<f32> {
-        let mut output = Vec::with_capacity(input.len());
-        let mut envelope = 0.0f32;
 
-        let threshold_linear = self.threshold.abs() / 100.0;
+    pub fn set_ceiling_db(&mut self, ceiling_db: f32) {
+        self.ceiling = db_to_linear(ceiling_db);
+    }
 
-        for &sample in input {
-            let input_level = sample.abs();
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+    }
 
-            // Update envelope
-            let rate = if input_level > envelope {
-                self.attack
+    // Limits interleaved frames in place; every channel gets the same gain
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        for frame in samples.chunks_mut(channels.max(1)) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let allowed = if peak > self.ceiling {
+                self.ceiling / peak
             } else {
-                self.release
+                1.0
             };
+            self.gain = (self.gain + (1.0 - self.gain) * self.release_coeff).min(allowed);
+            frame.iter_mut().for_each(|s| *s *= self.gain);
+        }
+    }
+}
 
-            envelope += (input_level - envelope) * rate;
+// Decay of the ducker's key level detector; long enough to ride over single
+// waveform cycles, short next to the gain release
+const DUCKER_DETECTOR_RELEASE_SECS: f32 = 0.05;
 
-            // Apply gate
-            let gain = if envelope < threshold_linear {
-                1.0 / self.ratio
+// Sidechain ducker: follows the level of a key signal and turns another one
+// down by `depth_db` while the key is above the threshold, e.g. a music bed
+// under the voice. The gain falls over the attack time and recovers over the
+// release time once the key goes quiet.
+pub struct Ducker {
+    threshold: f32,
+    depth_gain: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    detector_coeff: f32,
+    envelope: f32,
+    gain: f32,
+}
+
+impl Ducker {
+    pub fn new(
+        threshold_db: f32,
+        depth_db: f32,
+        attack_ms: f32,
+        release_ms: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let mut ducker = Self {
+            threshold: 0.0,
+            depth_gain: 1.0,
+            attack_coeff: 1.0,
+            release_coeff: 1.0,
+            detector_coeff: time_coefficient(DUCKER_DETECTOR_RELEASE_SECS, sample_rate),
+            envelope: 0.0,
+            gain: 1.0,
+        };
+        ducker.set_params(threshold_db, depth_db, attack_ms, release_ms, sample_rate);
+        ducker
+    }
+
+    // Takes effect from the next frame without resetting the current gain
+    pub fn set_params(
+        &mut self,
+        threshold_db: f32,
+        depth_db: f32,
+        attack_ms: f32,
+        release_ms: f32,
+        sample_rate: f32,
+    ) {
+        self.threshold = db_to_linear(threshold_db);
+        self.depth_gain = db_to_linear(-depth_db.max(0.0));
+        self.attack_coeff = time_coefficient(attack_ms.max(0.0) / 1000.0, sample_rate);
+        self.release_coeff = time_coefficient(release_ms.max(0.0) / 1000.0, sample_rate);
+    }
+
+    // Current gain applied to the ducked signal, 1.0 when not ducking
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    // Ducks interleaved `signal` in place by the level of `key`, frame by frame
+    pub fn process(&mut self, signal: &mut [f32], key: &[f32], channels: usize) {
+        let channels = channels.max(1);
+        for (frame, key_frame) in signal.chunks_mut(channels).zip(key.chunks(channels)) {
+            let peak = frame_peak(key_frame);
+            if peak > self.envelope {
+                self.envelope = peak;
+            } else {
+                self.envelope += (peak - self.envelope) * self.detector_coeff;
+            }
+
+            let target = if self.envelope > self.threshold {
+                self.depth_gain
             } else {
                 1.0
             };
-
-            output.push(sample * gain);
+            let coeff = if target < self.gain {
+                self.attack_coeff
+            } else {
+                self.release_coeff
+            };
+            self.gain += (target - self.gain) * coeff;
+            frame.iter_mut().for_each(|s| *s *= self.gain);
         }
+    }
+}
 
-        output
+// Second-order IIR section (RBJ cookbook coefficients, transposed direct form II)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    pub fn lowpass(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(frequency, q, sample_rate);
+        let b1 = 1.0 - cos_w;
+        Self::normalized(
+            b1 / 2.0,
+            b1,
+            b1 / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w,
+            1.0 - alpha,
+        )
     }
 
-    fn get_name(&self) -> &str {
-        "Noise Gate"
+    pub fn highpass(frequency: f32, q: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(frequency, q, sample_rate);
+        let b1 = -(1.0 + cos_w);
+        Self::normalized(
+            -b1 / 2.0,
+            b1,
+            -b1 / 2.0,
+            1.0 + alpha,
+            -2.0 * cos_w,
+            1.0 - alpha,
+        )
+    }
+
+    // Bell boost or cut of `gain_db` around `frequency`; 0 dB passes the signal unchanged
+    pub fn peaking(frequency: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        let (cos_w, alpha) = Self::prewarp(frequency, q, sample_rate);
+        let a = 10.0f32.powf(gain_db / 40.0);
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * cos_w,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * cos_w,
+            1.0 - alpha / a,
+        )
+    }
+
+    // Direct-form coefficients, for filters designed elsewhere; a0 is divided out
+    pub fn from_coefficients(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self::normalized(b[0], b[1], b[2], a[0], a[1], a[2])
+    }
+
+    fn prewarp(frequency: f32, q: f32, sample_rate: f32) -> (f32, f32) {
+        let frequency = frequency.clamp(1.0, sample_rate * 0.49);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * q))
+    }
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    // Swap in new coefficients while keeping the filter history, so retuning doesn't click
+    pub fn set_coefficients(&mut self, other: &Biquad) {
+        self.b0 = other.b0;
+        self.b1 = other.b1;
+        self.b2 = other.b2;
+        self.a1 = other.a1;
+        self.a2 = other.a2;
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+// 4th-order Linkwitz-Riley crossover: two cascaded Butterworth sections per
+// side, so the low and high outputs sum back to an allpass (flat magnitude)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkwitzRileyCrossover {
+    lowpass: [Biquad; 2],
+    highpass: [Biquad; 2],
+}
+
+impl LinkwitzRileyCrossover {
+    pub fn new(frequency: f32, sample_rate: f32) -> Self {
+        let mut crossover = Self::default();
+        crossover.set_frequency(frequency, sample_rate);
+        crossover
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32, sample_rate: f32) {
+        let lowpass = Biquad::lowpass(frequency, FRAC_1_SQRT_2, sample_rate);
+        let highpass = Biquad::highpass(frequency, FRAC_1_SQRT_2, sample_rate);
+        for section in self.lowpass.iter_mut() {
+            section.set_coefficients(&lowpass);
+        }
+        for section in self.highpass.iter_mut() {
+            section.set_coefficients(&highpass);
+        }
+    }
+
+    pub fn split(&mut self, x: f32) -> (f32, f32) {
+        let low = self.lowpass[0].process(x);
+        let high = self.highpass[0].process(x);
+        (self.lowpass[1].process(low), self.highpass[1].process(high))
+    }
+
+    pub fn reset(&mut self) {
+        for section in self.lowpass.iter_mut().chain(self.highpass.iter_mut()) {
+            section.reset();
+        }
+    }
+}
+
+// How long a band meter holds its peak before falling, and how fast it falls
+const BAND_PEAK_HOLD_SECS: f32 = 1.0;
+const BAND_PEAK_FALL_DB_PER_SEC: f32 = 20.0;
+
+// How long a band's clip indicator stays lit after the last sample over 0 dBFS
+const BAND_CLIP_HOLD_SECS: f32 = 2.0;
+
+// Peak-hold meter for one EQ band
+#[derive(Debug, Clone, Copy)]
+struct BandMeter {
+    peak_db: f32,
+    hold_frames: usize,
+    clip_frames: usize,
+}
+
+impl Default for BandMeter {
+    fn default() -> Self {
+        Self {
+            peak_db: METER_FLOOR_DB,
+            hold_frames: 0,
+            clip_frames: 0,
+        }
+    }
+}
+
+impl BandMeter {
+    // Folds in the largest sample of a block `frames` long
+    fn update(&mut self, block_peak: f32, frames: usize, sample_rate: f32) {
+        let block_db = linear_to_db(block_peak).max(METER_FLOOR_DB);
+        if block_db >= self.peak_db {
+            self.peak_db = block_db;
+            self.hold_frames = (BAND_PEAK_HOLD_SECS * sample_rate) as usize;
+        } else if self.hold_frames > frames {
+            self.hold_frames -= frames;
+        } else {
+            self.hold_frames = 0;
+            let fall = BAND_PEAK_FALL_DB_PER_SEC * frames as f32 / sample_rate;
+            self.peak_db = (self.peak_db - fall).max(block_db);
+        }
+
+        if block_peak > 1.0 {
+            self.clip_frames = (BAND_CLIP_HOLD_SECS * sample_rate) as usize;
+        } else {
+            self.clip_frames = self.clip_frames.saturating_sub(frames);
+        }
+    }
+}
+
+// Equalizer Effect: ten peaking bands in series
+pub struct EqualizerEffect {
+    bands: Vec<EQBand>,
+    sample_rate: f32,
+    // Per band, one filter per channel
+    filters: Vec<Vec<Biquad>>,
+    // Each band's filter run on its own against the EQ input, so a band's
+    // meter shows what that band does to the signal rather than the cascade
+    meter_filters: Vec<Vec<Biquad>>,
+    meters: Vec<BandMeter>,
+    block_peaks: Vec<f32>,
+    // In mid/side the filters and band meters see M and S instead of L and R
+    processing_mode: ProcessingMode,
+}
+
+impl EqualizerEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        // Initialize 10-band EQ with standard frequencies
+        let bands: Vec<EQBand> = [
+            32.0,    // Sub-bass
+            64.0,    // Bass
+            125.0,   // Low
+            250.0,   // Low-mid
+            500.0,   // Mid
+            1000.0,  // High-mid
+            2000.0,  // Presence
+            4000.0,  // Brilliance
+            8000.0,  // Air
+            16000.0, // Sparkle
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &frequency)| {
+            EQBand::new(
+                frequency,
+                1.0,
+                params.get(&format!("band_{}", i)).unwrap_or(0.0),
+            )
+        })
+        .collect();
+
+        let filters: Vec<Vec<Biquad>> = bands
+            .iter()
+            .map(|band| {
+                vec![Biquad::peaking(
+                    band.frequency,
+                    band.q,
+                    band.gain,
+                    sample_rate,
+                )]
+            })
+            .collect();
+        Self {
+            meter_filters: filters.clone(),
+            filters,
+            meters: vec![BandMeter::default(); bands.len()],
+            block_peaks: vec![0.0; bands.len()],
+            bands,
+            sample_rate,
+            processing_mode: ProcessingMode::from_param(
+                params.get("processing_mode").unwrap_or(0.0),
+            ),
+        }
+    }
+
+    fn retune_band(&mut self, index: usize) {
+        let band = &self.bands[index];
+        let coefficients = Biquad::peaking(band.frequency, band.q, band.gain, self.sample_rate);
+        for filter in self.filters[index]
+            .iter_mut()
+            .chain(self.meter_filters[index].iter_mut())
+        {
+            filter.set_coefficients(&coefficients);
+        }
+    }
+
+    fn filter_block(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for frame in output.chunks_mut(channels) {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                let dry = *sample;
+                let mut wet = dry;
+                for band in 0..self.bands.len() {
+                    wet = self.filters[band][ch].process(wet);
+                    let level = self.meter_filters[band][ch].process(dry).abs();
+                    self.block_peaks[band] = self.block_peaks[band].max(level);
+                }
+                *sample = wet;
+            }
+        }
+        output
+    }
+}
+
+impl AudioEffect for EqualizerEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let channels = channels.max(1);
+        for (filters, meter_filters) in self.filters.iter_mut().zip(self.meter_filters.iter_mut()) {
+            let fresh = filters[0];
+            filters.resize(channels, fresh);
+            meter_filters.resize(channels, fresh);
+        }
+        self.block_peaks.iter_mut().for_each(|peak| *peak = 0.0);
+
+        let output = if self.processing_mode.applies(channels) {
+            process_mid_side(input, |mid_side| self.filter_block(mid_side, channels))
+        } else {
+            self.filter_block(input, channels)
+        };
+
+        let frames = input.len() / channels;
+        for (meter, &peak) in self.meters.iter_mut().zip(self.block_peaks.iter()) {
+            meter.update(peak, frames, self.sample_rate);
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Equalizer"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        let mut parameters: Vec<EffectParameter> = self
+            .bands
+            .iter()
+            .enumerate()
+            .map(|(i, band)| EffectParameter {
+                name: format!("band_{}", i),
+                value: band.gain,
+                min: -12.0,
+                max: 12.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            })
+            .collect();
+        // 0 = stereo, 1 = mid/side
+        parameters.push(EffectParameter {
+            name: "processing_mode".to_string(),
+            value: self.processing_mode.as_param(),
+            min: 0.0,
+            max: 1.0,
+            step: 1.0,
+            scale: ParamScale::Linear,
+        });
+        parameters
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        if name == "processing_mode" {
+            let mode = ProcessingMode::from_param(value);
+            // The filter state belongs to the other representation
+            if mode != self.processing_mode {
+                self.processing_mode = mode;
+                self.filters
+                    .iter_mut()
+                    .chain(self.meter_filters.iter_mut())
+                    .flatten()
+                    .for_each(Biquad::reset);
+            }
+        } else if let Some(band_idx) = name
+            .strip_prefix("band_")
+            .and_then(|s| s.parse::<usize>().ok())
+        {
+            if band_idx < self.bands.len() {
+                self.bands[band_idx].gain = value;
+                self.retune_band(band_idx);
+            }
+        }
+    }
+
+    fn band_levels(&self) -> Option<Vec<BandLevel>> {
+        Some(
+            self.bands
+                .iter()
+                .zip(self.meters.iter())
+                .map(|(band, meter)| BandLevel {
+                    frequency: band.frequency,
+                    peak_db: meter.peak_db,
+                    clipping: meter.clip_frames > 0,
+                })
+                .collect(),
+        )
+    }
+
+    fn reset(&mut self) {
+        for filter in self
+            .filters
+            .iter_mut()
+            .chain(self.meter_filters.iter_mut())
+            .flatten()
+        {
+            filter.reset();
+        }
+        self.meters
+            .iter_mut()
+            .for_each(|meter| *meter = BandMeter::default());
+    }
+}
+
+// Static gain curve shared by the single- and multi-band compressors
+pub fn compression_gain(envelope: f32, threshold_linear: f32, ratio: f32) -> f32 {
+    if envelope > threshold_linear {
+        let over = envelope - threshold_linear;
+        let compressed = over / ratio;
+        (threshold_linear + compressed) / envelope.max(0.001)
+    } else {
+        1.0
+    }
+}
+
+// Compressor Effect
+pub struct CompressorEffect {
+    threshold: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    makeup_gain: ParamSmoother,
+    detector: LevelDetector,
+    processing_mode: ProcessingMode,
+    // Follows S in mid/side, while `detector` follows M
+    side_detector: LevelDetector,
+    // Smoothed detector levels, carried across blocks so attack and release
+    // run continuously; `envelope` is M's in mid/side
+    envelope: f32,
+    side_envelope: f32,
+}
+
+impl CompressorEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let detector_mode = DetectorMode::from_param(params.get("detector").unwrap_or(0.0));
+        Self {
+            threshold: params.get("threshold").unwrap_or(-20.0),
+            ratio: params.get("ratio").unwrap_or(4.0),
+            attack: params.get("attack").unwrap_or(0.01),
+            release: params.get("release").unwrap_or(0.1),
+            makeup_gain: ParamSmoother::with_default_ramp(
+                params.get("makeup").unwrap_or(1.0),
+                sample_rate,
+            ),
+            detector: LevelDetector::new(detector_mode, sample_rate),
+            processing_mode: ProcessingMode::from_param(
+                params.get("processing_mode").unwrap_or(0.0),
+            ),
+            side_detector: LevelDetector::new(detector_mode, sample_rate),
+            envelope: 0.0,
+            side_envelope: 0.0,
+        }
+    }
+
+    // Linked detection would give M and S the same gain, which is no different
+    // from compressing L and R, so each gets its own envelope: a wide pad
+    // doesn't pull the centred voice down, nor the voice the pad
+    fn compress_mid_side(&mut self, input: &[f32]) -> Vec<f32> {
+        let threshold_linear = db_to_linear(self.threshold);
+        let mut output = Vec::with_capacity(input.len());
+
+        for frame in input.chunks_exact(2) {
+            let makeup = self.makeup_gain.tick();
+            let detectors = [&mut self.detector, &mut self.side_detector];
+            let envelopes = [&mut self.envelope, &mut self.side_envelope];
+            for ((&sample, envelope), detector) in frame.iter().zip(envelopes).zip(detectors) {
+                let level = detector.level(&[sample]);
+                let rate = if level > *envelope {
+                    self.attack
+                } else {
+                    self.release
+                };
+                *envelope += (level - *envelope) * rate;
+                output.push(
+                    sample * compression_gain(*envelope, threshold_linear, self.ratio) * makeup,
+                );
+            }
+        }
+
+        output
+    }
+}
+
+impl AudioEffect for CompressorEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        if self.processing_mode.applies(channels) {
+            return process_mid_side(input, |mid_side| self.compress_mid_side(mid_side));
+        }
+
+        let mut output = Vec::with_capacity(input.len());
+
+        for frame in input.chunks(channels.max(1)) {
+            let input_level = self.detector.level(frame);
+
+            // Update envelope
+            let target = input_level;
+
+            let rate = if target > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+
+            self.envelope += (target - self.envelope) * rate;
+
+            // Apply compression
+            let threshold_linear = db_to_linear(self.threshold);
+            let gain = compression_gain(self.envelope, threshold_linear, self.ratio);
+
+            let makeup = self.makeup_gain.tick();
+            output.extend(frame.iter().map(|s| s * gain * makeup));
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Compressor"
     }
 
     fn get_parameters(&self) -> Vec<EffectParameter> {
@@ -344,16 +1052,18 @@ impl AudioEffect for NoiseGateEffect {
             EffectParameter {
                 name: "threshold".to_string(),
                 value: self.threshold,
-                min: -80.0,
+                min: -60.0,
                 max: 0.0,
                 step: 0.1,
+                scale: ParamScale::Linear,
             },
             EffectParameter {
                 name: "ratio".to_string(),
                 value: self.ratio,
                 min: 1.0,
-                max: 100.0,
-                step: 1.0,
+                max: 20.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
             },
             EffectParameter {
                 name: "attack".to_string(),
@@ -361,6 +1071,7 @@ impl AudioEffect for NoiseGateEffect {
                 min: 0.001,
                 max: 0.1,
                 step: 0.001,
+                scale: ParamScale::Logarithmic,
             },
             EffectParameter {
                 name: "release".to_string(),
@@ -368,6 +1079,33 @@ impl AudioEffect for NoiseGateEffect {
                 min: 0.01,
                 max: 1.0,
                 step: 0.01,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "makeup".to_string(),
+                value: self.makeup_gain.target(),
+                min: 0.0,
+                max: 24.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            // 0 = peak, 1 = RMS
+            EffectParameter {
+                name: "detector".to_string(),
+                value: self.detector.mode().as_param(),
+                min: 0.0,
+                max: 1.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            // 0 = stereo, 1 = mid/side
+            EffectParameter {
+                name: "processing_mode".to_string(),
+                value: self.processing_mode.as_param(),
+                min: 0.0,
+                max: 1.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
             },
         ]
     }
@@ -378,7 +1116,2395 @@ impl AudioEffect for NoiseGateEffect {
             "ratio" => self.ratio = value,
             "attack" => self.attack = value,
             "release" => self.release = value,
+            "makeup" => self.makeup_gain.set_target(value),
+            "detector" => {
+                let mode = DetectorMode::from_param(value);
+                self.detector.set_mode(mode);
+                self.side_detector.set_mode(mode);
+            }
+            "processing_mode" => {
+                let mode = ProcessingMode::from_param(value);
+                if mode != self.processing_mode {
+                    self.processing_mode = mode;
+                    self.reset();
+                }
+            }
             _ => {}
         }
     }
+
+    fn smoothable_parameters(&self) -> &[&str] {
+        &["makeup"]
+    }
+
+    fn set_smoothing_frames(&mut self, frames: usize) {
+        self.makeup_gain.set_ramp_frames(frames);
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.side_detector.reset();
+        self.envelope = 0.0;
+        self.side_envelope = 0.0;
+    }
+}
+
+// Reverb Effect
+//
+// Freeverb: eight parallel damped feedback combs into four series allpasses
+// per side. The right tank's delays are FREEVERB_STEREO_SPREAD samples longer
+// than the left's, so even a mono voice comes out with two decorrelated tails;
+// `stereo_width` then crossfeeds them from fully wide (1) to mono (0). The
+// input is summed to mono and both tanks are fed from it, which also covers a
+// mono source upmixed to a stereo stream. Tunings are Jezar's, in samples at
+// 44.1 kHz, and get scaled to the stream's rate.
+//
+// Freeze holds the current tail indefinitely: the combs go to unity feedback
+// with no damping and stop taking input. It fades in and out over
+// REVERB_FREEZE_FADE_SECS so toggling it doesn't click.
+const FREEVERB_COMB_TUNINGS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const FREEVERB_ALLPASS_TUNINGS: [usize; 4] = [556, 441, 341, 225];
+const FREEVERB_STEREO_SPREAD: usize = 23;
+const FREEVERB_TUNING_RATE: f32 = 44100.0;
+// Keeps the summed comb outputs in range; the wet gain makes up for it
+const FREEVERB_INPUT_GAIN: f32 = 0.015;
+const FREEVERB_WET_SCALE: f32 = 3.0;
+const FREEVERB_ALLPASS_FEEDBACK: f32 = 0.5;
+const REVERB_FREEZE_FADE_SECS: f32 = 0.05;
+
+// Lowpass-filtered feedback comb
+struct CombFilter {
+    buffer: Vec<f32>,
+    pos: usize,
+    filter_store: f32,
+}
+
+impl CombFilter {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            pos: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.pos];
+        self.filter_store = output * (1.0 - damping) + self.filter_store * damping;
+        self.buffer[self.pos] = input + self.filter_store * feedback;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        output
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
+        self.filter_store = 0.0;
+    }
+}
+
+struct AllpassFilter {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl AllpassFilter {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = input + delayed * FREEVERB_ALLPASS_FEEDBACK;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - input
+    }
+
+    fn clear(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
+    }
+}
+
+// One side's combs and allpasses, `spread` samples longer than the base tuning
+struct ReverbTank {
+    combs: Vec<CombFilter>,
+    allpasses: Vec<AllpassFilter>,
+}
+
+impl ReverbTank {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = |length: usize| {
+            ((length + spread) as f32 * sample_rate / FREEVERB_TUNING_RATE) as usize
+        };
+        Self {
+            combs: FREEVERB_COMB_TUNINGS
+                .iter()
+                .map(|&l| CombFilter::new(scale(l)))
+                .collect(),
+            allpasses: FREEVERB_ALLPASS_TUNINGS
+                .iter()
+                .map(|&l| AllpassFilter::new(scale(l)))
+                .collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let combed: f32 = self
+            .combs
+            .iter_mut()
+            .map(|comb| comb.process(input, feedback, damping))
+            .sum();
+        self.allpasses
+            .iter_mut()
+            .fold(combed, |sample, allpass| allpass.process(sample))
+    }
+
+    fn clear(&mut self) {
+        self.combs.iter_mut().for_each(CombFilter::clear);
+        self.allpasses.iter_mut().for_each(AllpassFilter::clear);
+    }
+}
+
+pub struct ReverbEffect {
+    room_size: f32,
+    damping: f32,
+    stereo_width: f32,
+    // 0 running, 1 frozen; ramps between the two
+    freeze: ParamSmoother,
+    wet_level: ParamSmoother,
+    dry_level: ParamSmoother,
+    left: ReverbTank,
+    right: ReverbTank,
+}
+
+impl ReverbEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        Self {
+            room_size: params.get("room_size").unwrap_or(0.5),
+            damping: params.get("damping").unwrap_or(0.5),
+            stereo_width: params.get("stereo_width").unwrap_or(1.0).clamp(0.0, 1.0),
+            freeze: ParamSmoother::new(
+                Self::freeze_target(params.get("freeze").unwrap_or(0.0)),
+                (REVERB_FREEZE_FADE_SECS * sample_rate) as usize,
+            ),
+            wet_level: ParamSmoother::with_default_ramp(
+                params.get("wet_level").unwrap_or(0.3),
+                sample_rate,
+            ),
+            dry_level: ParamSmoother::with_default_ramp(
+                params.get("dry_level").unwrap_or(0.7),
+                sample_rate,
+            ),
+            left: ReverbTank::new(sample_rate, 0),
+            right: ReverbTank::new(sample_rate, FREEVERB_STEREO_SPREAD),
+        }
+    }
+
+    fn freeze_target(value: f32) -> f32 {
+        if value >= 0.5 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AudioEffect for ReverbEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+        let channels = channels.max(1);
+
+        // Room size 0-1 maps onto Freeverb's 0.7-0.98 comb feedback
+        let feedback = 0.7 + self.room_size.clamp(0.0, 1.0) * 0.28;
+        let damping = self.damping.clamp(0.0, 1.0) * 0.4;
+        let direct = 0.5 + self.stereo_width * 0.5;
+        let cross = 0.5 - self.stereo_width * 0.5;
+
+        for frame in input.chunks(channels) {
+            let wet_level = self.wet_level.tick() * FREEVERB_WET_SCALE;
+            let dry_level = self.dry_level.tick();
+            let freeze = self.freeze.tick();
+            let feedback = feedback + (1.0 - feedback) * freeze;
+            let damping = damping * (1.0 - freeze);
+
+            let input_gain = FREEVERB_INPUT_GAIN * (1.0 - freeze);
+            let mono = frame.iter().sum::<f32>() / frame.len() as f32 * input_gain;
+            let left = self.left.process(mono, feedback, damping);
+            if channels == 1 {
+                output.push(left * wet_level + frame[0] * dry_level);
+                continue;
+            }
+
+            // Even channels take the left tail and odd ones the right, so
+            // layouts past stereo alternate sides
+            let right = self.right.process(mono, feedback, damping);
+            let wet_left = (left * direct + right * cross) * wet_level;
+            let wet_right = (right * direct + left * cross) * wet_level;
+            for (channel, &sample) in frame.iter().enumerate() {
+                let wet = if channel % 2 == 0 {
+                    wet_left
+                } else {
+                    wet_right
+                };
+                output.push(wet + sample * dry_level);
+            }
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Reverb"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            EffectParameter {
+                name: "room_size".to_string(),
+                value: self.room_size,
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "damping".to_string(),
+                value: self.damping,
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "stereo_width".to_string(),
+                value: self.stereo_width,
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            // 0 = off, 1 = hold the tail
+            EffectParameter {
+                name: "freeze".to_string(),
+                value: self.freeze.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "wet_level".to_string(),
+                value: self.wet_level.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "dry_level".to_string(),
+                value: self.dry_level.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "room_size" => self.room_size = value,
+            "damping" => self.damping = value,
+            "stereo_width" => self.stereo_width = value.clamp(0.0, 1.0),
+            "freeze" => self.freeze.set_target(Self::freeze_target(value)),
+            "wet_level" => self.wet_level.set_target(value),
+            "dry_level" => self.dry_level.set_target(value),
+            _ => {}
+        }
+    }
+
+    fn smoothable_parameters(&self) -> &[&str] {
+        &["wet_level", "dry_level"]
+    }
+
+    fn set_smoothing_frames(&mut self, frames: usize) {
+        self.wet_level.set_ramp_frames(frames);
+        self.dry_level.set_ramp_frames(frames);
+    }
+
+    fn reset(&mut self) {
+        self.left.clear();
+        self.right.clear();
+    }
+}
+
+// Noise Gate Effect
+//
+// Gate mode drops to a fixed 1/ratio gain once closed. Expander mode instead
+// attenuates by (ratio - 1) dB for every dB the envelope falls below the
+// threshold, so quiet passages fade down smoothly; hysteresis and hold only
+// apply to the gate.
+//
+// With lookahead the audio is delayed while the detector runs on the
+// undelayed input, so the gate is already open when a word's first transient
+// reaches the output. The hold is stretched by the same amount so word
+// endings aren't cut short in turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateMode {
+    Gate,
+    Expander,
+}
+
+impl GateMode {
+    fn from_param(value: f32) -> Self {
+        if value >= 0.5 {
+            GateMode::Expander
+        } else {
+            GateMode::Gate
+        }
+    }
+
+    fn as_param(self) -> f32 {
+        match self {
+            GateMode::Gate => 0.0,
+            GateMode::Expander => 1.0,
+        }
+    }
+}
+
+const NOISE_GATE_MAX_LOOKAHEAD_MS: f32 = 20.0;
+
+pub struct NoiseGateEffect {
+    mode: GateMode,
+    threshold: f32,
+    hysteresis: f32,
+    hold: f32,
+    ratio: f32,
+    attack: f32,
+    release: f32,
+    sample_rate: f32,
+    lookahead_ms: f32,
+    lookahead: DelayLine,
+    detector: LevelDetector,
+    envelope: f32,
+    is_open: bool,
+    hold_remaining: usize,
+}
+
+impl NoiseGateEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let lookahead_ms = Self::clamp_lookahead(params.get("lookahead_ms").unwrap_or(0.0));
+        Self {
+            mode: GateMode::from_param(params.get("mode").unwrap_or(0.0)),
+            threshold: params.get("threshold").unwrap_or(-40.0),
+            hysteresis: params.get("hysteresis").unwrap_or(6.0),
+            hold: params.get("hold").unwrap_or(0.05),
+            ratio: params.get("ratio").unwrap_or(10.0),
+            attack: params.get("attack").unwrap_or(0.001),
+            release: params.get("release").unwrap_or(0.1),
+            sample_rate,
+            lookahead_ms,
+            lookahead: DelayLine::new(Self::lookahead_frames(lookahead_ms, sample_rate), 1),
+            detector: LevelDetector::new(
+                DetectorMode::from_param(params.get("detector").unwrap_or(0.0)),
+                sample_rate,
+            ),
+            envelope: 0.0,
+            is_open: false,
+            hold_remaining: 0,
+        }
+    }
+}
+
+impl NoiseGateEffect {
+    fn clamp_lookahead(ms: f32) -> f32 {
+        ms.clamp(0.0, NOISE_GATE_MAX_LOOKAHEAD_MS)
+    }
+
+    fn lookahead_frames(ms: f32, sample_rate: f32) -> usize {
+        (ms * 0.001 * sample_rate).round() as usize
+    }
+
+    // Downward expansion below the threshold, unity above it
+    fn expander_gain(&self) -> f32 {
+        let level_db = linear_to_db(self.envelope);
+        if level_db >= self.threshold {
+            return 1.0;
+        }
+        let below = self.threshold - level_db;
+        db_to_linear(-below * (self.ratio.max(1.0) - 1.0))
+    }
+}
+
+impl AudioEffect for NoiseGateEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let channels = channels.max(1);
+        let lookahead_frames = Self::lookahead_frames(self.lookahead_ms, self.sample_rate);
+        if self.lookahead.channels() != channels {
+            self.lookahead = DelayLine::new(lookahead_frames, channels);
+        }
+        self.lookahead.set_delay(lookahead_frames);
+
+        let mut output = input.to_vec();
+        self.lookahead.process(&mut output);
+
+        // Opens above `threshold` but only closes once the envelope falls
+        // `hysteresis` dB lower, so a level hovering at the threshold can't chatter
+        let open_linear = db_to_linear(self.threshold);
+        let close_linear = db_to_linear(self.threshold - self.hysteresis.max(0.0));
+        let hold_samples = (self.hold.max(0.0) * self.sample_rate) as usize + lookahead_frames;
+
+        // The detector sees the input; the gain lands on the delayed output
+        for (frame, delayed) in input.chunks(channels).zip(output.chunks_mut(channels)) {
+            let input_level = self.detector.level(frame);
+
+            // Update envelope
+            let rate = if input_level > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+
+            self.envelope += (input_level - self.envelope) * rate;
+
+            if self.envelope >= open_linear {
+                self.is_open = true;
+                self.hold_remaining = hold_samples;
+            } else if self.is_open && self.envelope < close_linear {
+                // Stay open for the hold time after the signal drops away
+                if self.hold_remaining > 0 {
+                    self.hold_remaining -= 1;
+                } else {
+                    self.is_open = false;
+                }
+            }
+
+            // Apply gate
+            let gain = match self.mode {
+                GateMode::Gate if self.is_open => 1.0,
+                GateMode::Gate => 1.0 / self.ratio,
+                GateMode::Expander => self.expander_gain(),
+            };
+
+            delayed.iter_mut().for_each(|s| *s *= gain);
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Noise Gate"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            // 0 = gate, 1 = expander
+            EffectParameter {
+                name: "mode".to_string(),
+                value: self.mode.as_param(),
+                min: 0.0,
+                max: 1.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "threshold".to_string(),
+                value: self.threshold,
+                min: -80.0,
+                max: 0.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "hysteresis".to_string(),
+                value: self.hysteresis,
+                min: 0.0,
+                max: 20.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "hold".to_string(),
+                value: self.hold,
+                min: 0.0,
+                max: 2.0,
+                step: 0.001,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "ratio".to_string(),
+                value: self.ratio,
+                min: 1.0,
+                max: 100.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "attack".to_string(),
+                value: self.attack,
+                min: 0.001,
+                max: 0.1,
+                step: 0.001,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "release".to_string(),
+                value: self.release,
+                min: 0.01,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Logarithmic,
+            },
+            // 0 = peak, 1 = RMS
+            EffectParameter {
+                name: "detector".to_string(),
+                value: self.detector.mode().as_param(),
+                min: 0.0,
+                max: 1.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "lookahead_ms".to_string(),
+                value: self.lookahead_ms,
+                min: 0.0,
+                max: NOISE_GATE_MAX_LOOKAHEAD_MS,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "mode" => self.mode = GateMode::from_param(value),
+            "threshold" => self.threshold = value,
+            "hysteresis" => self.hysteresis = value,
+            "hold" => self.hold = value,
+            "ratio" => self.ratio = value,
+            "attack" => self.attack = value,
+            "release" => self.release = value,
+            "detector" => self.detector.set_mode(DetectorMode::from_param(value)),
+            "lookahead_ms" => self.lookahead_ms = Self::clamp_lookahead(value),
+            _ => {}
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        Self::lookahead_frames(self.lookahead_ms, self.sample_rate)
+    }
+
+    fn reset(&mut self) {
+        self.detector.reset();
+        self.lookahead.reset();
+        self.envelope = 0.0;
+        self.is_open = false;
+        self.hold_remaining = 0;
+    }
+}
+
+// Flanger Effect
+const FLANGER_MIN_DELAY_MS: f32 = 1.0;
+const FLANGER_MAX_DELAY_MS: f32 = 10.0;
+const FLANGER_MAX_FEEDBACK: f32 = 0.95;
+
+pub struct FlangerEffect {
+    rate: f32,
+    depth: f32,
+    feedback: f32,
+    mix: ParamSmoother,
+    sample_rate: f32,
+    // One line per channel, all sharing the write position and LFO
+    delay_lines: Vec<Vec<f32>>,
+    write_pos: usize,
+    lfo_phase: f32,
+}
+
+impl FlangerEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        // Room for the longest modulated delay plus one sample for interpolation
+        let max_delay = (FLANGER_MAX_DELAY_MS * 0.001 * sample_rate).ceil() as usize + 2;
+
+        Self {
+            rate: params.get("rate").unwrap_or(0.25),
+            depth: Self::clamp_depth(params.get("depth").unwrap_or(0.7)),
+            feedback: Self::clamp_feedback(params.get("feedback").unwrap_or(0.5)),
+            mix: ParamSmoother::with_default_ramp(params.get("mix").unwrap_or(0.5), sample_rate),
+            sample_rate,
+            delay_lines: vec![vec![0.0; max_delay]],
+            write_pos: 0,
+            lfo_phase: 0.0,
+        }
+    }
+
+    // Feedback at or above unity makes the delay loop unstable
+    fn clamp_feedback(value: f32) -> f32 {
+        value.clamp(-FLANGER_MAX_FEEDBACK, FLANGER_MAX_FEEDBACK)
+    }
+
+    // Depth past 1 would sweep the delay beyond the end of the line
+    fn clamp_depth(value: f32) -> f32 {
+        value.clamp(0.0, 1.0)
+    }
+
+    fn read_delayed(&self, channel: usize, delay_samples: f32) -> f32 {
+        let line = &self.delay_lines[channel];
+        let len = line.len();
+        let whole = delay_samples.floor() as usize;
+        let frac = delay_samples - whole as f32;
+
+        let a = line[(self.write_pos + len - whole) % len];
+        let b = line[(self.write_pos + len - whole - 1) % len];
+
+        a + (b - a) * frac
+    }
+}
+
+impl AudioEffect for FlangerEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+
+        let channels = channels.max(1);
+        let len = self.delay_lines[0].len();
+        self.delay_lines.resize(channels, vec![0.0; len]);
+
+        let min_delay = FLANGER_MIN_DELAY_MS * 0.001 * self.sample_rate;
+        let sweep = (FLANGER_MAX_DELAY_MS - FLANGER_MIN_DELAY_MS) * 0.001 * self.sample_rate;
+        let phase_inc = 2.0 * PI * self.rate / self.sample_rate;
+
+        for frame in input.chunks(channels) {
+            // LFO sweeps the delay between the minimum and depth-scaled maximum
+            let lfo = 0.5 + 0.5 * self.lfo_phase.sin();
+            let delay_samples = min_delay + sweep * self.depth * lfo;
+            let mix = self.mix.tick();
+
+            for (channel, &sample) in frame.iter().enumerate() {
+                let delayed = self.read_delayed(channel, delay_samples);
+                self.delay_lines[channel][self.write_pos] = sample + delayed * self.feedback;
+                output.push(sample * (1.0 - mix) + delayed * mix);
+            }
+            self.write_pos = (self.write_pos + 1) % len;
+
+            self.lfo_phase += phase_inc;
+            if self.lfo_phase >= 2.0 * PI {
+                self.lfo_phase -= 2.0 * PI;
+            }
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Flanger"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            EffectParameter {
+                name: "rate".to_string(),
+                value: self.rate,
+                min: 0.05,
+                max: 5.0,
+                step: 0.01,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "depth".to_string(),
+                value: self.depth,
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "feedback".to_string(),
+                value: self.feedback,
+                min: -FLANGER_MAX_FEEDBACK,
+                max: FLANGER_MAX_FEEDBACK,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "mix".to_string(),
+                value: self.mix.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "rate" => self.rate = value,
+            "depth" => self.depth = Self::clamp_depth(value),
+            "feedback" => self.feedback = Self::clamp_feedback(value),
+            "mix" => self.mix.set_target(value),
+            _ => {}
+        }
+    }
+
+    fn smoothable_parameters(&self) -> &[&str] {
+        &["mix"]
+    }
+
+    fn set_smoothing_frames(&mut self, frames: usize) {
+        self.mix.set_ramp_frames(frames);
+    }
+
+    fn reset(&mut self) {
+        self.delay_lines.iter_mut().for_each(|line| line.fill(0.0));
+        self.write_pos = 0;
+        self.lfo_phase = 0.0;
+    }
+}
+
+// Pitch Shift Effect
+//
+// Time-domain overlap-add shifter: two read taps sweep through a delay line at
+// the pitch ratio, each faded with a sin² window offset by half a grain so the
+// gains always sum to one. Cost is two interpolated reads per sample; latency
+// is bounded by the grain window (PITCH_SHIFT_WINDOW_MS, ~20ms on average).
+const PITCH_SHIFT_WINDOW_MS: f32 = 40.0;
+const PITCH_SHIFT_MAX_SEMITONES: f32 = 12.0;
+
+pub struct PitchShiftEffect {
+    semitones: f32,
+    mix: ParamSmoother,
+    ratio: f32,
+    window: f32,
+    // One line per channel; the grain phase is shared so channels stay aligned
+    delay_lines: Vec<Vec<f32>>,
+    write_pos: usize,
+    grain_phase: f32,
+}
+
+impl PitchShiftEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let window = PITCH_SHIFT_WINDOW_MS * 0.001 * sample_rate;
+        let semitones = Self::clamp_semitones(params.get("semitones").unwrap_or(0.0));
+
+        Self {
+            semitones,
+            mix: ParamSmoother::with_default_ramp(params.get("mix").unwrap_or(1.0), sample_rate),
+            ratio: Self::ratio_for(semitones),
+            window,
+            delay_lines: vec![vec![0.0; window.ceil() as usize + 2]],
+            write_pos: 0,
+            grain_phase: 0.0,
+        }
+    }
+
+    /// Worst-case delay introduced by the grain window, in samples.
+    pub fn max_latency_samples(&self) -> usize {
+        self.window.ceil() as usize
+    }
+
+    fn clamp_semitones(value: f32) -> f32 {
+        value.clamp(-PITCH_SHIFT_MAX_SEMITONES, PITCH_SHIFT_MAX_SEMITONES)
+    }
+
+    fn ratio_for(semitones: f32) -> f32 {
+        2.0f32.powf(semitones / 12.0)
+    }
+
+    // At 0 semitones the taps would only add delay and combing
+    fn is_unity(&self) -> bool {
+        self.ratio == 1.0
+    }
+
+    fn read_delayed(&self, channel: usize, delay_samples: f32) -> f32 {
+        let line = &self.delay_lines[channel];
+        let len = line.len();
+        let whole = delay_samples.floor() as usize;
+        let frac = delay_samples - whole as f32;
+
+        let a = line[(self.write_pos + len - whole) % len];
+        let b = line[(self.write_pos + len - whole - 1) % len];
+
+        a + (b - a) * frac
+    }
+
+    // Delay for a tap at the given grain phase; raising pitch shortens the
+    // delay over the grain, lowering it lengthens the delay
+    fn tap_delay(&self, phase: f32) -> f32 {
+        if self.ratio > 1.0 {
+            (1.0 - phase) * self.window
+        } else {
+            phase * self.window
+        }
+    }
+}
+
+impl AudioEffect for PitchShiftEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+        let phase_inc = (1.0 - self.ratio).abs() / self.window;
+
+        let channels = channels.max(1);
+        let len = self.delay_lines[0].len();
+        self.delay_lines.resize(channels, vec![0.0; len]);
+
+        for frame in input.chunks(channels) {
+            let phase_a = self.grain_phase;
+            let phase_b = (self.grain_phase + 0.5) % 1.0;
+            let gain_a = (PI * phase_a).sin().powi(2);
+            let gain_b = (PI * phase_b).sin().powi(2);
+            let delay_a = self.tap_delay(phase_a);
+            let delay_b = self.tap_delay(phase_b);
+            let mix = self.mix.tick();
+
+            for (channel, &sample) in frame.iter().enumerate() {
+                // The line keeps filling, so moving off 0 semitones starts
+                // from real history instead of silence
+                self.delay_lines[channel][self.write_pos] = sample;
+                if self.is_unity() {
+                    output.push(sample);
+                    continue;
+                }
+
+                let shifted = self.read_delayed(channel, delay_a) * gain_a
+                    + self.read_delayed(channel, delay_b) * gain_b;
+
+                output.push(sample * (1.0 - mix) + shifted * mix);
+            }
+
+            self.write_pos = (self.write_pos + 1) % len;
+            self.grain_phase = (self.grain_phase + phase_inc) % 1.0;
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Pitch Shift"
+    }
+
+    // The two taps sweep the whole window, so on average the wet signal sits
+    // half a window behind; at 0 semitones the input passes straight through
+    fn latency_samples(&self) -> usize {
+        if self.mix.target() <= 0.0 || self.is_unity() {
+            0
+        } else {
+            (self.window / 2.0).round() as usize
+        }
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            EffectParameter {
+                name: "semitones".to_string(),
+                value: self.semitones,
+                min: -PITCH_SHIFT_MAX_SEMITONES,
+                max: PITCH_SHIFT_MAX_SEMITONES,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "mix".to_string(),
+                value: self.mix.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "semitones" => {
+                self.semitones = Self::clamp_semitones(value);
+                self.ratio = Self::ratio_for(self.semitones);
+            }
+            "mix" => self.mix.set_target(value),
+            _ => {}
+        }
+    }
+
+    fn smoothable_parameters(&self) -> &[&str] {
+        &["mix"]
+    }
+
+    fn set_smoothing_frames(&mut self, frames: usize) {
+        self.mix.set_ramp_frames(frames);
+    }
+
+    fn reset(&mut self) {
+        self.delay_lines.iter_mut().for_each(|line| line.fill(0.0));
+        self.write_pos = 0;
+        self.grain_phase = 0.0;
+    }
+}
+
+// Automatic Gain Control
+//
+// Tracks the input RMS with a slow detector and steers a gain toward the
+// target level. Gain rises at the release rate but falls at the attack rate,
+// so a sudden loud passage is pulled down before it can clip.
+const AGC_RMS_WINDOW_SECS: f32 = 0.3;
+const AGC_SILENCE_DB: f32 = -60.0;
+
+pub struct AgcEffect {
+    target_level: f32,
+    max_gain: f32,
+    attack: f32,
+    release: f32,
+    sample_rate: f32,
+    mean_square: f32,
+    gain: f32,
+}
+
+impl AgcEffect {
+    pub fn new(params: EffectParams) -> Self {
+        Self {
+            target_level: params.get("target_level").unwrap_or(-18.0),
+            max_gain: params.get("max_gain").unwrap_or(24.0),
+            attack: params.get("attack").unwrap_or(0.01),
+            release: params.get("release").unwrap_or(2.0),
+            sample_rate: params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE),
+            mean_square: 0.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl AudioEffect for AgcEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+
+        let rms_coeff = time_coefficient(AGC_RMS_WINDOW_SECS, self.sample_rate);
+        let attack_coeff = time_coefficient(self.attack, self.sample_rate);
+        let release_coeff = time_coefficient(self.release, self.sample_rate);
+        let target = db_to_linear(self.target_level);
+        let max_gain = db_to_linear(self.max_gain);
+        let silence = db_to_linear(AGC_SILENCE_DB);
+
+        for frame in input.chunks(channels.max(1)) {
+            let frame_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            self.mean_square += (frame_square - self.mean_square) * rms_coeff;
+            let rms = self.mean_square.sqrt();
+
+            // Hold the current gain during silence instead of boosting the noise floor
+            if rms > silence {
+                let desired = (target / rms).min(max_gain);
+                let coeff = if desired < self.gain {
+                    attack_coeff
+                } else {
+                    release_coeff
+                };
+                self.gain += (desired - self.gain) * coeff;
+            }
+
+            // The RMS detector lags a sudden jump; never let the gain push a sample past full scale
+            let peak = frame_peak(frame);
+            if peak * self.gain > 1.0 {
+                self.gain = 1.0 / peak;
+            }
+
+            output.extend(frame.iter().map(|s| s * self.gain));
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "AGC"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            EffectParameter {
+                name: "target_level".to_string(),
+                value: self.target_level,
+                min: -40.0,
+                max: -3.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "max_gain".to_string(),
+                value: self.max_gain,
+                min: 0.0,
+                max: 40.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "attack".to_string(),
+                value: self.attack,
+                min: 0.001,
+                max: 0.5,
+                step: 0.001,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "release".to_string(),
+                value: self.release,
+                min: 0.1,
+                max: 10.0,
+                step: 0.1,
+                scale: ParamScale::Logarithmic,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "target_level" => self.target_level = value,
+            "max_gain" => self.max_gain = value,
+            "attack" => self.attack = value,
+            "release" => self.release = value,
+            _ => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.mean_square = 0.0;
+        self.gain = 1.0;
+    }
+}
+
+// Multiband Compressor Effect
+//
+// Three bands split by Linkwitz-Riley crossovers, each with its own envelope
+// and gain computer. The low band is run through an allpass at the upper
+// crossover so all three stay phase-aligned and sum back flat. Cost per
+// sample is 12 biquad sections plus three envelope followers, roughly four
+// times a single-band compressor.
+const MULTIBAND_BANDS: [&str; 3] = ["low", "mid", "high"];
+
+// Crossover state for one channel: low/mid/high, phase-aligned
+#[derive(Debug, Clone, Copy)]
+struct ThreeBandSplitter {
+    low_split: LinkwitzRileyCrossover,
+    high_split: LinkwitzRileyCrossover,
+    low_allpass: LinkwitzRileyCrossover,
+}
+
+// Inverted crossovers would overlap the bands and the sum would no longer be
+// flat, so the lower frequency always splits off the low band
+fn ordered_crossovers(a: f32, b: f32) -> (f32, f32) {
+    (a.min(b), a.max(b))
+}
+
+impl ThreeBandSplitter {
+    fn new(low_crossover: f32, high_crossover: f32, sample_rate: f32) -> Self {
+        let (low_crossover, high_crossover) = ordered_crossovers(low_crossover, high_crossover);
+        Self {
+            low_split: LinkwitzRileyCrossover::new(low_crossover, sample_rate),
+            high_split: LinkwitzRileyCrossover::new(high_crossover, sample_rate),
+            low_allpass: LinkwitzRileyCrossover::new(high_crossover, sample_rate),
+        }
+    }
+
+    fn set_frequencies(&mut self, low_crossover: f32, high_crossover: f32, sample_rate: f32) {
+        let (low_crossover, high_crossover) = ordered_crossovers(low_crossover, high_crossover);
+        self.low_split.set_frequency(low_crossover, sample_rate);
+        self.high_split.set_frequency(high_crossover, sample_rate);
+        self.low_allpass.set_frequency(high_crossover, sample_rate);
+    }
+
+    fn split(&mut self, x: f32) -> [f32; 3] {
+        let (low, rest) = self.low_split.split(x);
+        let (mid, high) = self.high_split.split(rest);
+        let (low_a, low_b) = self.low_allpass.split(low);
+        [low_a + low_b, mid, high]
+    }
+
+    fn reset(&mut self) {
+        self.low_split.reset();
+        self.high_split.reset();
+        self.low_allpass.reset();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CompressorBand {
+    threshold: f32,
+    ratio: f32,
+    gain: f32,
+    envelope: f32,
+}
+
+pub struct MultibandCompressorEffect {
+    low_crossover: f32,
+    high_crossover: f32,
+    attack: f32,
+    release: f32,
+    sample_rate: f32,
+    bands: [CompressorBand; 3],
+    // Per-channel filters; band envelopes above are linked across channels
+    splitters: Vec<ThreeBandSplitter>,
+    split: Vec<[f32; 3]>,
+}
+
+impl MultibandCompressorEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let (low_crossover, high_crossover) = ordered_crossovers(
+            params.get("low_crossover").unwrap_or(200.0),
+            params.get("high_crossover").unwrap_or(3000.0),
+        );
+
+        let band = |name: &str| CompressorBand {
+            threshold: params.get(&format!("{}_threshold", name)).unwrap_or(-20.0),
+            ratio: params.get(&format!("{}_ratio", name)).unwrap_or(3.0),
+            gain: params.get(&format!("{}_gain", name)).unwrap_or(0.0),
+            envelope: 0.0,
+        };
+
+        Self {
+            low_crossover,
+            high_crossover,
+            attack: params.get("attack").unwrap_or(0.01),
+            release: params.get("release").unwrap_or(0.15),
+            sample_rate,
+            bands: [band("low"), band("mid"), band("high")],
+            splitters: vec![ThreeBandSplitter::new(
+                low_crossover,
+                high_crossover,
+                sample_rate,
+            )],
+            split: Vec::new(),
+        }
+    }
+
+    fn retune_crossovers(&mut self) {
+        for splitter in self.splitters.iter_mut() {
+            splitter.set_frequencies(self.low_crossover, self.high_crossover, self.sample_rate);
+        }
+    }
+}
+
+impl AudioEffect for MultibandCompressorEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let mut output = Vec::with_capacity(input.len());
+
+        let attack_coeff = time_coefficient(self.attack, self.sample_rate);
+        let release_coeff = time_coefficient(self.release, self.sample_rate);
+
+        let channels = channels.max(1);
+        let fresh =
+            ThreeBandSplitter::new(self.low_crossover, self.high_crossover, self.sample_rate);
+        self.splitters.resize(channels, fresh);
+
+        for frame in input.chunks(channels) {
+            self.split.clear();
+            for (splitter, &sample) in self.splitters.iter_mut().zip(frame.iter()) {
+                self.split.push(splitter.split(sample));
+            }
+
+            let mut gains = [0.0f32; 3];
+            for (i, band) in self.bands.iter_mut().enumerate() {
+                let level = self
+                    .split
+                    .iter()
+                    .fold(0.0f32, |peak, bands| peak.max(bands[i].abs()));
+                let coeff = if level > band.envelope {
+                    attack_coeff
+                } else {
+                    release_coeff
+                };
+                band.envelope += (level - band.envelope) * coeff;
+
+                gains[i] =
+                    compression_gain(band.envelope, db_to_linear(band.threshold), band.ratio)
+                        * db_to_linear(band.gain);
+            }
+
+            output.extend(self.split.iter().map(|bands| {
+                bands
+                    .iter()
+                    .zip(gains.iter())
+                    .map(|(x, gain)| x * gain)
+                    .sum::<f32>()
+            }));
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Multiband Compressor"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        let mut parameters = vec![
+            EffectParameter {
+                name: "low_crossover".to_string(),
+                value: self.low_crossover,
+                min: 40.0,
+                max: 1000.0,
+                step: 1.0,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "high_crossover".to_string(),
+                value: self.high_crossover,
+                min: 1000.0,
+                max: 12000.0,
+                step: 10.0,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "attack".to_string(),
+                value: self.attack,
+                min: 0.001,
+                max: 0.2,
+                step: 0.001,
+                scale: ParamScale::Logarithmic,
+            },
+            EffectParameter {
+                name: "release".to_string(),
+                value: self.release,
+                min: 0.01,
+                max: 2.0,
+                step: 0.01,
+                scale: ParamScale::Logarithmic,
+            },
+        ];
+
+        for (name, band) in MULTIBAND_BANDS.iter().zip(self.bands.iter()) {
+            parameters.push(EffectParameter {
+                name: format!("{}_threshold", name),
+                value: band.threshold,
+                min: -60.0,
+                max: 0.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            });
+            parameters.push(EffectParameter {
+                name: format!("{}_ratio", name),
+                value: band.ratio,
+                min: 1.0,
+                max: 20.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            });
+            parameters.push(EffectParameter {
+                name: format!("{}_gain", name),
+                value: band.gain,
+                min: -12.0,
+                max: 12.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            });
+        }
+
+        parameters
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            // Moving one crossover past the other drags the other along, so
+            // the mid band narrows to nothing instead of inverting
+            "low_crossover" => {
+                self.low_crossover = value;
+                self.high_crossover = self.high_crossover.max(value);
+                self.retune_crossovers();
+            }
+            "high_crossover" => {
+                self.high_crossover = value;
+                self.low_crossover = self.low_crossover.min(value);
+                self.retune_crossovers();
+            }
+            "attack" => self.attack = value,
+            "release" => self.release = value,
+            _ => {
+                let Some((band_name, field)) = name.split_once('_') else {
+                    return;
+                };
+                let Some(index) = MULTIBAND_BANDS.iter().position(|b| *b == band_name) else {
+                    return;
+                };
+                let band = &mut self.bands[index];
+                match field {
+                    "threshold" => band.threshold = value,
+                    "ratio" => band.ratio = value.max(1.0),
+                    "gain" => band.gain = value,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.splitters.iter_mut().for_each(ThreeBandSplitter::reset);
+        self.bands.iter_mut().for_each(|band| band.envelope = 0.0);
+    }
+}
+
+// Saturation Effect
+//
+// Memoryless waveshaper. The input is pushed into the curve by `drive` and
+// divided back down by the same gain, so quiet material passes at its
+// original level while peaks are rounded off more the harder it is driven;
+// that also makes it a gentle overload protector. Every curve has unity slope
+// at zero, so the compensation holds across curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaturationCurve {
+    Tanh,
+    Arctan,
+    // x - x³/3, flat at ±2/3 beyond |x| = 1
+    Cubic,
+}
+
+impl SaturationCurve {
+    fn from_param(value: f32) -> Self {
+        match value.round() as i32 {
+            1 => SaturationCurve::Arctan,
+            2 => SaturationCurve::Cubic,
+            _ => SaturationCurve::Tanh,
+        }
+    }
+
+    fn as_param(self) -> f32 {
+        match self {
+            SaturationCurve::Tanh => 0.0,
+            SaturationCurve::Arctan => 1.0,
+            SaturationCurve::Cubic => 2.0,
+        }
+    }
+
+    fn shape(self, x: f32) -> f32 {
+        match self {
+            SaturationCurve::Tanh => x.tanh(),
+            SaturationCurve::Arctan => x.atan(),
+            SaturationCurve::Cubic => {
+                let x = x.clamp(-1.0, 1.0);
+                x - x * x * x / 3.0
+            }
+        }
+    }
+}
+
+pub struct SaturationEffect {
+    curve: SaturationCurve,
+    drive: f32,
+    mix: ParamSmoother,
+    output: f32,
+    // Linear form of `output`, which is what gets ramped
+    output_gain: ParamSmoother,
+}
+
+impl SaturationEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let output = params.get("output").unwrap_or(0.0);
+        Self {
+            curve: SaturationCurve::from_param(params.get("curve").unwrap_or(0.0)),
+            drive: params.get("drive").unwrap_or(6.0),
+            mix: ParamSmoother::with_default_ramp(
+                params.get("mix").unwrap_or(1.0).clamp(0.0, 1.0),
+                sample_rate,
+            ),
+            output,
+            output_gain: ParamSmoother::with_default_ramp(db_to_linear(output), sample_rate),
+        }
+    }
+}
+
+impl AudioEffect for SaturationEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let drive = db_to_linear(self.drive);
+        let mut output = Vec::with_capacity(input.len());
+
+        for frame in input.chunks(channels.max(1)) {
+            let mix = self.mix.tick();
+            let output_gain = self.output_gain.tick();
+            output.extend(frame.iter().map(|&dry| {
+                let wet = self.curve.shape(dry * drive) / drive;
+                (dry + (wet - dry) * mix) * output_gain
+            }));
+        }
+
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "Saturation"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![
+            // 0 = tanh, 1 = arctan, 2 = cubic
+            EffectParameter {
+                name: "curve".to_string(),
+                value: self.curve.as_param(),
+                min: 0.0,
+                max: 2.0,
+                step: 1.0,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "drive".to_string(),
+                value: self.drive,
+                min: 0.0,
+                max: 36.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "mix".to_string(),
+                value: self.mix.target(),
+                min: 0.0,
+                max: 1.0,
+                step: 0.01,
+                scale: ParamScale::Linear,
+            },
+            EffectParameter {
+                name: "output".to_string(),
+                value: self.output,
+                min: -24.0,
+                max: 12.0,
+                step: 0.1,
+                scale: ParamScale::Linear,
+            },
+        ]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        match name {
+            "curve" => self.curve = SaturationCurve::from_param(value),
+            "drive" => self.drive = value,
+            "mix" => self.mix.set_target(value.clamp(0.0, 1.0)),
+            "output" => {
+                self.output = value;
+                self.output_gain.set_target(db_to_linear(value));
+            }
+            _ => {}
+        }
+    }
+
+    fn smoothable_parameters(&self) -> &[&str] {
+        &["mix", "output"]
+    }
+
+    fn set_smoothing_frames(&mut self, frames: usize) {
+        self.mix.set_ramp_frames(frames);
+        self.output_gain.set_ramp_frames(frames);
+    }
+}
+
+// De-click Effect
+//
+// Mouth clicks and pops show up as sudden jumps between neighbouring samples.
+// Each channel's second difference (x[n] - 2x[n-1] + x[n-2]) is compared with
+// its recent average; a sample where it spikes past the sensitivity's
+// threshold starts a repair region, which runs a little past the last spiking
+// sample and is replaced by a straight line between the good samples on
+// either side. A jump that keeps going longer than DECLICK_MAX_CLICK_MS is a
+// real transient and is left alone. Output is delayed by the longest
+// repairable click plus its two anchors, so the right-hand anchor is always
+// seen before the region has to go out.
+const DECLICK_MAX_CLICK_MS: f32 = 2.0;
+const DECLICK_SPREAD_MS: f32 = 0.1;
+const DECLICK_RESIDUAL_WINDOW_SECS: f32 = 0.01;
+// Spike over the running average that counts as a click, at sensitivity 0
+// and 1; the ratio is interpolated logarithmically in between
+const DECLICK_RATIO_AT_LEAST_SENSITIVE: f32 = 64.0;
+const DECLICK_RATIO_AT_MOST_SENSITIVE: f32 = 8.0;
+// Keeps digital silence from making every tiny step a click
+const DECLICK_RESIDUAL_FLOOR: f32 = 1e-4;
+
+struct ClickRepair {
+    // Samples waiting to go out; the oldest is at absolute index
+    // `next_index - pending.len()`
+    pending: VecDeque<f32>,
+    next_index: u64,
+    previous: [f32; 2],
+    residual_average: f32,
+    // First and last bad sample of the click being repaired
+    region: Option<(u64, u64)>,
+    // After a transient too long to repair, detection rests until this index
+    resume_at: u64,
+}
+
+impl ClickRepair {
+    fn new(delay: usize) -> Self {
+        Self {
+            pending: VecDeque::from(vec![0.0; delay]),
+            next_index: delay as u64,
+            previous: [0.0; 2],
+            residual_average: 0.0,
+            region: None,
+            resume_at: 0,
+        }
+    }
+
+    fn process(&mut self, sample: f32, ratio: f32, settings: &DeClickSettings) -> f32 {
+        let index = self.next_index;
+        let residual = (sample - 2.0 * self.previous[0] + self.previous[1]).abs();
+        let threshold = self.residual_average * ratio + DECLICK_RESIDUAL_FLOOR;
+
+        if residual > threshold && index >= self.resume_at {
+            let start = self.region.map_or(index, |(start, _)| start);
+            let end = index + settings.spread;
+            self.region = (end - start <= settings.max_click).then_some((start, end));
+            if self.region.is_none() {
+                // Too long for a click; let it through untouched
+                self.resume_at = index + settings.max_click;
+            }
+        } else {
+            self.residual_average +=
+                (residual - self.residual_average) * settings.average_coefficient;
+        }
+
+        self.previous = [sample, self.previous[0]];
+        self.pending.push_back(sample);
+        self.next_index += 1;
+
+        if let Some((start, end)) = self.region {
+            if index > end {
+                self.interpolate(start, end);
+                self.region = None;
+            }
+        }
+
+        self.pending.pop_front().unwrap_or(0.0)
+    }
+
+    // Straight line from the sample before `start` to the one after `end`
+    fn interpolate(&mut self, start: u64, end: u64) {
+        let first = self.next_index - self.pending.len() as u64;
+        let (Some(left), Some(right)) =
+            (start.checked_sub(first + 1), (end + 1).checked_sub(first))
+        else {
+            return;
+        };
+        let (left, right) = (left as usize, right as usize);
+        let (from, to) = (self.pending[left], self.pending[right]);
+        let span = (right - left) as f32;
+        for (step, position) in (left + 1..right).enumerate() {
+            self.pending[position] = from + (to - from) * (step + 1) as f32 / span;
+        }
+    }
+}
+
+// Sample counts derived from the rate, shared by every channel
+struct DeClickSettings {
+    max_click: u64,
+    spread: u64,
+    average_coefficient: f32,
+}
+
+pub struct DeClickEffect {
+    sensitivity: f32,
+    settings: DeClickSettings,
+    delay: usize,
+    channels: Vec<ClickRepair>,
+}
+
+impl DeClickEffect {
+    pub fn new(params: EffectParams) -> Self {
+        let sample_rate = params.get("sample_rate").unwrap_or(DEFAULT_SAMPLE_RATE);
+        let max_click = (DECLICK_MAX_CLICK_MS * 0.001 * sample_rate) as u64;
+        Self {
+            sensitivity: params.get("sensitivity").unwrap_or(0.5).clamp(0.0, 1.0),
+            settings: DeClickSettings {
+                max_click,
+                spread: ((DECLICK_SPREAD_MS * 0.001 * sample_rate) as u64).max(1),
+                average_coefficient: time_coefficient(DECLICK_RESIDUAL_WINDOW_SECS, sample_rate),
+            },
+            delay: max_click as usize + 2,
+            channels: Vec::new(),
+        }
+    }
+
+    fn spike_ratio(&self) -> f32 {
+        let span = DECLICK_RATIO_AT_MOST_SENSITIVE / DECLICK_RATIO_AT_LEAST_SENSITIVE;
+        DECLICK_RATIO_AT_LEAST_SENSITIVE * span.powf(self.sensitivity)
+    }
+}
+
+impl AudioEffect for DeClickEffect {
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32> {
+        let channels = channels.max(1);
+        if self.channels.len() != channels {
+            self.channels = (0..channels)
+                .map(|_| ClickRepair::new(self.delay))
+                .collect();
+        }
+
+        let ratio = self.spike_ratio();
+        let mut output = Vec::with_capacity(input.len());
+        for frame in input.chunks(channels) {
+            for (repair, &sample) in self.channels.iter_mut().zip(frame) {
+                output.push(repair.process(sample, ratio, &self.settings));
+            }
+        }
+        output
+    }
+
+    fn get_name(&self) -> &str {
+        "De-click"
+    }
+
+    fn get_parameters(&self) -> Vec<EffectParameter> {
+        vec![EffectParameter {
+            name: "sensitivity".to_string(),
+            value: self.sensitivity,
+            min: 0.0,
+            max: 1.0,
+            step: 0.01,
+            scale: ParamScale::Linear,
+        }]
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        if name == "sensitivity" {
+            self.sensitivity = value.clamp(0.0, 1.0);
+        }
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.delay
+    }
+
+    fn reset(&mut self) {
+        self.channels.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::{num_complex::Complex, FftPlanner};
+
+    fn params(values: &[(&str, f32)]) -> EffectParams {
+        let mut params = EffectParams::new();
+        for (name, value) in values {
+            params.set(name.to_string(), *value);
+        }
+        params
+    }
+
+    // Indices and levels of the local maxima of |signal| above `floor`
+    fn echo_peaks(signal: &[f32], floor: f32) -> Vec<(usize, f32)> {
+        (1..signal.len() - 1)
+            .filter(|&i| {
+                let level = signal[i].abs();
+                level > floor && level >= signal[i - 1].abs() && level > signal[i + 1].abs()
+            })
+            .map(|i| (i, signal[i].abs()))
+            .collect()
+    }
+
+    #[test]
+    fn flanger_feedback_gives_decaying_moving_echoes() {
+        let mut flanger = FlangerEffect::new(params(&[
+            ("feedback", 0.7),
+            ("mix", 1.0),
+            ("rate", 5.0),
+            ("depth", 1.0),
+        ]));
+        let mut input = vec![0.0f32; 4800];
+        input[0] = 1.0;
+        let output = flanger.process(&input, 1);
+
+        let echoes = echo_peaks(&output, 0.02);
+        assert!(echoes.len() >= 4, "only {} echoes", echoes.len());
+        for pair in echoes.windows(2) {
+            assert!(pair[1].1 < pair[0].1, "echo grew: {:?}", pair);
+        }
+        // The LFO moves the delay, so the echoes aren't evenly spaced
+        let gaps: Vec<usize> = echoes
+            .windows(2)
+            .map(|pair| pair[1].0 - pair[0].0)
+            .collect();
+        assert!(
+            gaps.iter().any(|&gap| gap != gaps[0]),
+            "evenly spaced echoes: {:?}",
+            gaps
+        );
+    }
+
+    #[test]
+    fn flanger_depth_is_clamped_to_the_delay_line() {
+        let depth = |flanger: &FlangerEffect| {
+            flanger
+                .get_parameters()
+                .into_iter()
+                .find(|p| p.name == "depth")
+                .unwrap()
+                .value
+        };
+        let input = sine(440.0, 0.5, 4800);
+
+        let mut flanger = FlangerEffect::new(params(&[("depth", 5.0), ("rate", 5.0)]));
+        assert_eq!(depth(&flanger), 1.0);
+        assert!(flanger.process(&input, 1).iter().all(|s| s.is_finite()));
+
+        flanger.set_parameter("depth", 5.0);
+        assert_eq!(depth(&flanger), 1.0);
+        flanger.set_parameter("depth", -5.0);
+        assert_eq!(depth(&flanger), 0.0);
+        assert!(flanger.process(&input, 1).iter().all(|s| s.is_finite()));
+    }
+
+    fn sine(freq: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|n| amplitude * (2.0 * PI * freq * n as f32 / DEFAULT_SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    // Frequency of the strongest FFT bin of a mono signal
+    fn peak_frequency(signal: &[f32]) -> f32 {
+        let mut spectrum: Vec<Complex<f32>> = signal
+            .iter()
+            .enumerate()
+            .map(|(n, &s)| {
+                let hann = 0.5 - 0.5 * (2.0 * PI * n as f32 / signal.len() as f32).cos();
+                Complex::new(s * hann, 0.0)
+            })
+            .collect();
+        FftPlanner::new()
+            .plan_fft_forward(spectrum.len())
+            .process(&mut spectrum);
+        let peak = (1..spectrum.len() / 2)
+            .max_by(|&a, &b| spectrum[a].norm().total_cmp(&spectrum[b].norm()))
+            .unwrap();
+        peak as f32 * DEFAULT_SAMPLE_RATE / signal.len() as f32
+    }
+
+    #[test]
+    fn pitch_shift_up_an_octave_doubles_the_frequency() {
+        let mut shifter = PitchShiftEffect::new(params(&[("semitones", 12.0), ("mix", 1.0)]));
+        let input = sine(440.0, 0.5, 48_000);
+        let output: Vec<f32> = input
+            .chunks(480)
+            .flat_map(|block| shifter.process(block, 1))
+            .collect();
+
+        // Past the grain window's start-up
+        let peak = peak_frequency(&output[16_384..32_768]);
+        assert!((peak - 880.0).abs() < 15.0, "peak at {} Hz", peak);
+    }
+
+    #[test]
+    fn pitch_shift_at_unity_passes_through() {
+        let mut shifter = PitchShiftEffect::new(params(&[("semitones", 0.0), ("mix", 1.0)]));
+        let input = sine(440.0, 0.5, 4_800);
+        assert_eq!(shifter.process(&input, 1), input);
+        assert_eq!(shifter.latency_samples(), 0);
+    }
+
+    fn rms_db(signal: &[f32]) -> f32 {
+        let mean_square = signal.iter().map(|s| s * s).sum::<f32>() / signal.len() as f32;
+        10.0 * mean_square.log10()
+    }
+
+    #[test]
+    fn agc_converges_on_the_target_after_a_step() {
+        let mut agc = AgcEffect::new(params(&[("target_level", -18.0)]));
+        let seconds = 48_000 * 4;
+        let quiet = sine(300.0, 0.02, seconds);
+        let loud: Vec<f32> = sine(300.0, 0.5, 2 * seconds)[seconds..].to_vec();
+
+        let quiet_out: Vec<f32> = quiet
+            .chunks(480)
+            .flat_map(|block| agc.process(block, 1))
+            .collect();
+        let loud_out: Vec<f32> = loud
+            .chunks(480)
+            .flat_map(|block| agc.process(block, 1))
+            .collect();
+
+        let tail = 4_800;
+        for out in [&quiet_out, &loud_out] {
+            let level = rms_db(&out[out.len() - tail..]);
+            assert!((level + 18.0).abs() < 1.5, "settled at {} dBFS", level);
+        }
+    }
+
+    #[test]
+    fn gate_hold_rides_through_a_short_dip() {
+        let dip = 960;
+        let mut signal = vec![0.5f32; 4_800];
+        signal.extend(vec![0.0005f32; dip]);
+        signal.extend(vec![0.5f32; 4_800]);
+
+        // 20 ms below the threshold, inside the default 50 ms hold
+        let mut gate = NoiseGateEffect::new(params(&[("release", 0.01)]));
+        let held: Vec<f32> = signal
+            .chunks(256)
+            .flat_map(|block| gate.process(block, 1))
+            .collect();
+        assert!(
+            held[4_800..4_800 + dip]
+                .iter()
+                .all(|s| (s - 0.0005).abs() < 1e-7),
+            "gate closed during the dip"
+        );
+
+        let mut gate = NoiseGateEffect::new(params(&[("release", 0.01), ("hold", 0.0)]));
+        let chattering: Vec<f32> = signal
+            .chunks(256)
+            .flat_map(|block| gate.process(block, 1))
+            .collect();
+        assert!(
+            chattering[4_800..4_800 + dip].iter().any(|s| *s < 0.0001),
+            "gate without hold stayed open"
+        );
+    }
+
+    fn peak(signal: &[f32]) -> f32 {
+        signal.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    fn parameter(effect: &dyn AudioEffect, name: &str) -> f32 {
+        effect
+            .get_parameters()
+            .into_iter()
+            .find(|p| p.name == name)
+            .unwrap()
+            .value
+    }
+
+    #[test]
+    fn multiband_bands_sum_flat_without_compression() {
+        for freq in [50.0, 200.0, 700.0, 3_000.0, 9_000.0] {
+            let mut compressor = MultibandCompressorEffect::new(params(&[
+                ("low_ratio", 1.0),
+                ("mid_ratio", 1.0),
+                ("high_ratio", 1.0),
+            ]));
+            let output = compressor.process(&sine(freq, 0.5, 48_000), 1);
+            let level = peak(&output[24_000..]);
+            assert!(
+                (level - 0.5).abs() < 0.01,
+                "{} Hz came out at {}",
+                freq,
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn multiband_crossovers_stay_ordered() {
+        let mut compressor = MultibandCompressorEffect::new(params(&[
+            ("low_crossover", 4_000.0),
+            ("high_crossover", 1_000.0),
+        ]));
+        assert!(parameter(&compressor, "low_crossover") < parameter(&compressor, "high_crossover"));
+
+        compressor.set_parameter("low_crossover", 5_000.0);
+        assert!(parameter(&compressor, "high_crossover") >= 5_000.0);
+        compressor.set_parameter("high_crossover", 100.0);
+        assert!(parameter(&compressor, "low_crossover") <= 100.0);
+    }
+
+    #[test]
+    fn gain_ramp_fades_instead_of_cutting() {
+        // Push-to-talk's 5 ms open and 40 ms close on a constant stereo signal
+        let mut ramp = GainRamp::new(0.0, 0.005, DEFAULT_SAMPLE_RATE);
+        let mut opened = vec![1.0f32; 960];
+        ramp.apply(&mut opened, 2, 1.0);
+        ramp.set_ramp_time(0.04, DEFAULT_SAMPLE_RATE);
+        let mut closed = vec![1.0f32; 4_800];
+        ramp.apply(&mut closed, 2, 0.0);
+
+        for (signal, frames) in [(&opened, 240.0), (&closed, 1_920.0)] {
+            let left: Vec<f32> = signal.iter().step_by(2).copied().collect();
+            let largest_step = left
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .fold(0.0, f32::max);
+            assert!(
+                largest_step <= 1.0 / frames + 1e-6,
+                "step of {}",
+                largest_step
+            );
+            // Both channels get the same gain
+            assert!(signal.chunks(2).all(|frame| frame[0] == frame[1]));
+        }
+        assert_eq!(*opened.last().unwrap(), 1.0);
+        assert_eq!(*closed.last().unwrap(), 0.0);
+        assert!(opened[0] > 0.0 && opened[0] < 0.01);
+    }
+
+    // Runs the same left-only signal through one effect as mono and another as
+    // stereo: the left channel has to match the mono run and the right stay silent
+    fn assert_channels_independent(mut mono: impl AudioEffect, mut stereo: impl AudioEffect) {
+        let left: Vec<f32> = (0..9_600)
+            .map(|n| 0.8 * (n as f32 * 0.031).sin() * ((n / 700) % 2) as f32)
+            .collect();
+        let interleaved: Vec<f32> = left.iter().flat_map(|&s| [s, 0.0]).collect();
+
+        let mono_out: Vec<f32> = left
+            .chunks(480)
+            .flat_map(|block| mono.process(block, 1))
+            .collect();
+        let stereo_out: Vec<f32> = interleaved
+            .chunks(960)
+            .flat_map(|block| stereo.process(block, 2))
+            .collect();
+        for (frame, expected) in stereo_out.chunks(2).zip(&mono_out) {
+            assert!(
+                (frame[0] - expected).abs() < 1e-5,
+                "{}: L {} vs {}",
+                stereo.get_name(),
+                frame[0],
+                expected
+            );
+            assert!(
+                frame[1].abs() < 1e-6,
+                "{}: R picked up {}",
+                stereo.get_name(),
+                frame[1]
+            );
+        }
+    }
+
+    #[test]
+    fn stereo_filters_keep_separate_histories() {
+        let boosted = || params(&[("band_2", 9.0), ("band_6", -6.0)]);
+        assert_channels_independent(
+            EqualizerEffect::new(boosted()),
+            EqualizerEffect::new(boosted()),
+        );
+        assert_channels_independent(
+            MultibandCompressorEffect::new(params(&[])),
+            MultibandCompressorEffect::new(params(&[])),
+        );
+        assert_channels_independent(
+            FlangerEffect::new(params(&[])),
+            FlangerEffect::new(params(&[])),
+        );
+    }
+
+    // Steady-state gain in dB a gate settles to on a constant input at `level_db`
+    fn gate_gain_db(mode: f32, level_db: f32) -> f32 {
+        let mut gate = NoiseGateEffect::new(params(&[
+            ("mode", mode),
+            ("threshold", -40.0),
+            ("ratio", 10.0),
+        ]));
+        let level = 10f32.powf(level_db / 20.0);
+        let output = gate.process(&vec![level; 48_000], 1);
+        20.0 * (output[47_999] / level).log10()
+    }
+
+    #[test]
+    fn expander_attenuates_gently_just_below_threshold() {
+        let expander = gate_gain_db(1.0, -41.0);
+        let gate = gate_gain_db(0.0, -41.0);
+        // 1 dB under the threshold at 10:1 expands to 9 dB of attenuation, where
+        // the gate drops straight to its 1/ratio floor
+        assert!(
+            (expander + 9.0).abs() < 0.1,
+            "expander gain {} dB",
+            expander
+        );
+        assert!((gate + 20.0).abs() < 0.1, "gate gain {} dB", gate);
+        assert!(gate_gain_db(1.0, -6.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn saturation_is_linear_when_quiet_and_bounded_when_driven() {
+        let wave: Vec<f32> = (0..4_800).map(|i| (i as f32 * 0.05).sin()).collect();
+        let quiet: Vec<f32> = wave.iter().map(|s| s * 0.1).collect();
+        let hot: Vec<f32> = wave.iter().map(|s| s * 4.0).collect();
+
+        for curve in [0.0, 1.0, 2.0] {
+            let output = SaturationEffect::new(params(&[("curve", curve), ("drive", 0.0)]))
+                .process(&quiet, 1);
+            for (input, output) in quiet.iter().zip(&output) {
+                assert!(
+                    (input - output).abs() < 0.002,
+                    "curve {} bent {} into {}",
+                    curve,
+                    input,
+                    output
+                );
+            }
+
+            // Every curve levels off at or below pi/2 before the 1/drive makeup
+            let output = SaturationEffect::new(params(&[("curve", curve), ("drive", 30.0)]))
+                .process(&hot, 1);
+            let ceiling = std::f32::consts::FRAC_PI_2 / db_to_linear(30.0);
+            assert!(
+                peak(&output) <= ceiling,
+                "curve {} peaked at {}",
+                curve,
+                peak(&output)
+            );
+        }
+    }
+
+    #[test]
+    fn dc_blocker_centers_an_offset_signal() {
+        let mut blocker = DcBlocker::new(5.0, 48_000.0, 2);
+        let mut signal: Vec<f32> = sine(1_000.0, 0.25, 48_000)
+            .iter()
+            .flat_map(|&s| [s + 0.3, s - 0.2])
+            .collect();
+        blocker.process(&mut signal);
+
+        // After a second (many time constants at 5 Hz) each channel's mean is zero
+        // and the tone itself goes through untouched
+        let settled = &signal[signal.len() - 9_600..];
+        for channel in 0..2 {
+            let samples: Vec<f32> = settled.iter().skip(channel).step_by(2).copied().collect();
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(
+                mean.abs() < 0.005,
+                "channel {} still sits at {}",
+                channel,
+                mean
+            );
+            assert!(
+                (peak(&samples) - 0.25).abs() < 0.01,
+                "channel {} peak {}",
+                channel,
+                peak(&samples)
+            );
+        }
+    }
+
+    #[test]
+    fn parameter_step_ramps_linearly_over_the_smoothing_time() {
+        let mut saturation = SaturationEffect::new(params(&[("mix", 0.0)]));
+        saturation.set_smoothing_frames(100);
+        let input = vec![0.5; 400];
+        assert!(saturation
+            .process(&input, 2)
+            .iter()
+            .all(|s| (s - 0.5).abs() < 1e-6));
+
+        // -6.02 dB of output gain halves the level, one step per frame with both
+        // channels moving together
+        saturation.set_parameter("output", -6.0206);
+        let output = saturation.process(&input, 2);
+        for (frame, pair) in output.chunks(2).enumerate() {
+            let expected = 0.5 - 0.25 * ((frame + 1).min(100) as f32 / 100.0);
+            assert!(
+                (pair[0] - expected).abs() < 1e-4,
+                "frame {} at {}",
+                frame,
+                pair[0]
+            );
+            assert_eq!(pair[0], pair[1]);
+        }
+        assert_eq!(parameter(&saturation, "output"), -6.0206);
+
+        // Without smoothing the change lands on the next sample
+        saturation.set_smoothing_frames(0);
+        saturation.set_parameter("output", 0.0);
+        assert!((saturation.process(&input, 2)[0] - 0.5).abs() < 1e-5);
+    }
+
+    fn stereo(mono: &[f32]) -> Vec<f32> {
+        mono.iter().flat_map(|&s| [s, s]).collect()
+    }
+
+    #[test]
+    fn boosted_eq_band_reports_clipping_and_flat_bands_dont() {
+        let mut eq = EqualizerEffect::new(EffectParams::new());
+        eq.set_parameter("band_5", 12.0);
+        let hot = stereo(&sine(1_000.0, 0.7, 4_800));
+        eq.process(&hot, 2);
+
+        let levels = eq.band_levels().unwrap();
+        assert_eq!(levels[5].frequency, 1_000.0);
+        assert!(
+            levels[5].clipping && levels[5].peak_db > 6.0,
+            "{:?}",
+            levels[5]
+        );
+        for (band, level) in levels.iter().enumerate().filter(|(band, _)| *band != 5) {
+            assert!(!level.clipping, "flat band {} clipped: {:?}", band, level);
+        }
+
+        // The indicator stays lit for two seconds after the last clip, then clears
+        let one_second = vec![0.0; 48_000 * 2];
+        eq.process(&one_second, 2);
+        assert!(eq.band_levels().unwrap()[5].clipping);
+        eq.process(&one_second, 2);
+        eq.process(&one_second, 2);
+        assert!(!eq.band_levels().unwrap()[5].clipping);
+    }
+
+    #[test]
+    fn reset_silences_delay_and_reverb_tails() {
+        let mut impulse = vec![0.0; 960];
+        impulse[0] = 1.0;
+        let silence = vec![0.0; 960];
+        let effects: Vec<Box<dyn AudioEffect>> = vec![
+            Box::new(FlangerEffect::new(params(&[
+                ("feedback", 0.9),
+                ("mix", 1.0),
+            ]))),
+            Box::new(ReverbEffect::new(params(&[
+                ("wet_level", 1.0),
+                ("dry_level", 0.0),
+            ]))),
+        ];
+        for mut effect in effects {
+            effect.process(&impulse, 1);
+            assert!(
+                peak(&effect.process(&silence, 1)) > 1e-3,
+                "{} left no tail",
+                effect.get_name()
+            );
+            effect.reset();
+            assert_eq!(
+                peak(&effect.process(&silence, 1)),
+                0.0,
+                "{} still ringing",
+                effect.get_name()
+            );
+        }
+    }
+
+    // Left and right reverb tails for a mono impulse (the same on both inputs)
+    fn reverb_tails(stereo_width: f32) -> (Vec<f32>, Vec<f32>) {
+        let mut reverb = ReverbEffect::new(params(&[
+            ("stereo_width", stereo_width),
+            ("dry_level", 0.0),
+        ]));
+        let mut impulse = vec![0.0; 48_000 * 2];
+        impulse[..2].fill(1.0);
+        let output = reverb.process(&impulse, 2);
+        (
+            output.iter().step_by(2).copied().collect(),
+            output.iter().skip(1).step_by(2).copied().collect(),
+        )
+    }
+
+    fn cross_correlation(a: &[f32], b: &[f32]) -> f32 {
+        let dot = |x: &[f32], y: &[f32]| x.iter().zip(y).map(|(x, y)| x * y).sum::<f32>();
+        dot(a, b) / (dot(a, a) * dot(b, b)).sqrt()
+    }
+
+    #[test]
+    fn reverb_tails_are_decorrelated_for_a_mono_impulse() {
+        let (left, right) = reverb_tails(1.0);
+        assert!(peak(&left) > 0.01);
+        let wide = cross_correlation(&left, &right);
+        assert!(wide.abs() < 0.2, "full width correlation {}", wide);
+
+        // Zero width folds the tails back to mono
+        let (left, right) = reverb_tails(0.0);
+        assert!(cross_correlation(&left, &right) > 0.999);
+    }
+
+    // Gain the compressor applies to the frame after a lone click on a quiet bed
+    fn gain_after_click(detector: DetectorMode) -> f32 {
+        let mut compressor = CompressorEffect::new(params(&[
+            ("detector", detector.as_param()),
+            ("threshold", -10.0),
+            ("ratio", 10.0),
+            ("attack", 1.0),
+        ]));
+        let mut input = vec![0.05f32; 480];
+        input[100] = 1.0;
+        compressor.process(&input, 1)[101] / input[101]
+    }
+
+    #[test]
+    fn rms_detector_reacts_less_to_a_transient_than_peak() {
+        let peak = gain_after_click(DetectorMode::Peak);
+        let rms = gain_after_click(DetectorMode::Rms);
+        assert!(rms > peak, "peak gain {}, rms gain {}", peak, rms);
+    }
+
+    #[test]
+    fn rms_window_carries_across_blocks() {
+        let rms = || {
+            CompressorEffect::new(params(&[
+                ("detector", 1.0),
+                ("threshold", -20.0),
+                ("ratio", 4.0),
+            ]))
+        };
+        let input: Vec<f32> = (0..4_800)
+            .map(|n| if n % 97 == 0 { 0.9 } else { 0.1 })
+            .collect();
+        let whole = rms().process(&input, 1);
+
+        let mut split = rms();
+        let blocks: Vec<f32> = input
+            .chunks(160)
+            .flat_map(|block| split.process(block, 1))
+            .collect();
+        assert_eq!(blocks, whole);
+    }
+
+    #[test]
+    fn frozen_reverb_holds_its_tail_through_silence() {
+        let mut reverb = ReverbEffect::new(params(&[("dry_level", 0.0)]));
+        let noise: Vec<f32> = (0..48_000 * 2)
+            .map(|n| ((n as f32 * 12.9898).sin() * 43_758.545).fract() - 0.5)
+            .collect();
+        reverb.process(&noise, 2);
+
+        // One second blocks of silence
+        let silence = vec![0.0; 48_000 * 2];
+        reverb.set_parameter("freeze", 1.0);
+        let first = rms_db(&reverb.process(&silence, 2));
+        reverb.process(&silence, 2);
+        let third = rms_db(&reverb.process(&silence, 2));
+        assert!((third - first).abs() < 1.0, "{} dB -> {} dB", first, third);
+
+        reverb.set_parameter("freeze", 0.0);
+        reverb.process(&silence, 2);
+        let released = rms_db(&reverb.process(&silence, 2));
+        assert!(released < first - 20.0, "{} dB after unfreezing", released);
+    }
+
+    // Share of the first 5 ms of a word onset, after silence, that the gate lets
+    // through, lined up for the gate's own delay
+    fn onset_kept(lookahead_ms: f32) -> f32 {
+        let mut gate =
+            NoiseGateEffect::new(params(&[("lookahead_ms", lookahead_ms), ("attack", 0.001)]));
+        let input: Vec<f32> = (0..4_800)
+            .map(|n| match n {
+                0..=2_399 => 0.0,
+                _ if (n / 10) % 2 == 0 => 0.05,
+                _ => -0.05,
+            })
+            .collect();
+        let output: Vec<f32> = input
+            .chunks(256)
+            .flat_map(|block| gate.process(block, 1))
+            .collect();
+
+        let delay = gate.latency_samples();
+        let onset = 2_400..2_640;
+        let kept: f32 = onset.clone().map(|n| output[n + delay].powi(2)).sum();
+        kept / onset.map(|n| input[n].powi(2)).sum::<f32>()
+    }
+
+    #[test]
+    fn gate_lookahead_keeps_the_word_onset() {
+        let without = onset_kept(0.0);
+        let with = onset_kept(10.0);
+        assert!(without < 0.5, "{} kept without lookahead", without);
+        assert!(with > 0.95, "{} kept with lookahead", with);
+    }
+
+    #[test]
+    fn declick_smooths_the_click_and_leaves_the_sine_alone() {
+        let clean = sine(440.0, 0.5, 9_600);
+        let click = 6_000;
+        let mut input = clean.clone();
+        input[click] += 0.4;
+        input[click + 1] -= 0.3;
+
+        let mut declick = DeClickEffect::new(EffectParams::new());
+        let output: Vec<f32> = input
+            .chunks(480)
+            .flat_map(|block| declick.process(block, 1))
+            .collect();
+        let delay = declick.latency_samples();
+        let error = |n: usize| (output[n + delay] - clean[n]).abs();
+
+        let near_click = (click - 5..click + 10).map(error).fold(0.0, f32::max);
+        assert!(
+            near_click < 0.02,
+            "{} off the sine at the click",
+            near_click
+        );
+        assert!((1_000..5_000)
+            .chain(7_000..9_600 - delay)
+            .all(|n| error(n) == 0.0));
+
+        // Only the clicked channel is touched
+        let mut declick = DeClickEffect::new(EffectParams::new());
+        let stereo: Vec<f32> = input
+            .iter()
+            .zip(&clean)
+            .flat_map(|(clicked, clean)| [*clicked, *clean])
+            .collect();
+        let output = declick.process(&stereo, 2);
+        assert!((0..9_600 - delay).all(|n| output[(n + delay) * 2 + 1] == clean[n]));
+    }
+
+    // Different tones left and right, so mid and side both carry signal
+    fn two_tones(frames: usize) -> Vec<f32> {
+        let left = sine(440.0, 0.3, frames);
+        let right = sine(1_250.0, 0.2, frames);
+        left.iter()
+            .zip(&right)
+            .flat_map(|(l, r)| [*l, *r])
+            .collect()
+    }
+
+    fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b)
+            .fold(0.0, |max, (a, b)| max.max((a - b).abs()))
+    }
+
+    #[test]
+    fn mid_side_without_gain_change_reconstructs_the_stereo() {
+        let input = two_tones(4_800);
+        let mut encoded = input.clone();
+        encode_mid_side(&mut encoded);
+        assert!(max_difference(&encoded, &input) > 0.1);
+        decode_mid_side(&mut encoded);
+        assert!(max_difference(&encoded, &input) < 1e-6);
+
+        let mut eq = EqualizerEffect::new(params(&[("processing_mode", 1.0)]));
+        assert!(max_difference(&eq.process(&input, 2), &input) < 1e-5);
+        // Full scale, above anything M or S reach
+        let mut compressor =
+            CompressorEffect::new(params(&[("processing_mode", 1.0), ("threshold", 0.0)]));
+        assert!(max_difference(&compressor.process(&input, 2), &input) < 1e-5);
+    }
+
+    #[test]
+    fn mid_side_compressor_reduces_mid_and_side_by_their_own_levels() {
+        let threshold = db_to_linear(-12.0);
+        let mut compressor = CompressorEffect::new(params(&[
+            ("processing_mode", 1.0),
+            ("threshold", -12.0),
+            ("ratio", 4.0),
+            ("attack", 1.0),
+        ]));
+        // M = 0.4 is over the threshold and S = 0.2 under it
+        let input: Vec<f32> = [0.6, 0.2].repeat(480);
+        let output = compressor.process(&input, 2);
+
+        let mid = threshold + (0.4 - threshold) / 4.0;
+        let expected = [mid + 0.2, mid - 0.2];
+        for frame in output.chunks(2) {
+            assert!(
+                max_difference(frame, &expected) < 1e-5,
+                "{:?}, expected {:?}",
+                frame,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn compressor_envelopes_carry_across_blocks() {
+        let input = two_tones(4_800);
+        for mode in [0.0, 1.0] {
+            // -30 dBFS, well under the tones in either mode
+            let compressor = || {
+                CompressorEffect::new(params(&[("processing_mode", mode), ("threshold", -30.0)]))
+            };
+            let whole = compressor().process(&input, 2);
+            assert!(max_difference(&whole, &input) > 0.01);
+            let mut split = compressor();
+            let blocks: Vec<f32> = input
+                .chunks(480)
+                .flat_map(|block| split.process(block, 2))
+                .collect();
+            assert_eq!(blocks, whole, "processing mode {}", mode);
+        }
+    }
 }
@@ -0,0 +1,195 @@
+// Chains that ship with the app, so a new user can start from something that
+// sounds right instead of an empty chain. Each effect lists only the
+// parameters it changes; the rest keep the effect's defaults.
+use super::{ChainPreset, EffectParams, EffectPreset, EffectType};
+
+// One effect of a factory preset
+pub struct EffectSpec {
+    pub effect_type: EffectType,
+    pub params: &'static [(&'static str, f32)],
+}
+
+impl EffectSpec {
+    const fn new(effect_type: EffectType, params: &'static [(&'static str, f32)]) -> Self {
+        Self {
+            effect_type,
+            params,
+        }
+    }
+
+    fn to_preset(&self) -> EffectPreset {
+        let mut params = EffectParams::new();
+        for &(name, value) in self.params {
+            params.set(name.to_string(), value);
+        }
+        EffectPreset {
+            effect_type: self.effect_type.key().to_string(),
+            params,
+            mix: 1.0,
+        }
+    }
+}
+
+pub static FACTORY_PRESETS: &[(&str, &[EffectSpec])] = &[
+    // Close-miked speech: quiet the room between sentences, take out rumble
+    // and boxiness, add presence, then even out the level
+    (
+        "Podcast Voice",
+        &[
+            EffectSpec::new(
+                EffectType::NoiseGate,
+                &[("mode", 1.0), ("threshold", -50.0), ("ratio", 2.0)],
+            ),
+            EffectSpec::new(
+                EffectType::Eq,
+                &[
+                    ("band_0", -12.0),
+                    ("band_1", -4.0),
+                    ("band_3", -2.0),
+                    ("band_6", 2.0),
+                    ("band_7", 3.0),
+                ],
+            ),
+            EffectSpec::new(
+                EffectType::Compressor,
+                &[("threshold", -18.0), ("ratio", 3.0), ("detector", 1.0)],
+            ),
+        ],
+    ),
+    // Dense and forward, to sit over game audio
+    (
+        "Streamer Loud",
+        &[
+            EffectSpec::new(EffectType::NoiseGate, &[("threshold", -45.0)]),
+            EffectSpec::new(
+                EffectType::Eq,
+                &[
+                    ("band_0", -12.0),
+                    ("band_1", 3.0),
+                    ("band_6", 3.0),
+                    ("band_8", 2.0),
+                ],
+            ),
+            EffectSpec::new(
+                EffectType::MultibandCompressor,
+                &[
+                    ("low_threshold", -24.0),
+                    ("low_ratio", 4.0),
+                    ("mid_threshold", -24.0),
+                    ("mid_ratio", 4.0),
+                    ("mid_gain", 3.0),
+                    ("high_threshold", -24.0),
+                    ("high_ratio", 4.0),
+                    ("high_gain", 2.0),
+                ],
+            ),
+            EffectSpec::new(EffectType::Saturation, &[("drive", 4.0), ("mix", 0.3)]),
+            EffectSpec::new(
+                EffectType::Compressor,
+                &[("threshold", -10.0), ("ratio", 8.0), ("makeup", 1.5)],
+            ),
+        ],
+    ),
+    // Whisper-level detail: no gating of breaths, air lifted, a gentle
+    // compressor and a small, mostly dry room
+    (
+        "ASMR",
+        &[
+            EffectSpec::new(
+                EffectType::Eq,
+                &[("band_0", -12.0), ("band_8", 3.0), ("band_9", 4.0)],
+            ),
+            EffectSpec::new(
+                EffectType::Compressor,
+                &[("threshold", -30.0), ("ratio", 2.0), ("detector", 1.0)],
+            ),
+            EffectSpec::new(
+                EffectType::Agc,
+                &[("target_level", -24.0), ("max_gain", 30.0)],
+            ),
+            EffectSpec::new(
+                EffectType::Reverb,
+                &[
+                    ("room_size", 0.2),
+                    ("damping", 0.7),
+                    ("wet_level", 0.1),
+                    ("dry_level", 0.9),
+                ],
+            ),
+        ],
+    ),
+    // Narrow band around 1-2 kHz with a little grit, like a phone line
+    (
+        "Telephone",
+        &[
+            EffectSpec::new(
+                EffectType::Eq,
+                &[
+                    ("band_0", -12.0),
+                    ("band_1", -12.0),
+                    ("band_2", -12.0),
+                    ("band_3", -8.0),
+                    ("band_5", 4.0),
+                    ("band_6", 6.0),
+                    ("band_7", -4.0),
+                    ("band_8", -12.0),
+                    ("band_9", -12.0),
+                ],
+            ),
+            EffectSpec::new(
+                EffectType::Saturation,
+                &[("curve", 1.0), ("drive", 12.0), ("mix", 0.6)],
+            ),
+            EffectSpec::new(
+                EffectType::Compressor,
+                &[("threshold", -25.0), ("ratio", 6.0)],
+            ),
+        ],
+    ),
+];
+
+pub fn factory_preset_names() -> Vec<String> {
+    FACTORY_PRESETS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+// The named preset as a chain in the current schema; names match
+// case-insensitively
+pub fn factory_preset(name: &str) -> Option<ChainPreset> {
+    FACTORY_PRESETS
+        .iter()
+        .find(|(preset_name, _)| preset_name.eq_ignore_ascii_case(name))
+        .map(|(_, effects)| ChainPreset::new(effects.iter().map(EffectSpec::to_preset).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::EffectRegistry;
+
+    #[test]
+    fn every_preset_builds_with_the_values_it_names() {
+        let registry = EffectRegistry::with_builtin_effects();
+        assert_eq!(
+            factory_preset_names(),
+            ["Podcast Voice", "Streamer Loud", "ASMR", "Telephone"]
+        );
+        for name in factory_preset_names() {
+            for effect in factory_preset(&name).unwrap().effects {
+                let saved = effect.build(&registry, 48_000).unwrap().to_preset();
+                for (parameter, value) in effect.params.params.iter() {
+                    assert_eq!(
+                        saved.params.get(parameter),
+                        Some(*value),
+                        "{} {} {}",
+                        name,
+                        effect.effect_type,
+                        parameter
+                    );
+                }
+            }
+        }
+    }
+}
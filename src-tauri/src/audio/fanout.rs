@@ -0,0 +1,109 @@
+// Encoded audio reaches its consumers through tokio broadcast channels. The
+// capture path can never wait on a consumer, so when one falls behind by more
+// than the channel capacity something has to be dropped; these types pick
+// what and make sure it shows up in the stream stats.
+use super::stats::StatsTracker;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+pub const DEFAULT_BROADCAST_CAPACITY: usize = 1024;
+
+// A channel this small would lag on any scheduling hiccup
+pub const MIN_BROADCAST_CAPACITY: usize = 16;
+
+// Blocking the sender isn't offered: it would stall the audio callback
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    // New packets overwrite the oldest unread ones; a slow consumer skips
+    // ahead and the gap is counted as lagged
+    #[default]
+    DropOldest,
+    // A full channel refuses new packets, which are counted as dropped, so
+    // what a slow consumer does receive stays contiguous
+    DropNewest,
+}
+
+impl OverflowPolicy {
+    // Whether a packet may be sent on `tx` without overwriting unread ones
+    pub fn admits<T>(self, tx: &broadcast::Sender<T>, capacity: usize) -> bool {
+        match self {
+            OverflowPolicy::DropOldest => true,
+            OverflowPolicy::DropNewest => tx.len() < capacity,
+        }
+    }
+}
+
+// Broadcast receiver that records how many packets it missed by lagging
+pub struct StreamReceiver<T> {
+    rx: broadcast::Receiver<T>,
+    stats: Arc<Mutex<StatsTracker>>,
+    name: &'static str,
+}
+
+impl<T: Clone> StreamReceiver<T> {
+    pub fn new(
+        rx: broadcast::Receiver<T>,
+        stats: Arc<Mutex<StatsTracker>>,
+        name: &'static str,
+    ) -> Self {
+        Self { rx, stats, name }
+    }
+
+    // Next packet, skipping (and counting) any lost to lag; None once the
+    // sender is gone
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            match self.rx.recv().await {
+                Ok(packet) => return Some(packet),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("{} fell behind and skipped {} packets", self.name, skipped);
+                    self.stats.lock().unwrap().record_lagged(skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    // Drops everything queued so the next recv() waits for a fresh packet
+    pub fn skip_queued(&mut self) {
+        self.rx = self.rx.resubscribe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_receiver_skips_ahead_and_counts_the_gap() {
+        let (tx, rx) = broadcast::channel(MIN_BROADCAST_CAPACITY);
+        let stats = Arc::new(Mutex::new(StatsTracker::default()));
+        let mut receiver = StreamReceiver::new(rx, stats.clone(), "test sink");
+
+        for packet in 0..40u32 {
+            tx.send(packet).unwrap();
+        }
+        assert_eq!(receiver.recv().await, Some(24));
+        assert_eq!(stats.lock().unwrap().snapshot().lagged_packets, 24);
+
+        drop(tx);
+        let mut rest = Vec::new();
+        while let Some(packet) = receiver.recv().await {
+            rest.push(packet);
+        }
+        assert_eq!(rest, (25..40).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn drop_newest_refuses_packets_once_full() {
+        let (tx, _rx) = broadcast::channel::<u32>(MIN_BROADCAST_CAPACITY);
+        for packet in 0..MIN_BROADCAST_CAPACITY as u32 {
+            assert!(OverflowPolicy::DropNewest.admits(&tx, MIN_BROADCAST_CAPACITY));
+            tx.send(packet).unwrap();
+        }
+        assert!(!OverflowPolicy::DropNewest.admits(&tx, MIN_BROADCAST_CAPACITY));
+        assert!(OverflowPolicy::DropOldest.admits(&tx, MIN_BROADCAST_CAPACITY));
+    }
+}
@@ -0,0 +1,290 @@
+use super::backoff::{Backoff, ReconnectPolicy};
+use super::fanout::StreamReceiver;
+use super::ogg::{ChannelMapping, OggOpusWriter};
+use super::{AudioEvent, EncodedFrame};
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+
+const DEFAULT_ICECAST_PORT: u16 = 8000;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_RESPONSE_HEADER_LEN: usize = 8192;
+// How long close() waits for the final Ogg page to go out before giving up
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IcecastConfig {
+    pub url: String,
+    pub mount: String,
+    pub user: String,
+    pub password: String,
+    #[serde(default)]
+    pub reconnect: ReconnectPolicy,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IcecastError {
+    #[error("Invalid Icecast URL: {0}")]
+    InvalidUrl(String),
+    #[error("Connection failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Connection timed out")]
+    Timeout,
+    #[error("Authentication failed (401): check the source user and password")]
+    Unauthorized,
+    #[error("Forbidden (403): mountpoint in use or not permitted")]
+    Forbidden,
+    #[error("Unexpected server response: {0}")]
+    UnexpectedResponse(String),
+}
+
+// Layout of the Ogg Opus stream the sink produces
+#[derive(Debug, Clone)]
+pub struct OpusStreamInfo {
+    pub mapping: ChannelMapping,
+    pub input_sample_rate: u32,
+}
+
+// Source client pushing Ogg Opus to an Icecast mountpoint over HTTP PUT
+pub struct IcecastSink {
+    task: JoinHandle<()>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl IcecastSink {
+    // Connects once up front so bad credentials fail the command directly;
+    // later drops are retried in the background
+    pub async fn connect(
+        config: IcecastConfig,
+        info: OpusStreamInfo,
+        frames: StreamReceiver<EncodedFrame>,
+        events: broadcast::Sender<AudioEvent>,
+    ) -> Result<Self, IcecastError> {
+        let connection = open_source_connection(&config).await?;
+        let _ = events.send(AudioEvent::SinkConnected {
+            sink: "icecast".to_string(),
+        });
+
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let task = tokio::spawn(run_sink(
+            config,
+            info,
+            frames,
+            events,
+            connection,
+            shutdown_rx,
+        ));
+        Ok(Self { task, shutdown })
+    }
+
+    pub fn disconnect(self) {
+        self.task.abort();
+    }
+
+    // Ends the Ogg stream cleanly and closes the connection, waiting for the
+    // task to finish; falls back to aborting if the server stops reading
+    pub async fn close(mut self) {
+        let _ = self.shutdown.send(true);
+        if tokio::time::timeout(CLOSE_TIMEOUT, &mut self.task)
+            .await
+            .is_err()
+        {
+            log::warn!("Icecast sink did not close in time, aborting");
+            self.task.abort();
+        }
+    }
+}
+
+async fn run_sink(
+    config: IcecastConfig,
+    info: OpusStreamInfo,
+    mut frames: StreamReceiver<EncodedFrame>,
+    events: broadcast::Sender<AudioEvent>,
+    mut connection: TcpStream,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let reason = match stream_frames(&mut connection, &info, &mut frames, &mut shutdown).await {
+            Ok(()) => return,
+            Err(e) => e,
+        };
+        log::warn!("Icecast connection lost: {}", reason);
+
+        let dropped_at = Instant::now();
+        let mut backoff = Backoff::new(config.reconnect);
+        let mut reconnected = None;
+        while let Some(delay) = backoff.next_delay(dropped_at.elapsed()) {
+            let attempt = backoff.attempts();
+            let _ = events.send(AudioEvent::SinkReconnecting {
+                sink: "icecast".to_string(),
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.changed() => return,
+            }
+
+            match open_source_connection(&config).await {
+                Ok(stream) => {
+                    reconnected = Some(stream);
+                    break;
+                }
+                // Credentials won't fix themselves; stop retrying
+                Err(e @ (IcecastError::Unauthorized | IcecastError::Forbidden)) => {
+                    let _ = events.send(AudioEvent::SinkFailed {
+                        sink: "icecast".to_string(),
+                        reason: e.to_string(),
+                    });
+                    return;
+                }
+                Err(e) => log::warn!("Icecast reconnect attempt {} failed: {}", attempt, e),
+            }
+        }
+
+        match reconnected {
+            Some(stream) => {
+                connection = stream;
+                // Sending the audio queued during the outage would leave the
+                // mount behind live for good; pick up from now instead
+                frames.skip_queued();
+                let _ = events.send(AudioEvent::SinkConnected {
+                    sink: "icecast".to_string(),
+                });
+            }
+            None => {
+                let _ = events.send(AudioEvent::SinkFailed {
+                    sink: "icecast".to_string(),
+                    reason: reason.to_string(),
+                });
+                return;
+            }
+        }
+    }
+}
+
+// Writes a fresh Ogg stream until the connection fails (Err), or the engine
+// stops producing audio or asks the sink to close (Ok)
+async fn stream_frames(
+    connection: &mut TcpStream,
+    info: &OpusStreamInfo,
+    frames: &mut StreamReceiver<EncodedFrame>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<(), IcecastError> {
+    let mut ogg = OggOpusWriter::new();
+    connection
+        .write_all(&ogg.headers(&info.mapping, info.input_sample_rate))
+        .await?;
+
+    loop {
+        // Lag is counted in the stream stats by the receiver
+        let received = tokio::select! {
+            received = frames.recv() => received,
+            _ = shutdown.changed() => None,
+        };
+
+        let Some(frame) = received else {
+            connection.write_all(&ogg.finish()).await?;
+            connection.shutdown().await?;
+            return Ok(());
+        };
+
+        let pages = ogg.write_packet(&frame.data, frame.duration_48k);
+        if !pages.is_empty() {
+            connection.write_all(&pages).await?;
+        }
+    }
+}
+
+async fn open_source_connection(config: &IcecastConfig) -> Result<TcpStream, IcecastError> {
+    let (host, port) = parse_server(&config.url)?;
+    let mount = if config.mount.starts_with('/') {
+        config.mount.clone()
+    } else {
+        format!("/{}", config.mount)
+    };
+
+    let mut stream =
+        tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), port)))
+            .await
+            .map_err(|_| IcecastError::Timeout)??;
+
+    let credentials = base64::engine::general_purpose::STANDARD
+        .encode(format!("{}:{}", config.user, config.password));
+    let request = format!(
+        "PUT {mount} HTTP/1.1\r\n\
+         Host: {host}:{port}\r\n\
+         Authorization: Basic {credentials}\r\n\
+         User-Agent: VoiceCast/{version}\r\n\
+         Content-Type: audio/ogg\r\n\
+         Ice-Name: VoiceCast\r\n\
+         Ice-Public: 0\r\n\
+         Expect: 100-continue\r\n\
+         \r\n",
+        version = env!("CARGO_PKG_VERSION"),
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let status = tokio::time::timeout(CONNECT_TIMEOUT, read_status(&mut stream))
+        .await
+        .map_err(|_| IcecastError::Timeout)??;
+
+    match status {
+        100 | 200 => Ok(stream),
+        401 => Err(IcecastError::Unauthorized),
+        403 => Err(IcecastError::Forbidden),
+        other => Err(IcecastError::UnexpectedResponse(format!("HTTP {}", other))),
+    }
+}
+
+// Reads the response header block and returns its status code
+async fn read_status(stream: &mut TcpStream) -> Result<u16, IcecastError> {
+    let mut header = Vec::new();
+    let mut byte = [0u8; 1];
+    while !header.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).await? == 0 {
+            return Err(IcecastError::UnexpectedResponse(
+                "connection closed".to_string(),
+            ));
+        }
+        header.push(byte[0]);
+        if header.len() > MAX_RESPONSE_HEADER_LEN {
+            return Err(IcecastError::UnexpectedResponse(
+                "response header too long".to_string(),
+            ));
+        }
+    }
+
+    let text = String::from_utf8_lossy(&header);
+    let status_line = text.lines().next().unwrap_or_default();
+    status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| IcecastError::UnexpectedResponse(status_line.to_string()))
+}
+
+// Accepts "http://host[:port][/]" or "host[:port]"
+fn parse_server(url: &str) -> Result<(String, u16), IcecastError> {
+    if url.starts_with("https://") {
+        return Err(IcecastError::InvalidUrl(
+            "TLS is not supported, use http://".to_string(),
+        ));
+    }
+    let authority = url.trim_start_matches("http://").trim_end_matches('/');
+    if authority.is_empty() || authority.contains('/') {
+        return Err(IcecastError::InvalidUrl(url.to_string()));
+    }
+
+    match authority.rsplit_once(':') {
+        Some((host, port)) => port
+            .parse()
+            .map(|port| (host.to_string(), port))
+            .map_err(|_| IcecastError::InvalidUrl(url.to_string())),
+        None => Ok((authority.to_string(), DEFAULT_ICECAST_PORT)),
+    }
+}
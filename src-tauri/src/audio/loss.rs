@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+// Development aid: degrades the outgoing packet stream on purpose so FEC,
+// concealment and jitter buffering can be exercised without a bad network.
+// Never enable this for a real broadcast.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PacketLossConfig {
+    // Chance of dropping each packet, 0-100
+    pub loss_percent: f32,
+    // Chance of holding a packet back and sending it after the next one, 0-100
+    #[serde(default)]
+    pub reorder_percent: f32,
+}
+
+impl PacketLossConfig {
+    pub fn clamped(self) -> Self {
+        Self {
+            loss_percent: self.loss_percent.clamp(0.0, 100.0),
+            reorder_percent: self.reorder_percent.clamp(0.0, 100.0),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.loss_percent > 0.0 || self.reorder_percent > 0.0
+    }
+}
+
+pub struct PacketLossSimulator {
+    rng_state: u64,
+    held: Option<Vec<u8>>,
+}
+
+impl PacketLossSimulator {
+    pub fn new() -> Self {
+        Self {
+            // xorshift must not start at zero
+            rng_state: rand::random::<u64>() | 1,
+            held: None,
+        }
+    }
+
+    // Returns the packets to send now, in order: none if this one was dropped
+    // or held back, two if a held packet is released behind it
+    pub fn apply(&mut self, config: &PacketLossConfig, packet: Vec<u8>) -> Vec<Vec<u8>> {
+        if !config.is_active() {
+            return self.held.take().into_iter().chain(Some(packet)).collect();
+        }
+
+        if self.chance(config.loss_percent) {
+            return Vec::new();
+        }
+
+        match self.held.take() {
+            Some(held) => vec![packet, held],
+            None if self.chance(config.reorder_percent) => {
+                self.held = Some(packet);
+                Vec::new()
+            }
+            None => vec![packet],
+        }
+    }
+
+    fn chance(&mut self, percent: f32) -> bool {
+        percent > 0.0 && self.next_unit() * 100.0 < percent
+    }
+
+    // Uniform in [0, 1)
+    fn next_unit(&mut self) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+impl Default for PacketLossSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
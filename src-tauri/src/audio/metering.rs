@@ -0,0 +1,439 @@
+use super::Biquad;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MeterBallistics {
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    pub peak_hold_ms: f32,
+}
+
+impl Default for MeterBallistics {
+    fn default() -> Self {
+        Self {
+            attack_ms: 10.0,
+            release_ms: 300.0,
+            peak_hold_ms: 1000.0,
+        }
+    }
+}
+
+// Smooths per-block RMS and holds peaks so UI meters don't jitter
+pub struct LevelMeter {
+    ballistics: MeterBallistics,
+    rms: f32,
+    peak: f32,
+    hold_remaining_ms: f32,
+}
+
+impl LevelMeter {
+    pub fn new(ballistics: MeterBallistics) -> Self {
+        Self {
+            ballistics,
+            rms: 0.0,
+            peak: 0.0,
+            hold_remaining_ms: 0.0,
+        }
+    }
+
+    pub fn set_ballistics(&mut self, ballistics: MeterBallistics) {
+        self.ballistics = MeterBallistics {
+            attack_ms: ballistics.attack_ms.max(0.0),
+            release_ms: ballistics.release_ms.max(0.0),
+            peak_hold_ms: ballistics.peak_hold_ms.max(0.0),
+        };
+    }
+
+    pub fn ballistics(&self) -> MeterBallistics {
+        self.ballistics
+    }
+
+    // Feed one block lasting `block_ms`; returns the smoothed (peak, rms)
+    pub fn process(&mut self, samples: &[f32], block_ms: f32) -> (f32, f32) {
+        if samples.is_empty() {
+            return (self.peak, self.rms);
+        }
+
+        let block_peak = samples.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+        let block_rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+
+        let rms_time = if block_rms > self.rms {
+            self.ballistics.attack_ms
+        } else {
+            self.ballistics.release_ms
+        };
+        self.rms += (block_rms - self.rms) * Self::smoothing(block_ms, rms_time);
+
+        // Hold a new maximum, then let it fall at the release rate
+        if block_peak >= self.peak {
+            self.peak = block_peak;
+            self.hold_remaining_ms = self.ballistics.peak_hold_ms;
+        } else if self.hold_remaining_ms > 0.0 {
+            self.hold_remaining_ms -= block_ms;
+        } else {
+            let decayed = self.peak * (1.0 - Self::smoothing(block_ms, self.ballistics.release_ms));
+            self.peak = decayed.max(block_peak);
+        }
+
+        (self.peak, self.rms)
+    }
+
+    fn smoothing(block_ms: f32, time_ms: f32) -> f32 {
+        if time_ms <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-block_ms / time_ms).exp()
+        }
+    }
+}
+
+// Integration time of the correlation meter, similar to hardware phase meters
+const CORRELATION_WINDOW_SECS: f32 = 0.3;
+
+// Below this the signal is treated as silence and the meter reads +1
+const CORRELATION_SILENCE: f32 = 1e-9;
+
+// Stereo phase correlation: +1 when L and R are identical, 0 when unrelated,
+// -1 when one is the inverse of the other (cancels when summed to mono).
+// Averages L*R, L² and R² over an exponential window of about 300 ms.
+pub struct CorrelationMeter {
+    coefficient: f32,
+    lr: f32,
+    ll: f32,
+    rr: f32,
+}
+
+impl CorrelationMeter {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            coefficient: if sample_rate > 0.0 {
+                1.0 - (-1.0 / (CORRELATION_WINDOW_SECS * sample_rate)).exp()
+            } else {
+                1.0
+            },
+            lr: 0.0,
+            ll: 0.0,
+            rr: 0.0,
+        }
+    }
+
+    // Feed an interleaved block; returns the current correlation. Anything
+    // that isn't stereo reads +1, since a single channel can't cancel.
+    pub fn process(&mut self, samples: &[f32], channels: usize) -> f32 {
+        if channels != 2 {
+            return 1.0;
+        }
+
+        for frame in samples.chunks_exact(2) {
+            let (l, r) = (frame[0], frame[1]);
+            self.lr += (l * r - self.lr) * self.coefficient;
+            self.ll += (l * l - self.ll) * self.coefficient;
+            self.rr += (r * r - self.rr) * self.coefficient;
+        }
+
+        let energy = (self.ll * self.rr).sqrt();
+        if energy < CORRELATION_SILENCE {
+            1.0
+        } else {
+            (self.lr / energy).clamp(-1.0, 1.0)
+        }
+    }
+}
+
+// Lowest level reported by dB meters, so silence serializes as a number
+pub const METER_FLOOR_DB: f32 = -120.0;
+
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+// Inter-sample peak detector: 4x polyphase FIR interpolation per channel,
+// in the spirit of ITU-R BS.1770 true-peak metering
+pub struct TruePeakMeter {
+    phases: Vec<[f32; TRUE_PEAK_TAPS_PER_PHASE]>,
+    history: Vec<[f32; TRUE_PEAK_TAPS_PER_PHASE]>,
+}
+
+impl TruePeakMeter {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            phases: Self::design_phases(),
+            history: vec![[0.0; TRUE_PEAK_TAPS_PER_PHASE]; channels.max(1)],
+        }
+    }
+
+    // Blackman-windowed sinc lowpass at the original Nyquist, split into
+    // one sub-filter per oversampling phase
+    fn design_phases() -> Vec<[f32; TRUE_PEAK_TAPS_PER_PHASE]> {
+        let factor = TRUE_PEAK_OVERSAMPLING;
+        let len = factor * TRUE_PEAK_TAPS_PER_PHASE;
+        let center = (len - 1) as f64 / 2.0;
+
+        let taps: Vec<f64> = (0..len)
+            .map(|n| {
+                let t = (n as f64 - center) / factor as f64;
+                let sinc = if t.abs() < 1e-9 {
+                    1.0
+                } else {
+                    (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+                };
+                let x = 2.0 * std::f64::consts::PI * n as f64 / (len - 1) as f64;
+                let window = 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos();
+                sinc * window
+            })
+            .collect();
+
+        (0..factor)
+            .map(|phase| {
+                let mut coeffs = [0.0f32; TRUE_PEAK_TAPS_PER_PHASE];
+                for (k, coeff) in coeffs.iter_mut().enumerate() {
+                    *coeff = taps[phase + k * factor] as f32;
+                }
+                // Normalize each phase to unity DC gain
+                let sum: f32 = coeffs.iter().sum();
+                coeffs.iter_mut().for_each(|c| *c /= sum);
+                coeffs
+            })
+            .collect()
+    }
+
+    // Returns the highest interpolated magnitude in an interleaved block
+    pub fn process(&mut self, samples: &[f32]) -> f32 {
+        let channels = self.history.len();
+        let mut true_peak = 0.0f32;
+
+        for frame in samples.chunks(channels) {
+            for (history, &sample) in self.history.iter_mut().zip(frame) {
+                history.copy_within(0..TRUE_PEAK_TAPS_PER_PHASE - 1, 1);
+                history[0] = sample;
+
+                true_peak = true_peak.max(sample.abs());
+                for phase in &self.phases {
+                    let interpolated: f32 =
+                        phase.iter().zip(history.iter()).map(|(c, x)| c * x).sum();
+                    true_peak = true_peak.max(interpolated.abs());
+                }
+            }
+        }
+
+        true_peak
+    }
+}
+
+// K-weighting from ITU-R BS.1770: a high shelf modelling the head, then a
+// highpass (the "RLB" curve). Given as analog prototypes and mapped with the
+// bilinear transform, as libebur128 does, so the curve holds at any rate.
+const K_SHELF_HZ: f64 = 1_681.974_450_955_532;
+const K_SHELF_Q: f64 = 0.707_175_236_955_419_3;
+const K_SHELF_GAIN_DB: f64 = 3.999_843_853_97;
+const K_SHELF_BAND_EXPONENT: f64 = 0.499_666_774_154_541_6;
+const K_HIGHPASS_HZ: f64 = 38.135_470_876_139_82;
+const K_HIGHPASS_Q: f64 = 0.500_327_037_325_395_3;
+
+fn k_weighting(sample_rate: f32) -> [Biquad; 2] {
+    let sample_rate = sample_rate as f64;
+
+    let k = (std::f64::consts::PI * K_SHELF_HZ / sample_rate).tan();
+    let vh = 10.0f64.powf(K_SHELF_GAIN_DB / 20.0);
+    let vb = vh.powf(K_SHELF_BAND_EXPONENT);
+    let shelf = Biquad::from_coefficients(
+        [
+            (vh + vb * k / K_SHELF_Q + k * k) as f32,
+            (2.0 * (k * k - vh)) as f32,
+            (vh - vb * k / K_SHELF_Q + k * k) as f32,
+        ],
+        [
+            (1.0 + k / K_SHELF_Q + k * k) as f32,
+            (2.0 * (k * k - 1.0)) as f32,
+            (1.0 - k / K_SHELF_Q + k * k) as f32,
+        ],
+    );
+
+    // The reference keeps the numerator at 1, -2, 1 rather than normalizing it
+    let k = (std::f64::consts::PI * K_HIGHPASS_HZ / sample_rate).tan();
+    let a0 = 1.0 + k / K_HIGHPASS_Q + k * k;
+    let highpass = Biquad::from_coefficients(
+        [a0 as f32, (-2.0 * a0) as f32, a0 as f32],
+        [
+            a0 as f32,
+            (2.0 * (k * k - 1.0)) as f32,
+            (1.0 - k / K_HIGHPASS_Q + k * k) as f32,
+        ],
+    );
+    [shelf, highpass]
+}
+
+// Gating blocks are 400 ms, overlapping by 75%
+const LOUDNESS_STEP_SECS: f32 = 0.1;
+const LOUDNESS_STEPS_PER_BLOCK: usize = 4;
+
+const LOUDNESS_ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const LOUDNESS_RELATIVE_GATE_LU: f64 = -10.0;
+
+// Integrated loudness (BS.1770-4 / EBU R128) over everything fed in. All
+// channels are weighted 1, which is exact for mono and stereo. Keeps one
+// number per 100 ms of audio, about 140 KB an hour.
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    step_frames: usize,
+    step_energy: f64,
+    step_filled: usize,
+    // Mean square of each 100 ms step, summed across channels
+    steps: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate); channels.max(1)],
+            step_frames: ((LOUDNESS_STEP_SECS * sample_rate) as usize).max(1),
+            step_energy: 0.0,
+            step_filled: 0,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.filters.len()) {
+            for ([shelf, highpass], &sample) in self.filters.iter_mut().zip(frame) {
+                let weighted = highpass.process(shelf.process(sample)) as f64;
+                self.step_energy += weighted * weighted;
+            }
+            self.step_filled += 1;
+            if self.step_filled == self.step_frames {
+                self.steps.push(self.step_energy / self.step_frames as f64);
+                self.step_energy = 0.0;
+                self.step_filled = 0;
+            }
+        }
+    }
+
+    // Gated integrated loudness in LUFS; None until there is a block above
+    // the absolute gate (under 400 ms of audio, or only silence)
+    pub fn integrated_lufs(&self) -> Option<f32> {
+        let blocks: Vec<f64> = self
+            .steps
+            .windows(LOUDNESS_STEPS_PER_BLOCK)
+            .map(|steps| steps.iter().sum::<f64>() / LOUDNESS_STEPS_PER_BLOCK as f64)
+            .filter(|&power| power_to_lufs(power) > LOUDNESS_ABSOLUTE_GATE_LUFS)
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+
+        let relative_gate = power_to_lufs(mean(&blocks)) + LOUDNESS_RELATIVE_GATE_LU;
+        let gated: Vec<f64> = blocks
+            .into_iter()
+            .filter(|&power| power_to_lufs(power) > relative_gate)
+            .collect();
+        Some(power_to_lufs(mean(&gated)) as f32)
+    }
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.max(1e-20).log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len().max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_meter_holds_then_decays_a_spike() {
+        let ballistics = MeterBallistics {
+            attack_ms: 10.0,
+            release_ms: 300.0,
+            peak_hold_ms: 100.0,
+        };
+        let mut meter = LevelMeter::new(ballistics);
+        let mut spike = vec![0.0f32; 480];
+        spike[10] = 1.0;
+        let silence = vec![0.0f32; 480];
+
+        let (mut previous, _) = meter.process(&spike, 10.0);
+        assert_eq!(previous, 1.0);
+        let mut readings = Vec::new();
+        for _ in 0..100 {
+            let (peak, _) = meter.process(&silence, 10.0);
+            // Never jumps up, and falls at most a release step per block
+            assert!(peak <= previous && peak >= previous * (-10.0f32 / 300.0).exp() - 1e-6);
+            previous = peak;
+            readings.push(peak);
+        }
+
+        // Held for the hold time, then released towards zero
+        assert!(readings[..9].iter().all(|&peak| peak == 1.0));
+        assert!(readings[20] < 1.0 && readings[20] > 0.5);
+        assert!(readings[99] < 0.1);
+    }
+
+    #[test]
+    fn true_peak_catches_inter_sample_peaks() {
+        // fs/4 at a 45 degree phase offset: every sample lands at 0.707 of the
+        // waveform's real peak
+        let signal: Vec<f32> = (0..4_800)
+            .map(|n| {
+                0.9 * (std::f32::consts::FRAC_PI_2 * n as f32 + std::f32::consts::FRAC_PI_4).sin()
+            })
+            .collect();
+        let sample_peak = signal.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+        let mut meter = TruePeakMeter::new(1);
+        let true_peak = meter.process(&signal);
+        assert!(
+            true_peak > sample_peak * 1.3,
+            "true peak {} vs sample peak {}",
+            true_peak,
+            sample_peak
+        );
+        assert!((true_peak - 0.9).abs() < 0.05);
+    }
+
+    #[test]
+    fn true_peak_state_carries_across_blocks() {
+        let signal: Vec<f32> = (0..4_800)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * 1_000.0 * n as f32 / 48_000.0).sin())
+            .collect();
+        let mut whole = TruePeakMeter::new(1);
+        let mut split = TruePeakMeter::new(1);
+        let expected = whole.process(&signal);
+        let measured = signal
+            .chunks(7)
+            .map(|block| split.process(block))
+            .fold(0.0f32, f32::max);
+        assert!((measured - expected).abs() < 1e-4);
+    }
+
+    fn stereo(left: &[f32], right: impl Fn(usize, f32) -> f32) -> Vec<f32> {
+        left.iter()
+            .enumerate()
+            .flat_map(|(i, &s)| [s, right(i, s)])
+            .collect()
+    }
+
+    #[test]
+    fn correlation_reads_plus_one_in_phase_and_minus_one_inverted() {
+        let signal: Vec<f32> = (0..48_000)
+            .map(|i| (i as f32 * 0.05).sin() * 0.5 + (i as f32 * 0.013).sin() * 0.2)
+            .collect();
+
+        let in_phase = CorrelationMeter::new(48_000.0).process(&stereo(&signal, |_, s| s), 2);
+        assert!((in_phase - 1.0).abs() < 1e-3, "in phase read {}", in_phase);
+
+        let inverted = CorrelationMeter::new(48_000.0).process(&stereo(&signal, |_, s| -s), 2);
+        assert!((inverted + 1.0).abs() < 1e-3, "inverted read {}", inverted);
+
+        let unrelated = CorrelationMeter::new(48_000.0)
+            .process(&stereo(&signal, |i, _| (i as f32 * 0.0713).cos() * 0.4), 2);
+        assert!(unrelated.abs() < 0.2, "unrelated read {}", unrelated);
+    }
+
+    #[test]
+    fn correlation_of_mono_is_one() {
+        let signal: Vec<f32> = (0..4_800).map(|i| (i as f32 * 0.05).sin()).collect();
+        assert_eq!(CorrelationMeter::new(48_000.0).process(&signal, 1), 1.0);
+    }
+}
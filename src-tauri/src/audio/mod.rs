@@ -1,38 +1,354 @@
+#[cfg(feature = "aac")]
+pub mod aac;
+pub mod backoff;
+pub mod bitrate;
+pub mod chain;
+pub mod channels;
+pub mod classifier;
+pub mod codec;
+pub mod devices;
 pub mod effects;
+pub mod factory_presets;
+pub mod fanout;
+pub mod icecast;
+pub mod loss;
+pub mod metering;
+pub mod monitor;
+pub mod music;
+pub mod ogg;
+pub mod outputs;
+pub mod pipeline;
+pub mod quality;
+pub mod recorder;
+pub mod registry;
+pub mod resample;
+pub mod ring_buffer;
+pub mod rtp;
+pub mod sample_capture;
+pub mod selftest;
+pub mod session;
+pub mod silence;
+pub mod snapshot;
+pub mod source;
+pub mod spectrum;
+pub mod stats;
+pub mod versioning;
+pub mod waveform;
+pub mod webrtc;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use opus::{Channels, Application};
+use opus::Application;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
-use serde::{Serialize, Deserialize};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
 
+#[cfg(feature = "aac")]
+pub use aac::{AacEncoder, AAC_FRAME_FRAMES};
+pub use backoff::{Backoff, ReconnectPolicy};
+pub use bitrate::{
+    AdaptiveBitrateConfig, BitrateController, BITRATE_ADAPT_INTERVAL, MAX_ADAPTIVE_BITRATE,
+    MIN_ADAPTIVE_BITRATE,
+};
+pub use chain::{
+    ChainPreset, EffectCpuUsage, EffectOverrun, EffectPreset, EffectSlot, EffectsChain,
+    ParameterChange, ParameterChangeResult,
+};
+pub use channels::{convert_channels, downmix_to_mono, extract_channels, upmix_to_stereo};
+pub use classifier::{ContentClassifier, ContentType, CLASSIFY_WINDOW_SECS};
+pub use codec::{
+    content_signal, new_encoder, opus_frame_frames, Codec, CodecError, Encoder, OpusDecoder,
+    OpusEncoder, MAX_CHANNELS, OPUS_FRAME_SIZES_MS,
+};
+pub use devices::{device_capabilities, ConfigRange, DeviceCapabilities};
 pub use effects::*;
+pub use factory_presets::{factory_preset, factory_preset_names, EffectSpec, FACTORY_PRESETS};
+pub use fanout::{
+    OverflowPolicy, StreamReceiver, DEFAULT_BROADCAST_CAPACITY, MIN_BROADCAST_CAPACITY,
+};
+pub use icecast::{IcecastConfig, IcecastError, IcecastSink, OpusStreamInfo};
+pub use loss::{PacketLossConfig, PacketLossSimulator};
+pub use metering::{
+    CorrelationMeter, LevelMeter, LoudnessMeter, MeterBallistics, TruePeakMeter, METER_FLOOR_DB,
+};
+pub use monitor::{
+    MonitorBuffer, MonitorLimiterConfig, MAX_MONITOR_CEILING_DB, MAX_MONITOR_QUEUE_SECS,
+    MIN_MONITOR_CEILING_DB,
+};
+pub use music::{DuckingConfig, MusicBus};
+pub use ogg::ChannelMapping;
+pub use outputs::{EncoderOutput, EncoderOutputSummary};
+pub use pipeline::CapturePipeline;
+pub use quality::{CodecQuality, TestSignal, DEFAULT_QUALITY_BITRATES};
+pub use recorder::{
+    DitherMode, Recorder, RecorderError, RecordingContainer, RecordingFormat, RecordingSummary,
+};
+pub use registry::{EffectConstructor, EffectRegistry, EffectType};
+pub use resample::{LinearResampler, ResampleQuality};
+pub use ring_buffer::RingBuffer;
+pub use rtp::{RtpPacketizer, SinkFormat, RTP_OPUS_CLOCK_RATE, RTP_OPUS_PAYLOAD_TYPE};
+pub use sample_capture::{CapturedSample, SampleCapture, MAX_SAMPLE_CAPTURE_MS};
+pub use selftest::{SelfTestReport, SelfTestStage};
+pub use session::{Sessions, StreamSession, StreamStatus, StreamSummary};
+pub use silence::{
+    SignalState, SilenceDetectionConfig, SilenceDetector, MAX_SILENCE_HOLD_MS,
+    MAX_SILENCE_THRESHOLD_DB, MIN_SILENCE_THRESHOLD_DB,
+};
+pub use snapshot::{AudioSnapshot, EffectSnapshot, SnapshotConfig};
+pub use source::{DecodeFormat, DecodedFile, Source, SourceError, SourceWorker};
+pub use spectrum::{Spectrogram, SpectrumAnalyzer, MAX_SPECTROGRAM_FRAMES, SPECTRUM_FFT_SIZE};
+pub use stats::{StatsTracker, StreamStats};
+pub use versioning::{CONFIG_VERSION, PRESET_VERSION};
+pub use waveform::{downsample_waveform, WaveformPoint, WaveformPreview, MAX_WAVEFORM_POINTS};
+pub use webrtc::{IceCandidate, PeerAudioFormat, WebRtcError, WebRtcPeer};
+
+// Input trim range; beyond this the mic is better fixed at the interface
+pub const MIN_INPUT_GAIN_DB: f32 = -24.0;
+pub const MAX_INPUT_GAIN_DB: f32 = 24.0;
+
+// Final level after the effects chain, for makeup once gates and compressors
+// have taken level out
+pub const MIN_OUTPUT_GAIN_DB: f32 = -24.0;
+pub const MAX_OUTPUT_GAIN_DB: f32 = 24.0;
+
+// Chain snapshot slots flipped by toggle_ab
+pub const AB_SLOT_A: u8 = 0;
+pub const AB_SLOT_B: u8 = 1;
+
+// Opus complexity (0-10). Each step down cuts encode CPU at a small cost in
+// quality; phones get a middle setting so the encoder doesn't pin a core.
+pub const MAX_ENCODER_COMPLEXITY: i32 = 10;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub const DEFAULT_ENCODER_COMPLEXITY: i32 = MAX_ENCODER_COMPLEXITY;
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub const DEFAULT_ENCODER_COMPLEXITY: i32 = 5;
+
+// Recalling a snapshot fades between the old and new chain over this long
+const CHAIN_CROSSFADE_SECS: f32 = 0.02;
+
+// Bypassing one effect fades it out (or back in) over this long
+const EFFECT_BYPASS_FADE_SECS: f32 = 0.01;
+
+// Ramp for per-effect dry/wet changes
+const EFFECT_MIX_FADE_SECS: f32 = 0.02;
+
+// Upper bound on an output test tone, so a stray call can't leave it ringing
+pub const MAX_OUTPUT_TEST_TONE_MS: u64 = 10_000;
+
+// Longest software delay the monitor path can add to line up with a hardware one
+pub const MAX_MONITOR_DELAY_MS: f32 = 500.0;
+
+// Longest ramp set_parameter_smoothing accepts; beyond this a change feels laggy
+pub const MAX_PARAM_SMOOTHING_MS: f32 = 500.0;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioConfig {
+    #[serde(default = "versioning::unversioned")]
+    pub version: u32,
     pub sample_rate: u32,
+    // 1-8; more than two are encoded as Opus multistream
     pub channels: u16,
     pub buffer_size: usize,
     pub bit_depth: u16,
+    // cpal backend name (e.g. "ALSA", "JACK", "WASAPI", "ASIO"); None uses the platform default
+    #[serde(default)]
+    pub host_id: Option<String>,
+    // Interpolation used when a file source is decoded at another sample
+    // rate. Device capture and output rate fallbacks always interpolate
+    // linearly, as they run in the audio callback.
+    #[serde(default, alias = "resample_quality")]
+    pub file_resample_quality: ResampleQuality,
+    // Packets each broadcast channel holds for a subscriber that is behind
+    #[serde(default = "default_broadcast_capacity")]
+    pub broadcast_capacity: usize,
+    // What gives when a subscriber falls further behind than that
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    // Opus encoder complexity, 0-10
+    #[serde(default = "default_encoder_complexity")]
+    pub complexity: i32,
+    // Input device channels (0-based) to capture, in order; empty captures all
+    #[serde(default)]
+    pub capture_channels: Vec<u16>,
+}
+
+fn default_broadcast_capacity() -> usize {
+    DEFAULT_BROADCAST_CAPACITY
+}
+
+fn default_encoder_complexity() -> i32 {
+    DEFAULT_ENCODER_COMPLEXITY
 }
 
 impl Default for AudioConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             sample_rate: 48000,
             channels: 2,
             buffer_size: 960,
             bit_depth: 24,
+            host_id: None,
+            file_resample_quality: ResampleQuality::default(),
+            broadcast_capacity: DEFAULT_BROADCAST_CAPACITY,
+            overflow_policy: OverflowPolicy::default(),
+            complexity: DEFAULT_ENCODER_COMPLEXITY,
+            capture_channels: Vec::new(),
+        }
+    }
+}
+
+impl AudioConfig {
+    // This config in the current schema, upgraded step by step from whatever
+    // version it was saved with
+    pub fn upgrade(mut self) -> Result<Self, AudioError> {
+        versioning::check_version("config", self.version, CONFIG_VERSION)?;
+        if self.version < 2 {
+            // Nothing moved; serde has already filled the fields added since
+            self.version = 2;
         }
+        Ok(self)
+    }
+}
+
+pub fn available_host_names() -> Vec<String> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| id.name().to_string())
+        .collect()
+}
+
+// Resolves the requested backend, falling back to the default host if it is
+// unknown or unavailable on this machine
+fn select_host(host_id: Option<&str>) -> cpal::Host {
+    let Some(requested) = host_id else {
+        return cpal::default_host();
+    };
+
+    let host = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(requested))
+        .and_then(|id| cpal::host_from_id(id).ok());
+
+    host.unwrap_or_else(|| {
+        log::warn!("Audio host '{}' unavailable, using default host", requested);
+        cpal::default_host()
+    })
+}
+
+// Prefers capturing at the engine rate so the encoder gets what it was built
+// for; otherwise takes the f32 config whose rate comes closest (Bluetooth
+// headsets often force 16 kHz), which the capture pipeline resamples to the
+// engine rate. The capture callback only takes f32, so a device without an
+// f32 config is refused rather than opened in a format it can't deliver.
+// `min_channels` steers the choice towards a config that opens enough inputs
+// for the selected capture channels; whether it did is checked by the caller.
+fn capture_config(
+    device: &cpal::Device,
+    sample_rate: u32,
+    min_channels: u16,
+) -> Result<cpal::SupportedStreamConfig, AudioError> {
+    let f32_configs: Vec<_> = device
+        .supported_input_configs()
+        .map_err(|e| AudioError::DeviceError(e.to_string()))?
+        .filter(|c| c.sample_format() == cpal::SampleFormat::F32)
+        .collect();
+    let preferred = f32_configs.iter().find(|c| {
+        c.channels() >= min_channels
+            && c.min_sample_rate().0 <= sample_rate
+            && c.max_sample_rate().0 >= sample_rate
+    });
+    if let Some(range) = preferred {
+        return Ok(range
+            .clone()
+            .with_sample_rate(cpal::SampleRate(sample_rate)));
+    }
+
+    // Enough channels first, then the smallest rate change
+    let nearest_rate = |c: &cpal::SupportedStreamConfigRange| {
+        sample_rate.clamp(c.min_sample_rate().0, c.max_sample_rate().0)
+    };
+    let fallback = f32_configs
+        .iter()
+        .min_by_key(|c| {
+            (
+                c.channels() < min_channels,
+                nearest_rate(c).abs_diff(sample_rate),
+            )
+        })
+        .ok_or_else(|| AudioError::DeviceError("Input device has no f32 format".to_string()))?;
+    let rate = nearest_rate(fallback);
+    log::warn!(
+        "Input device does not support {} Hz, capturing at {} Hz and resampling",
+        sample_rate,
+        rate
+    );
+    Ok(fallback.clone().with_sample_rate(cpal::SampleRate(rate)))
+}
+
+fn find_output_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, AudioError> {
+    host.output_devices()
+        .map_err(|e| AudioError::DeviceError(e.to_string()))?
+        .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        .ok_or(AudioError::NoOutputDevice)
+}
+
+// The output device's default config when it takes f32, otherwise its
+// highest-rate f32 config
+fn output_config(device: &cpal::Device) -> Result<cpal::StreamConfig, AudioError> {
+    let default = device.default_output_config()?;
+    if default.sample_format() == cpal::SampleFormat::F32 {
+        return Ok(default.into());
     }
+
+    device
+        .supported_output_configs()
+        .ok()
+        .and_then(|mut configs| configs.find(|c| c.sample_format() == cpal::SampleFormat::F32))
+        .map(|range| range.with_max_sample_rate().config())
+        .ok_or_else(|| AudioError::DeviceError("Output device has no f32 format".to_string()))
+}
+
+// Builds and starts an output stream playing a sine at `level` dBFS
+fn start_output_tone(
+    device: &cpal::Device,
+    freq: f32,
+    level: f32,
+) -> Result<cpal::Stream, AudioError> {
+    let config = output_config(device)?;
+    let mut tone = source::test_tone(
+        freq,
+        level,
+        config.sample_rate.0 as f32,
+        config.channels as usize,
+    );
+    let stream = device.build_output_stream(
+        &config,
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            tone(data);
+        },
+        |err| log::error!("Output stream error: {}", err),
+        None,
+    )?;
+    stream.play()?;
+    Ok(stream)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioLevels {
     pub input_level: f32,
     pub output_level: f32,
+    // Post-effects: what is recorded and encoded
     pub peak: f32,
     pub rms: f32,
+    // Pre-effects: the signal entering the chain, after input gain
+    pub input_peak: f32,
+    pub input_rms: f32,
+    pub true_peak_dbtp: f32,
+    // Stereo phase correlation, -1..+1; always 1.0 for mono
+    pub phase_correlation: f32,
 }
 
 impl Default for AudioLevels {
@@ -42,10 +358,224 @@ impl Default for AudioLevels {
             output_level: 0.0,
             peak: 0.0,
             rms: 0.0,
+            input_peak: 0.0,
+            input_rms: 0.0,
+            true_peak_dbtp: METER_FLOOR_DB,
+            phase_correlation: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamLatency {
+    pub sample_rate: u32,
+    pub buffer_frames: Option<u32>,
+    pub buffer_latency_ms: Option<f32>,
+    pub device_latency_ms: Option<f32>,
+    pub monitoring_latency_ms: Option<f32>,
+    pub effect_latency_samples: usize,
+    pub effect_latency_ms: f32,
+}
+
+// How the software monitor lines up with a hardware (direct) monitor path.
+// Hearing both at slightly different times combs the voice into a flangy
+// doubling; delaying the software path to match the hardware one fuses them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringAlignment {
+    // Capture to callback, as the input device reports it
+    pub input_latency_ms: Option<f32>,
+    // Callback to playback, as the output device reports it
+    pub output_latency_ms: Option<f32>,
+    // Mic to ear through the engine, including monitor_delay_ms; None while
+    // not monitoring
+    pub software_path_ms: Option<f32>,
+    pub monitor_delay_ms: f32,
+    // Delay that puts the software path level with the hardware path passed
+    // in. Zero when the software path is already the later of the two, which
+    // no delay can fix; turn one of the monitors off instead.
+    pub recommended_delay_ms: Option<f32>,
+}
+
+// Result of a dry run of the capture setup; nothing is played or sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamValidation {
+    // False when starting the stream as configured would fail
+    pub ok: bool,
+    pub source: Source,
+    pub device_name: Option<String>,
+    pub format: Option<NegotiatedFormat>,
+    pub effects: Vec<String>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NegotiatedFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: String,
+    pub buffer_frames: Option<u32>,
+}
+
+// Notifications raised by the engine, forwarded to the frontend as Tauri events
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum AudioEvent {
+    EncodeError {
+        message: String,
+        total_errors: u64,
+    },
+    SinkConnected {
+        sink: String,
+    },
+    SinkReconnecting {
+        sink: String,
+        attempt: u32,
+        delay_ms: u64,
+    },
+    SinkFailed {
+        sink: String,
+        reason: String,
+    },
+    OutputDeviceChanged {
+        device: String,
+    },
+    // The running output streams stayed on the previous device
+    OutputDeviceFailed {
+        device: String,
+        reason: String,
+    },
+    // The adaptive bitrate controller moved a stream's encoder bitrate
+    BitrateChanged {
+        stream: StreamId,
+        bitrate: u32,
+    },
+    // The content classifier switched a stream's encoder between voice and music
+    SignalChanged {
+        stream: StreamId,
+        content: ContentType,
+    },
+    // Overrun protection bypassed a stream's most expensive effect
+    EffectsOverrun {
+        stream: StreamId,
+        #[serde(flatten)]
+        overrun: EffectOverrun,
+    },
+    // The input level rose above, or fell below, the silence detection
+    // threshold and stayed there for the hold time
+    SignalDetected {
+        stream: StreamId,
+        level_db: f32,
+    },
+    SilenceDetected {
+        stream: StreamId,
+        level_db: f32,
+    },
+    // A local candidate for the remote WebRTC peer, to pass over signaling
+    #[serde(rename_all = "camelCase")]
+    IceCandidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+impl AudioEvent {
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            AudioEvent::EncodeError { .. } => "encode-error",
+            AudioEvent::SinkConnected { .. } => "sink-connected",
+            AudioEvent::SinkReconnecting { .. } => "sink-reconnecting",
+            AudioEvent::SinkFailed { .. } => "sink-failed",
+            AudioEvent::OutputDeviceChanged { .. } => "output-device-changed",
+            AudioEvent::OutputDeviceFailed { .. } => "output-device-failed",
+            AudioEvent::BitrateChanged { .. } => "bitrate-changed",
+            AudioEvent::SignalChanged { .. } => "signal-changed",
+            AudioEvent::EffectsOverrun { .. } => "effects-overrun",
+            AudioEvent::SignalDetected { .. } => "signal-detected",
+            AudioEvent::SilenceDetected { .. } => "silence-detected",
+            AudioEvent::IceCandidate { .. } => "ice-candidate",
         }
     }
 }
 
+// An Icecast sink ready to connect, taken from the engine by prepare_icecast
+// or prepare_encoder_output. Connecting means a TCP connect and an HTTP
+// handshake with a possibly slow server, so it happens with the engine
+// unlocked and the sink is handed back to attach the result.
+pub struct PendingIcecast {
+    stream: StreamId,
+    config: IcecastConfig,
+    info: OpusStreamInfo,
+    frames: StreamReceiver<EncodedFrame>,
+    events: broadcast::Sender<AudioEvent>,
+}
+
+impl PendingIcecast {
+    // The stream the sink belongs to
+    pub fn stream(&self) -> &StreamId {
+        &self.stream
+    }
+
+    pub async fn connect(self) -> Result<IcecastSink, AudioError> {
+        Ok(IcecastSink::connect(self.config, self.info, self.frames, self.events).await?)
+    }
+}
+
+// One raw Opus packet plus its length in 48 kHz samples, for internal sinks
+// that need timing (Ogg granule positions) regardless of the sink format
+#[derive(Debug, Clone)]
+pub struct EncodedFrame {
+    pub data: Vec<u8>,
+    pub duration_48k: u32,
+}
+
+// Identifies one stream. Random rather than time-based, so two streams started
+// together can't collide and ids can't be guessed; serialized as the bare string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StreamId(String);
+
+impl StreamId {
+    pub fn new() -> Self {
+        Self(format!("stream_{}", uuid::Uuid::new_v4()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for StreamId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for StreamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Timing observed by the capture callback
+#[derive(Debug, Clone, Copy, Default)]
+struct CaptureTiming {
+    callback_frames: u32,
+    device_latency_ms: Option<f32>,
+}
+
+// How a parameter should be laid out on a slider. Frequencies and times span
+// orders of magnitude, so equal slider travel should multiply the value
+// rather than add to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamScale {
+    #[default]
+    Linear,
+    Logarithmic,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EffectParameter {
     pub name: String,
@@ -53,13 +583,85 @@ pub struct EffectParameter {
     pub min: f32,
     pub max: f32,
     pub step: f32,
+    #[serde(default)]
+    pub scale: ParamScale,
+}
+
+impl EffectParameter {
+    // Clamps to the range (which may be given in either order) and snaps to step
+    pub fn constrain(&self, value: f32) -> f32 {
+        let (low, high) = (self.min.min(self.max), self.min.max(self.max));
+        let snapped = if self.step > 0.0 {
+            self.min + ((value - self.min) / self.step).round() * self.step
+        } else {
+            value
+        };
+        snapped.clamp(low, high)
+    }
+
+    // A log mapping needs a range that stays above zero; anything else is
+    // treated as linear
+    fn is_logarithmic(&self) -> bool {
+        self.scale == ParamScale::Logarithmic && self.min > 0.0 && self.max > 0.0
+    }
+
+    // Current value as a 0-1 slider position
+    pub fn normalized(&self) -> f32 {
+        if self.min == self.max {
+            return 0.0;
+        }
+        let position = if self.is_logarithmic() {
+            (self.value.max(f32::MIN_POSITIVE) / self.min).ln() / (self.max / self.min).ln()
+        } else {
+            (self.value - self.min) / (self.max - self.min)
+        };
+        position.clamp(0.0, 1.0)
+    }
+
+    // Parameter value for a 0-1 slider position, snapped to step. On a log
+    // scale the midpoint is the geometric mean of min and max.
+    pub fn from_normalized(&self, normalized: f32) -> f32 {
+        let normalized = normalized.clamp(0.0, 1.0);
+        let value = if self.is_logarithmic() {
+            self.min * (self.max / self.min).powf(normalized)
+        } else {
+            self.min + (self.max - self.min) * normalized
+        };
+        self.constrain(value)
+    }
 }
 
 pub trait AudioEffect: Send + Sync {
-    fn process(&self, input: &[f32]) -> Vec<f32>;
+    // `input` is interleaved, `channels` samples per frame. Filters and delay
+    // lines keep separate state per channel; level detectors are linked so
+    // every channel gets the same gain and the stereo image holds.
+    fn process(&mut self, input: &[f32], channels: usize) -> Vec<f32>;
     fn get_name(&self) -> &str;
     fn get_parameters(&self) -> Vec<EffectParameter>;
     fn set_parameter(&mut self, name: &str, value: f32);
+
+    // Delay the effect adds, in frames (samples per channel)
+    fn latency_samples(&self) -> usize {
+        0
+    }
+
+    // Parameters that ramp to a new value rather than jumping. Effects opt in
+    // by listing them here and stepping a ParamSmoother per frame in process.
+    fn smoothable_parameters(&self) -> &[&str] {
+        &[]
+    }
+
+    // Ramp length for those parameters, in frames; 0 makes changes instant
+    fn set_smoothing_frames(&mut self, _frames: usize) {}
+
+    // Clears delay lines, envelopes and filter histories so nothing from
+    // earlier audio (a reverb or echo tail) carries into what comes next
+    fn reset(&mut self) {}
+
+    // Per-band peak meters, for effects that split the signal into bands
+    fn band_levels(&self) -> Option<Vec<BandLevel>> {
+        None
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -72,116 +674,422 @@ pub enum AudioError {
     StreamError(#[from] cpal::StreamError),
     #[error("Build stream error: {0}")]
     BuildStreamError(#[from] cpal::BuildStreamError),
+    #[error("Play stream error: {0}")]
+    PlayStreamError(#[from] cpal::PlayStreamError),
     #[error("Default stream config error: {0}")]
     DefaultStreamConfigError(#[from] cpal::DefaultStreamConfigError),
     #[error("Opus error: {0}")]
     OpusError(#[from] opus::Error),
+    #[error("Codec error: {0}")]
+    Codec(#[from] CodecError),
     #[error("Device error: {0}")]
     DeviceError(String),
+    #[error("Icecast error: {0}")]
+    Icecast(#[from] IcecastError),
+    #[error("WebRTC error: {0}")]
+    WebRtc(#[from] WebRtcError),
+    #[error("Source error: {0}")]
+    Source(#[from] SourceError),
+    #[error("Recording error: {0}")]
+    Recorder(#[from] RecorderError),
+    #[error("Unknown effect type: {0}")]
+    UnknownEffect(String),
+    #[error("No effect at chain index {0}")]
+    NoSuchEffect(usize),
+    #[error("Effect {0} has no parameter {1}")]
+    NoSuchParameter(usize, String),
+    #[error("Effect {0} has no band meters")]
+    NoBandLevels(usize),
+    #[error("No chain snapshot in slot {0}")]
+    EmptySnapshot(u8),
+    #[error("No factory preset named {0}")]
+    NoSuchFactoryPreset(String),
+    #[error("No stream with id {0}")]
+    NoSuchStream(StreamId),
+    #[error("No music bus is set")]
+    NoMusicBus,
+    #[error("The music bus plays a file or a tone, not the input device")]
+    UnsupportedMusicSource,
+    #[error("Input channel {0} does not exist; the device has {1} channel(s)")]
+    NoSuchInputChannel(u16, u16),
+    #[error("No encoder output with id {0}")]
+    NoSuchOutput(u32),
+    #[error("The stream is not live")]
+    NotLive,
+    #[error("{0} only carries Opus; this stream encodes {1:?}")]
+    OpusOnly(&'static str, Codec),
+    #[error("No sample has been captured yet")]
+    NoSampleCapture,
+    #[error("{0} must be a finite number, got {1}")]
+    NonFiniteValue(&'static str, f32),
+    #[error("Opus can't encode {0} ms packets; use 2.5, 5, 10, 20, 40 or 60")]
+    UnsupportedFrameSize(f32),
+    #[error(
+        "The {kind} is version {found}, from a newer app; this one reads up to version {supported}"
+    )]
+    UnsupportedVersion {
+        kind: &'static str,
+        found: u32,
+        supported: u32,
+    },
+    #[error("Cannot write snapshot: {0}")]
+    Snapshot(#[from] serde_json::Error),
 }
 
 pub struct AudioEngine {
+    host_id: cpal::HostId,
     input_device: Option<cpal::Device>,
     output_device: Option<cpal::Device>,
-    encoder: Arc<Mutex<opus::Encoder>>,
-    decoder: Arc<Mutex<opus::Decoder>>,
+    // Name of the output the user picked with set_output_device; None follows
+    // the host default
+    picked_output: Option<String>,
+    decoder: Arc<Mutex<OpusDecoder>>,
     sample_rate: u32,
     channels: u16,
-    broadcast_tx: broadcast::Sender<Vec<u8>>,
-    effects_chain: Arc<Mutex<Vec<Box<dyn AudioEffect>>>>,
-    monitoring_enabled: Arc<Mutex<bool>>,
-    current_levels: Arc<Mutex<AudioLevels>>,
-    stream: Arc<Mutex<Option<cpal::Stream>>>,
+    buffer_size: usize,
+    // Encode output buffer, sized once for the largest packet a block can
+    // produce at any bitrate
+    encode_buffer_bytes: usize,
+    // Sample size local recordings are written with
+    bit_depth: u16,
+    file_resample_quality: ResampleQuality,
+    // File sources decoded before the engine was locked to start them, by
+    // path; kept for restarts while a stream still plays the file
+    decoded_files: HashMap<String, DecodedFile>,
+    broadcast_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    complexity: i32,
+    // Applied to every stream's chain, including streams started later
+    overrun_protection: bool,
+    capture_channels: Vec<u16>,
+    events_tx: broadcast::Sender<AudioEvent>,
+    sessions: Sessions,
+    chain_snapshots: HashMap<u8, ChainPreset>,
+    active_ab_slot: u8,
+    // Output stream playing the selected stream's monitor bus, while monitoring
+    monitor_stream: Option<cpal::Stream>,
+    dry_monitor_level: Arc<Mutex<f32>>,
+    // Monitor mix: the stream's own (processed) input, and the audio coming
+    // back from a WebRTC peer
+    monitor_input_level: Arc<Mutex<f32>>,
+    monitor_return_level: Arc<Mutex<f32>>,
+    monitor_delay_ms: Arc<Mutex<f32>>,
+    monitor_limiter: Arc<Mutex<MonitorLimiterConfig>>,
+    silence_detection: Arc<Mutex<SilenceDetectionConfig>>,
+    input_gain_db: Arc<Mutex<f32>>,
+    output_gain_db: Arc<Mutex<f32>>,
+    dc_block_enabled: Arc<Mutex<bool>>,
+    chain_bypass: Arc<Mutex<bool>>,
+    is_muted: Arc<Mutex<bool>>,
+    ptt_enabled: Arc<Mutex<bool>>,
+    ptt_active: Arc<Mutex<bool>>,
+    level_events_task: Option<tokio::task::JoinHandle<()>>,
+    // Runs adapt_bitrates() while any stream has adaptive bitrate on
+    bitrate_task: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl AudioEngine {
     pub fn new(config: AudioConfig) -> Result<Self, AudioError> {
-        let host = cpal::default_host();
+        let config = config.upgrade()?;
+        let host = select_host(config.host_id.as_deref());
+        log::info!("Using audio host: {}", host.id().name());
 
         let input_device = host.default_input_device();
         let output_device = host.default_output_device();
 
-        // Create Opus encoder
-        let mut encoder = opus::Encoder::new(
-            config.sample_rate,
-            Channels::Stereo,
-            Application::Audio
-        )?;
+        let channels = config.channels;
+        let broadcast_capacity = config.broadcast_capacity.max(MIN_BROADCAST_CAPACITY);
+        let complexity = config.complexity.clamp(0, MAX_ENCODER_COMPLEXITY);
+        let session =
+            StreamSession::new(config.sample_rate, channels, broadcast_capacity, complexity)?;
 
-        // Set maximum quality
-        encoder.set_bitrate(opus::Bitrate::Max)?;
-        encoder.set_complexity(10)?;
-        encoder.set_signal(opus::Signal::Music)?;
+        // Create Opus decoder with the Opus stream layout (the same for every
+        // session, since they share the engine's channel count)
+        let opus = OpusEncoder::new(config.sample_rate, channels, Application::Audio)?;
+        let decoder = OpusDecoder::new(config.sample_rate, opus.mapping())?;
 
-        // Create Opus decoder
-        let decoder = opus::Decoder::new(
-            config.sample_rate,
-            Channels::Stereo
-        )?;
+        let encode_buffer_bytes =
+            opus.max_packet_bytes(config.buffer_size.max(1), config.sample_rate);
 
-        let (broadcast_tx, _) = broadcast::channel(1024);
+        let (events_tx, _) = broadcast::channel(64);
 
         Ok(Self {
+            host_id: host.id(),
             input_device,
             output_device,
-            encoder: Arc::new(Mutex::new(encoder)),
+            picked_output: None,
             decoder: Arc::new(Mutex::new(decoder)),
             sample_rate: config.sample_rate,
-            channels: config.channels,
-            broadcast_tx,
-            effects_chain: Arc::new(Mutex::new(Vec::new())),
-            monitoring_enabled: Arc::new(Mutex::new(false)),
-            current_levels: Arc::new(Mutex::new(AudioLevels::default())),
-            stream: Arc::new(Mutex::new(None)),
+            channels,
+            buffer_size: config.buffer_size.max(1),
+            encode_buffer_bytes,
+            bit_depth: config.bit_depth,
+            file_resample_quality: config.file_resample_quality,
+            decoded_files: HashMap::new(),
+            broadcast_capacity,
+            overflow_policy: config.overflow_policy,
+            complexity,
+            overrun_protection: false,
+            capture_channels: config.capture_channels,
+            events_tx,
+            sessions: Sessions::new(session),
+            chain_snapshots: HashMap::new(),
+            active_ab_slot: AB_SLOT_A,
+            monitor_stream: None,
+            dry_monitor_level: Arc::new(Mutex::new(0.0)),
+            monitor_input_level: Arc::new(Mutex::new(1.0)),
+            monitor_return_level: Arc::new(Mutex::new(1.0)),
+            monitor_delay_ms: Arc::new(Mutex::new(0.0)),
+            monitor_limiter: Arc::new(Mutex::new(MonitorLimiterConfig::default())),
+            silence_detection: Arc::new(Mutex::new(SilenceDetectionConfig::default())),
+            input_gain_db: Arc::new(Mutex::new(0.0)),
+            output_gain_db: Arc::new(Mutex::new(0.0)),
+            dc_block_enabled: Arc::new(Mutex::new(true)),
+            chain_bypass: Arc::new(Mutex::new(false)),
+            is_muted: Arc::new(Mutex::new(false)),
+            ptt_enabled: Arc::new(Mutex::new(false)),
+            ptt_active: Arc::new(Mutex::new(false)),
+            level_events_task: None,
+            bitrate_task: None,
         })
     }
 
+    pub fn selected_stream(&self) -> &StreamId {
+        self.sessions.selected_id()
+    }
+
+    // Points the per-stream commands (effects, meters, stats, sinks) and the
+    // monitor at `id`
+    pub fn select_stream(&mut self, id: &StreamId) -> Result<(), AudioError> {
+        let monitoring = self.monitor_stream.is_some();
+        if monitoring {
+            self.set_monitoring(false)?;
+        }
+        self.sessions.select(id)?;
+        if monitoring {
+            self.set_monitoring(true)?;
+        }
+        Ok(())
+    }
+
+    pub fn streams(&self) -> Vec<StreamSummary> {
+        let selected = self.sessions.selected_id();
+        self.sessions
+            .iter()
+            .map(|(id, session)| StreamSummary {
+                id: id.clone(),
+                status: session.status(),
+                source: session.source.clone(),
+                codec: session.codec(),
+                bitrate: session.bitrate,
+                frame_size_ms: self.block_frames(session) as f32 * 1000.0 / self.sample_rate as f32,
+                selected: id == selected,
+            })
+            .collect()
+    }
+
+    // Frames per block for `session`: its own packet size if it set one,
+    // else the engine's buffer size
+    fn block_frames(&self, session: &StreamSession) -> usize {
+        session.block_frames.unwrap_or(self.buffer_size)
+    }
+
+    // Starts a stream encoding `codec` at `bitrate`, from `source` if given,
+    // in `frame_size_ms` blocks (the engine's buffer size if None). The
+    // selected session is reused while it is idle; otherwise a new session
+    // with an empty chain is created and selected, leaving the running one
+    // untouched.
+    pub async fn start_stream(
+        &mut self,
+        source: Option<Source>,
+        codec: Codec,
+        bitrate: u32,
+        frame_size_ms: Option<f32>,
+    ) -> Result<StreamId, AudioError> {
+        let block_frames = match frame_size_ms {
+            Some(ms) => Some(
+                opus_frame_frames(ms, self.sample_rate)
+                    .ok_or(AudioError::UnsupportedFrameSize(ms))?,
+            ),
+            None => None,
+        };
+        if self.sessions.selected().is_running() {
+            let session = StreamSession::new(
+                self.sample_rate,
+                self.channels,
+                self.broadcast_capacity,
+                self.complexity,
+            )?;
+            session
+                .effects_chain
+                .lock()
+                .unwrap()
+                .set_overrun_protection(self.overrun_protection, self.sample_rate);
+            self.sessions.insert(session);
+        }
+        // Sinks connected ahead of the start would get nothing they can use
+        let session = self.sessions.selected();
+        if codec != Codec::Opus && session.icecast_sink.is_some() {
+            return Err(AudioError::OpusOnly("Icecast", codec));
+        }
+        if codec != Codec::Opus && session.webrtc_peer.is_some() {
+            return Err(AudioError::OpusOnly("WebRTC", codec));
+        }
+        if let Some(source) = source {
+            self.set_source(source);
+        }
+        let (sample_rate, channels, complexity) =
+            (self.sample_rate, self.channels, self.complexity);
+        let session = self.sessions.selected_mut();
+        session.set_codec(codec, sample_rate, channels, complexity)?;
+        session.set_bitrate(bitrate)?;
+        session.block_frames = block_frames;
+        self.start_capture().await?;
+        Ok(self.selected_stream().clone())
+    }
+
+    // Stops one stream. Any session other than the selected one is discarded
+    // along with its sinks; the selected one stays, idle, to be started again.
+    pub async fn stop_stream(&mut self, id: &StreamId) -> Result<(), AudioError> {
+        let session = self.sessions.get_mut(id)?;
+        *session.is_paused.lock().unwrap() = false;
+        session.stop_sources();
+
+        if id == self.sessions.selected_id() {
+            self.stop_level_events();
+        } else if let Some(mut session) = self.sessions.remove(id) {
+            session.disconnect_icecast();
+            session.disconnect_outputs();
+            session.close_webrtc().await;
+        }
+        Ok(())
+    }
+
+    pub fn set_stream_paused(&mut self, id: &StreamId, paused: bool) -> Result<(), AudioError> {
+        *self.sessions.get_mut(id)?.is_paused.lock().unwrap() = paused;
+        Ok(())
+    }
+
+    // Starts (or restarts) capture on the selected stream
     pub async fn start_capture(&mut self) -> Result<(), AudioError> {
-        let input_device = self.input_device.as_ref()
-            .ok_or(AudioError::NoInputDevice)?;
+        let session = self.sessions.selected_mut();
+        session.stop_sources();
+        *session.capture_timing.lock().unwrap() = CaptureTiming::default();
+        session.stream_stats.lock().unwrap().start();
+        session.spectrum.lock().unwrap().reset();
+        session.effects_chain.lock().unwrap().reset();
+        let source = session.source.clone();
 
-        let config = input_device.default_input_config()?;
-        let encoder = self.encoder.clone();
-        let tx = self.broadcast_tx.clone();
-        let effects_chain = self.effects_chain.clone();
-        let current_levels = self.current_levels.clone();
+        match self.generator_for(&source)? {
+            Some(generator) => {
+                self.start_generated_capture(generator);
+                Ok(())
+            }
+            None => self.start_device_capture(),
+        }
+    }
 
-        let stream = input_device.build_input_stream(
-            &config.into(),
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                // Process audio through effects chain
-                let processed = {
-                    let effects = effects_chain.lock().unwrap();
-                    let mut output = data.to_vec();
-                    for effect in effects.iter() {
-                        output = effect.process(&output);
+    // Generator producing `source` at the engine's rate and channel count;
+    // None for the input device, which has no generator
+    fn generator_for(&self, source: &Source) -> Result<Option<source::BlockGenerator>, AudioError> {
+        let channels = self.channels as usize;
+        match source {
+            Source::Device => Ok(None),
+            Source::TestTone { freq, level } => Ok(Some(source::test_tone(
+                *freq,
+                *level,
+                self.sample_rate as f32,
+                channels,
+            ))),
+            Source::File { path, looping } => {
+                let samples = match self.decoded_file(path) {
+                    Some(file) => file.samples.clone(),
+                    // Not preloaded (an OSC start, say): decode here, under the lock
+                    None => {
+                        let quality = self.file_resample_quality;
+                        Arc::new(source::load_file(
+                            Path::new(path),
+                            self.sample_rate,
+                            channels,
+                            quality,
+                        )?)
                     }
-                    output
                 };
+                Ok(Some(source::file_playback(samples, *looping)))
+            }
+        }
+    }
+
+    // What files are decoded to right now; a change of resample quality
+    // makes earlier decodes stale
+    pub fn decode_format(&self) -> DecodeFormat {
+        DecodeFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels as usize,
+            quality: self.file_resample_quality,
+        }
+    }
+
+    pub fn decoded_file(&self, path: &str) -> Option<&DecodedFile> {
+        self.decoded_files
+            .get(path)
+            .filter(|file| file.format == self.decode_format())
+    }
+
+    // Keeps a file decoded with source::decode_file for the next start.
+    // Files no stream plays any more are dropped to free their memory.
+    pub fn store_decoded_file(&mut self, file: DecodedFile) {
+        let sessions = &self.sessions;
+        self.decoded_files.retain(|path, _| {
+            sessions.iter().any(
+                |(_, session)| matches!(&session.source, Source::File { path: p, .. } if p == path),
+            )
+        });
+        self.decoded_files.insert(file.path.clone(), file);
+    }
+
+    fn start_device_capture(&mut self) -> Result<(), AudioError> {
+        let input_device = self
+            .input_device
+            .as_ref()
+            .ok_or(AudioError::NoInputDevice)?;
 
-                // Calculate audio levels
-                let peak = processed.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
-                let rms = (processed.iter().map(|s| s * s).sum::<f32>() / processed.len() as f32).sqrt();
+        let stream_config = capture_config(
+            input_device,
+            self.sample_rate,
+            self.required_input_channels(),
+        )?
+        .config();
+        self.check_capture_channels(stream_config.channels)?;
+        let device_channels = stream_config.channels.max(1) as usize;
+        let session = self.sessions.selected();
+        let capture_timing = session.capture_timing.clone();
+        let capture_channels = self.capture_channels.clone();
+        let input_channels = if capture_channels.is_empty() {
+            device_channels
+        } else {
+            capture_channels.len()
+        };
+        // A fallback rate is resampled to the engine rate inside the pipeline
+        let mut pipeline =
+            CapturePipeline::new(self, session, stream_config.sample_rate.0, input_channels);
 
-                // Update current levels
-                if let Ok(mut levels) = current_levels.lock() {
-                    levels.input_level = rms;
-                    levels.peak = peak;
-                    levels.rms = rms;
+        let stream = input_device.build_input_stream(
+            &stream_config,
+            move |data: &[f32], info: &cpal::InputCallbackInfo| {
+                if let Ok(mut timing) = capture_timing.lock() {
+                    let timestamp = info.timestamp();
+                    timing.callback_frames = (data.len() / device_channels) as u32;
+                    timing.device_latency_ms = timestamp
+                        .callback
+                        .duration_since(&timestamp.capture)
+                        .map(|d| d.as_secs_f32() * 1000.0);
                 }
 
-                // Encode to Opus
-                if let Ok(mut enc) = encoder.lock() {
-                    let mut encoded = vec![0u8; 4000];
-                    match enc.encode_float(&processed, &mut encoded) {
-                        Ok(size) => {
-                            encoded.truncate(size);
-                            let _ = tx.send(encoded);
-                        }
-                        Err(e) => {
-                            log::error!("Encoding error: {}", e);
-                        }
-                    }
+                if capture_channels.is_empty() {
+                    pipeline.process(data);
+                } else {
+                    pipeline.process(&extract_channels(data, device_channels, &capture_channels));
                 }
             },
             |err| log::error!("Stream error: {}", err),
@@ -191,56 +1099,2136 @@ impl AudioEngine {
         stream.play()?;
 
         // Store stream
-        *self.stream.lock().unwrap() = Some(stream);
+        let session = self.sessions.selected_mut();
+        *session.stream.lock().unwrap() = Some(stream);
+        session.input_stream_config = Some(stream_config);
+
+        Ok(())
+    }
+
+    // Channels the input config has to open for the selected capture channels
+    fn required_input_channels(&self) -> u16 {
+        self.capture_channels.iter().max().map_or(0, |&c| c + 1)
+    }
+
+    fn check_capture_channels(&self, device_channels: u16) -> Result<(), AudioError> {
+        match self
+            .capture_channels
+            .iter()
+            .find(|&&c| c >= device_channels)
+        {
+            Some(&channel) => Err(AudioError::NoSuchInputChannel(channel, device_channels)),
+            None => Ok(()),
+        }
+    }
 
+    // Which input device channels (0-based) feed the streams, e.g. [2, 3] for
+    // the third and fourth inputs of an interface; empty takes them all. The
+    // selection is converted to the engine's channel count like any input.
+    // Checked against the current input device; takes effect on the next
+    // start_capture.
+    pub fn set_capture_channels(&mut self, channels: Vec<u16>) -> Result<(), AudioError> {
+        let previous = std::mem::replace(&mut self.capture_channels, channels);
+        if let Some(device) = self.input_device.as_ref() {
+            let checked = capture_config(device, self.sample_rate, self.required_input_channels())
+                .and_then(|config| self.check_capture_channels(config.channels()));
+            if let Err(e) = checked {
+                self.capture_channels = previous;
+                return Err(e);
+            }
+        }
         Ok(())
     }
 
+    pub fn capture_channels(&self) -> &[u16] {
+        &self.capture_channels
+    }
+
+    // Runs an internal generator through the same pipeline as the device
+    // callback, at the engine's rate and channel count
+    fn start_generated_capture(&mut self, generator: source::BlockGenerator) {
+        let sample_rate = self.sample_rate as f32;
+        let channels = self.channels as usize;
+        let pipeline =
+            CapturePipeline::new(self, self.sessions.selected(), self.sample_rate, channels);
+
+        let buffer_size = self.buffer_size;
+        let session = self.sessions.selected_mut();
+        session.capture_timing.lock().unwrap().callback_frames = buffer_size as u32;
+        session.source_worker = Some(SourceWorker::spawn(
+            generator,
+            pipeline,
+            sample_rate,
+            channels,
+            buffer_size,
+        ));
+    }
+
+    // Stops the selected stream
     pub async fn stop_capture(&mut self) -> Result<(), AudioError> {
-        let mut stream = self.stream.lock().unwrap();
-        *stream = None;
+        let id = self.selected_stream().clone();
+        self.stop_stream(&id).await
+    }
+
+    // Stops every stream and closes every sink so nothing is left
+    // half-written on exit. Safe to call more than once.
+    pub async fn shutdown(&mut self) {
+        self.stop_level_events();
+        self.stop_bitrate_task();
+        self.monitor_stream = None;
+        for (_, session) in self.sessions.iter_mut() {
+            session.shutdown().await;
+        }
+    }
+
+    // Checks everything start_capture would set up without starting it: the
+    // input opens and a stream builds (but never plays), the source decodes,
+    // and a scratch encoder accepts the bitrate and one block of audio at the
+    // requested frame size. The live encoder and broadcast channel are not touched.
+    pub fn validate_stream_setup(
+        &self,
+        codec: Codec,
+        bitrate: u32,
+        sample_rate: u32,
+        channels: u16,
+        frame_size_ms: Option<f32>,
+    ) -> StreamValidation {
+        let session = self.sessions.selected();
+        let mut report = StreamValidation {
+            ok: true,
+            source: session.source.clone(),
+            device_name: None,
+            format: None,
+            effects: session
+                .effects_chain
+                .lock()
+                .unwrap()
+                .slots()
+                .iter()
+                .map(|s| s.effect.get_name().to_string())
+                .collect(),
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        if sample_rate != self.sample_rate {
+            report.warnings.push(format!(
+                "Requested {} Hz but the engine runs at {} Hz",
+                sample_rate, self.sample_rate
+            ));
+        }
+        if channels == 0 || channels > MAX_CHANNELS {
+            report
+                .errors
+                .push(CodecError::UnsupportedChannels(channels).to_string());
+        } else if channels != self.channels {
+            report.warnings.push(format!(
+                "Requested {} channel(s) but the encoder has {}",
+                channels, self.channels
+            ));
+        }
+        if codec != Codec::Opus && *session.sink_format.lock().unwrap() == SinkFormat::Rtp {
+            report.warnings.push(format!(
+                "RTP packetization is Opus-only; {:?} packets are sent raw",
+                codec
+            ));
+        } else if self.channels > 2 && *session.sink_format.lock().unwrap() == SinkFormat::Rtp {
+            report.warnings.push(
+                "RTP Opus (RFC 7587) only carries mono or stereo; receivers may not decode multistream packets"
+                    .to_string(),
+            );
+        }
+
+        match &session.source {
+            Source::Device => self.validate_device(&mut report),
+            Source::TestTone { .. } => {}
+            // Already decoded means it reads
+            Source::File { path, .. } if self.decoded_file(path).is_some() => {}
+            Source::File { path, .. } => {
+                let channels = self.channels as usize;
+                let quality = self.file_resample_quality;
+                if let Err(e) =
+                    source::load_file(Path::new(path), self.sample_rate, channels, quality)
+                {
+                    report
+                        .errors
+                        .push(format!("Cannot read source file: {}", e));
+                }
+            }
+        }
+
+        let block_frames = match frame_size_ms {
+            Some(ms) => opus_frame_frames(ms, self.sample_rate),
+            None => Some(self.buffer_size),
+        };
+        match block_frames {
+            Some(frames) => {
+                if let Err(e) = self.validate_encoder(codec, bitrate, frames) {
+                    report
+                        .errors
+                        .push(format!("Encoder rejected the settings: {}", e));
+                }
+            }
+            None => report.errors.push(
+                AudioError::UnsupportedFrameSize(frame_size_ms.unwrap_or_default()).to_string(),
+            ),
+        }
+
+        if *self.is_muted.lock().unwrap() {
+            report.warnings.push("Input is muted".to_string());
+        }
+        if *self.ptt_enabled.lock().unwrap() && !*self.ptt_active.lock().unwrap() {
+            report
+                .warnings
+                .push("Push-to-talk is enabled; nothing is sent until it is held".to_string());
+        }
+
+        report.ok = report.errors.is_empty();
+        report
+    }
+
+    fn validate_device(&self, report: &mut StreamValidation) {
+        let Some(device) = self.input_device.as_ref() else {
+            report.errors.push(AudioError::NoInputDevice.to_string());
+            return;
+        };
+        report.device_name = device.name().ok();
+
+        let config = match capture_config(device, self.sample_rate, self.required_input_channels())
+        {
+            Ok(config) => config,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                return;
+            }
+        };
+        if let Err(e) = self.check_capture_channels(config.channels()) {
+            report.errors.push(e.to_string());
+        }
+        let captured_channels = if self.capture_channels.is_empty() {
+            config.channels()
+        } else {
+            self.capture_channels.len() as u16
+        };
+
+        if config.sample_rate().0 != self.sample_rate {
+            report.warnings.push(format!(
+                "Device captures at {} Hz instead of {} Hz; input is resampled",
+                config.sample_rate().0,
+                self.sample_rate
+            ));
+        }
+        if captured_channels != self.channels {
+            report.warnings.push(format!(
+                "Capturing {} input channel(s); audio is converted to {}",
+                captured_channels, self.channels
+            ));
+        }
+
+        report.format = Some(NegotiatedFormat {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            sample_format: format!("{:?}", config.sample_format()),
+            // Capture asks for the device's default buffer, whose size is
+            // only known once a stream is open
+            buffer_frames: None,
+        });
+
+        // Building proves the device opens with the negotiated config; the
+        // stream is dropped without ever being played
+        let built = device.build_input_stream(
+            &config.config(),
+            |_: &[f32], _: &cpal::InputCallbackInfo| {},
+            |err| log::error!("Stream error: {}", err),
+            None,
+        );
+        if let Err(e) = built {
+            report.errors.push(AudioError::from(e).to_string());
+        }
+    }
+
+    fn validate_encoder(
+        &self,
+        codec: Codec,
+        bitrate: u32,
+        block_frames: usize,
+    ) -> Result<(), AudioError> {
+        let mut encoder = new_encoder(codec, self.sample_rate, self.channels, self.complexity)?;
+        encoder.set_target_bitrate(Some(bitrate))?;
+
+        // One block of silence at the stream's block size
+        let block = vec![0.0f32; block_frames * self.channels as usize];
+        let mut encoded = vec![0u8; encoder.max_packet_bytes(block_frames, self.sample_rate)];
+        encoder.encode(&block, &mut encoded)?;
         Ok(())
     }
 
-    pub fn add_effect(&mut self, effect: Box<dyn AudioEffect>) {
-        let mut effects = self.effects_chain.lock().unwrap();
-        effects.push(effect);
+    // Runs `signal` through a scratch encoder set up like the live one at each
+    // bitrate, decodes it with the engine's decoder and scores the result
+    // against the original. The live stream is not touched.
+    pub fn measure_codec_quality(
+        &self,
+        signal: TestSignal,
+        bitrates: &[u32],
+    ) -> Result<Vec<CodecQuality>, AudioError> {
+        let blocks =
+            (quality::REFERENCE_SECS * self.sample_rate as f32) as usize / self.buffer_size;
+        let reference = signal.generate(
+            self.sample_rate as f32,
+            self.channels as usize,
+            blocks * self.buffer_size,
+        );
+        bitrates
+            .iter()
+            .map(|&bitrate| {
+                self.codec_round_trip(&reference, bitrate)
+                    .map(|(quality, _)| quality)
+            })
+            .collect()
     }
 
-    pub fn clear_effects(&mut self) {
-        let mut effects = self.effects_chain.lock().unwrap();
-        effects.clear();
+    // Encodes `reference` (whole blocks of interleaved audio) with a scratch
+    // encoder set up like the live one and decodes it with the engine's
+    // decoder. Returns the score and the decoded audio, lined up with the
+    // reference.
+    fn codec_round_trip(
+        &self,
+        reference: &[f32],
+        bitrate: u32,
+    ) -> Result<(CodecQuality, Vec<f32>), AudioError> {
+        let channels = self.channels as usize;
+        let block_len = self.buffer_size * channels;
+        let max_lag = (quality::MAX_ALIGNMENT_SECS * self.sample_rate as f32) as usize;
+
+        let mut encoder = OpusEncoder::new(self.sample_rate, self.channels, Application::Audio)?;
+        encoder.set_bitrate(opus::Bitrate::Bits(bitrate as i32))?;
+        encoder.set_complexity(self.complexity)?;
+        encoder.set_signal(opus::Signal::Music)?;
+        let mut decoder = self.decoder.lock().unwrap();
+        decoder.reset()?;
+
+        let mut packet = vec![0u8; self.encode_buffer_bytes];
+        let mut block = vec![0.0f32; block_len];
+        let mut decoded = Vec::with_capacity(reference.len());
+        for input in reference.chunks_exact(block_len) {
+            let len = encoder.encode_float(input, &mut packet)?;
+            let frames = decoder.decode_float(&packet[..len], &mut block, false)?;
+            decoded.extend_from_slice(&block[..frames * channels]);
+        }
+
+        let lag = quality::alignment(reference, &decoded, channels, max_lag);
+        decoded.drain(..lag * channels);
+        let (segmental_snr_db, snr_db) =
+            quality::measure_snr(reference, &decoded, channels, self.sample_rate as f32);
+        let quality = CodecQuality {
+            bitrate,
+            segmental_snr_db,
+            snr_db,
+        };
+        Ok((quality, decoded))
     }
 
-    pub fn get_current_levels(&self) -> AudioLevels {
-        self.current_levels.lock().unwrap().clone()
+    // One-click check of the audio stack that needs no microphone: a test
+    // tone through a fixed chain, the codec and the meters, each stage checked
+    // on its own. Engine-wide gain, mute and push-to-talk are left out, and no
+    // stream is touched.
+    pub fn run_selftest(&self) -> SelfTestReport {
+        let channels = self.channels as usize;
+        let block_len = self.buffer_size * channels;
+        let blocks = ((selftest::SELFTEST_SECS * self.sample_rate as f32) as usize
+            / self.buffer_size)
+            .max(1);
+        let mut report = SelfTestReport::default();
+
+        let mut tone = source::test_tone(
+            selftest::SELFTEST_TONE_HZ,
+            selftest::SELFTEST_TONE_DB,
+            self.sample_rate as f32,
+            channels,
+        );
+        let mut generated = vec![0.0f32; blocks * block_len];
+        for block in generated.chunks_mut(block_len) {
+            tone(block);
+        }
+        if !report.record(selftest::check_signal(&generated)) {
+            return report.finish();
+        }
+
+        let mut chain = selftest::selftest_chain(self.sample_rate);
+        let processed: Vec<f32> = generated
+            .chunks(block_len)
+            .flat_map(|block| chain.process(block, channels))
+            .collect();
+        if !report.record(selftest::check_signal(&processed)) {
+            return report.finish();
+        }
+
+        let decoded = match self.codec_round_trip(&processed, selftest::SELFTEST_BITRATE) {
+            Ok((quality, decoded)) => {
+                let detail = format!(
+                    "{:.1} dB segmental SNR at {} kbps",
+                    quality.segmental_snr_db,
+                    quality.bitrate / 1000
+                );
+                if quality.segmental_snr_db < selftest::MIN_SELFTEST_SNR_DB {
+                    report.record(Err(format!("Only {}", detail)));
+                    return report.finish();
+                }
+                report.record(Ok(detail));
+                decoded
+            }
+            Err(e) => {
+                report.record(Err(e.to_string()));
+                return report.finish();
+            }
+        };
+
+        report.record(selftest::check_meters(
+            &decoded,
+            channels,
+            self.buffer_size,
+            self.sample_rate,
+        ));
+        report.finish()
     }
 
-    pub fn set_monitoring(&mut self, enabled: bool) {
-        *self.monitoring_enabled.lock().unwrap() = enabled;
+    // Plays `source` under the selected stream's mic at `level_db`, ducked
+    // while the mic is active; None removes the bus. A file is decoded here,
+    // before the bus reaches the audio thread.
+    pub fn set_music_bus(
+        &mut self,
+        source: Option<Source>,
+        level_db: f32,
+        ducking: DuckingConfig,
+    ) -> Result<(), AudioError> {
+        let bus = match source {
+            Some(source) => {
+                let generator = self
+                    .generator_for(&source)?
+                    .ok_or(AudioError::UnsupportedMusicSource)?;
+                Some(MusicBus::new(
+                    generator,
+                    level_db,
+                    ducking,
+                    self.sample_rate as f32,
+                ))
+            }
+            None => None,
+        };
+        *self.sessions.selected().music_bus.lock().unwrap() = bus;
+        Ok(())
     }
 
-    pub fn subscribe_to_audio(&self) -> broadcast::Receiver<Vec<u8>> {
-        self.broadcast_tx.subscribe()
+    pub fn set_music_level(&mut self, level_db: f32) -> Result<(), AudioError> {
+        let mut bus = self.sessions.selected().music_bus.lock().unwrap();
+        bus.as_mut()
+            .ok_or(AudioError::NoMusicBus)?
+            .set_level_db(level_db);
+        Ok(())
     }
-}
 
-// EQBand helper struct for equalizer
-#[derive(Debug, Clone)]
-pub struct EQBand {
-    pub frequency: f32,
-    pub q: f32,
-    pub gain: f32,
-}
+    pub fn set_music_ducking(&mut self, ducking: DuckingConfig) -> Result<(), AudioError> {
+        let mut bus = self.sessions.selected().music_bus.lock().unwrap();
+        bus.as_mut()
+            .ok_or(AudioError::NoMusicBus)?
+            .set_ducking(ducking);
+        Ok(())
+    }
 
-impl EQBand {
-    pub fn new(frequency: f32, q: f32, gain: f32) -> Self {
-        Self { frequency, q, gain }
+    // Takes effect on the next start_capture
+    pub fn set_source(&mut self, source: Source) {
+        self.sessions.selected_mut().source = source;
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.sessions.selected().source
+    }
+
+    // Takes effect the next time a file source is decoded
+    pub fn set_file_resample_quality(&mut self, quality: ResampleQuality) {
+        self.file_resample_quality = quality;
+    }
+
+    pub fn file_resample_quality(&self) -> ResampleQuality {
+        self.file_resample_quality
+    }
+
+    pub fn get_stream_latency(&self) -> StreamLatency {
+        let session = self.sessions.selected();
+        let timing = *session.capture_timing.lock().unwrap();
+        let sample_rate = session
+            .input_stream_config
+            .as_ref()
+            .map(|c| c.sample_rate.0)
+            .unwrap_or(self.sample_rate);
+
+        // Prefer the fixed buffer we asked for, else what the callback actually delivered
+        let buffer_frames = match session.input_stream_config.as_ref().map(|c| &c.buffer_size) {
+            Some(cpal::BufferSize::Fixed(frames)) => Some(*frames),
+            _ if timing.callback_frames > 0 => Some(timing.callback_frames),
+            _ => None,
+        };
+
+        let effect_latency_samples = self.total_effect_latency();
+
+        // Queued monitor audio plus the output buffer; the chain's own
+        // latency is reported separately
+        let monitor = session.monitor.lock().unwrap();
+        let monitoring_latency_ms = monitor.is_attached().then(|| {
+            monitor.latency_frames(self.channels as usize) as f32 / self.sample_rate as f32 * 1000.0
+        });
+
+        StreamLatency {
+            sample_rate,
+            buffer_frames,
+            buffer_latency_ms: buffer_frames.map(|f| f as f32 / sample_rate as f32 * 1000.0),
+            device_latency_ms: timing.device_latency_ms,
+            monitoring_latency_ms,
+            effect_latency_samples,
+            effect_latency_ms: effect_latency_samples as f32 / sample_rate as f32 * 1000.0,
+        }
+    }
+
+    // Takes ownership of the task emitting level events, cancelling any previous one
+    pub fn set_level_events_task(&mut self, task: tokio::task::JoinHandle<()>) {
+        self.stop_level_events();
+        self.level_events_task = Some(task);
+    }
+
+    pub fn stop_level_events(&mut self) {
+        if let Some(task) = self.level_events_task.take() {
+            task.abort();
+        }
+    }
+
+    // Turns adaptive bitrate on within `config`'s bounds, or off with None, for
+    // the selected stream. Turning it off leaves the bitrate where it was.
+    pub fn set_adaptive_bitrate(&mut self, config: Option<AdaptiveBitrateConfig>) {
+        self.sessions.selected_mut().set_adaptive_bitrate(config);
+        if !self
+            .sessions
+            .iter()
+            .any(|(_, session)| session.bitrate_controller.is_some())
+        {
+            self.stop_bitrate_task();
+        }
+    }
+
+    pub fn get_adaptive_bitrate(&self) -> Option<AdaptiveBitrateConfig> {
+        self.sessions
+            .selected()
+            .bitrate_controller
+            .as_ref()
+            .map(|c| c.config())
+    }
+
+    // True when some stream has adaptive bitrate on but nothing is driving it
+    pub fn needs_bitrate_task(&self) -> bool {
+        self.bitrate_task.is_none()
+            && self
+                .sessions
+                .iter()
+                .any(|(_, session)| session.bitrate_controller.is_some())
+    }
+
+    // Takes ownership of the task calling adapt_bitrates(), cancelling any previous one
+    pub fn set_bitrate_task(&mut self, task: tokio::task::JoinHandle<()>) {
+        self.stop_bitrate_task();
+        self.bitrate_task = Some(task);
     }
 
-    pub fn apply(&self, input: &[f32]) -> Vec<f32> {
-        // Simple biquad filter implementation
-        // This is a placeholder - in production, use a proper DSP library
-        input.to_vec()
+    fn stop_bitrate_task(&mut self) {
+        if let Some(task) = self.bitrate_task.take() {
+            task.abort();
+        }
+    }
+
+    // Lets the selected stream's encoder hint follow its content, switching
+    // between voice and music tuning as the show moves between talk and songs.
+    // Turning it off puts the encoder back on music, where every stream starts.
+    pub fn set_auto_signal(&mut self, enabled: bool) -> Result<(), AudioError> {
+        let (sample_rate, channels) = (self.sample_rate, self.channels);
+        self.sessions
+            .selected_mut()
+            .set_auto_signal(enabled, sample_rate, channels)
+    }
+
+    // The selected stream's current decision, or None while auto signal is off
+    pub fn get_auto_signal(&self) -> Option<ContentType> {
+        self.sessions
+            .selected()
+            .content_classifier
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|c| c.content())
+    }
+
+    // One controller step for every live stream with adaptive bitrate on
+    pub fn adapt_bitrates(&mut self) {
+        for (id, session) in self.sessions.iter_mut() {
+            match session.adapt_bitrate() {
+                Ok(Some(bitrate)) => {
+                    log::info!("Adaptive bitrate: stream {} now at {} bps", id, bitrate);
+                    let _ = self.events_tx.send(AudioEvent::BitrateChanged {
+                        stream: id.clone(),
+                        bitrate,
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => log::warn!(
+                    "Adaptive bitrate: cannot set bitrate on stream {}: {}",
+                    id,
+                    e
+                ),
+            }
+        }
+    }
+
+    // `effect_type` is the registry key the effect was built from
+    pub fn add_effect(&mut self, effect_type: &str, effect: Box<dyn AudioEffect>) {
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        effects.push(EffectSlot::new(effect_type, effect));
+    }
+
+    // Sum of the latency every effect in the chain reports
+    pub fn total_effect_latency(&self) -> usize {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .latency_samples()
+    }
+
+    // Ramp time for smoothable parameters on every effect in the chain,
+    // including ones added later; 0 makes changes instant
+    pub fn set_parameter_smoothing(&mut self, ms: f32) {
+        let frames =
+            (ms.clamp(0.0, MAX_PARAM_SMOOTHING_MS) * 0.001 * self.sample_rate as f32) as usize;
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .set_smoothing_frames(frames);
+    }
+
+    // Encoder complexity for every stream, clamped to 0-10; new streams start
+    // with it too
+    pub fn set_complexity(&mut self, complexity: i32) -> Result<(), AudioError> {
+        let complexity = complexity.clamp(0, MAX_ENCODER_COMPLEXITY);
+        for (_, session) in self.sessions.iter_mut() {
+            session.encoder.lock().unwrap().set_complexity(complexity)?;
+        }
+        self.complexity = complexity;
+        Ok(())
+    }
+
+    pub fn complexity(&self) -> i32 {
+        self.complexity
+    }
+
+    pub fn set_effect_profiling(&mut self, enabled: bool) {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .set_profiling(enabled);
+    }
+
+    // When on, a stream whose chain keeps taking more than 75% of each block
+    // for half a second gets its most expensive effect bypassed, with an
+    // `effects-overrun` event, instead of dropping out. Bypassed effects stay
+    // off until re-enabled with set_effect_bypass. Applies to every stream.
+    pub fn set_overrun_protection(&mut self, enabled: bool) {
+        self.overrun_protection = enabled;
+        for (_, session) in self.sessions.iter() {
+            session
+                .effects_chain
+                .lock()
+                .unwrap()
+                .set_overrun_protection(enabled, self.sample_rate);
+        }
+    }
+
+    pub fn overrun_protection(&self) -> bool {
+        self.overrun_protection
+    }
+
+    // Averages stay at zero unless profiling is on
+    pub fn effect_cpu_usage(&self) -> Vec<EffectCpuUsage> {
+        let block_secs =
+            self.block_frames(self.sessions.selected()) as f32 / self.sample_rate as f32;
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .cpu_usage(block_secs)
+    }
+
+    pub fn effect_parameters(&self, index: usize) -> Result<Vec<EffectParameter>, AudioError> {
+        let effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slots()
+            .get(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        Ok(slot.effect.get_parameters())
+    }
+
+    // Per-band peak and clip state of the equalizer at `index`
+    pub fn get_eq_band_levels(&self, index: usize) -> Result<Vec<BandLevel>, AudioError> {
+        let effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slots()
+            .get(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        slot.effect
+            .band_levels()
+            .ok_or(AudioError::NoBandLevels(index))
+    }
+
+    // Takes the effect at `index` out of the signal (or puts it back) with a
+    // short crossfade. A bypassed effect isn't run once the fade ends.
+    pub fn set_effect_bypass(&mut self, index: usize, bypassed: bool) -> Result<(), AudioError> {
+        let fade_frames = (EFFECT_BYPASS_FADE_SECS * self.sample_rate as f32) as usize;
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        slot.set_bypassed(bypassed, fade_frames);
+        Ok(())
+    }
+
+    // Parallel processing: blends the effect at `index` with its own input,
+    // 0 (input only) to 1 (effect only). The input is delayed by the effect's
+    // latency so the blend doesn't comb.
+    pub fn set_effect_mix(&mut self, index: usize, mix: f32) -> Result<(), AudioError> {
+        let fade_frames = (EFFECT_MIX_FADE_SECS * self.sample_rate as f32) as usize;
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        slot.set_mix(mix, fade_frames);
+        Ok(())
+    }
+
+    // Clears the internal state of the effect at `index`, leaving its parameters
+    pub fn reset_effect(&mut self, index: usize) -> Result<(), AudioError> {
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        slot.reset();
+        Ok(())
+    }
+
+    // Changes one parameter of the effect at `index` in the live chain
+    pub fn set_effect_parameter(
+        &mut self,
+        index: usize,
+        name: &str,
+        value: f32,
+    ) -> Result<(), AudioError> {
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        slot.effect.set_parameter(name, value);
+        Ok(())
+    }
+
+    // Applies the whole batch under one lock on the chain, so the audio thread
+    // never processes a block with only some of the changes in place
+    pub fn apply_parameter_batch(
+        &mut self,
+        changes: &[ParameterChange],
+    ) -> Vec<ParameterChangeResult> {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .apply_parameter_batch(changes)
+    }
+
+    // Sets a parameter from a 0-1 slider position mapped through its range;
+    // returns the value applied
+    pub fn set_effect_parameter_normalized(
+        &mut self,
+        index: usize,
+        name: &str,
+        normalized: f32,
+    ) -> Result<f32, AudioError> {
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        let slot = effects
+            .slot_mut(index)
+            .ok_or(AudioError::NoSuchEffect(index))?;
+        let parameter = slot
+            .effect
+            .get_parameters()
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| AudioError::NoSuchParameter(index, name.to_string()))?;
+        let value = parameter.from_normalized(normalized);
+        slot.effect.set_parameter(name, value);
+        Ok(value)
+    }
+
+    pub fn chain_preset(&self) -> ChainPreset {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .to_preset()
+    }
+
+    // Rebuilds the chain from `preset`, upgraded to the current schema, and
+    // crossfades over to it. Nothing changes if any effect type is unknown or
+    // the preset is from a newer app.
+    pub fn load_chain(
+        &mut self,
+        preset: &ChainPreset,
+        registry: &EffectRegistry,
+    ) -> Result<(), AudioError> {
+        let preset = preset.upgrade(registry)?;
+        let slots = preset
+            .effects
+            .iter()
+            .map(|effect| {
+                effect
+                    .build(registry, self.sample_rate)
+                    .ok_or_else(|| AudioError::UnknownEffect(effect.effect_type.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fade_frames = (CHAIN_CROSSFADE_SECS * self.sample_rate as f32) as usize;
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .replace(slots, fade_frames);
+        Ok(())
+    }
+
+    // Replaces the chain with one of the built-in starting points
+    pub fn apply_factory_preset(
+        &mut self,
+        name: &str,
+        registry: &EffectRegistry,
+    ) -> Result<(), AudioError> {
+        let preset = factory_preset(name)
+            .ok_or_else(|| AudioError::NoSuchFactoryPreset(name.to_string()))?;
+        self.load_chain(&preset, registry)
+    }
+
+    // In-memory A/B comparison; separate from presets saved to disk
+    pub fn snapshot_chain_to(&mut self, slot: u8) {
+        let preset = self.chain_preset();
+        self.chain_snapshots.insert(slot, preset);
+    }
+
+    pub fn recall_chain_snapshot(
+        &mut self,
+        slot: u8,
+        registry: &EffectRegistry,
+    ) -> Result<(), AudioError> {
+        let preset = self
+            .chain_snapshots
+            .get(&slot)
+            .cloned()
+            .ok_or(AudioError::EmptySnapshot(slot))?;
+        self.load_chain(&preset, registry)?;
+        if slot == AB_SLOT_A || slot == AB_SLOT_B {
+            self.active_ab_slot = slot;
+        }
+        Ok(())
+    }
+
+    // Saves the live chain into the active A/B slot and recalls the other one.
+    // An empty slot starts as a copy of the current chain. Returns the slot now live.
+    pub fn toggle_ab(&mut self, registry: &EffectRegistry) -> Result<u8, AudioError> {
+        let current = self.active_ab_slot;
+        let other = if current == AB_SLOT_A {
+            AB_SLOT_B
+        } else {
+            AB_SLOT_A
+        };
+
+        self.snapshot_chain_to(current);
+        if !self.chain_snapshots.contains_key(&other) {
+            self.snapshot_chain_to(other);
+        }
+        self.recall_chain_snapshot(other, registry)?;
+        Ok(other)
+    }
+
+    // Runs only the effect at `index` until the solo is cleared (or the chain
+    // is replaced); the rest of the chain keeps its state
+    pub fn set_effect_solo(&mut self, index: usize) -> Result<(), AudioError> {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .set_solo(index)
+    }
+
+    pub fn clear_effect_solo(&mut self) {
+        self.sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .clear_solo();
+    }
+
+    pub fn clear_effects(&mut self) {
+        let mut effects = self.sessions.selected().effects_chain.lock().unwrap();
+        effects.clear();
+    }
+
+    pub fn get_current_levels(&self) -> AudioLevels {
+        self.sessions
+            .selected()
+            .current_levels
+            .lock()
+            .unwrap()
+            .clone()
+    }
+
+    // The last `frames` spectrum frames of the selected stream (at most
+    // MAX_SPECTROGRAM_FRAMES), oldest first
+    pub fn get_spectrogram(&self, frames: usize) -> Spectrogram {
+        self.sessions
+            .selected()
+            .spectrum
+            .lock()
+            .unwrap()
+            .spectrogram(frames)
+    }
+
+    // Meters, latest spectrum frame, chain and setup of the selected stream
+    pub fn audio_snapshot(&self) -> AudioSnapshot {
+        let session = self.sessions.selected();
+        let effects = session
+            .effects_chain
+            .lock()
+            .unwrap()
+            .slots()
+            .iter()
+            .enumerate()
+            .map(|(index, slot)| EffectSnapshot {
+                index,
+                effect_type: slot.effect_type.clone(),
+                name: slot.effect.get_name().to_string(),
+                bypassed: slot.is_bypassed(),
+                mix: slot.mix(),
+                parameters: slot.effect.get_parameters(),
+            })
+            .collect();
+
+        AudioSnapshot {
+            captured_at_ms: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            config: SnapshotConfig {
+                host: self.host_id.name().to_string(),
+                input_device: self.input_device.as_ref().and_then(|d| d.name().ok()),
+                output_device: self.output_device.as_ref().and_then(|d| d.name().ok()),
+                sample_rate: self.sample_rate,
+                channels: self.channels,
+                buffer_size: self.buffer_size,
+                source: session.source.clone(),
+                bitrate: session.bitrate,
+                complexity: self.complexity,
+                input_gain_db: self.get_input_gain(),
+                output_gain_db: self.get_output_gain(),
+                muted: self.is_muted(),
+                paused: self.is_paused(),
+                dc_block: *self.dc_block_enabled.lock().unwrap(),
+                chain_bypassed: self.is_chain_bypassed(),
+                monitoring: self.is_monitoring(),
+            },
+            levels: self.get_current_levels(),
+            spectrum: self.get_spectrogram(1),
+            effects,
+            latency: self.get_stream_latency(),
+            stats: self.get_stream_stats(),
+        }
+    }
+
+    // Writes audio_snapshot() to `path` as JSON
+    pub fn export_audio_snapshot(&self, path: &Path) -> Result<(), AudioError> {
+        self.audio_snapshot().write_to(path)?;
+        Ok(())
+    }
+
+    pub fn set_meter_ballistics(&mut self, ballistics: MeterBallistics) {
+        let session = self.sessions.selected();
+        session
+            .level_meter
+            .lock()
+            .unwrap()
+            .set_ballistics(ballistics);
+        session
+            .input_meter
+            .lock()
+            .unwrap()
+            .set_ballistics(ballistics);
+    }
+
+    // Plays the selected stream's chain output on the output device
+    pub fn set_monitoring(&mut self, enabled: bool) -> Result<(), AudioError> {
+        let monitor = self.sessions.selected().monitor.clone();
+        if !enabled {
+            self.monitor_stream = None;
+            monitor.lock().unwrap().detach();
+            return Ok(());
+        }
+
+        let device = self
+            .output_device
+            .as_ref()
+            .ok_or(AudioError::NoOutputDevice)?;
+        let max_samples =
+            (MAX_MONITOR_QUEUE_SECS * self.sample_rate as f32) as usize * self.channels as usize;
+        monitor.lock().unwrap().attach(max_samples);
+        match monitor::start_monitor_output(
+            device,
+            monitor.clone(),
+            self.monitor_input_level.clone(),
+            self.monitor_limiter.clone(),
+            self.sample_rate,
+            self.channels as usize,
+        ) {
+            Ok(stream) => {
+                self.monitor_stream = Some(stream);
+                Ok(())
+            }
+            Err(e) => {
+                monitor.lock().unwrap().detach();
+                Err(e)
+            }
+        }
+    }
+
+    pub fn is_monitoring(&self) -> bool {
+        self.monitor_stream.is_some()
+    }
+
+    // Level (0-1) of the unprocessed input mixed into the monitor alongside
+    // the chain output. It is delayed by the chain's reported latency so the
+    // two stay aligned instead of combing.
+    pub fn set_dry_monitor(&mut self, level: f32) {
+        *self.dry_monitor_level.lock().unwrap() = level.clamp(0.0, 1.0);
+    }
+
+    // Levels (0-1) of what the performer hears: their own input (chain output
+    // plus any dry bus) and the return from a WebRTC peer. Both apply at the
+    // output, so a change is heard within one device buffer.
+    pub fn set_monitor_mix(&mut self, input_level: f32, return_level: f32) {
+        *self.monitor_input_level.lock().unwrap() = input_level.clamp(0.0, 1.0);
+        *self.monitor_return_level.lock().unwrap() = return_level.clamp(0.0, 1.0);
+    }
+
+    // Extra delay (0 to MAX_MONITOR_DELAY_MS) on the monitor feed, to line
+    // it up with a hardware monitor path heard at the same time
+    pub fn set_monitor_delay(&mut self, ms: f32) {
+        *self.monitor_delay_ms.lock().unwrap() = ms.clamp(0.0, MAX_MONITOR_DELAY_MS);
+    }
+
+    // Brickwall limiter on every monitor output (the monitor and a WebRTC
+    // peer's return), not the stream. Takes effect on running outputs at once.
+    pub fn set_monitor_limiter(&mut self, enabled: bool, ceiling_db: f32) -> MonitorLimiterConfig {
+        let config = MonitorLimiterConfig {
+            enabled,
+            ceiling_db: ceiling_db.clamp(MIN_MONITOR_CEILING_DB, MAX_MONITOR_CEILING_DB),
+        };
+        *self.monitor_limiter.lock().unwrap() = config;
+        config
+    }
+
+    pub fn get_monitor_limiter(&self) -> MonitorLimiterConfig {
+        *self.monitor_limiter.lock().unwrap()
+    }
+
+    // Takes effect on the next block; a running stream starts over from
+    // silence, so speaking fires signal-detected again
+    pub fn configure_silence_detection(
+        &mut self,
+        threshold_db: f32,
+        hold_ms: f32,
+        enabled: bool,
+    ) -> SilenceDetectionConfig {
+        let config = SilenceDetectionConfig {
+            enabled,
+            threshold_db: threshold_db.clamp(MIN_SILENCE_THRESHOLD_DB, MAX_SILENCE_THRESHOLD_DB),
+            hold_ms: hold_ms.clamp(0.0, MAX_SILENCE_HOLD_MS),
+        };
+        *self.silence_detection.lock().unwrap() = config;
+        config
+    }
+
+    pub fn silence_detection(&self) -> SilenceDetectionConfig {
+        *self.silence_detection.lock().unwrap()
+    }
+
+    // Measured latencies of the software monitor path and the delay that
+    // would line it up with a hardware path of `hardware_path_ms`
+    pub fn monitoring_alignment(&self, hardware_path_ms: f32) -> MonitoringAlignment {
+        let latency = self.get_stream_latency();
+        let output_latency_ms = self
+            .sessions
+            .selected()
+            .monitor
+            .lock()
+            .unwrap()
+            .output_latency_ms();
+        let monitor_delay_ms = *self.monitor_delay_ms.lock().unwrap();
+
+        // Device input, block regrouping, chain, monitor queue and output
+        // buffer, and the device output, before any delay we add
+        let undelayed_ms = latency.monitoring_latency_ms.map(|monitoring_ms| {
+            latency.device_latency_ms.unwrap_or(0.0)
+                + latency.buffer_latency_ms.unwrap_or(0.0)
+                + latency.effect_latency_ms
+                + monitoring_ms
+                + output_latency_ms.unwrap_or(0.0)
+        });
+
+        MonitoringAlignment {
+            input_latency_ms: latency.device_latency_ms,
+            output_latency_ms,
+            software_path_ms: undelayed_ms.map(|ms| ms + monitor_delay_ms),
+            monitor_delay_ms,
+            recommended_delay_ms: undelayed_ms
+                .map(|ms| (hardware_path_ms - ms).clamp(0.0, MAX_MONITOR_DELAY_MS)),
+        }
+    }
+
+    // False on a machine with audio disabled or no hardware (headless CI);
+    // the engine still runs, but device capture and output fail
+    pub fn has_input_device(&self) -> bool {
+        self.input_device.is_some()
+    }
+
+    pub fn has_output_device(&self) -> bool {
+        self.output_device.is_some()
+    }
+
+    // Re-reads the host's devices after a route change, e.g. a headset
+    // connecting. An output picked with set_output_device is kept while it is
+    // still there and only replaced by the default once it has gone. The
+    // input has no picker and always follows the default.
+    pub fn refresh_default_devices(&mut self) {
+        let Ok(host) = cpal::host_from_id(self.host_id) else {
+            return;
+        };
+        self.input_device = host.default_input_device();
+
+        if let Some(name) = self.picked_output.clone() {
+            match find_output_device(&host, &name) {
+                Ok(device) => {
+                    self.output_device = Some(device);
+                    return;
+                }
+                Err(_) => {
+                    log::warn!("Output device {} has gone, using the default", name);
+                    self.picked_output = None;
+                }
+            }
+        }
+        self.output_device = host.default_output_device();
+    }
+
+    // Picks the output device by name; the test tone and monitoring use it.
+    // A running monitor and WebRTC playout move to it straight away while
+    // capture carries on; if it can't be opened they stay where they were.
+    pub fn set_output_device(&mut self, name: &str) -> Result<(), AudioError> {
+        let host =
+            cpal::host_from_id(self.host_id).map_err(|e| AudioError::DeviceError(e.to_string()))?;
+        let device = find_output_device(&host, name)?;
+
+        if let Err(e) = self.move_outputs_to(&device) {
+            let _ = self.events_tx.send(AudioEvent::OutputDeviceFailed {
+                device: name.to_string(),
+                reason: e.to_string(),
+            });
+            return Err(e);
+        }
+        self.output_device = Some(device);
+        self.picked_output = Some(name.to_string());
+        let _ = self.events_tx.send(AudioEvent::OutputDeviceChanged {
+            device: name.to_string(),
+        });
+        Ok(())
+    }
+
+    // Rebuilds the monitor and WebRTC playout streams on `device`. All the new
+    // streams are built before any old one is dropped, so a failure leaves
+    // every output as it was. The buffers feeding them are kept, so nothing
+    // queued is lost.
+    fn move_outputs_to(&mut self, device: &cpal::Device) -> Result<(), AudioError> {
+        let monitor_stream = match self.monitor_stream {
+            Some(_) => Some(monitor::start_monitor_output(
+                device,
+                self.sessions.selected().monitor.clone(),
+                self.monitor_input_level.clone(),
+                self.monitor_limiter.clone(),
+                self.sample_rate,
+                self.channels as usize,
+            )?),
+            None => None,
+        };
+
+        let mut playouts = Vec::new();
+        for (id, session) in self.sessions.iter() {
+            if let (Some(peer), Some(_)) = (&session.webrtc_peer, &session.webrtc_playout) {
+                let stream = monitor::start_monitor_output(
+                    device,
+                    peer.playout(),
+                    self.monitor_return_level.clone(),
+                    self.monitor_limiter.clone(),
+                    self.sample_rate,
+                    self.channels as usize,
+                )?;
+                playouts.push((id.clone(), stream));
+            }
+        }
+
+        if monitor_stream.is_some() {
+            self.monitor_stream = monitor_stream;
+        }
+        for (id, stream) in playouts {
+            self.sessions.get_mut(&id)?.webrtc_playout = Some(stream);
+        }
+        Ok(())
+    }
+
+    // Plays a sine burst on the output device so the user can check their
+    // headphones or monitors without streaming. Returns once the tone has
+    // started; the temporary stream is dropped after `duration_ms`.
+    pub async fn play_test_tone_output(
+        &self,
+        freq: f32,
+        level: f32,
+        duration_ms: u64,
+    ) -> Result<(), AudioError> {
+        let device = self
+            .output_device
+            .clone()
+            .ok_or(AudioError::NoOutputDevice)?;
+        let duration = Duration::from_millis(duration_ms.min(MAX_OUTPUT_TEST_TONE_MS));
+
+        // cpal streams can't move between threads on every platform, so the
+        // stream lives entirely on its own thread
+        let (started_tx, started_rx) = oneshot::channel();
+        std::thread::spawn(move || match start_output_tone(&device, freq, level) {
+            Ok(stream) => {
+                let _ = started_tx.send(Ok(()));
+                std::thread::sleep(duration);
+                drop(stream);
+            }
+            Err(e) => {
+                let _ = started_tx.send(Err(e));
+            }
+        });
+
+        started_rx.await.unwrap_or_else(|_| {
+            Err(AudioError::DeviceError(
+                "Test tone thread exited".to_string(),
+            ))
+        })
+    }
+
+    pub fn host_id(&self) -> cpal::HostId {
+        self.host_id
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // clamp passes NaN through, and one NaN gain would poison every sample
+    // after it, so non-finite values are refused outright
+    pub fn set_input_gain(&mut self, gain_db: f32) -> Result<(), AudioError> {
+        if !gain_db.is_finite() {
+            return Err(AudioError::NonFiniteValue("Input gain", gain_db));
+        }
+        *self.input_gain_db.lock().unwrap() = gain_db.clamp(MIN_INPUT_GAIN_DB, MAX_INPUT_GAIN_DB);
+        Ok(())
+    }
+
+    // Applied to every stream after the effects chain (bypassed or not) and
+    // before the monitor, meters and encoder, ramped so a change doesn't click
+    pub fn set_output_gain(&mut self, gain_db: f32) -> Result<(), AudioError> {
+        if !gain_db.is_finite() {
+            return Err(AudioError::NonFiniteValue("Output gain", gain_db));
+        }
+        *self.output_gain_db.lock().unwrap() =
+            gain_db.clamp(MIN_OUTPUT_GAIN_DB, MAX_OUTPUT_GAIN_DB);
+        Ok(())
+    }
+
+    pub fn get_output_gain(&self) -> f32 {
+        *self.output_gain_db.lock().unwrap()
+    }
+
+    // On by default; there's rarely a reason to pass DC to the encoder
+    pub fn set_dc_block(&mut self, enabled: bool) {
+        *self.dc_block_enabled.lock().unwrap() = enabled;
+    }
+
+    // Skips every stream's whole effects chain, for telling a chain problem
+    // from a device one. The effects keep their state and pick up where they
+    // left off when the bypass is lifted.
+    pub fn set_chain_bypass(&mut self, bypassed: bool) {
+        *self.chain_bypass.lock().unwrap() = bypassed;
+    }
+
+    pub fn is_chain_bypassed(&self) -> bool {
+        *self.chain_bypass.lock().unwrap()
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        *self.is_muted.lock().unwrap() = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        *self.is_muted.lock().unwrap()
+    }
+
+    // Paused streams keep the device open but stop processing and sending
+    pub fn set_paused(&mut self, paused: bool) {
+        *self.sessions.selected().is_paused.lock().unwrap() = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.sessions.selected().is_paused.lock().unwrap()
+    }
+
+    pub fn set_ptt_enabled(&mut self, enabled: bool) {
+        *self.ptt_enabled.lock().unwrap() = enabled;
+    }
+
+    pub fn set_ptt_active(&mut self, active: bool) {
+        *self.ptt_active.lock().unwrap() = active;
+    }
+
+    pub fn is_ptt_active(&self) -> bool {
+        *self.ptt_active.lock().unwrap()
+    }
+
+    // Whether audio should currently pass the push-to-talk stage
+    fn ptt_target_gain(&self) -> bool {
+        !*self.ptt_enabled.lock().unwrap() || *self.ptt_active.lock().unwrap()
+    }
+
+    pub fn get_input_gain(&self) -> f32 {
+        *self.input_gain_db.lock().unwrap()
+    }
+
+    pub fn get_stream_stats(&self) -> StreamStats {
+        self.sessions
+            .selected()
+            .stream_stats
+            .lock()
+            .unwrap()
+            .snapshot()
+    }
+
+    pub fn set_sink_format(&mut self, format: SinkFormat) {
+        *self.sessions.selected().sink_format.lock().unwrap() = format;
+    }
+
+    // Testing only: drops/reorders packets on the broadcast path
+    pub fn set_packet_loss_simulation(&mut self, config: PacketLossConfig) {
+        let config = config.clamped();
+        if config.is_active() {
+            log::warn!(
+                "Packet loss simulation enabled ({}% loss, {}% reorder) - not for production use",
+                config.loss_percent,
+                config.reorder_percent
+            );
+        }
+        *self.sessions.selected().packet_loss.lock().unwrap() = config;
+    }
+
+    // First half of connecting the selected stream to Icecast: drops its
+    // current sink (freeing the mount) and subscribes to its frames. The
+    // caller connects the returned PendingIcecast after releasing the engine
+    // lock and hands the sink to attach_icecast.
+    pub fn prepare_icecast(&mut self, config: IcecastConfig) -> Result<PendingIcecast, AudioError> {
+        self.disconnect_icecast();
+
+        let session = self.sessions.selected();
+        // The Ogg muxer only knows Opus
+        let mapping = session.encoder.lock().unwrap().opus_mapping().cloned();
+        let info = OpusStreamInfo {
+            mapping: mapping.ok_or(AudioError::OpusOnly("Icecast", session.codec()))?,
+            input_sample_rate: self.sample_rate,
+        };
+        Ok(PendingIcecast {
+            stream: self.sessions.selected_id().clone(),
+            config,
+            info,
+            frames: StreamReceiver::new(
+                session.frames_tx.subscribe(),
+                session.stream_stats.clone(),
+                "Icecast sink",
+            ),
+            events: self.events_tx.clone(),
+        })
+    }
+
+    // Second half of connect_icecast. The stream may have been removed or
+    // switched to AAC while the connection was being made; the sink is then
+    // closed rather than attached.
+    pub fn attach_icecast(
+        &mut self,
+        stream: &StreamId,
+        sink: IcecastSink,
+    ) -> Result<(), AudioError> {
+        let session = match self.sessions.get_mut(stream) {
+            Ok(session) => session,
+            Err(e) => {
+                sink.disconnect();
+                return Err(e);
+            }
+        };
+        if session.codec() != Codec::Opus {
+            sink.disconnect();
+            return Err(AudioError::OpusOnly("Icecast", session.codec()));
+        }
+        session.disconnect_icecast();
+        session.icecast_sink = Some(sink);
+        Ok(())
+    }
+
+    pub fn disconnect_icecast(&mut self) {
+        self.sessions.selected_mut().disconnect_icecast();
+    }
+
+    // First half of adding an encoder output: builds an encoder for the
+    // selected stream at `bitrate` and its channel. Like prepare_icecast, the
+    // returned connection is made without the engine lock and the result goes
+    // to attach_encoder_output.
+    pub fn prepare_encoder_output(
+        &mut self,
+        bitrate: u32,
+        sink: IcecastConfig,
+    ) -> Result<(PendingIcecast, EncoderOutput), AudioError> {
+        let mut encoder = OpusEncoder::new(self.sample_rate, self.channels, Application::Audio)?;
+        encoder.set_bitrate(opus::Bitrate::Bits(bitrate as i32))?;
+        encoder.set_complexity(self.complexity)?;
+        // Joins in on whatever the classifier last decided for the stream
+        encoder.set_signal(content_signal(self.sessions.selected().content()))?;
+
+        let info = OpusStreamInfo {
+            mapping: encoder.mapping().clone(),
+            input_sample_rate: self.sample_rate,
+        };
+        let (frames_tx, _) = broadcast::channel(self.broadcast_capacity);
+        let stream = self.sessions.selected_id().clone();
+        let session = self.sessions.selected_mut();
+        let pending = PendingIcecast {
+            stream,
+            config: sink,
+            info,
+            frames: StreamReceiver::new(
+                frames_tx.subscribe(),
+                session.stream_stats.clone(),
+                "Encoder output",
+            ),
+            events: self.events_tx.clone(),
+        };
+        // Taken now, so an output that fails to connect just leaves a gap
+        let id = session.next_output_id;
+        session.next_output_id += 1;
+        Ok((pending, EncoderOutput::new(id, bitrate, encoder, frames_tx)))
+    }
+
+    // Second half of add_encoder_output: starts encoding into `output` once
+    // its sink is connected. Returns the output's id.
+    pub fn attach_encoder_output(
+        &mut self,
+        stream: &StreamId,
+        output: EncoderOutput,
+        sink: IcecastSink,
+    ) -> Result<u32, AudioError> {
+        let session = match self.sessions.get_mut(stream) {
+            Ok(session) => session,
+            Err(e) => {
+                sink.disconnect();
+                return Err(e);
+            }
+        };
+        let id = output.id();
+        session.encoder_outputs.lock().unwrap().push(output);
+        session.output_sinks.insert(id, sink);
+        Ok(id)
+    }
+
+    pub fn remove_encoder_output(&mut self, id: u32) -> Result<(), AudioError> {
+        let session = self.sessions.selected_mut();
+        let sink = session
+            .output_sinks
+            .remove(&id)
+            .ok_or(AudioError::NoSuchOutput(id))?;
+        session
+            .encoder_outputs
+            .lock()
+            .unwrap()
+            .retain(|output| output.id() != id);
+        sink.disconnect();
+        Ok(())
+    }
+
+    pub fn encoder_outputs(&self) -> Vec<EncoderOutputSummary> {
+        let session = self.sessions.selected();
+        let outputs = session.encoder_outputs.lock().unwrap();
+        outputs.iter().map(EncoderOutput::summary).collect()
+    }
+
+    // Records the selected stream to a FLAC file at the configured bit depth,
+    // taking the same audio that is encoded (after effects, mute and PTT)
+    pub fn start_flac_recording(&mut self, path: &Path) -> Result<(), AudioError> {
+        let format =
+            self.recording_format(RecordingContainer::Flac, self.bit_depth, DitherMode::None);
+        self.start_recording(path, format, None)
+    }
+
+    // As start_flac_recording, but the file is scaled when the recording stops
+    // so its integrated loudness comes out at `target_lufs`
+    pub fn start_recording_normalized(
+        &mut self,
+        path: &Path,
+        target_lufs: f32,
+    ) -> Result<(), AudioError> {
+        let format =
+            self.recording_format(RecordingContainer::Flac, self.bit_depth, DitherMode::None);
+        self.start_recording(path, format, Some(target_lufs))
+    }
+
+    // Records the same audio to an uncompressed WAV file at `bit_depth`
+    // (8-24), with `dither` applied below 24 bits
+    pub fn start_wav_recording(
+        &mut self,
+        path: &Path,
+        bit_depth: u16,
+        dither: DitherMode,
+    ) -> Result<(), AudioError> {
+        let format = self.recording_format(RecordingContainer::Wav, bit_depth, dither);
+        self.start_recording(path, format, None)
+    }
+
+    fn recording_format(
+        &self,
+        container: RecordingContainer,
+        bit_depth: u16,
+        dither: DitherMode,
+    ) -> RecordingFormat {
+        RecordingFormat {
+            container,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            bit_depth,
+            dither,
+        }
+    }
+
+    fn start_recording(
+        &mut self,
+        path: &Path,
+        format: RecordingFormat,
+        target_lufs: Option<f32>,
+    ) -> Result<(), AudioError> {
+        let session = self.sessions.selected();
+        let mut recorder = session.recorder.lock().unwrap();
+        if recorder.is_some() {
+            return Err(RecorderError::AlreadyRecording.into());
+        }
+        *recorder = Some(match target_lufs {
+            Some(target_lufs) => Recorder::start_normalized(path, format, target_lufs)?,
+            None => Recorder::start(path, format)?,
+        });
+        Ok(())
+    }
+
+    // Grabs the next `duration_ms` (up to 10 s) of the selected stream's
+    // processed audio, the same audio that is recorded and encoded. The
+    // receiver resolves once it is full; a second capture cancels the first.
+    pub fn capture_sample(
+        &self,
+        duration_ms: u32,
+    ) -> Result<oneshot::Receiver<CapturedSample>, AudioError> {
+        let session = self.sessions.selected();
+        if session.status() != StreamStatus::Live {
+            return Err(AudioError::NotLive);
+        }
+        let (capture, rx) = SampleCapture::new(duration_ms, self.sample_rate, self.channels);
+        *session.sample_capture.lock().unwrap() = Some(capture);
+        Ok(rx)
+    }
+
+    // The selected stream's last finished capture reduced to `points` (1 to
+    // MAX_WAVEFORM_POINTS) min/max pairs per channel
+    pub fn waveform_preview(&self, points: usize) -> Result<WaveformPreview, AudioError> {
+        let last = self.sessions.selected().last_sample_capture.lock().unwrap();
+        let capture = last.as_ref().ok_or(AudioError::NoSampleCapture)?;
+        Ok(WaveformPreview::from_capture(
+            capture,
+            points.clamp(1, MAX_WAVEFORM_POINTS),
+        ))
+    }
+
+    // Finishes the file, FLAC or WAV, once everything queued has been written
+    pub fn stop_recording(&mut self) -> Result<RecordingSummary, AudioError> {
+        let recorder = self.sessions.selected().recorder.lock().unwrap().take();
+        Ok(recorder.ok_or(RecorderError::NotRecording)?.stop()?)
+    }
+
+    // Starts a WebRTC call carrying the selected stream, replacing any
+    // earlier one, and returns the SDP offer for the remote peer. Audio the
+    // peer sends back plays on the output device.
+    pub async fn create_webrtc_offer(
+        &mut self,
+        ice_servers: Vec<String>,
+    ) -> Result<String, AudioError> {
+        self.close_webrtc().await;
+
+        let session = self.sessions.selected();
+        if session.codec() != Codec::Opus {
+            return Err(AudioError::OpusOnly("WebRTC", session.codec()));
+        }
+        let format = PeerAudioFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        };
+        let frames = StreamReceiver::new(
+            session.frames_tx.subscribe(),
+            session.stream_stats.clone(),
+            "WebRTC peer",
+        );
+        let (peer, offer) =
+            WebRtcPeer::offer(ice_servers, format, frames, self.events_tx.clone()).await?;
+
+        // Without an output device the call is send-only
+        let playout = match &self.output_device {
+            Some(device) => {
+                match monitor::start_monitor_output(
+                    device,
+                    peer.playout(),
+                    self.monitor_return_level.clone(),
+                    self.monitor_limiter.clone(),
+                    self.sample_rate,
+                    self.channels as usize,
+                ) {
+                    Ok(stream) => Some(stream),
+                    Err(e) => {
+                        log::warn!("Cannot play remote WebRTC audio: {}", e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let session = self.sessions.selected_mut();
+        session.webrtc_peer = Some(peer);
+        session.webrtc_playout = playout;
+        Ok(offer)
+    }
+
+    pub async fn accept_webrtc_answer(&mut self, sdp: String) -> Result<(), AudioError> {
+        let peer = self
+            .sessions
+            .selected()
+            .webrtc_peer
+            .as_ref()
+            .ok_or(WebRtcError::NoPeer)?;
+        Ok(peer.accept_answer(sdp).await?)
+    }
+
+    pub async fn add_webrtc_ice_candidate(
+        &mut self,
+        candidate: IceCandidate,
+    ) -> Result<(), AudioError> {
+        let peer = self
+            .sessions
+            .selected()
+            .webrtc_peer
+            .as_ref()
+            .ok_or(WebRtcError::NoPeer)?;
+        Ok(peer.add_ice_candidate(candidate).await?)
+    }
+
+    pub async fn close_webrtc(&mut self) {
+        self.sessions.selected_mut().close_webrtc().await;
+    }
+
+    pub fn subscribe_to_events(&self) -> broadcast::Receiver<AudioEvent> {
+        self.events_tx.subscribe()
+    }
+
+    // Packets skipped because the subscriber lagged show up in the stream stats
+    pub fn subscribe_to_audio(&self) -> StreamReceiver<Vec<u8>> {
+        let session = self.sessions.selected();
+        StreamReceiver::new(
+            session.broadcast_tx.subscribe(),
+            session.stream_stats.clone(),
+            "Audio subscriber",
+        )
+    }
+}
+
+// EQBand helper struct for equalizer
+#[derive(Debug, Clone)]
+pub struct EQBand {
+    pub frequency: f32,
+    pub q: f32,
+    pub gain: f32,
+}
+
+impl EQBand {
+    pub fn new(frequency: f32, q: f32, gain: f32) -> Self {
+        Self { frequency, q, gain }
+    }
+}
+
+// Held peak of one EQ band's output. `clipping` stays set for a couple of
+// seconds after the band last went over 0 dBFS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandLevel {
+    pub frequency: f32,
+    pub peak_db: f32,
+    pub clipping: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::effects::EffectParams;
+    use std::path::PathBuf;
+
+    fn engine() -> AudioEngine {
+        AudioEngine::new(AudioConfig::default()).unwrap()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("voicecast-{}-{}", std::process::id(), name))
+    }
+
+    // Sample rate, channels, bits per sample and total frames from a FLAC file's
+    // STREAMINFO block, which libFLAC only fills in once the stream is finished
+    fn flac_stream_info(path: &Path) -> (u32, u32, u32, u64) {
+        let bytes = std::fs::read(path).unwrap();
+        assert_eq!(&bytes[..4], b"fLaC");
+        assert_eq!(bytes[4] & 0x7f, 0, "first metadata block isn't STREAMINFO");
+        let packed = u64::from_be_bytes(bytes[18..26].try_into().unwrap());
+        (
+            (packed >> 44) as u32,
+            ((packed >> 41) & 0x7) as u32 + 1,
+            ((packed >> 36) & 0x1f) as u32 + 1,
+            packed & 0xf_ffff_ffff,
+        )
+    }
+
+    #[tokio::test]
+    async fn shutdown_finishes_a_running_recording() {
+        let mut engine = engine();
+        let path = temp_path("shutdown.flac");
+        engine.start_flac_recording(&path).unwrap();
+
+        let mut pipeline =
+            CapturePipeline::new(&engine, engine.sessions.selected(), engine.sample_rate, 2);
+        for _ in 0..50 {
+            pipeline.process(&[0.25; 1_920]);
+        }
+        engine.shutdown().await;
+
+        assert_eq!(flac_stream_info(&path), (48_000, 2, 24, 48_000));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recalling_a_snapshot_restores_its_parameters() {
+        let mut engine = engine();
+        let registry = EffectRegistry::with_builtin_effects();
+        for key in ["compressor", "eq"] {
+            engine.add_effect(key, registry.create(key, EffectParams::new()).unwrap());
+        }
+        engine.set_effect_parameter(0, "threshold", -32.0).unwrap();
+        engine.snapshot_chain_to(AB_SLOT_A);
+        let saved = engine.chain_preset();
+
+        engine.set_effect_parameter(0, "threshold", -8.0).unwrap();
+        engine.set_effect_parameter(0, "ratio", 10.0).unwrap();
+        assert_ne!(engine.chain_preset(), saved);
+
+        engine.recall_chain_snapshot(AB_SLOT_A, &registry).unwrap();
+        assert_eq!(engine.chain_preset(), saved);
+        assert_eq!(
+            engine
+                .recall_chain_snapshot(7, &registry)
+                .unwrap_err()
+                .to_string(),
+            "No chain snapshot in slot 7"
+        );
+    }
+
+    #[test]
+    fn loading_a_chain_keeps_its_order() {
+        let mut engine = engine();
+        let registry = EffectRegistry::with_builtin_effects();
+        let order = ["noise_gate", "eq", "compressor", "saturation", "reverb"];
+        let effects = order
+            .iter()
+            .map(|key| EffectPreset {
+                effect_type: key.to_string(),
+                params: EffectParams::new(),
+                mix: 1.0,
+            })
+            .collect();
+        engine
+            .load_chain(&ChainPreset::new(effects), &registry)
+            .unwrap();
+
+        let loaded: Vec<String> = engine
+            .chain_preset()
+            .effects
+            .into_iter()
+            .map(|e| e.effect_type)
+            .collect();
+        assert_eq!(loaded, order);
+    }
+
+    #[test]
+    fn loading_a_chain_with_an_unknown_effect_changes_nothing() {
+        let mut engine = engine();
+        let registry = EffectRegistry::with_builtin_effects();
+        engine.add_effect("eq", registry.create("eq", EffectParams::new()).unwrap());
+        let before = engine.chain_preset();
+
+        let effects = ["compressor", "wah"]
+            .iter()
+            .map(|key| EffectPreset {
+                effect_type: key.to_string(),
+                params: EffectParams::new(),
+                mix: 1.0,
+            })
+            .collect();
+        assert!(engine
+            .load_chain(&ChainPreset::new(effects), &registry)
+            .is_err());
+        assert_eq!(engine.chain_preset(), before);
+    }
+
+    #[test]
+    fn higher_bitrates_measure_higher_snr() {
+        let engine = engine();
+        let results = engine
+            .measure_codec_quality(
+                TestSignal::Sweep {
+                    from: 50.0,
+                    to: 16_000.0,
+                },
+                &[16_000, 64_000, 256_000],
+            )
+            .unwrap();
+        for pair in results.windows(2) {
+            assert!(
+                pair[1].snr_db > pair[0].snr_db,
+                "{} bps measured {} dB, {} bps measured {} dB",
+                pair[0].bitrate,
+                pair[0].snr_db,
+                pair[1].bitrate,
+                pair[1].snr_db
+            );
+        }
+    }
+
+    #[test]
+    fn slider_positions_round_trip_for_every_builtin_parameter() {
+        let registry = EffectRegistry::with_builtin_effects();
+        for key in registry.keys() {
+            let effect = registry.create(&key, EffectParams::new()).unwrap();
+            for parameter in effect.get_parameters() {
+                let value = parameter.from_normalized(parameter.normalized());
+                assert!(
+                    (value - parameter.constrain(parameter.value)).abs()
+                        <= parameter.step.max(1e-4),
+                    "{}.{} came back as {} from {}",
+                    key,
+                    parameter.name,
+                    value,
+                    parameter.value
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn normalized_value_clamps_and_snaps() {
+        let parameter = EffectParameter {
+            name: "threshold".to_string(),
+            value: -20.0,
+            min: -60.0,
+            max: 0.0,
+            step: 0.5,
+            scale: ParamScale::Linear,
+        };
+        assert_eq!(parameter.normalized(), 2.0 / 3.0);
+        assert_eq!(parameter.from_normalized(0.25), -45.0);
+        assert_eq!(parameter.from_normalized(0.501), -30.0);
+        assert_eq!(parameter.from_normalized(-1.0), -60.0);
+        assert_eq!(parameter.from_normalized(2.0), 0.0);
+    }
+
+    #[test]
+    fn log_scaled_midpoint_is_the_geometric_mean() {
+        let frequency = EffectParameter {
+            name: "frequency".to_string(),
+            value: 1_000.0,
+            min: 20.0,
+            max: 20_000.0,
+            step: 0.0,
+            scale: ParamScale::Logarithmic,
+        };
+        let midpoint = frequency.from_normalized(0.5);
+        assert!(
+            (midpoint - (20.0f32 * 20_000.0).sqrt()).abs() < 0.01,
+            "midpoint {}",
+            midpoint
+        );
+        let at_midpoint = EffectParameter {
+            value: midpoint,
+            ..frequency.clone()
+        };
+        assert!((at_midpoint.normalized() - 0.5).abs() < 1e-5);
+
+        let linear = EffectParameter {
+            scale: ParamScale::Linear,
+            ..frequency
+        };
+        assert_eq!(linear.from_normalized(0.5), 10_010.0);
+    }
+
+    #[test]
+    fn builtin_crossovers_are_log_scaled() {
+        let registry = EffectRegistry::with_builtin_effects();
+        let effect = registry
+            .create("multiband_compressor", EffectParams::new())
+            .unwrap();
+        let crossover = effect
+            .get_parameters()
+            .into_iter()
+            .find(|p| p.name == "low_crossover")
+            .unwrap();
+        assert_eq!(crossover.scale, ParamScale::Logarithmic);
+        assert!(
+            (crossover.from_normalized(0.5) - (crossover.min * crossover.max).sqrt()).abs() < 1e-3
+        );
+    }
+
+    // An engine as it comes up on a machine with no audio hardware at all
+    fn engine_without_devices() -> AudioEngine {
+        let mut engine = engine();
+        engine.input_device = None;
+        engine.output_device = None;
+        engine
+    }
+
+    #[tokio::test]
+    async fn missing_devices_are_reported_not_fatal() {
+        let mut engine = engine_without_devices();
+        assert!(!engine.has_input_device());
+        assert!(!engine.has_output_device());
+
+        assert!(matches!(
+            engine.start_capture().await,
+            Err(AudioError::NoInputDevice)
+        ));
+        assert!(matches!(
+            engine.set_monitoring(true),
+            Err(AudioError::NoOutputDevice)
+        ));
+        assert!(matches!(
+            engine.play_test_tone_output(440.0, 0.1, 100).await,
+            Err(AudioError::NoOutputDevice)
+        ));
+        let report = engine.validate_stream_setup(Codec::Opus, 128_000, 48_000, 2, None);
+        assert!(!report.ok);
+        assert!(report
+            .errors
+            .contains(&AudioError::NoInputDevice.to_string()));
+
+        // Sources that don't need a device still run
+        engine.set_source(Source::TestTone {
+            freq: 440.0,
+            level: 0.1,
+        });
+        engine.start_capture().await.unwrap();
+        engine.shutdown().await;
+    }
+
+    #[test]
+    fn every_complexity_level_encodes() {
+        let mut engine = engine();
+        for complexity in 0..=MAX_ENCODER_COMPLEXITY {
+            engine.set_complexity(complexity).unwrap();
+            assert_eq!(engine.complexity(), complexity);
+            let quality = engine
+                .measure_codec_quality(TestSignal::Sine { freq: 1_000.0 }, &[64_000])
+                .unwrap();
+            assert!(
+                quality[0].snr_db > 10.0,
+                "complexity {} measured {} dB",
+                complexity,
+                quality[0].snr_db
+            );
+        }
+
+        engine.set_complexity(-3).unwrap();
+        assert_eq!(engine.complexity(), 0);
+        engine.set_complexity(42).unwrap();
+        assert_eq!(engine.complexity(), MAX_ENCODER_COMPLEXITY);
+    }
+
+    #[test]
+    fn exported_snapshot_parses_with_every_section() {
+        let mut engine = engine();
+        let registry = EffectRegistry::with_builtin_effects();
+        for key in ["noise_gate", "compressor"] {
+            engine.add_effect(key, registry.create(key, EffectParams::new()).unwrap());
+        }
+        engine.set_effect_bypass(1, true).unwrap();
+        let path = temp_path("snapshot.json");
+        engine.export_audio_snapshot(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        for section in [
+            "captured_at_ms",
+            "config",
+            "levels",
+            "spectrum",
+            "effects",
+            "latency",
+            "stats",
+        ] {
+            assert!(json.get(section).is_some(), "snapshot has no {}", section);
+        }
+        assert_eq!(json["config"]["sample_rate"], 48_000);
+
+        let snapshot: AudioSnapshot = serde_json::from_value(json).unwrap();
+        let effects: Vec<(&str, bool)> = snapshot
+            .effects
+            .iter()
+            .map(|effect| (effect.effect_type.as_str(), effect.bypassed))
+            .collect();
+        assert_eq!(effects, [("noise_gate", false), ("compressor", true)]);
+        assert!(snapshot
+            .effects
+            .iter()
+            .all(|effect| !effect.parameters.is_empty()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Waits for the selected stream's next `count` packets, failing after five
+    // seconds without one
+    async fn next_packets(audio: &mut StreamReceiver<Vec<u8>>, count: usize) {
+        for _ in 0..count {
+            tokio::time::timeout(Duration::from_secs(5), audio.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs an output device"]
+    async fn switching_output_mid_monitor_keeps_capture_running() {
+        let mut engine = engine();
+        let output = engine
+            .output_device
+            .as_ref()
+            .and_then(|device| device.name().ok())
+            .expect("no output device");
+        engine.set_source(Source::TestTone {
+            freq: 440.0,
+            level: 0.1,
+        });
+        engine.start_capture().await.unwrap();
+        engine.set_monitoring(true).unwrap();
+        let mut audio = engine.subscribe_to_audio();
+        let mut events = engine.subscribe_to_events();
+        next_packets(&mut audio, 5).await;
+
+        engine.set_output_device(&output).unwrap();
+        let changed = std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+            AudioEvent::OutputDeviceChanged { device } => Some(device),
+            _ => None,
+        });
+        assert_eq!(changed, Some(output));
+        assert!(engine.is_monitoring());
+        next_packets(&mut audio, 5).await;
+        assert_eq!(engine.get_stream_stats().dropped_frames, 0);
+        engine.shutdown().await;
+    }
+
+    #[test]
+    fn healthy_engine_passes_the_selftest() {
+        let report = engine().run_selftest();
+        let stages: Vec<(&str, bool)> = report
+            .stages
+            .iter()
+            .map(|stage| (stage.name.as_str(), stage.passed))
+            .collect();
+        assert!(report.passed, "{:?}", report.stages);
+        assert_eq!(
+            stages,
+            [
+                ("source", true),
+                ("effects", true),
+                ("codec", true),
+                ("metering", true)
+            ]
+        );
+    }
+
+    #[test]
+    fn applying_a_factory_preset_builds_its_chain_in_order() {
+        let mut engine = engine();
+        let registry = EffectRegistry::with_builtin_effects();
+        engine
+            .apply_factory_preset("podcast voice", &registry)
+            .unwrap();
+        let types: Vec<String> = engine
+            .chain_preset()
+            .effects
+            .into_iter()
+            .map(|effect| effect.effect_type)
+            .collect();
+        assert_eq!(types, ["noise_gate", "eq", "compressor"]);
+
+        engine.apply_factory_preset("Telephone", &registry).unwrap();
+        let types: Vec<String> = engine
+            .chain_preset()
+            .effects
+            .into_iter()
+            .map(|effect| effect.effect_type)
+            .collect();
+        assert_eq!(types, ["eq", "saturation", "compressor"]);
+        assert!(matches!(
+            engine.apply_factory_preset("Nope", &registry),
+            Err(AudioError::NoSuchFactoryPreset(_))
+        ));
     }
 }
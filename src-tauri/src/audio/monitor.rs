@@ -0,0 +1,275 @@
+// Monitoring: the capture pipeline hands processed blocks to an output stream
+// on the monitor device so the performer can hear themselves.
+use super::resample::LinearResampler;
+use super::{convert_channels, output_config, AudioError, BrickwallLimiter};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Most audio the monitor queue holds. The capture and output devices run on
+// separate clocks, so the queue slowly fills or drains; past this the oldest
+// audio is dropped to keep the monitor from drifting late.
+pub const MAX_MONITOR_QUEUE_SECS: f32 = 0.1;
+
+// Range for the monitor limiter's ceiling
+pub const MIN_MONITOR_CEILING_DB: f32 = -30.0;
+pub const MAX_MONITOR_CEILING_DB: f32 = 0.0;
+
+// Hearing protection on the monitor outputs only; the stream never sees it.
+// Each output (the monitor, a WebRTC peer's return) is limited on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MonitorLimiterConfig {
+    pub enabled: bool,
+    pub ceiling_db: f32,
+}
+
+impl Default for MonitorLimiterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ceiling_db: -1.0,
+        }
+    }
+}
+
+// FIFO from a stream's pipeline to the monitor output. The pipeline only
+// pushes while an output is attached.
+#[derive(Debug, Default)]
+pub struct MonitorBuffer {
+    samples: VecDeque<f32>,
+    attached: bool,
+    max_samples: usize,
+    // Frames the output callback asked for last time, for latency reporting
+    callback_frames: usize,
+    // Callback to playback time the output device reported last time
+    output_latency_ms: Option<f32>,
+}
+
+impl MonitorBuffer {
+    pub fn attach(&mut self, max_samples: usize) {
+        self.samples.clear();
+        self.attached = true;
+        self.max_samples = max_samples;
+        self.output_latency_ms = None;
+    }
+
+    pub fn detach(&mut self) {
+        self.samples.clear();
+        self.attached = false;
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    pub fn push(&mut self, block: &[f32]) {
+        if !self.attached {
+            return;
+        }
+        self.samples.extend(block);
+        let excess = self.samples.len().saturating_sub(self.max_samples);
+        self.samples.drain(..excess);
+    }
+
+    // Fills `out` from the queue, padding with silence on an underrun
+    pub fn pop_into(&mut self, out: &mut [f32]) {
+        let available = self.samples.len().min(out.len());
+        for (sample, queued) in out.iter_mut().zip(self.samples.drain(..available)) {
+            *sample = queued;
+        }
+        out[available..].fill(0.0);
+    }
+
+    // Queued audio plus one output callback, in frames of `channels` samples
+    pub fn latency_frames(&self, channels: usize) -> usize {
+        self.samples.len() / channels.max(1) + self.callback_frames
+    }
+
+    // None until the output has run, or when the host doesn't report it
+    pub fn output_latency_ms(&self) -> Option<f32> {
+        self.output_latency_ms
+    }
+}
+
+// The monitor device at the engine rate when it can run there, so nothing
+// needs converting; otherwise its usual output config, with the feed
+// resampled to match
+fn monitor_config(
+    device: &cpal::Device,
+    sample_rate: u32,
+) -> Result<cpal::StreamConfig, AudioError> {
+    let native = device
+        .supported_output_configs()
+        .ok()
+        .and_then(|mut configs| {
+            configs.find(|c| {
+                c.sample_format() == cpal::SampleFormat::F32
+                    && c.min_sample_rate().0 <= sample_rate
+                    && c.max_sample_rate().0 >= sample_rate
+            })
+        });
+    match native {
+        Some(range) => Ok(range
+            .with_sample_rate(cpal::SampleRate(sample_rate))
+            .config()),
+        None => output_config(device),
+    }
+}
+
+// Builds and starts an output stream playing whatever `buffer` is fed at
+// `level` (0-1, read every callback) through the limiter when it is on,
+// converted from `channels` and `sample_rate` to the device's channel count
+// and rate
+pub fn start_monitor_output(
+    device: &cpal::Device,
+    buffer: Arc<Mutex<MonitorBuffer>>,
+    level: Arc<Mutex<f32>>,
+    limiter_config: Arc<Mutex<MonitorLimiterConfig>>,
+    sample_rate: u32,
+    channels: usize,
+) -> Result<cpal::Stream, AudioError> {
+    let config = monitor_config(device, sample_rate)?;
+    let output_channels = config.channels.max(1) as usize;
+    let channels = channels.max(1);
+    let mut block = Vec::new();
+    let mut resampler = (config.sample_rate.0 != sample_rate).then(|| {
+        log::info!(
+            "Monitoring at {} Hz, resampled from {} Hz",
+            config.sample_rate.0,
+            sample_rate
+        );
+        LinearResampler::new(sample_rate, config.sample_rate.0, channels)
+    });
+    let mut limiter = BrickwallLimiter::new(0.0, config.sample_rate.0 as f32);
+
+    let stream = device.build_output_stream(
+        &config,
+        move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+            let frames = data.len() / output_channels;
+            block.resize(frames * channels, 0.0);
+            let level = level.lock().map(|level| *level).unwrap_or(1.0);
+            if let Ok(mut buffer) = buffer.lock() {
+                let timestamp = info.timestamp();
+                buffer.callback_frames = frames;
+                buffer.output_latency_ms = timestamp
+                    .playback
+                    .duration_since(&timestamp.callback)
+                    .map(|d| d.as_secs_f32() * 1000.0);
+                render(&mut buffer, resampler.as_mut(), level, &mut block);
+            } else {
+                block.fill(0.0);
+            }
+            let limiter_config = limiter_config
+                .lock()
+                .map(|config| *config)
+                .unwrap_or_default();
+            limit(&mut limiter, limiter_config, &mut block, channels);
+
+            if channels == output_channels {
+                data.copy_from_slice(&block);
+            } else {
+                data.copy_from_slice(&convert_channels(&block, channels, output_channels));
+            }
+        },
+        |err| log::error!("Monitor stream error: {}", err),
+        None,
+    )?;
+    stream.play()?;
+    Ok(stream)
+}
+
+// Fills `block` with the next queued audio at the output rate, scaled by the
+// output's mix level
+fn render(
+    buffer: &mut MonitorBuffer,
+    resampler: Option<&mut LinearResampler>,
+    level: f32,
+    block: &mut [f32],
+) {
+    match resampler {
+        Some(resampler) => resampler.process(block, |input| buffer.pop_into(input)),
+        None => buffer.pop_into(block),
+    }
+    if level != 1.0 {
+        block.iter_mut().for_each(|s| *s *= level);
+    }
+}
+
+// Holds a rendered block under the ceiling while the limiter is on; off, the
+// block passes untouched and the limiter starts fresh when turned back on
+fn limit(
+    limiter: &mut BrickwallLimiter,
+    config: MonitorLimiterConfig,
+    block: &mut [f32],
+    channels: usize,
+) {
+    if config.enabled {
+        limiter.set_ceiling_db(config.ceiling_db);
+        limiter.process(block, channels);
+    } else {
+        limiter.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::db_to_linear;
+
+    // A buffer holding `samples`, as the pipeline or a WebRTC peer leaves it
+    fn queued(samples: &[f32]) -> MonitorBuffer {
+        let mut buffer = MonitorBuffer::default();
+        buffer.attach(samples.len());
+        buffer.push(samples);
+        buffer
+    }
+
+    // What the performer hears: the input and return outputs rendered at their
+    // levels and summed, as the device mixes the two streams
+    fn heard(input: &[f32], input_level: f32, peer: &[f32], return_level: f32) -> Vec<f32> {
+        let mut from_input = vec![0.0; input.len()];
+        render(&mut queued(input), None, input_level, &mut from_input);
+        let mut from_peer = vec![0.0; peer.len()];
+        render(&mut queued(peer), None, return_level, &mut from_peer);
+        from_input
+            .iter()
+            .zip(&from_peer)
+            .map(|(a, b)| a + b)
+            .collect()
+    }
+
+    #[test]
+    fn return_level_zero_leaves_only_the_input() {
+        let input: Vec<f32> = (0..960).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        let peer: Vec<f32> = (0..960).map(|i| (i as f32 * 0.011).cos() * 0.3).collect();
+
+        assert_eq!(heard(&input, 1.0, &peer, 0.0), input);
+        assert_eq!(heard(&input, 0.0, &peer, 1.0), peer);
+        let blend = heard(&input, 0.5, &peer, 0.5);
+        for ((mixed, a), b) in blend.iter().zip(&input).zip(&peer) {
+            assert!((mixed - (a + b) * 0.5).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn monitor_limiter_holds_a_hot_signal_under_the_ceiling() {
+        let hot: Vec<f32> = (0..9_600).map(|i| (i as f32 * 0.05).sin() * 1.5).collect();
+        let mut block = vec![0.0; hot.len()];
+        render(&mut queued(&hot), None, 1.0, &mut block);
+        let mut limiter = BrickwallLimiter::new(0.0, 48_000.0);
+
+        let off = MonitorLimiterConfig::default();
+        let mut unlimited = block.clone();
+        limit(&mut limiter, off, &mut unlimited, 2);
+        assert_eq!(unlimited, block);
+
+        let ceiling = MonitorLimiterConfig {
+            enabled: true,
+            ceiling_db: -6.0,
+        };
+        limit(&mut limiter, ceiling, &mut block, 2);
+        let peak = block.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak <= db_to_linear(-6.0) + 1e-6, "peak {}", peak);
+    }
+}
@@ -0,0 +1,164 @@
+// Music bed mixed under the voice. The bus plays its own generated source
+// (a file or a tone) and is ducked by the outgoing mic signal before the two
+// are summed for encoding.
+use super::source::BlockGenerator;
+use super::{db_to_linear, Ducker};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DuckingConfig {
+    // Mic level above which the music is ducked
+    pub threshold_db: f32,
+    // How far the music drops while the mic is active
+    pub depth_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for DuckingConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: -40.0,
+            depth_db: 12.0,
+            attack_ms: 20.0,
+            release_ms: 500.0,
+        }
+    }
+}
+
+pub struct MusicBus {
+    generator: BlockGenerator,
+    level: f32,
+    ducking: DuckingConfig,
+    ducker: Ducker,
+    sample_rate: f32,
+    block: Vec<f32>,
+    finished: bool,
+}
+
+impl MusicBus {
+    pub fn new(
+        generator: BlockGenerator,
+        level_db: f32,
+        ducking: DuckingConfig,
+        sample_rate: f32,
+    ) -> Self {
+        Self {
+            generator,
+            level: db_to_linear(level_db),
+            ducking,
+            ducker: Self::ducker_for(&ducking, sample_rate),
+            sample_rate,
+            block: Vec::new(),
+            finished: false,
+        }
+    }
+
+    fn ducker_for(config: &DuckingConfig, sample_rate: f32) -> Ducker {
+        Ducker::new(
+            config.threshold_db,
+            config.depth_db,
+            config.attack_ms,
+            config.release_ms,
+            sample_rate,
+        )
+    }
+
+    pub fn set_level_db(&mut self, level_db: f32) {
+        self.level = db_to_linear(level_db);
+    }
+
+    pub fn ducking(&self) -> DuckingConfig {
+        self.ducking
+    }
+
+    pub fn set_ducking(&mut self, config: DuckingConfig) {
+        self.ducking = config;
+        self.ducker.set_params(
+            config.threshold_db,
+            config.depth_db,
+            config.attack_ms,
+            config.release_ms,
+            self.sample_rate,
+        );
+    }
+
+    // Gain the ducker currently applies, 1.0 when the mic is quiet
+    pub fn duck_gain(&self) -> f32 {
+        self.ducker.gain()
+    }
+
+    // Adds the next block of music, ducked by what is already in `mix`, to
+    // `mix` in place. A non-looping file goes silent once it ends.
+    pub fn mix_into(&mut self, mix: &mut [f32], channels: usize) {
+        if self.finished {
+            return;
+        }
+
+        self.block.resize(mix.len(), 0.0);
+        if !(self.generator)(&mut self.block) {
+            self.finished = true;
+            return;
+        }
+
+        let level = self.level;
+        self.block.iter_mut().for_each(|s| *s *= level);
+        self.ducker.process(&mut self.block, mix, channels);
+        for (out, music) in mix.iter_mut().zip(&self.block) {
+            *out += music;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::linear_to_db;
+
+    // A bus playing constant full-scale music at unity level
+    fn bus() -> MusicBus {
+        let generator: BlockGenerator = Box::new(|block: &mut [f32]| {
+            block.fill(1.0);
+            true
+        });
+        MusicBus::new(generator, 0.0, DuckingConfig::default(), 48_000.0)
+    }
+
+    // Runs one second of `mic` through the bus in 20 ms stereo blocks and returns
+    // the music level in the last block
+    fn run(bus: &mut MusicBus, mic: impl Fn(usize) -> f32) -> f32 {
+        let mut music = 0.0;
+        for block in 0..50 {
+            let voice: Vec<f32> = (0..960).flat_map(|i| [mic(block * 960 + i); 2]).collect();
+            let mut mix = voice.clone();
+            bus.mix_into(&mut mix, 2);
+            music = mix[mix.len() - 1] - voice[voice.len() - 1];
+        }
+        music
+    }
+
+    #[test]
+    fn mic_activity_ducks_the_music_and_silence_restores_it() {
+        let mut bus = bus();
+        assert!((run(&mut bus, |_| 0.0) - 1.0).abs() < 1e-6);
+
+        // Speech well above the -40 dB threshold pulls the music down by the full 12 dB
+        let ducked = run(&mut bus, |i| 0.5 * (i as f32 * 0.05).sin());
+        assert!(
+            (linear_to_db(ducked) + 12.0).abs() < 0.5,
+            "music at {} dB",
+            linear_to_db(ducked)
+        );
+        assert!((bus.duck_gain() - ducked).abs() < 1e-6);
+
+        // A 500 ms release has fully recovered after a few seconds of silence
+        for _ in 0..3 {
+            run(&mut bus, |_| 0.0);
+        }
+        assert!(
+            bus.duck_gain() > 0.99,
+            "music still ducked to {}",
+            bus.duck_gain()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+// Encoder delay signalled in OpusHead; libopus' default lookahead at 48 kHz
+pub const OPUS_PRE_SKIP: u16 = 312;
+
+const OGG_VERSION: u8 = 0;
+const HEADER_BOS: u8 = 0x02;
+const HEADER_EOS: u8 = 0x04;
+const MAX_SEGMENTS: usize = 255;
+// Flush a page once it holds about this much audio to keep streaming latency low
+const MAX_PAGE_DURATION_48K: u64 = 48000 / 5;
+
+const VENDOR: &str = concat!("VoiceCast ", env!("CARGO_PKG_VERSION"));
+
+// How decoded channels come out of the Opus streams, as signalled in OpusHead
+// (RFC 7845 section 5.1.1). Family 0 is a single mono or stereo stream;
+// family 1 splits up to 8 channels over several streams in Vorbis order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelMapping {
+    pub family: u8,
+    pub streams: u8,
+    pub coupled_streams: u8,
+    // Output channel -> decoded stream channel; its length is the channel count
+    pub mapping: Vec<u8>,
+}
+
+impl ChannelMapping {
+    // Family 0: one stream, coupled when stereo
+    pub fn single_stream(channels: u8) -> Self {
+        Self {
+            family: 0,
+            streams: 1,
+            coupled_streams: (channels == 2) as u8,
+            mapping: (0..channels).collect(),
+        }
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.mapping.len() as u8
+    }
+
+    // Reads the layout from an OpusHead packet; None if it's malformed
+    pub fn from_opus_head(head: &[u8]) -> Option<Self> {
+        if head.len() < 19 || !head.starts_with(b"OpusHead") {
+            return None;
+        }
+        let channels = head[9];
+        match head[18] {
+            0 if channels == 1 || channels == 2 => Some(Self::single_stream(channels)),
+            0 => None,
+            family => {
+                let table = head.get(21..21 + channels as usize)?;
+                Some(Self {
+                    family,
+                    streams: head[19],
+                    coupled_streams: head[20],
+                    mapping: table.to_vec(),
+                })
+            }
+        }
+    }
+}
+
+// Ogg CRC-32: polynomial 0x04c11db7, no reflection, zero init
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// Muxes Opus packets into an Ogg Opus (RFC 7845) byte stream
+pub struct OggOpusWriter {
+    serial: u32,
+    page_sequence: u32,
+    granule: u64,
+    segments: Vec<u8>,
+    page_data: Vec<u8>,
+    page_duration: u64,
+}
+
+impl OggOpusWriter {
+    pub fn new() -> Self {
+        Self {
+            serial: rand::random(),
+            page_sequence: 0,
+            granule: 0,
+            segments: Vec::new(),
+            page_data: Vec::new(),
+            page_duration: 0,
+        }
+    }
+
+    // OpusHead and OpusTags pages that must start every logical stream
+    pub fn headers(&mut self, mapping: &ChannelMapping, input_sample_rate: u32) -> Vec<u8> {
+        let mut head = Vec::with_capacity(21 + mapping.mapping.len());
+        head.extend_from_slice(b"OpusHead");
+        head.push(1);
+        head.push(mapping.channels());
+        head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+        head.extend_from_slice(&input_sample_rate.to_le_bytes());
+        head.extend_from_slice(&0i16.to_le_bytes());
+        head.push(mapping.family);
+        // Family 0 implies the layout; every other family spells it out
+        if mapping.family != 0 {
+            head.push(mapping.streams);
+            head.push(mapping.coupled_streams);
+            head.extend_from_slice(&mapping.mapping);
+        }
+
+        let mut tags = Vec::new();
+        tags.extend_from_slice(b"OpusTags");
+        tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+        tags.extend_from_slice(VENDOR.as_bytes());
+        tags.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut out = self.page(HEADER_BOS, 0, &Self::lacing(head.len()), &head);
+        out.extend(self.page(0, 0, &Self::lacing(tags.len()), &tags));
+        out
+    }
+
+    // Queues a packet lasting `duration_48k` samples; returns any completed pages
+    pub fn write_packet(&mut self, packet: &[u8], duration_48k: u32) -> Vec<u8> {
+        let lacing = Self::lacing(packet.len());
+
+        // Close the pending page before adding to it, so finish() always has
+        // a packet to carry the end-of-stream flag
+        let out = if self.page_duration >= MAX_PAGE_DURATION_48K
+            || self.segments.len() + lacing.len() > MAX_SEGMENTS
+        {
+            self.flush()
+        } else {
+            Vec::new()
+        };
+
+        self.segments.extend_from_slice(&lacing);
+        self.page_data.extend_from_slice(packet);
+        self.granule += duration_48k as u64;
+        self.page_duration += duration_48k as u64;
+
+        out
+    }
+
+    pub fn flush(&mut self) -> Vec<u8> {
+        if self.segments.is_empty() {
+            return Vec::new();
+        }
+        let segments = std::mem::take(&mut self.segments);
+        let data = std::mem::take(&mut self.page_data);
+        self.page_duration = 0;
+        self.page(0, self.granule, &segments, &data)
+    }
+
+    // Flushes pending audio and closes the logical stream
+    pub fn finish(&mut self) -> Vec<u8> {
+        let segments = std::mem::take(&mut self.segments);
+        let data = std::mem::take(&mut self.page_data);
+        self.page_duration = 0;
+        self.page(HEADER_EOS, self.granule, &segments, &data)
+    }
+
+    // Segment table for one packet: runs of 255 terminated by a shorter value
+    fn lacing(len: usize) -> Vec<u8> {
+        let mut lacing = vec![255u8; len / 255];
+        lacing.push((len % 255) as u8);
+        lacing
+    }
+
+    fn page(&mut self, header_type: u8, granule: u64, segments: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut page = Vec::with_capacity(27 + segments.len() + data.len());
+        page.extend_from_slice(b"OggS");
+        page.push(OGG_VERSION);
+        page.push(header_type);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.page_sequence.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.push(segments.len() as u8);
+        page.extend_from_slice(segments);
+        page.extend_from_slice(data);
+
+        let crc = crc32(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+
+        self.page_sequence += 1;
+        page
+    }
+}
+
+impl Default for OggOpusWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OggError {
+    #[error("Missing Ogg capture pattern at byte {0}")]
+    InvalidCapture(usize),
+    #[error("Truncated Ogg page at byte {0}")]
+    Truncated(usize),
+}
+
+// Splits the first logical stream of an Ogg file back into packets.
+// Pages from other multiplexed streams are skipped.
+pub fn read_packets(data: &[u8]) -> Result<Vec<Vec<u8>>, OggError> {
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut serial = None;
+    let mut offset = 0;
+
+    while offset < data.len() {
+        if data.len() - offset < 27 {
+            return Err(OggError::Truncated(offset));
+        }
+        if &data[offset..offset + 4] != b"OggS" {
+            return Err(OggError::InvalidCapture(offset));
+        }
+
+        let page_serial = u32::from_le_bytes(data[offset + 14..offset + 18].try_into().unwrap());
+        let segment_count = data[offset + 26] as usize;
+        let table_start = offset + 27;
+        let body_start = table_start + segment_count;
+        if body_start > data.len() {
+            return Err(OggError::Truncated(offset));
+        }
+        let segments = &data[table_start..body_start];
+        let body_len: usize = segments.iter().map(|&s| s as usize).sum();
+        if body_start + body_len > data.len() {
+            return Err(OggError::Truncated(offset));
+        }
+
+        if *serial.get_or_insert(page_serial) == page_serial {
+            let mut pos = body_start;
+            for &segment in segments {
+                packet.extend_from_slice(&data[pos..pos + segment as usize]);
+                pos += segment as usize;
+                if segment < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+        }
+
+        offset = body_start + body_len;
+    }
+
+    Ok(packets)
+}
@@ -0,0 +1,127 @@
+// Extra encodes of a stream's post-effects audio at other bitrates, e.g. a
+// low-bitrate live feed alongside a high-quality archive. Each output has its
+// own encoder and broadcast channel, and feeds its own sink.
+use super::{AudioError, EncodedFrame, OpusEncoder};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncoderOutputSummary {
+    pub id: u32,
+    pub bitrate: u32,
+}
+
+pub struct EncoderOutput {
+    id: u32,
+    bitrate: u32,
+    encoder: OpusEncoder,
+    frames_tx: broadcast::Sender<EncodedFrame>,
+}
+
+impl EncoderOutput {
+    // `encoder` should already be set to `bitrate`
+    pub fn new(
+        id: u32,
+        bitrate: u32,
+        encoder: OpusEncoder,
+        frames_tx: broadcast::Sender<EncodedFrame>,
+    ) -> Self {
+        Self {
+            id,
+            bitrate,
+            encoder,
+            frames_tx,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn summary(&self) -> EncoderOutputSummary {
+        EncoderOutputSummary {
+            id: self.id,
+            bitrate: self.bitrate,
+        }
+    }
+
+    pub fn frames_tx(&self) -> &broadcast::Sender<EncodedFrame> {
+        &self.frames_tx
+    }
+
+    pub fn set_signal(&mut self, signal: opus::Signal) -> Result<(), AudioError> {
+        Ok(self.encoder.set_signal(signal)?)
+    }
+
+    // Encodes one block into `buffer` and publishes the packet. A send only
+    // fails while the sink is between connections, which isn't an error.
+    pub fn encode(
+        &mut self,
+        block: &[f32],
+        duration_48k: u32,
+        buffer: &mut [u8],
+    ) -> Result<(), AudioError> {
+        let size = self.encoder.encode_float(block, buffer)?;
+        let _ = self.frames_tx.send(EncodedFrame {
+            data: buffer[..size].to_vec(),
+            duration_48k,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(id: u32, bitrate: u32) -> (EncoderOutput, broadcast::Receiver<EncodedFrame>) {
+        let mut encoder = OpusEncoder::new(48_000, 2, opus::Application::Audio).unwrap();
+        encoder
+            .set_bitrate(opus::Bitrate::Bits(bitrate as i32))
+            .unwrap();
+        let (frames_tx, frames_rx) = broadcast::channel(256);
+        (
+            EncoderOutput::new(id, bitrate, encoder, frames_tx),
+            frames_rx,
+        )
+    }
+
+    // Bits per second carried by everything queued on `rx` over `secs`
+    fn measured_bitrate(rx: &mut broadcast::Receiver<EncodedFrame>, secs: f32) -> f32 {
+        let mut bytes = 0;
+        while let Ok(frame) = rx.try_recv() {
+            bytes += frame.data.len();
+        }
+        bytes as f32 * 8.0 / secs
+    }
+
+    #[test]
+    fn each_output_streams_at_its_own_bitrate() {
+        let (mut low, mut low_rx) = output(1, 32_000);
+        let (mut high, mut high_rx) = output(2, 128_000);
+
+        // Two seconds of noise, dense enough that VBR spends the whole budget
+        let mut seed = 1u32;
+        let mut buffer = vec![0u8; 4_000];
+        for _ in 0..100 {
+            let block: Vec<f32> = (0..1_920)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    ((seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0) * 0.3
+                })
+                .collect();
+            low.encode(&block, 960, &mut buffer).unwrap();
+            high.encode(&block, 960, &mut buffer).unwrap();
+        }
+
+        for (rx, target) in [(&mut low_rx, 32_000.0), (&mut high_rx, 128_000.0)] {
+            let bitrate = measured_bitrate(rx, 2.0);
+            assert!(
+                (bitrate / target - 1.0).abs() < 0.25,
+                "{} bps output ran at {} bps",
+                target,
+                bitrate
+            );
+        }
+    }
+}
@@ -0,0 +1,850 @@
+use super::session::apply_content_signal;
+use super::{
+    convert_channels, db_to_linear, linear_to_db, AudioEngine, AudioEvent, AudioLevels,
+    CapturedSample, Codec, ContentClassifier, CorrelationMeter, DcBlocker, DelayLine,
+    EffectOverrun, EffectsChain, EncodedFrame, Encoder, EncoderOutput, GainRamp, LevelMeter,
+    LinearResampler, MonitorBuffer, MusicBus, OverflowPolicy, PacketLossConfig,
+    PacketLossSimulator, ParamSmoother, Recorder, RingBuffer, RtpPacketizer, SampleCapture,
+    SignalState, SilenceDetectionConfig, SilenceDetector, SinkFormat, SpectrumAnalyzer,
+    StatsTracker, StreamId, StreamSession, TruePeakMeter, METER_FLOOR_DB, RTP_OPUS_CLOCK_RATE,
+    RTP_OPUS_PAYLOAD_TYPE,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+// Encode failures can happen every block; only tell the UI about them this often
+const ENCODE_ERROR_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Low enough to leave the voice alone, high enough to settle within a second
+const DC_BLOCK_CUTOFF_HZ: f32 = 5.0;
+
+// Mute fades over a few milliseconds so it never clicks
+const MUTE_RAMP_SECS: f32 = 0.005;
+
+// Push-to-talk opens fast so the first syllable survives, and closes a little
+// slower so releasing the key mid-word doesn't chop
+const PTT_ATTACK_SECS: f32 = 0.005;
+const PTT_RELEASE_SECS: f32 = 0.04;
+
+// Everything that happens to a block of captured audio: trim, effects, mute,
+// metering, encoding and broadcast. Owned by whichever source drives it (the
+// cpal callback or an internal generator thread); per-stream state lives here,
+// user-adjustable state is shared with the engine and the stream's session.
+pub struct CapturePipeline {
+    // The stream this pipeline feeds, for events
+    stream: StreamId,
+    sample_rate: f32,
+    input_channels: usize,
+    channels: usize,
+    encoder: Arc<Mutex<Box<dyn Encoder>>>,
+    tx: broadcast::Sender<Vec<u8>>,
+    frames_tx: broadcast::Sender<EncodedFrame>,
+    encoder_outputs: Arc<Mutex<Vec<EncoderOutput>>>,
+    broadcast_capacity: usize,
+    overflow_policy: OverflowPolicy,
+    events_tx: broadcast::Sender<AudioEvent>,
+    sink_format: Arc<Mutex<SinkFormat>>,
+    packet_loss: Arc<Mutex<PacketLossConfig>>,
+    stream_stats: Arc<Mutex<StatsTracker>>,
+    effects_chain: Arc<Mutex<EffectsChain>>,
+    input_gain_db: Arc<Mutex<f32>>,
+    output_gain_db: Arc<Mutex<f32>>,
+    dc_block_enabled: Arc<Mutex<bool>>,
+    chain_bypass: Arc<Mutex<bool>>,
+    is_muted: Arc<Mutex<bool>>,
+    is_paused: Arc<Mutex<bool>>,
+    ptt_enabled: Arc<Mutex<bool>>,
+    ptt_active: Arc<Mutex<bool>>,
+    current_levels: Arc<Mutex<AudioLevels>>,
+    level_meter: Arc<Mutex<LevelMeter>>,
+    input_meter: Arc<Mutex<LevelMeter>>,
+    spectrum: Arc<Mutex<SpectrumAnalyzer>>,
+    monitor: Arc<Mutex<MonitorBuffer>>,
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    sample_capture: Arc<Mutex<Option<SampleCapture>>>,
+    last_sample_capture: Arc<Mutex<Option<CapturedSample>>>,
+    music_bus: Arc<Mutex<Option<MusicBus>>>,
+    content_classifier: Arc<Mutex<Option<ContentClassifier>>>,
+    dry_monitor_level: Arc<Mutex<f32>>,
+    silence_detection: Arc<Mutex<SilenceDetectionConfig>>,
+    silence_detector: SilenceDetector,
+    dc_blocker: DcBlocker,
+    output_gain: ParamSmoother,
+    // Holds the dry monitor bus back by the chain's latency
+    dry_monitor_delay: DelayLine,
+    monitor_delay_ms: Arc<Mutex<f32>>,
+    // Holds the whole monitor feed back to line up with a hardware monitor path
+    monitor_delay: DelayLine,
+    mute_ramp: GainRamp,
+    ptt_ramp: GainRamp,
+    true_peak_meter: TruePeakMeter,
+    correlation_meter: CorrelationMeter,
+    rtp_packetizer: RtpPacketizer,
+    loss_simulator: PacketLossSimulator,
+    last_error_event: Option<Instant>,
+    // Brings a device that can't open at the engine rate up (or down) to it;
+    // None when the source already runs at the engine rate
+    input_resampler: Option<LinearResampler>,
+    resampled: Vec<f32>,
+    // Source blocks are regrouped into the stream's packet size (`buffer_size`
+    // frames unless it chose a frame size) before processing
+    ring: RingBuffer,
+    block: Vec<f32>,
+    // Reused for every encode, sized by the engine for the largest packet
+    encode_buffer: Vec<u8>,
+}
+
+impl CapturePipeline {
+    // `input_rate` and `input_channels` are what the source delivers; blocks
+    // are resampled to the engine rate and converted to the encoder's channel
+    // count before anything else touches them. Pipelines are only built for
+    // the selected stream.
+    pub fn new(
+        engine: &AudioEngine,
+        session: &StreamSession,
+        input_rate: u32,
+        input_channels: usize,
+    ) -> Self {
+        let sample_rate = engine.sample_rate as f32;
+        let channels = engine.channels.max(1) as usize;
+        let muted = *engine.is_muted.lock().unwrap();
+        let ptt_open = engine.ptt_target_gain();
+        let block_frames = engine.block_frames(session);
+        // The engine's cached size is for Opus at its own buffer size
+        let encoder = session.encoder.lock().unwrap();
+        let encode_buffer_bytes =
+            if encoder.codec() == Codec::Opus && block_frames == engine.buffer_size {
+                engine.encode_buffer_bytes
+            } else {
+                encoder.max_packet_bytes(block_frames, engine.sample_rate)
+            };
+        drop(encoder);
+
+        Self {
+            stream: engine.sessions.selected_id().clone(),
+            sample_rate,
+            input_channels: input_channels.max(1),
+            channels,
+            encoder: session.encoder.clone(),
+            tx: session.broadcast_tx.clone(),
+            frames_tx: session.frames_tx.clone(),
+            encoder_outputs: session.encoder_outputs.clone(),
+            broadcast_capacity: engine.broadcast_capacity,
+            overflow_policy: engine.overflow_policy,
+            events_tx: engine.events_tx.clone(),
+            sink_format: session.sink_format.clone(),
+            packet_loss: session.packet_loss.clone(),
+            stream_stats: session.stream_stats.clone(),
+            effects_chain: session.effects_chain.clone(),
+            input_gain_db: engine.input_gain_db.clone(),
+            output_gain_db: engine.output_gain_db.clone(),
+            dc_block_enabled: engine.dc_block_enabled.clone(),
+            chain_bypass: engine.chain_bypass.clone(),
+            is_muted: engine.is_muted.clone(),
+            is_paused: session.is_paused.clone(),
+            ptt_enabled: engine.ptt_enabled.clone(),
+            ptt_active: engine.ptt_active.clone(),
+            current_levels: session.current_levels.clone(),
+            level_meter: session.level_meter.clone(),
+            input_meter: session.input_meter.clone(),
+            spectrum: session.spectrum.clone(),
+            monitor: session.monitor.clone(),
+            recorder: session.recorder.clone(),
+            sample_capture: session.sample_capture.clone(),
+            last_sample_capture: session.last_sample_capture.clone(),
+            music_bus: session.music_bus.clone(),
+            content_classifier: session.content_classifier.clone(),
+            dry_monitor_level: engine.dry_monitor_level.clone(),
+            silence_detection: engine.silence_detection.clone(),
+            silence_detector: SilenceDetector::new(*engine.silence_detection.lock().unwrap()),
+            dc_blocker: DcBlocker::new(DC_BLOCK_CUTOFF_HZ, sample_rate, channels),
+            output_gain: ParamSmoother::with_default_ramp(
+                db_to_linear(*engine.output_gain_db.lock().unwrap()),
+                sample_rate,
+            ),
+            dry_monitor_delay: DelayLine::new(0, channels),
+            monitor_delay_ms: engine.monitor_delay_ms.clone(),
+            monitor_delay: DelayLine::new(0, channels),
+            mute_ramp: GainRamp::new(if muted { 0.0 } else { 1.0 }, MUTE_RAMP_SECS, sample_rate),
+            ptt_ramp: GainRamp::new(
+                if ptt_open { 1.0 } else { 0.0 },
+                PTT_ATTACK_SECS,
+                sample_rate,
+            ),
+            true_peak_meter: TruePeakMeter::new(channels),
+            correlation_meter: CorrelationMeter::new(sample_rate),
+            rtp_packetizer: RtpPacketizer::new(RTP_OPUS_PAYLOAD_TYPE),
+            loss_simulator: PacketLossSimulator::new(),
+            last_error_event: None,
+            input_resampler: (input_rate != engine.sample_rate).then(|| {
+                LinearResampler::new(input_rate, engine.sample_rate, input_channels.max(1))
+            }),
+            resampled: Vec::new(),
+            ring: RingBuffer::new(block_frames, channels),
+            block: Vec::with_capacity(block_frames * channels),
+            encode_buffer: vec![0u8; encode_buffer_bytes],
+        }
+    }
+
+    // Accepts a block of any size from the source; full blocks are processed
+    // as they become available and the remainder waits for the next call
+    pub fn process(&mut self, data: &[f32]) {
+        if *self.is_paused.lock().unwrap() {
+            // Don't replay stale audio on resume
+            self.ring.clear();
+            return;
+        }
+
+        let mut resampled = std::mem::take(&mut self.resampled);
+        let data = match self.input_resampler.as_mut() {
+            Some(resampler) => {
+                resampled.clear();
+                resampler.push(data, &mut resampled);
+                &resampled[..]
+            }
+            None => data,
+        };
+        if self.input_channels != self.channels {
+            self.ring
+                .push(&convert_channels(data, self.input_channels, self.channels));
+        } else {
+            self.ring.push(data);
+        }
+        self.resampled = resampled;
+        let overruns = self.ring.take_overruns();
+        if overruns > 0 {
+            self.stream_stats
+                .lock()
+                .unwrap()
+                .record_capture_overruns(overruns);
+        }
+
+        let mut block = std::mem::take(&mut self.block);
+        while self.ring.pop_block(&mut block) {
+            self.process_block(&block);
+        }
+        self.block = block;
+    }
+
+    fn process_block(&mut self, data: &[f32]) {
+        let mut input = data.to_vec();
+
+        // Strip DC offset from the raw capture before it eats headroom in the chain
+        if *self.dc_block_enabled.lock().unwrap() {
+            self.dc_blocker.process(&mut input);
+        } else {
+            self.dc_blocker.reset();
+        }
+
+        // Apply input trim so every effect sees the same operating point
+        let input_gain = db_to_linear(*self.input_gain_db.lock().unwrap());
+        input.iter_mut().for_each(|s| *s *= input_gain);
+
+        // Metered before the chain so the UI can show what the effects change
+        let block_ms = self.frames_in(&input) / self.sample_rate * 1000.0;
+        let input_levels = self.input_meter.lock().unwrap().process(&input, block_ms);
+        self.detect_silence(input_levels.1, block_ms);
+
+        // Process audio through effects chain, unless it is bypassed as a whole
+        let (mut processed, chain_latency) = if *self.chain_bypass.lock().unwrap() {
+            (input.clone(), 0)
+        } else {
+            let mut chain = self.effects_chain.lock().unwrap();
+            let processed = chain.process(&input, self.channels);
+            if let Some(overrun) = chain.take_overrun() {
+                self.report_overrun(overrun);
+            }
+            (processed, chain.latency_samples())
+        };
+
+        // One final level for whatever the chain left, ahead of everything
+        // the performer and the listeners hear
+        let output_gain = db_to_linear(*self.output_gain_db.lock().unwrap());
+        if output_gain != self.output_gain.target() {
+            self.output_gain.set_target(output_gain);
+        }
+        for frame in processed.chunks_mut(self.channels) {
+            let gain = self.output_gain.tick();
+            frame.iter_mut().for_each(|s| *s *= gain);
+        }
+
+        // The monitor follows the chain, not mute or push-to-talk, so the
+        // performer can still hear themselves while off air
+        self.feed_monitor(&input, &processed, chain_latency);
+
+        // Mute keeps the stream alive but ramps the output to silence
+        let mute_target = if *self.is_muted.lock().unwrap() {
+            0.0
+        } else {
+            1.0
+        };
+        self.mute_ramp
+            .apply(&mut processed, self.channels, mute_target);
+
+        // Push-to-talk fades in on key down and out on key up
+        if *self.ptt_enabled.lock().unwrap() {
+            let ptt_target = if *self.ptt_active.lock().unwrap() {
+                1.0
+            } else {
+                0.0
+            };
+            let ramp_secs = if ptt_target > self.ptt_ramp.gain() {
+                PTT_ATTACK_SECS
+            } else {
+                PTT_RELEASE_SECS
+            };
+            self.ptt_ramp.set_ramp_time(ramp_secs, self.sample_rate);
+            self.ptt_ramp
+                .apply(&mut processed, self.channels, ptt_target);
+        }
+
+        // The music bed goes in after mute and push-to-talk, which silence the
+        // voice but not the music, and is ducked by the voice that goes out
+        if let Some(music) = self.music_bus.lock().unwrap().as_mut() {
+            music.mix_into(&mut processed, self.channels);
+        }
+
+        self.update_levels(&processed, input_levels);
+        if let Some(recorder) = self.recorder.lock().unwrap().as_ref() {
+            recorder.push(&processed);
+        }
+        self.feed_sample_capture(&processed);
+        self.classify(&processed);
+        self.encode_and_send(&processed);
+    }
+
+    // Chain output, plus the dry input when the dry bus is up, delayed by the
+    // chain's latency so the two line up. The monitor delay then holds the
+    // mix back as a whole.
+    fn feed_monitor(&mut self, dry: &[f32], processed: &[f32], chain_latency: usize) {
+        let mut monitor = self.monitor.lock().unwrap();
+        if !monitor.is_attached() {
+            return;
+        }
+
+        let dry_level = *self.dry_monitor_level.lock().unwrap();
+        let mut mixed = if dry_level <= 0.0 {
+            processed.to_vec()
+        } else {
+            let mut dry = dry.to_vec();
+            self.dry_monitor_delay.set_delay(chain_latency);
+            self.dry_monitor_delay.process(&mut dry);
+            processed
+                .iter()
+                .zip(&dry)
+                .map(|(p, d)| p + d * dry_level)
+                .collect()
+        };
+
+        let delay_ms = *self.monitor_delay_ms.lock().unwrap();
+        self.monitor_delay
+            .set_delay((delay_ms * 0.001 * self.sample_rate).round() as usize);
+        self.monitor_delay.process(&mut mixed);
+        monitor.push(&mixed);
+    }
+
+    // Clears the capture once it is full and keeps it as the last capture
+    fn feed_sample_capture(&self, processed: &[f32]) {
+        let mut sample_capture = self.sample_capture.lock().unwrap();
+        let Some(finished) = sample_capture
+            .as_mut()
+            .and_then(|capture| capture.push(processed))
+        else {
+            return;
+        };
+        *sample_capture = None;
+        *self.last_sample_capture.lock().unwrap() = Some(finished);
+    }
+
+    // `input_rms` is the smoothed pre-chain level, so mute and push-to-talk
+    // don't count as the user going quiet
+    fn detect_silence(&mut self, input_rms: f32, block_ms: f32) {
+        let config = *self.silence_detection.lock().unwrap();
+        let level_db = linear_to_db(input_rms).max(METER_FLOOR_DB);
+        let stream = self.stream.clone();
+        let event = match self.silence_detector.process(config, level_db, block_ms) {
+            Some(SignalState::Signal) => AudioEvent::SignalDetected { stream, level_db },
+            Some(SignalState::Silence) => AudioEvent::SilenceDetected { stream, level_db },
+            None => return,
+        };
+        let _ = self.events_tx.send(event);
+    }
+
+    fn report_overrun(&self, overrun: EffectOverrun) {
+        log::warn!(
+            "Effects chain over budget ({:.0} of {:.0} us per block); bypassed effect {} ({})",
+            overrun.chain_micros,
+            overrun.budget_micros,
+            overrun.index,
+            overrun.effect_type
+        );
+        let _ = self.events_tx.send(AudioEvent::EffectsOverrun {
+            stream: self.stream.clone(),
+            overrun,
+        });
+    }
+
+    // Feeds the content classifier, while auto signal is on, and retunes the
+    // encoders when it changes its mind
+    fn classify(&self, processed: &[f32]) {
+        let content = match self.content_classifier.lock().unwrap().as_mut() {
+            Some(classifier) => classifier.process(processed),
+            None => None,
+        };
+        let Some(content) = content else {
+            return;
+        };
+        match apply_content_signal(&self.encoder, &self.encoder_outputs, content) {
+            Ok(()) => {
+                let _ = self.events_tx.send(AudioEvent::SignalChanged {
+                    stream: self.stream.clone(),
+                    content,
+                });
+            }
+            Err(e) => log::warn!("Cannot switch the encoder to {:?}: {}", content, e),
+        }
+    }
+
+    fn frames_in(&self, samples: &[f32]) -> f32 {
+        samples.len() as f32 / self.channels as f32
+    }
+
+    // `input_levels` is the pre-chain (peak, rms)
+    fn update_levels(&mut self, processed: &[f32], input_levels: (f32, f32)) {
+        // Calculate audio levels with meter ballistics
+        let block_ms = self.frames_in(processed) / self.sample_rate * 1000.0;
+        let (peak, rms) = self
+            .level_meter
+            .lock()
+            .unwrap()
+            .process(processed, block_ms);
+        let true_peak = linear_to_db(self.true_peak_meter.process(processed)).max(METER_FLOOR_DB);
+        let correlation = self.correlation_meter.process(processed, self.channels);
+        self.spectrum.lock().unwrap().process(processed);
+
+        // Update current levels
+        if let Ok(mut levels) = self.current_levels.lock() {
+            levels.input_level = rms;
+            levels.peak = peak;
+            levels.rms = rms;
+            levels.input_peak = input_levels.0;
+            levels.input_rms = input_levels.1;
+            levels.true_peak_dbtp = true_peak;
+            levels.phase_correlation = correlation;
+        }
+    }
+
+    fn encode_and_send(&mut self, processed: &[f32]) {
+        let duration_48k = (self.frames_in(processed) * RTP_OPUS_CLOCK_RATE as f32
+            / self.sample_rate)
+            .round() as u32;
+        self.encode_main(processed, duration_48k);
+        self.encode_outputs(processed, duration_48k);
+    }
+
+    fn encode_main(&mut self, processed: &[f32], duration_48k: u32) {
+        let Ok(mut enc) = self.encoder.lock() else {
+            return;
+        };
+        let codec = enc.codec();
+        let result = enc.encode(processed, &mut self.encode_buffer);
+        let mut stats = self.stream_stats.lock().unwrap();
+
+        match result {
+            // AAC still filling its first 1024-frame frame
+            Ok(0) => {}
+            Ok(size) => {
+                let encoded = self.encode_buffer[..size].to_vec();
+                stats.record_encoded(size);
+
+                // Ogg and WebRTC consumers expect one Opus packet per frame
+                if codec == Codec::Opus
+                    && self
+                        .overflow_policy
+                        .admits(&self.frames_tx, self.broadcast_capacity)
+                {
+                    let _ = self.frames_tx.send(EncodedFrame {
+                        data: encoded.clone(),
+                        duration_48k,
+                    });
+                }
+
+                // RTP framing is RFC 7587 Opus; ADTS already frames itself
+                let packet = match *self.sink_format.lock().unwrap() {
+                    SinkFormat::Rtp if codec == Codec::Opus => {
+                        self.rtp_packetizer.packetize(&encoded, duration_48k)
+                    }
+                    _ => encoded,
+                };
+
+                let loss = *self.packet_loss.lock().unwrap();
+                for packet in self.loss_simulator.apply(&loss, packet) {
+                    // Nobody listening isn't a drop; counting it as one would
+                    // report an idle stream as 100% loss and make adaptive
+                    // bitrate back off for no reason
+                    if self.tx.receiver_count() == 0 {
+                        continue;
+                    }
+                    if !self
+                        .overflow_policy
+                        .admits(&self.tx, self.broadcast_capacity)
+                    {
+                        stats.record_dropped();
+                        continue;
+                    }
+                    // Only fails if the last subscriber left since the check above
+                    let packet_len = packet.len();
+                    match self.tx.send(packet) {
+                        Ok(_) => stats.record_sent(packet_len),
+                        Err(_) => stats.record_dropped(),
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("Encoding error: {}", e);
+                let total_errors = stats.record_encode_error();
+
+                let due = match self.last_error_event {
+                    Some(t) => t.elapsed() >= ENCODE_ERROR_EVENT_INTERVAL,
+                    None => true,
+                };
+                if due {
+                    self.last_error_event = Some(Instant::now());
+                    let _ = self.events_tx.send(AudioEvent::EncodeError {
+                        message: e.to_string(),
+                        total_errors,
+                    });
+                }
+            }
+        }
+    }
+
+    // The extra bitrates; a failure on one is logged and leaves the main
+    // stream and the other outputs alone
+    fn encode_outputs(&mut self, processed: &[f32], duration_48k: u32) {
+        let mut outputs = self.encoder_outputs.lock().unwrap();
+        for output in outputs.iter_mut() {
+            if !self
+                .overflow_policy
+                .admits(output.frames_tx(), self.broadcast_capacity)
+            {
+                continue;
+            }
+            if let Err(e) = output.encode(processed, duration_48k, &mut self.encode_buffer) {
+                log::error!("Encoding error on output {}: {}", output.id(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{opus_frame_frames, AudioConfig, MAX_INPUT_GAIN_DB};
+    use crate::audio::{AudioEffect, EffectParameter, EffectParams, EffectRegistry};
+
+    fn engine() -> AudioEngine {
+        AudioEngine::new(AudioConfig::default()).unwrap()
+    }
+
+    // A pipeline on the selected stream, fed at the engine's own rate and layout
+    fn pipeline(engine: &AudioEngine) -> CapturePipeline {
+        CapturePipeline::new(
+            engine,
+            engine.sessions.selected(),
+            engine.sample_rate,
+            engine.channels as usize,
+        )
+    }
+
+    fn stereo_sine(freq: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|n| {
+                let s = amplitude * (2.0 * std::f32::consts::PI * freq * n as f32 / 48_000.0).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    // Feeds `input` through a fresh pipeline in 10 ms device-sized chunks and
+    // returns the levels it left
+    fn run(engine: &AudioEngine, input: &[f32]) -> AudioLevels {
+        let mut pipeline = pipeline(engine);
+        for chunk in input.chunks(960) {
+            pipeline.process(chunk);
+        }
+        engine.get_current_levels()
+    }
+
+    #[test]
+    fn input_gain_of_minus_6_db_halves_the_pre_effects_level() {
+        let input = stereo_sine(1_000.0, 0.5, 48_000);
+        let unity = run(&engine(), &input).input_peak;
+
+        let mut trimmed = engine();
+        trimmed.set_input_gain(-6.0).unwrap();
+        let halved = run(&trimmed, &input).input_peak;
+
+        assert!(
+            (halved / unity - 0.5).abs() < 0.01,
+            "{} -> {}",
+            unity,
+            halved
+        );
+    }
+
+    #[test]
+    fn input_gain_is_clamped_and_refuses_nan() {
+        let mut engine = engine();
+        engine.set_input_gain(60.0).unwrap();
+        assert_eq!(engine.get_input_gain(), MAX_INPUT_GAIN_DB);
+        assert!(engine.set_input_gain(f32::NAN).is_err());
+        assert_eq!(engine.get_input_gain(), MAX_INPUT_GAIN_DB);
+    }
+
+    // Runs `input` through `pipeline` and decodes every packet it broadcasts
+    fn decode_broadcast(
+        engine: &AudioEngine,
+        pipeline: &mut CapturePipeline,
+        input: &[f32],
+    ) -> Vec<Vec<f32>> {
+        let mut rx = engine.sessions.selected().broadcast_tx.subscribe();
+        let mut decoded = Vec::new();
+        for chunk in input.chunks(960) {
+            pipeline.process(chunk);
+            while let Ok(packet) = rx.try_recv() {
+                let mut pcm = vec![0.0f32; 5_760 * engine.channels as usize];
+                let frames = engine
+                    .decoder
+                    .lock()
+                    .unwrap()
+                    .decode_float(&packet, &mut pcm, false)
+                    .unwrap();
+                pcm.truncate(frames * engine.channels as usize);
+                decoded.push(pcm);
+            }
+        }
+        decoded
+    }
+
+    fn peak(signal: &[f32]) -> f32 {
+        signal.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn muted_stream_keeps_sending_silence() {
+        let mut engine = engine();
+        engine.set_muted(true);
+        let mut pipeline = pipeline(&engine);
+        let packets = decode_broadcast(&engine, &mut pipeline, &stereo_sine(1_000.0, 0.5, 48_000));
+
+        // One 20 ms packet per block, as when unmuted
+        assert_eq!(packets.len(), 50);
+        assert!(packets[5..].iter().all(|pcm| peak(pcm) < 1e-3));
+    }
+
+    // The first `frames` frames of what `input` comes out of the chain (and
+    // everything after it) as, the audio the stream encodes and records
+    fn processed_output(engine: &AudioEngine, input: &[f32], frames: usize) -> Vec<f32> {
+        let (capture, mut rx) = SampleCapture::new(
+            frames as u32 * 1000 / engine.sample_rate,
+            engine.sample_rate,
+            engine.channels,
+        );
+        *engine.sessions.selected().sample_capture.lock().unwrap() = Some(capture);
+        let mut pipeline = pipeline(engine);
+        for chunk in input.chunks(960) {
+            pipeline.process(chunk);
+        }
+        rx.try_recv().unwrap().samples
+    }
+
+    #[test]
+    fn chain_bypass_passes_the_input_through_untouched() {
+        let mut engine = engine();
+        engine.set_dc_block(false);
+        let registry = EffectRegistry::with_builtin_effects();
+        for key in ["compressor", "saturation", "eq", "reverb"] {
+            engine.add_effect(key, registry.create(key, EffectParams::new()).unwrap());
+        }
+        let input = stereo_sine(440.0, 0.5, 48_000);
+
+        assert_ne!(processed_output(&engine, &input, 24_000), input[..48_000]);
+        engine.set_chain_bypass(true);
+        assert_eq!(processed_output(&engine, &input, 24_000), input[..48_000]);
+    }
+
+    // Everything `input` sends to an attached monitor output
+    fn monitored_output(engine: &AudioEngine, input: &[f32]) -> Vec<f32> {
+        let monitor = engine.sessions.selected().monitor.clone();
+        monitor.lock().unwrap().attach(input.len());
+        let mut pipeline = pipeline(engine);
+        for chunk in input.chunks(960) {
+            pipeline.process(chunk);
+        }
+        let mut heard = vec![0.0; input.len()];
+        monitor.lock().unwrap().pop_into(&mut heard);
+        heard
+    }
+
+    #[test]
+    fn monitor_delay_holds_the_feed_back_by_the_requested_time() {
+        let mut engine = engine();
+        engine.set_dc_block(false);
+        let mut input = vec![0.0f32; 48_000];
+        input[200] = 0.5;
+        input[201] = -0.25;
+        let direct = monitored_output(&engine, &input);
+
+        // 12.5 ms at 48 kHz, 600 frames of two samples each
+        engine.set_monitor_delay(12.5);
+        let delayed = monitored_output(&engine, &input);
+        let offset = 600 * 2;
+
+        assert!(delayed[..offset].iter().all(|&s| s == 0.0));
+        assert_eq!(delayed[offset..], direct[..direct.len() - offset]);
+        assert_eq!(delayed[offset + 200..offset + 202], [0.5, -0.25]);
+    }
+
+    #[test]
+    fn output_gain_scales_the_recorded_and_monitored_level() {
+        let input = stereo_sine(1_000.0, 0.5, 24_000);
+        // Past the 20 ms gain ramp
+        let settled = 9_600..48_000;
+        let unity = engine();
+        let recorded = peak(&processed_output(&unity, &input, 24_000)[settled.clone()]);
+        let monitored = peak(&monitored_output(&unity, &input)[settled.clone()]);
+
+        let mut trimmed = engine();
+        trimmed.set_output_gain(-6.0206).unwrap();
+        let recorded_trimmed = peak(&processed_output(&trimmed, &input, 24_000)[settled.clone()]);
+        let monitored_trimmed = peak(&monitored_output(&trimmed, &input)[settled]);
+
+        assert!(
+            (recorded_trimmed / recorded - 0.5).abs() < 0.001,
+            "{} -> {}",
+            recorded,
+            recorded_trimmed
+        );
+        assert!(
+            (monitored_trimmed / monitored - 0.5).abs() < 0.001,
+            "{} -> {}",
+            monitored,
+            monitored_trimmed
+        );
+    }
+
+    #[test]
+    fn closed_gate_meters_high_before_the_chain_and_low_after() {
+        let mut engine = engine();
+        let mut gate = EffectParams::new();
+        // Nothing reaches 0 dBFS, so the gate never opens
+        gate.set("threshold".to_string(), 0.0);
+        let registry = EffectRegistry::with_builtin_effects();
+        engine.add_effect("noise_gate", registry.create("noise_gate", gate).unwrap());
+
+        let levels = run(&engine, &stereo_sine(1_000.0, 0.5, 48_000));
+        assert!(
+            levels.input_peak > 0.45 && levels.input_rms > 0.3,
+            "{:?}",
+            levels
+        );
+        assert!(levels.peak < 0.05 && levels.rms < 0.05, "{:?}", levels);
+    }
+
+    #[test]
+    fn monitor_limiter_leaves_the_stream_unlimited() {
+        let mut engine = engine();
+        engine.set_dc_block(false);
+        let input = stereo_sine(1_000.0, 0.95, 24_000);
+        engine.set_monitor_limiter(true, -12.0);
+        assert_eq!(processed_output(&engine, &input, 24_000), input);
+    }
+
+    // Packets one second of audio makes in `frame_size_ms` packets
+    fn packets_per_second(frame_size_ms: f32) -> usize {
+        let mut engine = engine();
+        engine.sessions.selected_mut().block_frames =
+            opus_frame_frames(frame_size_ms, engine.sample_rate);
+        let mut pipeline = pipeline(&engine);
+        decode_broadcast(&engine, &mut pipeline, &stereo_sine(1_000.0, 0.5, 48_000)).len()
+    }
+
+    #[test]
+    fn ten_ms_frames_send_twice_the_packets_of_twenty() {
+        assert_eq!(packets_per_second(20.0), 50);
+        assert_eq!(packets_per_second(10.0), 100);
+        assert_eq!(opus_frame_frames(10.0, 48_000), Some(480));
+        assert_eq!(opus_frame_frames(15.0, 48_000), None);
+    }
+
+    // Takes longer than a whole 20 ms block, as a heavy effect on a weak CPU would
+    struct Stalling;
+
+    impl AudioEffect for Stalling {
+        fn process(&mut self, input: &[f32], _channels: usize) -> Vec<f32> {
+            std::thread::sleep(Duration::from_millis(30));
+            input.to_vec()
+        }
+
+        fn get_name(&self) -> &str {
+            "Stalling"
+        }
+
+        fn get_parameters(&self) -> Vec<EffectParameter> {
+            Vec::new()
+        }
+
+        fn set_parameter(&mut self, _name: &str, _value: f32) {}
+    }
+
+    #[test]
+    fn sustained_overrun_bypasses_the_effect_and_reports_it() {
+        let mut engine = engine();
+        engine.add_effect("stalling", Box::new(Stalling));
+        engine.set_overrun_protection(true);
+        let mut events = engine.subscribe_to_events();
+        let mut pipeline = pipeline(&engine);
+
+        // The average takes ~15 blocks to pass the budget, then has to stay
+        // there for half a second
+        let overrun = stereo_sine(1_000.0, 0.5, 96_000)
+            .chunks(960 * 2)
+            .find_map(|chunk| {
+                pipeline.process(chunk);
+                std::iter::from_fn(|| events.try_recv().ok()).find_map(|event| match event {
+                    AudioEvent::EffectsOverrun { overrun, .. } => Some(overrun),
+                    _ => None,
+                })
+            });
+        let overrun = overrun.expect("no effects-overrun event");
+        assert_eq!(
+            (overrun.index, overrun.effect_type.as_str()),
+            (0, "stalling")
+        );
+        assert!(engine
+            .sessions
+            .selected()
+            .effects_chain
+            .lock()
+            .unwrap()
+            .slots()[0]
+            .is_bypassed());
+    }
+
+    #[test]
+    fn speaking_past_the_hold_time_emits_one_signal_event() {
+        let mut engine = engine();
+        engine.configure_silence_detection(-40.0, 200.0, true);
+        let mut events = engine.subscribe_to_events();
+        let mut input = vec![0.0; 48_000];
+        input.extend(stereo_sine(440.0, 0.3, 48_000));
+        run(&engine, &input);
+
+        let signals = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, AudioEvent::SignalDetected { .. }))
+            .count();
+        assert_eq!(signals, 1);
+    }
+}
@@ -0,0 +1,209 @@
+// Objective codec quality: a known reference is encoded and decoded, then the
+// decoded copy is compared against it with segmental SNR
+use super::db_to_linear;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Reference signals sit well below full scale so the codec never clips
+const REFERENCE_LEVEL_DB: f32 = -12.0;
+
+// Segments are 20 ms, and each one's SNR is clamped to this range (the usual
+// segmental SNR convention) so silent or perfect segments don't dominate
+const SEGMENT_SECS: f32 = 0.02;
+const MIN_SEGMENT_SNR_DB: f32 = -10.0;
+const MAX_SEGMENT_SNR_DB: f32 = 35.0;
+
+// Segments quieter than this in the reference are skipped
+const SILENT_SEGMENT_DB: f32 = -60.0;
+
+// Length of the reference run through the codec at each bitrate
+pub const REFERENCE_SECS: f32 = 2.0;
+
+// Swept when the caller doesn't pick bitrates, from speech-only to transparent
+pub const DEFAULT_QUALITY_BITRATES: [u32; 8] = [
+    24_000, 32_000, 48_000, 64_000, 96_000, 128_000, 192_000, 256_000,
+];
+
+// The decoder's output trails the input by the encoder lookahead; search this
+// far for the best alignment
+pub const MAX_ALIGNMENT_SECS: f32 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TestSignal {
+    Sine { freq: f32 },
+    // Logarithmic sweep across the band
+    Sweep { from: f32, to: f32 },
+    // Deterministic white noise, the same on every run
+    Noise,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodecQuality {
+    pub bitrate: u32,
+    pub segmental_snr_db: f32,
+    pub snr_db: f32,
+}
+
+impl TestSignal {
+    // `frames` frames of interleaved audio, identical on every channel
+    pub fn generate(&self, sample_rate: f32, channels: usize, frames: usize) -> Vec<f32> {
+        let amplitude = db_to_linear(REFERENCE_LEVEL_DB);
+        let sample_rate = sample_rate as f64;
+        let nyquist = sample_rate * 0.49;
+        // Accumulated in f64 so the phase doesn't drift over the run; the
+        // alignment search relies on the reference being exactly periodic
+        let mut phase = 0.0f64;
+        let mut seed = 0x1234_5678u32;
+
+        let mut output = Vec::with_capacity(frames * channels);
+        for i in 0..frames {
+            let sample = match *self {
+                TestSignal::Sine { freq } => {
+                    phase += 2.0 * PI * (freq as f64).clamp(1.0, nyquist) / sample_rate;
+                    phase.sin() as f32
+                }
+                TestSignal::Sweep { from, to } => {
+                    let (from, to) = (
+                        (from as f64).clamp(1.0, nyquist),
+                        (to as f64).clamp(1.0, nyquist),
+                    );
+                    let freq = from * (to / from).powf(i as f64 / frames as f64);
+                    phase += 2.0 * PI * freq / sample_rate;
+                    phase.sin() as f32
+                }
+                TestSignal::Noise => {
+                    seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+                }
+            };
+            phase %= 2.0 * PI;
+            output.resize(output.len() + channels, sample * amplitude);
+        }
+        output
+    }
+}
+
+// Frame offset into `decoded` that best lines it up with `reference`
+pub fn alignment(reference: &[f32], decoded: &[f32], channels: usize, max_lag: usize) -> usize {
+    let channels = channels.max(1);
+    let frames = (reference.len() / channels).min(decoded.len() / channels);
+    (0..=max_lag.min(frames))
+        .map(|lag| {
+            let score: f32 = (0..frames - lag)
+                .map(|i| reference[i * channels] * decoded[(i + lag) * channels])
+                .sum();
+            (lag, score)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(lag, _)| lag)
+        .unwrap_or(0)
+}
+
+// Returns (segmental SNR, overall SNR) in dB. `decoded` must already be
+// aligned with `reference`; the shorter of the two sets the length.
+pub fn measure_snr(
+    reference: &[f32],
+    decoded: &[f32],
+    channels: usize,
+    sample_rate: f32,
+) -> (f32, f32) {
+    let len = reference.len().min(decoded.len());
+    let segment_len = ((SEGMENT_SECS * sample_rate) as usize).max(1) * channels.max(1);
+    let silent = db_to_linear(SILENT_SEGMENT_DB).powi(2);
+
+    let mut total_signal = 0.0f64;
+    let mut total_noise = 0.0f64;
+    let mut segment_snrs = Vec::new();
+    for (reference, decoded) in reference[..len]
+        .chunks(segment_len)
+        .zip(decoded[..len].chunks(segment_len))
+    {
+        let signal: f64 = reference.iter().map(|&s| (s as f64).powi(2)).sum();
+        let noise: f64 = reference
+            .iter()
+            .zip(decoded)
+            .map(|(&r, &d)| ((r - d) as f64).powi(2))
+            .sum();
+        total_signal += signal;
+        total_noise += noise;
+
+        if signal / (reference.len() as f64) < silent as f64 {
+            continue;
+        }
+        segment_snrs.push(snr_db(signal, noise).clamp(MIN_SEGMENT_SNR_DB, MAX_SEGMENT_SNR_DB));
+    }
+
+    let segmental = if segment_snrs.is_empty() {
+        0.0
+    } else {
+        segment_snrs.iter().sum::<f32>() / segment_snrs.len() as f32
+    };
+    (segmental, snr_db(total_signal, total_noise))
+}
+
+fn snr_db(signal: f64, noise: f64) -> f32 {
+    (10.0 * (signal / noise.max(1e-20)).log10()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `reference` delayed by `lag` frames with deterministic noise at `noise` peak added
+    fn degraded(reference: &[f32], channels: usize, lag: usize, noise: f32) -> Vec<f32> {
+        let mut seed = 7u32;
+        let mut output = vec![0.0; lag * channels];
+        output.extend(reference.iter().map(|s| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            s + noise * ((seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0)
+        }));
+        output
+    }
+
+    #[test]
+    fn alignment_finds_the_decoder_delay() {
+        let reference = TestSignal::Sweep {
+            from: 50.0,
+            to: 16_000.0,
+        }
+        .generate(48_000.0, 2, 48_000);
+        let decoded = degraded(&reference, 2, 312, 0.0);
+        assert_eq!(alignment(&reference, &decoded, 2, 960), 312);
+    }
+
+    #[test]
+    fn snr_falls_as_noise_rises() {
+        for signal in [
+            TestSignal::Sine { freq: 1_000.0 },
+            TestSignal::Sweep {
+                from: 50.0,
+                to: 16_000.0,
+            },
+            TestSignal::Noise,
+        ] {
+            let reference = signal.generate(48_000.0, 2, 96_000);
+            // Loud enough that no segment hits the 35 dB clamp
+            let scores: Vec<(f32, f32)> = [0.01, 0.03, 0.1]
+                .iter()
+                .map(|&noise| {
+                    measure_snr(&reference, &degraded(&reference, 2, 0, noise), 2, 48_000.0)
+                })
+                .collect();
+            for pair in scores.windows(2) {
+                assert!(
+                    pair[1].0 < pair[0].0,
+                    "{:?} segmental SNR didn't fall: {:?}",
+                    signal,
+                    scores
+                );
+                assert!(
+                    pair[1].1 < pair[0].1,
+                    "{:?} SNR didn't fall: {:?}",
+                    signal,
+                    scores
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,768 @@
+// Local recording, to lossless FLAC or plain WAV. The pipeline hands each
+// processed block to a bounded channel and a writer thread converts it to
+// integer PCM and writes the file, so the audio callback never waits on the
+// encoder or the disk.
+//
+// A normalized recording can't be encoded as it arrives, since the gain
+// depends on the loudness of the whole take. The writer spools the float
+// samples to a file next to the target while metering them, then encodes the
+// spool with the gain applied when the recording stops.
+use super::{db_to_linear, linear_to_db, LoudnessMeter};
+use flac_bound::FlacEncoder;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+// Blocks waiting for the writer; at 20 ms blocks that is about 5 seconds of
+// disk stall before audio is dropped
+const RECORDER_QUEUE_BLOCKS: usize = 256;
+
+// libFLAC's default: close to the best ratio at a fraction of the CPU of level 8
+const FLAC_COMPRESSION_LEVEL: u32 = 5;
+
+// Range of sample sizes libFLAC encodes; WAV recordings keep to the same range
+pub const MIN_RECORDING_BIT_DEPTH: u16 = 8;
+pub const MAX_RECORDING_BIT_DEPTH: u16 = 24;
+
+// Loudness targets a normalized recording accepts, in LUFS
+pub const MIN_TARGET_LUFS: f32 = -70.0;
+pub const MAX_TARGET_LUFS: f32 = 0.0;
+
+// Normalization gain is held back so the loudest sample lands no higher than this
+const NORMALIZE_PEAK_CEILING_DB: f32 = -1.0;
+
+// Frames read back from the spool per encoder call
+const SPOOL_READ_FRAMES: usize = 4096;
+
+// The engine's f32 samples carry 24 bits of mantissa; at that depth there is
+// nothing to dither away
+const FLOAT_MANTISSA_BITS: u16 = 24;
+
+// Noise shaping feeds back at most this much quantization error, in LSBs, so
+// a clipped sample doesn't push its error into the next ones
+const MAX_SHAPED_ERROR_LSB: f32 = 2.0;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum RecorderError {
+    #[error("Recordings take 8-24 bits per sample, not {0}")]
+    UnsupportedBitDepth(u16),
+    #[error("Cannot create a FLAC encoder")]
+    EncoderUnavailable,
+    #[error("Cannot open {0} for recording")]
+    Open(String),
+    #[error("Writing the recording failed")]
+    Encode,
+    #[error("The recording writer stopped unexpectedly")]
+    WriterPanicked,
+    #[error("Already recording")]
+    AlreadyRecording,
+    #[error("Not recording")]
+    NotRecording,
+    #[error("Loudness target must be between -70 and 0 LUFS, not {0}")]
+    InvalidLoudnessTarget(f32),
+    #[error("Cannot write the normalization spool file")]
+    Spool,
+}
+
+// How samples are rounded to the recording's bit depth. Plain rounding leaves
+// distortion correlated with the signal on quiet passages and fades; TPDF
+// dither trades it for a constant, benign noise floor, and shaping moves that
+// noise up the spectrum where the ear is less sensitive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DitherMode {
+    #[default]
+    None,
+    // Triangular noise of +/-1 LSB, the textbook minimum that fully
+    // decorrelates the error from the signal
+    Tpdf,
+    // TPDF with first-order error feedback: the noise is pushed above about
+    // a sixth of the sample rate (8 kHz at 48 kHz), at 3 dB more in total
+    Shaped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingContainer {
+    Flac,
+    Wav,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RecordingFormat {
+    pub container: RecordingContainer,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bit_depth: u16,
+    pub dither: DitherMode,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingSummary {
+    pub path: String,
+    pub frames: u64,
+    pub duration_secs: f64,
+    // Blocks lost because the writer fell too far behind
+    pub dropped_blocks: u64,
+    // Integrated loudness of the take before normalization; only measured
+    // for normalized recordings, and None if it was silent throughout
+    pub loudness_lufs: Option<f32>,
+    // Gain applied on finalize, for normalized recordings
+    pub normalization_gain_db: Option<f32>,
+}
+
+// What the writer thread reports when it finishes
+struct WrittenRecording {
+    frames: u64,
+    loudness_lufs: Option<f32>,
+    normalization_gain_db: Option<f32>,
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordingFormat,
+    tx: mpsc::SyncSender<Vec<f32>>,
+    dropped: Arc<AtomicU64>,
+    writer: JoinHandle<Result<WrittenRecording, RecorderError>>,
+}
+
+impl Recorder {
+    // Returns once the file is open, so a bad path fails here rather than
+    // silently on the writer thread
+    pub fn start(path: &Path, format: RecordingFormat) -> Result<Self, RecorderError> {
+        Self::spawn(path, format, None)
+    }
+
+    // Like start(), but the file is scaled on stop() so its integrated
+    // loudness hits `target_lufs`, peaks permitting
+    pub fn start_normalized(
+        path: &Path,
+        format: RecordingFormat,
+        target_lufs: f32,
+    ) -> Result<Self, RecorderError> {
+        if !(MIN_TARGET_LUFS..=MAX_TARGET_LUFS).contains(&target_lufs) {
+            return Err(RecorderError::InvalidLoudnessTarget(target_lufs));
+        }
+        Self::spawn(path, format, Some(target_lufs))
+    }
+
+    fn spawn(
+        path: &Path,
+        format: RecordingFormat,
+        target_lufs: Option<f32>,
+    ) -> Result<Self, RecorderError> {
+        if !(MIN_RECORDING_BIT_DEPTH..=MAX_RECORDING_BIT_DEPTH).contains(&format.bit_depth) {
+            return Err(RecorderError::UnsupportedBitDepth(format.bit_depth));
+        }
+
+        let (tx, rx) = mpsc::sync_channel(RECORDER_QUEUE_BLOCKS);
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+        let writer_path = path.to_path_buf();
+        let writer = std::thread::spawn(move || match target_lufs {
+            Some(target_lufs) => write_normalized(&writer_path, format, target_lufs, rx, ready_tx),
+            None => write_pcm(&writer_path, format, rx, ready_tx),
+        });
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                path: path.to_path_buf(),
+                format,
+                tx,
+                dropped: Arc::new(AtomicU64::new(0)),
+                writer,
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(RecorderError::WriterPanicked),
+        }
+    }
+
+    // Called from the audio thread; never blocks
+    pub fn push(&self, block: &[f32]) {
+        if self.tx.try_send(block.to_vec()).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Flushes what is queued, finishes the file and closes it
+    pub fn stop(self) -> Result<RecordingSummary, RecorderError> {
+        drop(self.tx);
+        let written = self
+            .writer
+            .join()
+            .map_err(|_| RecorderError::WriterPanicked)??;
+        let dropped_blocks = self.dropped.load(Ordering::Relaxed);
+        if dropped_blocks > 0 {
+            log::warn!("Recording dropped {} blocks", dropped_blocks);
+        }
+        Ok(RecordingSummary {
+            path: self.path.display().to_string(),
+            frames: written.frames,
+            duration_secs: written.frames as f64 / self.format.sample_rate as f64,
+            dropped_blocks,
+            loudness_lufs: written.loudness_lufs,
+            normalization_gain_db: written.normalization_gain_db,
+        })
+    }
+}
+
+// Gain that brings `loudness_lufs` to the target, reduced if it would push
+// `peak` past the ceiling. A silent take is left alone.
+pub fn normalization_gain_db(loudness_lufs: Option<f32>, target_lufs: f32, peak: f32) -> f32 {
+    let Some(loudness_lufs) = loudness_lufs else {
+        return 0.0;
+    };
+    let headroom = NORMALIZE_PEAK_CEILING_DB - linear_to_db(peak);
+    (target_lufs - loudness_lufs).min(headroom)
+}
+
+// Converts float samples to integer PCM at the recording's bit depth. Lives
+// on the writer thread, since shaping carries error from block to block.
+pub struct Quantizer {
+    mode: DitherMode,
+    // Full scale in LSBs
+    max: f32,
+    // Last quantization error per channel, for shaping
+    error: Vec<f32>,
+    rng: StdRng,
+}
+
+impl Quantizer {
+    pub fn new(format: RecordingFormat) -> Self {
+        let mode = if format.bit_depth >= FLOAT_MANTISSA_BITS {
+            DitherMode::None
+        } else {
+            format.dither
+        };
+        Self {
+            mode,
+            max: ((1i64 << (format.bit_depth - 1)) - 1) as f32,
+            error: vec![0.0; format.channels.max(1) as usize],
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    // Scales each sample by `gain` to the full signed range, clipping
+    // anything past 0 dBFS, and appends it to `pcm`
+    pub fn quantize(&mut self, block: &[f32], gain: f32, pcm: &mut Vec<i32>) {
+        let channels = self.error.len();
+        for (i, &sample) in block.iter().enumerate() {
+            let scaled = (sample * gain).clamp(-1.0, 1.0) * self.max;
+            let quantized = match self.mode {
+                DitherMode::None => scaled.round(),
+                DitherMode::Tpdf => (scaled + self.tpdf()).round(),
+                DitherMode::Shaped => {
+                    let dither = self.tpdf();
+                    let error = &mut self.error[i % channels];
+                    // Subtracting the last error makes the total error
+                    // e[n] - e[n-1], a highpass on the quantization noise
+                    let shaped = scaled - *error;
+                    let quantized = (shaped + dither).round();
+                    *error =
+                        (quantized - shaped).clamp(-MAX_SHAPED_ERROR_LSB, MAX_SHAPED_ERROR_LSB);
+                    quantized
+                }
+            };
+            pcm.push(quantized.clamp(-self.max, self.max) as i32);
+        }
+    }
+
+    // Sum of two uniform +/-0.5 LSB values: triangular over +/-1 LSB
+    fn tpdf(&mut self) -> f32 {
+        self.rng.gen_range(-0.5..0.5f32) + self.rng.gen_range(-0.5..0.5f32)
+    }
+}
+
+// Where the integer samples go: libFLAC, or hound for uncompressed WAV
+enum PcmWriter {
+    Flac(FlacEncoder<'static>),
+    Wav(WavWriter<BufWriter<File>>),
+}
+
+impl PcmWriter {
+    fn open(path: &Path, format: RecordingFormat) -> Result<Self, RecorderError> {
+        match format.container {
+            RecordingContainer::Flac => FlacEncoder::new()
+                .ok_or(RecorderError::EncoderUnavailable)
+                .and_then(|config| {
+                    config
+                        .channels(format.channels as u32)
+                        .bits_per_sample(format.bit_depth as u32)
+                        .sample_rate(format.sample_rate)
+                        .compression_level(FLAC_COMPRESSION_LEVEL)
+                        .init_file(&path)
+                        .map_err(|_| RecorderError::Open(path.display().to_string()))
+                })
+                .map(Self::Flac),
+            RecordingContainer::Wav => {
+                let spec = WavSpec {
+                    channels: format.channels,
+                    sample_rate: format.sample_rate,
+                    bits_per_sample: format.bit_depth,
+                    sample_format: SampleFormat::Int,
+                };
+                WavWriter::create(path, spec)
+                    .map(Self::Wav)
+                    .map_err(|_| RecorderError::Open(path.display().to_string()))
+            }
+        }
+    }
+
+    fn write(&mut self, pcm: &[i32], frames: usize) -> Result<(), RecorderError> {
+        match self {
+            Self::Flac(encoder) => encoder
+                .process_interleaved(pcm, frames as u32)
+                .map_err(|_| RecorderError::Encode),
+            Self::Wav(writer) => pcm
+                .iter()
+                .try_for_each(|&sample| writer.write_sample(sample))
+                .map_err(|_| RecorderError::Encode),
+        }
+    }
+
+    fn finish(self) -> Result<(), RecorderError> {
+        match self {
+            Self::Flac(encoder) => encoder
+                .finish()
+                .map(drop)
+                .map_err(|_| RecorderError::Encode),
+            Self::Wav(writer) => writer.finalize().map_err(|_| RecorderError::Encode),
+        }
+    }
+}
+
+// Tells start() whether the writer got going, passing `opened` through
+fn report_ready<T>(
+    opened: Result<T, RecorderError>,
+    ready: &mpsc::SyncSender<Result<(), RecorderError>>,
+) -> Result<T, RecorderError> {
+    let _ = ready.send(opened.as_ref().map(|_| ()).map_err(Clone::clone));
+    opened
+}
+
+// Writes one interleaved block, scaled by `gain`; returns its frames
+fn encode_block(
+    writer: &mut PcmWriter,
+    block: &[f32],
+    format: RecordingFormat,
+    gain: f32,
+    quantizer: &mut Quantizer,
+    pcm: &mut Vec<i32>,
+) -> Result<u64, RecorderError> {
+    pcm.clear();
+    quantizer.quantize(block, gain, pcm);
+    let block_frames = pcm.len() / format.channels.max(1) as usize;
+    writer.write(pcm, block_frames)?;
+    Ok(block_frames as u64)
+}
+
+// Runs until the sender is dropped
+fn write_pcm(
+    path: &Path,
+    format: RecordingFormat,
+    rx: mpsc::Receiver<Vec<f32>>,
+    ready: mpsc::SyncSender<Result<(), RecorderError>>,
+) -> Result<WrittenRecording, RecorderError> {
+    let mut writer = report_ready(PcmWriter::open(path, format), &ready)?;
+
+    let mut quantizer = Quantizer::new(format);
+    let mut frames = 0u64;
+    let mut pcm = Vec::new();
+    for block in rx {
+        frames += encode_block(&mut writer, &block, format, 1.0, &mut quantizer, &mut pcm)?;
+    }
+
+    writer.finish()?;
+    Ok(WrittenRecording {
+        frames,
+        loudness_lufs: None,
+        normalization_gain_db: None,
+    })
+}
+
+// The spool sits next to the recording, so it lands on the same disk and a
+// bad directory fails start()
+fn spool_path(path: &Path) -> PathBuf {
+    let mut spool = path.as_os_str().to_owned();
+    spool.push(".spool");
+    PathBuf::from(spool)
+}
+
+// Spools and meters until the sender is dropped, then encodes the spool with
+// the normalization gain. The spool is removed either way.
+fn write_normalized(
+    path: &Path,
+    format: RecordingFormat,
+    target_lufs: f32,
+    rx: mpsc::Receiver<Vec<f32>>,
+    ready: mpsc::SyncSender<Result<(), RecorderError>>,
+) -> Result<WrittenRecording, RecorderError> {
+    let spool = spool_path(path);
+    let file = File::create(&spool).map_err(|_| RecorderError::Open(path.display().to_string()));
+    let mut writer = BufWriter::new(report_ready(file, &ready)?);
+
+    let mut meter = LoudnessMeter::new(format.sample_rate as f32, format.channels as usize);
+    let mut peak = 0.0f32;
+    let mut spooled = Ok(());
+    for block in rx {
+        meter.process(&block);
+        peak = block.iter().fold(peak, |peak, s| peak.max(s.abs()));
+        // Keep draining after a write error so the pipeline doesn't count drops
+        if spooled.is_ok() {
+            spooled = block
+                .iter()
+                .try_for_each(|s| writer.write_all(&s.to_le_bytes()));
+        }
+    }
+    let spooled = spooled.and_then(|_| writer.flush());
+    drop(writer);
+
+    let loudness_lufs = meter.integrated_lufs();
+    let gain_db = normalization_gain_db(loudness_lufs, target_lufs, peak);
+    let encoded = match spooled {
+        Ok(()) => encode_spool(path, &spool, format, db_to_linear(gain_db)),
+        Err(_) => Err(RecorderError::Spool),
+    };
+    let _ = std::fs::remove_file(&spool);
+
+    Ok(WrittenRecording {
+        frames: encoded?,
+        loudness_lufs,
+        normalization_gain_db: Some(gain_db),
+    })
+}
+
+fn encode_spool(
+    path: &Path,
+    spool: &Path,
+    format: RecordingFormat,
+    gain: f32,
+) -> Result<u64, RecorderError> {
+    let mut writer = PcmWriter::open(path, format)?;
+    let mut reader = BufReader::new(File::open(spool).map_err(|_| RecorderError::Spool)?);
+
+    let mut bytes = vec![0u8; SPOOL_READ_FRAMES * format.channels.max(1) as usize * 4];
+    let mut block = Vec::with_capacity(bytes.len() / 4);
+    let mut quantizer = Quantizer::new(format);
+    let mut pcm = Vec::new();
+    let mut frames = 0u64;
+    loop {
+        let filled = fill_from(&mut reader, &mut bytes)?;
+        if filled == 0 {
+            break;
+        }
+        block.clear();
+        block.extend(
+            bytes[..filled]
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        );
+        frames += encode_block(&mut writer, &block, format, gain, &mut quantizer, &mut pcm)?;
+    }
+
+    writer.finish()?;
+    Ok(frames)
+}
+
+// Reads until `buf` is full or the file ends; returns the bytes read
+fn fill_from(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, RecorderError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(_) => return Err(RecorderError::Spool),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "voicecast-recorder-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    fn format(bit_depth: u16) -> RecordingFormat {
+        RecordingFormat {
+            container: RecordingContainer::Flac,
+            sample_rate: 48_000,
+            channels: 2,
+            bit_depth,
+            dither: DitherMode::None,
+        }
+    }
+
+    // Records `blocks`, normalized if there's a target, and reads the file
+    // back as integer PCM
+    fn record_and_decode(
+        name: &str,
+        format: RecordingFormat,
+        target_lufs: Option<f32>,
+        blocks: &[Vec<f32>],
+    ) -> (RecordingSummary, Vec<i32>) {
+        let path = temp_path(name);
+        let recorder = match target_lufs {
+            Some(target_lufs) => Recorder::start_normalized(&path, format, target_lufs).unwrap(),
+            None => Recorder::start(&path, format).unwrap(),
+        };
+        for block in blocks {
+            recorder.push(block);
+        }
+        let summary = recorder.stop().unwrap();
+        assert_eq!(summary.dropped_blocks, 0);
+
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        let info = reader.streaminfo();
+        assert_eq!(
+            (info.sample_rate, info.channels, info.bits_per_sample),
+            (
+                format.sample_rate,
+                format.channels as u32,
+                format.bit_depth as u32
+            )
+        );
+        assert_eq!(info.samples, Some(summary.frames));
+        let pcm = reader.samples().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        (summary, pcm)
+    }
+
+    #[test]
+    fn flac_decodes_to_exactly_the_samples_recorded() {
+        // Every value sits on the 16-bit grid, so nothing is lost to rounding and
+        // the file has to give back these integers exactly
+        let expected: Vec<i32> = (0..48_000i32 * 2)
+            .map(|i| (i * 7_919) % 65_535 - 32_767)
+            .collect();
+        let blocks: Vec<Vec<f32>> = expected
+            .chunks(1_920)
+            .map(|block| block.iter().map(|&s| s as f32 / 32_767.0).collect())
+            .collect();
+        assert_eq!(
+            record_and_decode("exact.flac", format(16), None, &blocks).1,
+            expected
+        );
+    }
+
+    #[test]
+    fn flac_at_24_bits_is_within_half_an_lsb() {
+        let input: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let t = i as f32 / 48_000.0;
+                [
+                    (2.0 * std::f32::consts::PI * 440.0 * t).sin() * 0.8,
+                    (2.0 * std::f32::consts::PI * 3_000.0 * t).sin() * 0.1,
+                ]
+            })
+            .collect();
+        let blocks: Vec<Vec<f32>> = input.chunks(1_920).map(<[f32]>::to_vec).collect();
+        let (_, decoded) = record_and_decode("sine.flac", format(24), None, &blocks);
+
+        let full_scale = ((1 << 23) - 1) as f32;
+        assert_eq!(decoded.len(), input.len());
+        for (original, decoded) in input.iter().zip(&decoded) {
+            assert!(
+                (original * full_scale - *decoded as f32).abs() <= 0.5 + full_scale * f32::EPSILON
+            );
+        }
+    }
+
+    #[test]
+    fn quiet_recording_is_normalized_to_the_target() {
+        let input: Vec<f32> = (0..48_000 * 5)
+            .flat_map(|i| {
+                let s = (2.0 * std::f32::consts::PI * 1_000.0 * i as f32 / 48_000.0).sin() * 0.01;
+                [s, s]
+            })
+            .collect();
+        let blocks: Vec<Vec<f32>> = input.chunks(1_920).map(<[f32]>::to_vec).collect();
+        let (summary, pcm) = record_and_decode("normalized.flac", format(24), Some(-23.0), &blocks);
+
+        let full_scale = ((1 << 23) - 1) as f32;
+        let decoded: Vec<f32> = pcm.iter().map(|&s| s as f32 / full_scale).collect();
+        let mut meter = LoudnessMeter::new(48_000.0, 2);
+        meter.process(&decoded);
+        let loudness = meter.integrated_lufs().unwrap();
+        assert!(
+            (loudness + 23.0).abs() < 0.5,
+            "normalized to {} LUFS",
+            loudness
+        );
+
+        let before = summary.loudness_lufs.unwrap();
+        assert!(before < -35.0, "quiet take measured {} LUFS", before);
+        assert!((summary.normalization_gain_db.unwrap() - (-23.0 - before)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn normalization_gain_stops_at_the_peak_ceiling() {
+        // 17 dB would reach the target, but a -6 dB peak only has 5 dB below -1 dBFS
+        let gain = normalization_gain_db(Some(-40.0), -23.0, 0.5);
+        assert!(
+            (gain - (-1.0 - linear_to_db(0.5))).abs() < 1e-4,
+            "gain {}",
+            gain
+        );
+        assert_eq!(normalization_gain_db(None, -23.0, 0.0), 0.0);
+    }
+
+    // One second of a 1 kHz tone `amplitude_lsb` 16-bit steps high
+    fn tone_lsb(amplitude_lsb: f64) -> Vec<f64> {
+        (0..48_000)
+            .map(|n| {
+                (2.0 * std::f64::consts::PI * 1_000.0 * n as f64 / 48_000.0).sin() * amplitude_lsb
+            })
+            .collect()
+    }
+
+    // Error, in LSBs, of quantizing that tone to 16 bits with `dither`
+    fn quantization_error(dither: DitherMode, amplitude_lsb: f64) -> Vec<f64> {
+        let mut quantizer = Quantizer::new(RecordingFormat {
+            channels: 1,
+            dither,
+            ..format(16)
+        });
+        let tone = tone_lsb(amplitude_lsb);
+        let input: Vec<f32> = tone.iter().map(|s| (s / 32_767.0) as f32).collect();
+        let mut pcm = Vec::new();
+        for block in input.chunks(960) {
+            quantizer.quantize(block, 1.0, &mut pcm);
+        }
+        pcm.iter()
+            .zip(&input)
+            .map(|(&quantized, &s)| quantized as f64 - s as f64 * 32_767.0)
+            .collect()
+    }
+
+    // Power of `signal` at `freq`, by a single DFT bin
+    fn power_at(signal: &[f64], freq: f64) -> f64 {
+        let (re, im) = signal
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (n, s)| {
+                let phase = 2.0 * std::f64::consts::PI * freq * n as f64 / 48_000.0;
+                (re + s * phase.cos(), im + s * phase.sin())
+            });
+        (re * re + im * im) / (signal.len() as f64).powi(2)
+    }
+
+    fn variance(error: &[f64]) -> f64 {
+        error.iter().map(|e| e * e).sum::<f64>() / error.len() as f64
+    }
+
+    // Error power below 4 kHz, where the ear is most sensitive
+    fn low_band_power(error: &[f64]) -> f64 {
+        (1..40)
+            .map(|bin| power_at(error, bin as f64 * 100.0 + 50.0))
+            .sum()
+    }
+
+    #[test]
+    fn dither_trades_distortion_for_a_flat_noise_floor() {
+        let truncated = quantization_error(DitherMode::None, 1.3);
+        let tpdf = quantization_error(DitherMode::Tpdf, 1.3);
+        let shaped = quantization_error(DitherMode::Shaped, 1.3);
+
+        // Truncation concentrates the error in harmonics of the tone
+        assert!(power_at(&truncated, 3_000.0) > 100.0 * power_at(&tpdf, 3_000.0));
+        // TPDF: 1/12 LSB² of rounding plus 1/6 of dither, spread evenly
+        assert!(
+            (variance(&tpdf) - 0.25).abs() < 0.02,
+            "{} LSB²",
+            variance(&tpdf)
+        );
+        // Shaping adds noise overall but moves it out of the low band
+        assert!(variance(&shaped) > variance(&tpdf));
+        assert!(low_band_power(&shaped) < 0.5 * low_band_power(&tpdf));
+    }
+
+    #[test]
+    fn dither_keeps_a_tone_under_half_an_lsb() {
+        let tone = tone_lsb(0.4);
+        let heard =
+            |error: Vec<f64>| -> Vec<f64> { error.iter().zip(&tone).map(|(e, s)| e + s).collect() };
+
+        // Rounded away entirely without dither
+        assert!(heard(quantization_error(DitherMode::None, 0.4))
+            .iter()
+            .all(|s| s.abs() < 1e-3));
+        let dithered = heard(quantization_error(DitherMode::Tpdf, 0.4));
+        let amplitude = power_at(&dithered, 1_000.0).sqrt() * 2.0;
+        assert!((amplitude - 0.4).abs() < 0.05, "tone at {} LSB", amplitude);
+    }
+
+    #[test]
+    fn recordings_at_24_bits_are_not_dithered() {
+        let mut quantizer = Quantizer::new(RecordingFormat {
+            dither: DitherMode::Tpdf,
+            ..format(24)
+        });
+        let mut pcm = Vec::new();
+        quantizer.quantize(&[0.0; 64], 1.0, &mut pcm);
+        assert!(pcm.iter().all(|&s| s == 0));
+    }
+
+    // Records the stereo `blocks` to a 16-bit WAV with `dither` and reads the
+    // file back as integer PCM
+    fn record_wav(name: &str, dither: DitherMode, blocks: &[f32]) -> Vec<i32> {
+        let path = temp_path(name);
+        let recorder = Recorder::start(
+            &path,
+            RecordingFormat {
+                container: RecordingContainer::Wav,
+                dither,
+                ..format(16)
+            },
+        )
+        .unwrap();
+        for block in blocks.chunks(1_920) {
+            recorder.push(block);
+        }
+        let summary = recorder.stop().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(
+            reader.spec(),
+            WavSpec {
+                channels: 2,
+                sample_rate: 48_000,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            }
+        );
+        assert_eq!(reader.duration() as u64, summary.frames);
+        let pcm = reader.samples().map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+        pcm
+    }
+
+    #[test]
+    fn wav_at_16_bits_keeps_a_quiet_tone_only_when_dithered() {
+        let quiet: Vec<f32> = tone_lsb(0.4)
+            .iter()
+            .flat_map(|&s| [(s / 32_767.0) as f32; 2])
+            .collect();
+
+        assert!(record_wav("plain.wav", DitherMode::None, &quiet)
+            .iter()
+            .all(|&s| s == 0));
+        let dithered = record_wav("dithered.wav", DitherMode::Tpdf, &quiet);
+        let left: Vec<f64> = dithered.iter().step_by(2).map(|&s| s as f64).collect();
+        let amplitude = power_at(&left, 1_000.0).sqrt() * 2.0;
+        assert!((amplitude - 0.4).abs() < 0.05, "tone at {} LSB", amplitude);
+    }
+}
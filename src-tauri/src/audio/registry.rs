@@ -0,0 +1,104 @@
+use super::effects::{
+    AgcEffect, CompressorEffect, DeClickEffect, EqualizerEffect, FlangerEffect,
+    MultibandCompressorEffect, NoiseGateEffect, PitchShiftEffect, ReverbEffect, SaturationEffect,
+};
+use super::{AudioEffect, EffectParams};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub type EffectConstructor = fn(EffectParams) -> Box<dyn AudioEffect>;
+
+// Keys of the effects that ship with the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EffectType {
+    Eq,
+    Compressor,
+    Reverb,
+    NoiseGate,
+    Flanger,
+    PitchShift,
+    Agc,
+    MultibandCompressor,
+    Saturation,
+    DeClick,
+}
+
+impl EffectType {
+    pub fn key(&self) -> &'static str {
+        match self {
+            EffectType::Eq => "eq",
+            EffectType::Compressor => "compressor",
+            EffectType::Reverb => "reverb",
+            EffectType::NoiseGate => "noise_gate",
+            EffectType::Flanger => "flanger",
+            EffectType::PitchShift => "pitch_shift",
+            EffectType::Agc => "agc",
+            EffectType::MultibandCompressor => "multiband_compressor",
+            EffectType::Saturation => "saturation",
+            EffectType::DeClick => "declick",
+        }
+    }
+}
+
+// Maps effect keys to constructors, so adding an effect is one registration
+// instead of another match arm in every command that builds effects
+#[derive(Clone, Default)]
+pub struct EffectRegistry {
+    constructors: BTreeMap<String, EffectConstructor>,
+}
+
+impl EffectRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_builtin_effects() -> Self {
+        let mut registry = Self::new();
+        registry.register(EffectType::Eq.key(), |p| Box::new(EqualizerEffect::new(p)));
+        registry.register(EffectType::Compressor.key(), |p| {
+            Box::new(CompressorEffect::new(p))
+        });
+        registry.register(EffectType::Reverb.key(), |p| Box::new(ReverbEffect::new(p)));
+        registry.register(EffectType::NoiseGate.key(), |p| {
+            Box::new(NoiseGateEffect::new(p))
+        });
+        registry.register(EffectType::Flanger.key(), |p| {
+            Box::new(FlangerEffect::new(p))
+        });
+        registry.register(EffectType::PitchShift.key(), |p| {
+            Box::new(PitchShiftEffect::new(p))
+        });
+        registry.register(EffectType::Agc.key(), |p| Box::new(AgcEffect::new(p)));
+        registry.register(EffectType::MultibandCompressor.key(), |p| {
+            Box::new(MultibandCompressorEffect::new(p))
+        });
+        registry.register(EffectType::Saturation.key(), |p| {
+            Box::new(SaturationEffect::new(p))
+        });
+        registry.register(EffectType::DeClick.key(), |p| {
+            Box::new(DeClickEffect::new(p))
+        });
+        registry
+    }
+
+    // Replaces any constructor already registered under `key`
+    pub fn register(&mut self, key: &str, constructor: EffectConstructor) {
+        self.constructors.insert(key.to_string(), constructor);
+    }
+
+    pub fn create(&self, key: &str, params: EffectParams) -> Option<Box<dyn AudioEffect>> {
+        self.constructors
+            .get(key)
+            .map(|constructor| constructor(params))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.constructors.contains_key(key)
+    }
+
+    // Registered keys in sorted order
+    pub fn keys(&self) -> Vec<String> {
+        self.constructors.keys().cloned().collect()
+    }
+}
@@ -0,0 +1,308 @@
+use rubato::{
+    calculate_cutoff, FastFixedIn, PolynomialDegree, Resampler, SincFixedIn,
+    SincInterpolationParameters, SincInterpolationType, WindowFunction,
+};
+use serde::{Deserialize, Serialize};
+
+const RESAMPLE_CHUNK_FRAMES: usize = 1024;
+
+// Sinc filter length for High; rubato's suggested starting point
+const SINC_LEN: usize = 256;
+const SINC_OVERSAMPLING: usize = 256;
+
+// Trades CPU for alias rejection. Fast and Balanced interpolate a polynomial
+// between input samples with no anti-alias filter, so content near Nyquist
+// folds back when downsampling; High runs a windowed sinc filter first.
+// Only file sources are decoded through these; the realtime paths use
+// LinearResampler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleQuality {
+    // Linear interpolation, for weak mobile CPUs
+    Fast,
+    // Cubic interpolation
+    #[default]
+    Balanced,
+    // 256-tap windowed sinc
+    High,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ResampleError {
+    #[error("Resampler setup failed: {0}")]
+    Construction(#[from] rubato::ResamplerConstructionError),
+    #[error("Resampling failed: {0}")]
+    Process(#[from] rubato::ResampleError),
+}
+
+// Converts a whole interleaved buffer to another sample rate, trimming the
+// resampler's delay so the output lines up with the input
+pub fn resample_interleaved(
+    input: &[f32],
+    channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>, ResampleError> {
+    let channels = channels.max(1);
+    if from_rate == to_rate || input.len() < channels {
+        return Ok(input.to_vec());
+    }
+
+    let ratio = to_rate as f64 / from_rate as f64;
+    match quality {
+        ResampleQuality::Fast => {
+            let resampler = FastFixedIn::<f32>::new(
+                ratio,
+                1.0,
+                PolynomialDegree::Linear,
+                RESAMPLE_CHUNK_FRAMES,
+                channels,
+            )?;
+            let delay = resampler.output_delay();
+            run(resampler, delay, input, channels, from_rate, to_rate)
+        }
+        ResampleQuality::Balanced => {
+            let resampler = FastFixedIn::<f32>::new(
+                ratio,
+                1.0,
+                PolynomialDegree::Cubic,
+                RESAMPLE_CHUNK_FRAMES,
+                channels,
+            )?;
+            let delay = resampler.output_delay();
+            run(resampler, delay, input, channels, from_rate, to_rate)
+        }
+        ResampleQuality::High => {
+            let parameters = SincInterpolationParameters {
+                sinc_len: SINC_LEN,
+                f_cutoff: calculate_cutoff(SINC_LEN, WindowFunction::BlackmanHarris2),
+                oversampling_factor: SINC_OVERSAMPLING,
+                interpolation: SincInterpolationType::Cubic,
+                window: WindowFunction::BlackmanHarris2,
+            };
+            let resampler =
+                SincFixedIn::<f32>::new(ratio, 1.0, parameters, RESAMPLE_CHUNK_FRAMES, channels)?;
+            // SincFixedIn already holds back its first output frames, so its
+            // output starts aligned even though output_delay() reports half
+            // the filter length
+            run(resampler, 0, input, channels, from_rate, to_rate)
+        }
+    }
+}
+
+// Feeds the whole buffer through `resampler` and drops the first `delay`
+// output frames
+fn run<R: Resampler<f32>>(
+    mut resampler: R,
+    delay: usize,
+    input: &[f32],
+    channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+) -> Result<Vec<f32>, ResampleError> {
+    let planar = deinterleave(input, channels);
+    let frames = planar[0].len();
+    let expected = (frames as u64 * to_rate as u64 / from_rate as u64) as usize;
+    let mut output: Vec<Vec<f32>> = vec![Vec::with_capacity(expected + delay); channels];
+
+    let mut pos = 0;
+    while pos < frames {
+        let end = (pos + resampler.input_frames_next()).min(frames);
+        let chunk: Vec<&[f32]> = planar.iter().map(|ch| &ch[pos..end]).collect();
+        let resampled = if end - pos == resampler.input_frames_next() {
+            resampler.process(&chunk, None)?
+        } else {
+            resampler.process_partial(Some(&chunk), None)?
+        };
+        append(&mut output, resampled);
+        pos = end;
+    }
+
+    // Drain what is still buffered inside the resampler
+    while output[0].len() < expected + delay {
+        let resampled = resampler.process_partial::<&[f32]>(None, None)?;
+        if resampled[0].is_empty() {
+            break;
+        }
+        append(&mut output, resampled);
+    }
+
+    for ch in output.iter_mut() {
+        ch.drain(..delay.min(ch.len()));
+        ch.truncate(expected);
+    }
+
+    Ok(interleave(&output))
+}
+
+fn deinterleave(input: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    (0..channels)
+        .map(|c| input.chunks_exact(channels).map(|frame| frame[c]).collect())
+        .collect()
+}
+
+fn interleave(planar: &[Vec<f32>]) -> Vec<f32> {
+    let frames = planar.iter().map(|ch| ch.len()).min().unwrap_or(0);
+    let mut out = Vec::with_capacity(frames * planar.len());
+    for i in 0..frames {
+        out.extend(planar.iter().map(|ch| ch[i]));
+    }
+    out
+}
+
+fn append(output: &mut [Vec<f32>], chunk: Vec<Vec<f32>>) {
+    for (out, ch) in output.iter_mut().zip(chunk) {
+        out.extend(ch);
+    }
+}
+
+// Rate converter for a live stream whose output is asked for in blocks of any
+// size, such as an output device callback running at a different rate from
+// the engine. Linear interpolation only: cheap and adds about one frame of
+// delay, which suits monitoring, though content near Nyquist aliases.
+pub struct LinearResampler {
+    channels: usize,
+    // Input frames per output frame
+    step: f64,
+    // Input not yet passed, interleaved; `position` indexes its frames
+    pending: Vec<f32>,
+    position: f64,
+}
+
+impl LinearResampler {
+    pub fn new(from_rate: u32, to_rate: u32, channels: usize) -> Self {
+        Self {
+            channels: channels.max(1),
+            step: from_rate as f64 / to_rate.max(1) as f64,
+            pending: Vec::new(),
+            position: 0.0,
+        }
+    }
+
+    // Fills `output` (interleaved), asking `fill` for exactly as many input
+    // frames as that takes
+    pub fn process(&mut self, output: &mut [f32], fill: impl FnOnce(&mut [f32])) {
+        let channels = self.channels;
+        let frames = output.len() / channels;
+        if frames == 0 {
+            return;
+        }
+
+        // The last output frame interpolates between input frames
+        // floor(last) and floor(last) + 1
+        let last = self.position + (frames - 1) as f64 * self.step;
+        let needed = last.floor() as usize + 2;
+        let have = self.pending.len() / channels;
+        if needed > have {
+            let start = self.pending.len();
+            self.pending.resize(needed * channels, 0.0);
+            fill(&mut self.pending[start..]);
+        }
+
+        for (i, frame) in output.chunks_exact_mut(channels).enumerate() {
+            let position = self.position + i as f64 * self.step;
+            let index = position.floor() as usize;
+            let frac = (position - index as f64) as f32;
+            let a = &self.pending[index * channels..(index + 1) * channels];
+            let b = &self.pending[(index + 1) * channels..(index + 2) * channels];
+            for ((out, &a), &b) in frame.iter_mut().zip(a).zip(b) {
+                *out = a + (b - a) * frac;
+            }
+        }
+
+        // Keep the frame the next block starts interpolating from
+        self.position += frames as f64 * self.step;
+        self.drain_consumed();
+    }
+
+    // Push-side counterpart of `process`, for a source that delivers input in
+    // blocks of any size (an input device the engine can't open at its own
+    // rate): takes `input` (interleaved) and appends every output frame it
+    // completes to `output`
+    pub fn push(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let channels = self.channels;
+        self.pending.extend_from_slice(input);
+        let have = self.pending.len() / channels;
+
+        // Each output frame needs input frames floor(position) and the one after
+        while (self.position.floor() as usize) + 1 < have {
+            let index = self.position.floor() as usize;
+            let frac = (self.position - index as f64) as f32;
+            let a = &self.pending[index * channels..(index + 1) * channels];
+            let b = &self.pending[(index + 1) * channels..(index + 2) * channels];
+            output.extend(a.iter().zip(b).map(|(&a, &b)| a + (b - a) * frac));
+            self.position += self.step;
+        }
+        self.drain_consumed();
+    }
+
+    fn drain_consumed(&mut self) {
+        let channels = self.channels;
+        let consumed = (self.position.floor() as usize).min(self.pending.len() / channels);
+        self.pending.drain(..consumed * channels);
+        self.position -= consumed as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms(signal: &[f32]) -> f32 {
+        (signal.iter().map(|s| s * s).sum::<f32>() / signal.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn high_quality_aliases_less_than_fast() {
+        // A 9-20 kHz sweep taken from 48 kHz down to 16 kHz lies entirely above
+        // the new 8 kHz Nyquist, so whatever comes out has folded back
+        let mut phase = 0.0f32;
+        let sweep: Vec<f32> = (0..48_000)
+            .map(|n| {
+                let freq = 9_000.0 + 11_000.0 * n as f32 / 48_000.0;
+                phase += 2.0 * std::f32::consts::PI * freq / 48_000.0;
+                0.5 * phase.sin()
+            })
+            .collect();
+
+        let alias = |quality| {
+            let output = resample_interleaved(&sweep, 1, 48_000, 16_000, quality).unwrap();
+            rms(&output[1_000..output.len() - 1_000])
+        };
+        let (fast, high) = (alias(ResampleQuality::Fast), alias(ResampleQuality::High));
+        assert!(high < fast * 0.05, "high {} vs fast {}", high, fast);
+    }
+
+    #[test]
+    fn every_quality_keeps_an_in_band_tone() {
+        let tone: Vec<f32> = (0..44_100)
+            .map(|n| (2.0 * std::f32::consts::PI * 440.0 * n as f32 / 44_100.0).sin())
+            .collect();
+        for quality in [
+            ResampleQuality::Fast,
+            ResampleQuality::Balanced,
+            ResampleQuality::High,
+        ] {
+            let output = resample_interleaved(&tone, 1, 44_100, 48_000, quality).unwrap();
+            assert_eq!(output.len(), 48_000);
+            let steady = &output[500..47_500];
+            assert!(
+                (rms(steady) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01,
+                "{:?} changed the level",
+                quality
+            );
+            // Still 440 Hz: two zero crossings per cycle
+            let crossings = steady
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count();
+            assert!(
+                (crossings as i32 - 862).abs() <= 2,
+                "{:?} has {} zero crossings",
+                quality,
+                crossings
+            );
+        }
+    }
+}
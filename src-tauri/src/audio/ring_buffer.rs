@@ -0,0 +1,144 @@
+// Regroups interleaved audio arriving in arbitrary chunks (cpal delivers
+// whatever the device chooses) into fixed-size blocks, so effects, metering
+// and the Opus encoder always see the same frame count. It runs in the
+// device callback, so it never allocates after construction.
+
+// Largest source chunk, in frames, that is guaranteed to fit on top of a
+// partial block. Device callbacks (including resampled Bluetooth input) stay
+// well below it; anything beyond is dropped and counted as an overrun.
+const MAX_CHUNK_FRAMES: usize = 8192;
+
+pub struct RingBuffer {
+    data: Vec<f32>,
+    read: usize,
+    len: usize,
+    block_len: usize,
+    overruns: u64,
+}
+
+impl RingBuffer {
+    // `block_frames` frames of `channels` interleaved samples per block
+    pub fn new(block_frames: usize, channels: usize) -> Self {
+        let channels = channels.max(1);
+        let block_len = block_frames.max(1) * channels;
+        Self {
+            // At most one partial block is left between pushes, so this is
+            // the worst case; at least two full blocks either way
+            data: vec![0.0; (block_len + MAX_CHUNK_FRAMES * channels).max(block_len * 2)],
+            read: 0,
+            len: 0,
+            block_len,
+            overruns: 0,
+        }
+    }
+
+    // Samples per block, across all channels
+    pub fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    // Samples buffered but not yet handed out
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.read = 0;
+        self.len = 0;
+    }
+
+    // Buffers as much of `samples` as fits. The rest is dropped rather than
+    // growing the buffer, and counted as an overrun.
+    pub fn push(&mut self, samples: &[f32]) {
+        let capacity = self.data.len();
+        let samples = if self.len + samples.len() > capacity {
+            self.overruns += 1;
+            &samples[..capacity - self.len]
+        } else {
+            samples
+        };
+
+        let write = (self.read + self.len) % capacity;
+        let first = samples.len().min(capacity - write);
+        self.data[write..write + first].copy_from_slice(&samples[..first]);
+        self.data[..samples.len() - first].copy_from_slice(&samples[first..]);
+        self.len += samples.len();
+    }
+
+    // Moves the next full block into `out`; false (and `out` untouched) until
+    // enough samples have been pushed
+    pub fn pop_block(&mut self, out: &mut Vec<f32>) -> bool {
+        if self.len < self.block_len {
+            return false;
+        }
+
+        let capacity = self.data.len();
+        let first = self.block_len.min(capacity - self.read);
+        out.clear();
+        out.extend_from_slice(&self.data[self.read..self.read + first]);
+        out.extend_from_slice(&self.data[..self.block_len - first]);
+
+        self.read = (self.read + self.block_len) % capacity;
+        self.len -= self.block_len;
+        true
+    }
+
+    // Pushes that lost samples since the last call
+    pub fn take_overruns(&mut self) -> u64 {
+        std::mem::take(&mut self.overruns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irregular_chunks_come_out_as_steady_blocks() {
+        let mut ring = RingBuffer::new(480, 2);
+        let mut next = 0.0f32;
+        let mut expected = 0.0f32;
+        let mut block = Vec::new();
+        let mut blocks = 0;
+
+        for frames in [7usize, 1_000, 3, 4_096, 480, 1, 8_191, 250]
+            .iter()
+            .cycle()
+            .take(200)
+        {
+            let chunk: Vec<f32> = (0..frames * 2)
+                .map(|_| {
+                    next += 1.0;
+                    next
+                })
+                .collect();
+            ring.push(&chunk);
+            while ring.pop_block(&mut block) {
+                assert_eq!(block.len(), 960);
+                for &sample in &block {
+                    expected += 1.0;
+                    assert_eq!(sample, expected);
+                }
+                blocks += 1;
+            }
+            assert!(ring.len() < ring.block_len());
+        }
+        assert!(blocks > 100);
+        assert_eq!(ring.take_overruns(), 0);
+    }
+
+    #[test]
+    fn oversized_chunk_is_cut_and_counted() {
+        let mut ring = RingBuffer::new(480, 2);
+        let capacity = (480 + MAX_CHUNK_FRAMES) * 2;
+        ring.push(&vec![1.0; capacity + 10]);
+
+        assert_eq!(ring.len(), capacity);
+        assert_eq!(ring.take_overruns(), 1);
+        assert_eq!(ring.take_overruns(), 0);
+    }
+}
@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+// RFC 7587: Opus always uses a 48 kHz RTP clock, whatever the input rate
+pub const RTP_OPUS_CLOCK_RATE: u32 = 48000;
+// First dynamic payload type, the conventional choice for Opus
+pub const RTP_OPUS_PAYLOAD_TYPE: u8 = 111;
+
+const RTP_VERSION: u8 = 2;
+const RTP_HEADER_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkFormat {
+    // Bare Opus packets
+    #[default]
+    Raw,
+    // Opus wrapped in RTP headers for media servers and WebRTC gateways
+    Rtp,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum RtpError {
+    #[error("Packet too short: {0} bytes")]
+    TooShort(usize),
+    #[error("Unsupported RTP version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("Invalid padding length")]
+    InvalidPadding,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtpPacket {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub payload: Vec<u8>,
+}
+
+pub struct RtpPacketizer {
+    payload_type: u8,
+    ssrc: u32,
+    sequence: u16,
+    timestamp: u32,
+    first_packet: bool,
+}
+
+impl RtpPacketizer {
+    // Sequence number, timestamp and SSRC start random as RFC 3550 recommends
+    pub fn new(payload_type: u8) -> Self {
+        Self {
+            payload_type: payload_type & 0x7f,
+            ssrc: rand::random(),
+            sequence: rand::random(),
+            timestamp: rand::random(),
+            first_packet: true,
+        }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    // Wraps one Opus frame; `duration_48k` is the frame length in 48 kHz ticks
+    pub fn packetize(&mut self, payload: &[u8], duration_48k: u32) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(RTP_HEADER_LEN + payload.len());
+
+        // The marker bit flags the first packet of a talkspurt
+        let marker = if self.first_packet { 0x80 } else { 0x00 };
+        self.first_packet = false;
+
+        packet.push(RTP_VERSION << 6);
+        packet.push(marker | self.payload_type);
+        packet.extend_from_slice(&self.sequence.to_be_bytes());
+        packet.extend_from_slice(&self.timestamp.to_be_bytes());
+        packet.extend_from_slice(&self.ssrc.to_be_bytes());
+        packet.extend_from_slice(payload);
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(duration_48k);
+
+        packet
+    }
+}
+
+pub struct RtpDepacketizer;
+
+impl RtpDepacketizer {
+    pub fn depacketize(packet: &[u8]) -> Result<RtpPacket, RtpError> {
+        if packet.len() < RTP_HEADER_LEN {
+            return Err(RtpError::TooShort(packet.len()));
+        }
+
+        let version = packet[0] >> 6;
+        if version != RTP_VERSION {
+            return Err(RtpError::UnsupportedVersion(version));
+        }
+
+        let has_padding = packet[0] & 0x20 != 0;
+        let has_extension = packet[0] & 0x10 != 0;
+        let csrc_count = (packet[0] & 0x0f) as usize;
+
+        let mut offset = RTP_HEADER_LEN + csrc_count * 4;
+        if has_extension {
+            if packet.len() < offset + 4 {
+                return Err(RtpError::TooShort(packet.len()));
+            }
+            let words = u16::from_be_bytes([packet[offset + 2], packet[offset + 3]]) as usize;
+            offset += 4 + words * 4;
+        }
+
+        let mut end = packet.len();
+        if has_padding {
+            let padding = packet[end - 1] as usize;
+            if padding == 0 || offset + padding > end {
+                return Err(RtpError::InvalidPadding);
+            }
+            end -= padding;
+        }
+
+        if offset > end {
+            return Err(RtpError::TooShort(packet.len()));
+        }
+
+        Ok(RtpPacket {
+            marker: packet[1] & 0x80 != 0,
+            payload_type: packet[1] & 0x7f,
+            sequence: u16::from_be_bytes([packet[2], packet[3]]),
+            timestamp: u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]),
+            ssrc: u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]),
+            payload: packet[offset..end].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_fields_round_trip() {
+        let mut packetizer = RtpPacketizer::new(RTP_OPUS_PAYLOAD_TYPE);
+        let bytes = packetizer.packetize(&[1, 2, 3], 960);
+
+        assert_eq!(bytes.len(), RTP_HEADER_LEN + 3);
+        assert_eq!(bytes[0], RTP_VERSION << 6);
+        let packet = RtpDepacketizer::depacketize(&bytes).unwrap();
+        assert!(packet.marker);
+        assert_eq!(packet.payload_type, RTP_OPUS_PAYLOAD_TYPE);
+        assert_eq!(packet.ssrc, packetizer.ssrc());
+        assert_eq!(packet.payload, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn sequence_and_timestamp_advance_per_packet() {
+        let mut packetizer = RtpPacketizer::new(RTP_OPUS_PAYLOAD_TYPE);
+        let packets: Vec<RtpPacket> = [960, 960, 480, 2_880]
+            .iter()
+            .map(|&duration| {
+                RtpDepacketizer::depacketize(&packetizer.packetize(&[0], duration)).unwrap()
+            })
+            .collect();
+
+        for (pair, duration) in packets.windows(2).zip([960, 960, 480]) {
+            assert!(!pair[1].marker);
+            assert_eq!(pair[1].sequence, pair[0].sequence.wrapping_add(1));
+            assert_eq!(pair[1].timestamp, pair[0].timestamp.wrapping_add(duration));
+            assert_eq!(pair[1].ssrc, pair[0].ssrc);
+        }
+    }
+
+    #[test]
+    fn rejects_short_and_foreign_packets() {
+        assert_eq!(
+            RtpDepacketizer::depacketize(&[0x80; 4]),
+            Err(RtpError::TooShort(4))
+        );
+        assert_eq!(
+            RtpDepacketizer::depacketize(&[0x40; 12]),
+            Err(RtpError::UnsupportedVersion(1))
+        );
+    }
+}
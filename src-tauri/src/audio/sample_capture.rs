@@ -0,0 +1,108 @@
+// A short in-memory grab of what a stream sends, for waveform previews and
+// debugging without a recording. The pipeline appends each processed block
+// until the requested length is reached, then hands the samples over and
+// keeps a copy as the stream's last capture for get_waveform_preview.
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+// Longest capture; 10 s of 48 kHz stereo is under 4 MB of samples
+pub const MAX_SAMPLE_CAPTURE_MS: u32 = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedSample {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Interleaved, exactly duration_ms worth of frames
+    pub samples: Vec<f32>,
+}
+
+pub struct SampleCapture {
+    sample_rate: u32,
+    channels: u16,
+    samples: Vec<f32>,
+    wanted: usize,
+    done: Option<oneshot::Sender<CapturedSample>>,
+}
+
+impl SampleCapture {
+    // Clamps `duration_ms` to 1..=MAX_SAMPLE_CAPTURE_MS
+    pub fn new(
+        duration_ms: u32,
+        sample_rate: u32,
+        channels: u16,
+    ) -> (Self, oneshot::Receiver<CapturedSample>) {
+        let duration_ms = duration_ms.clamp(1, MAX_SAMPLE_CAPTURE_MS);
+        let frames = (duration_ms as u64 * sample_rate as u64 / 1000) as usize;
+        let wanted = frames * channels as usize;
+        let (done, rx) = oneshot::channel();
+        let capture = Self {
+            sample_rate,
+            channels,
+            samples: Vec::with_capacity(wanted),
+            wanted,
+            done: Some(done),
+        };
+        (capture, rx)
+    }
+
+    // Appends as much of `block` as still fits. Once the capture is full it
+    // is handed over and a copy returned; further pushes are ignored.
+    pub fn push(&mut self, block: &[f32]) -> Option<CapturedSample> {
+        let done = self.done.take()?;
+        let take = block.len().min(self.wanted - self.samples.len());
+        self.samples.extend_from_slice(&block[..take]);
+        if self.samples.len() < self.wanted {
+            self.done = Some(done);
+            return None;
+        }
+        let sample = CapturedSample {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            samples: std::mem::take(&mut self.samples),
+        };
+        // The caller may have given up waiting
+        let _ = done.send(sample.clone());
+        Some(sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capture_holds_exactly_the_requested_duration() {
+        // 250 ms isn't a whole number of 20 ms blocks, so the last one is cut
+        let (mut capture, mut rx) = SampleCapture::new(250, 48_000, 2);
+        let block = vec![0.5f32; 960 * 2];
+        let mut blocks = 1;
+        let kept = loop {
+            if let Some(sample) = capture.push(&block) {
+                break sample;
+            }
+            assert!(rx.try_recv().is_err());
+            blocks += 1;
+        };
+
+        assert_eq!(blocks, 13);
+        let sample = rx.try_recv().unwrap();
+        assert_eq!(sample.samples.len(), 250 * 48 * 2);
+        assert_eq!(kept.samples, sample.samples);
+        assert!(capture.push(&block).is_none());
+    }
+
+    #[test]
+    fn duration_is_bounded() {
+        let mut block = vec![0.0f32; 48_000];
+        let (mut capture, _rx) = SampleCapture::new(u32::MAX, 48_000, 1);
+        let mut seconds = 1;
+        while capture.push(&block).is_none() {
+            seconds += 1;
+        }
+        assert_eq!(seconds as u32 * 1000, MAX_SAMPLE_CAPTURE_MS);
+
+        block.truncate(48);
+        let (mut capture, _rx) = SampleCapture::new(0, 48_000, 1);
+        assert_eq!(capture.push(&block).unwrap().samples.len(), 48);
+    }
+}
@@ -0,0 +1,195 @@
+// First-run self-test: a test tone through a fixed effects chain, a scratch
+// Opus encoder and the engine's decoder, then the meters. Each stage checks
+// its own output, so the report says where the audio stack breaks without
+// needing a microphone.
+use super::{
+    linear_to_db, CompressorEffect, EffectParams, EffectSlot, EffectType, EffectsChain,
+    EqualizerEffect, LevelMeter, MeterBallistics, TruePeakMeter,
+};
+use serde::{Deserialize, Serialize};
+
+pub const SELFTEST_TONE_HZ: f32 = 1000.0;
+pub const SELFTEST_TONE_DB: f32 = -12.0;
+
+// Long enough for the compressor and the meter ballistics to settle
+pub const SELFTEST_SECS: f32 = 1.0;
+
+pub const SELFTEST_BITRATE: u32 = 128_000;
+
+// A pure tone at this bitrate decodes far cleaner; anything under this means
+// the codec is mangling the audio, not just coloring it
+pub const MIN_SELFTEST_SNR_DB: f32 = 10.0;
+
+// Quieter than this counts as silence
+const SILENT_PEAK_DB: f32 = -60.0;
+
+// How far the settled RMS meter may sit from the signal it measured
+const METER_TOLERANCE_DB: f32 = 3.0;
+
+pub const SELFTEST_STAGES: [&str; 4] = ["source", "effects", "codec", "metering"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelfTestStage {
+    pub name: String,
+    pub passed: bool,
+    // What was measured, or why the stage failed or was skipped
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub stages: Vec<SelfTestStage>,
+}
+
+impl SelfTestReport {
+    // Records the next stage; returns whether it passed
+    pub fn record(&mut self, result: Result<String, String>) -> bool {
+        let name = SELFTEST_STAGES[self.stages.len()].to_string();
+        let passed = result.is_ok();
+        let detail = result.unwrap_or_else(|e| e);
+        self.stages.push(SelfTestStage {
+            name,
+            passed,
+            detail,
+        });
+        passed
+    }
+
+    // Each stage feeds the next, so the ones after a failure are skipped
+    pub fn finish(mut self) -> Self {
+        for name in &SELFTEST_STAGES[self.stages.len()..] {
+            self.stages.push(SelfTestStage {
+                name: name.to_string(),
+                passed: false,
+                detail: "Skipped after an earlier stage failed".to_string(),
+            });
+        }
+        self.passed = self.stages.iter().all(|stage| stage.passed);
+        self
+    }
+}
+
+// A 1 kHz presence lift into a compressor, both at their usual settings, so
+// the chain does something measurable without any user preset involved
+pub fn selftest_chain(sample_rate: u32) -> EffectsChain {
+    let mut params = EffectParams::new();
+    params.set("sample_rate".to_string(), sample_rate as f32);
+    let mut eq_params = params.clone();
+    eq_params.set("band_5".to_string(), 3.0);
+
+    let mut chain = EffectsChain::new();
+    chain.push(EffectSlot::new(
+        EffectType::Eq.key(),
+        Box::new(EqualizerEffect::new(eq_params)),
+    ));
+    chain.push(EffectSlot::new(
+        EffectType::Compressor.key(),
+        Box::new(CompressorEffect::new(params)),
+    ));
+    chain
+}
+
+// Fails on non-finite samples, silence or clipping; otherwise describes the level
+pub fn check_signal(samples: &[f32]) -> Result<String, String> {
+    if samples.iter().any(|s| !s.is_finite()) {
+        return Err("Produced NaN or infinite samples".to_string());
+    }
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    let peak_db = linear_to_db(peak);
+    if peak_db < SILENT_PEAK_DB {
+        return Err("Output is silent".to_string());
+    }
+    if peak >= 1.0 {
+        return Err(format!("Output clips at {:.1} dBFS", peak_db));
+    }
+    Ok(format!(
+        "Peak {:.1} dBFS, RMS {:.1} dBFS",
+        peak_db,
+        linear_to_db(rms(samples))
+    ))
+}
+
+// Runs the level and true-peak meters over `samples` block by block, as the
+// pipeline does, and checks they end up reading the signal they were fed
+pub fn check_meters(
+    samples: &[f32],
+    channels: usize,
+    block_frames: usize,
+    sample_rate: u32,
+) -> Result<String, String> {
+    let block_len = block_frames.max(1) * channels.max(1);
+    let block_ms = block_frames as f32 / sample_rate as f32 * 1000.0;
+    let mut meter = LevelMeter::new(MeterBallistics::default());
+    let mut true_peak_meter = TruePeakMeter::new(channels);
+
+    let (mut meter_peak, mut meter_rms, mut true_peak) = (0.0f32, 0.0f32, 0.0f32);
+    for block in samples.chunks_exact(block_len) {
+        (meter_peak, meter_rms) = meter.process(block, block_ms);
+        true_peak = true_peak.max(true_peak_meter.process(block));
+    }
+
+    let last_block = samples
+        .chunks_exact(block_len)
+        .last()
+        .ok_or("Not enough audio to meter")?;
+    let expected_rms_db = linear_to_db(rms(last_block));
+    let (peak_db, rms_db, true_peak_db) = (
+        linear_to_db(meter_peak),
+        linear_to_db(meter_rms),
+        linear_to_db(true_peak),
+    );
+    if rms_db < SILENT_PEAK_DB {
+        return Err("Level meter never moved".to_string());
+    }
+    if (rms_db - expected_rms_db).abs() > METER_TOLERANCE_DB {
+        return Err(format!(
+            "Level meter reads {:.1} dBFS RMS for a {:.1} dBFS signal",
+            rms_db, expected_rms_db
+        ));
+    }
+    if true_peak_db + 0.1 < peak_db {
+        return Err(format!(
+            "True peak {:.1} dBTP is below the sample peak {:.1} dBFS",
+            true_peak_db, peak_db
+        ));
+    }
+    Ok(format!(
+        "RMS {:.1} dBFS, peak {:.1} dBFS, true peak {:.1} dBTP",
+        rms_db, peak_db, true_peak_db
+    ))
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_after_a_failure_are_skipped() {
+        let mut report = SelfTestReport::default();
+        assert!(!report.record(check_signal(&[0.0; 1_920])));
+        let report = report.finish();
+
+        assert!(!report.passed);
+        assert_eq!(report.stages.len(), SELFTEST_STAGES.len());
+        assert_eq!(report.stages[0].detail, "Output is silent");
+        assert!(report.stages[1..]
+            .iter()
+            .all(|stage| !stage.passed && stage.detail.starts_with("Skipped")));
+    }
+
+    #[test]
+    fn broken_signals_and_meters_fail_their_stage() {
+        assert!(check_signal(&[0.5, f32::NAN]).is_err());
+        assert!(check_signal(&[1.2, 0.0]).is_err());
+        assert!(check_signal(&[0.25, -0.25]).is_ok());
+        assert!(check_meters(&[0.0; 1_920 * 10], 2, 960, 48_000).is_err());
+    }
+}
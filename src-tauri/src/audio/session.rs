@@ -0,0 +1,363 @@
+// One independent stream. Each session has its own source and capture, encoder,
+// effects chain, broadcast channels and sinks, so a mic can go out live while a
+// file source feeds a second output. The engine's talent controls (input gain,
+// mute, push-to-talk) apply to every session.
+use super::{
+    content_signal, new_encoder, AdaptiveBitrateConfig, AudioError, AudioLevels, BitrateController,
+    CaptureTiming, CapturedSample, Codec, ContentClassifier, ContentType, EffectsChain,
+    EncodedFrame, Encoder, EncoderOutput, IcecastSink, LevelMeter, MeterBallistics, MonitorBuffer,
+    MusicBus, PacketLossConfig, Recorder, SampleCapture, SinkFormat, Source, SourceWorker,
+    SpectrumAnalyzer, StatsTracker, StreamId, WebRtcPeer,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StreamStatus {
+    Live,
+    Stopped,
+    Paused,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamSummary {
+    pub id: StreamId,
+    pub status: StreamStatus,
+    pub source: Source,
+    pub codec: Codec,
+    // None while the encoder runs at its maximum
+    pub bitrate: Option<u32>,
+    // Duration of each encoded packet
+    pub frame_size_ms: f32,
+    pub selected: bool,
+}
+
+pub struct StreamSession {
+    pub(super) source: Source,
+    pub(super) encoder: Arc<Mutex<Box<dyn Encoder>>>,
+    pub(super) bitrate: Option<u32>,
+    // Frames per block and Opus packet; None uses the engine's buffer size
+    pub(super) block_frames: Option<usize>,
+    // Set while adaptive bitrate is on for this stream
+    pub(super) bitrate_controller: Option<BitrateController>,
+    // Set while auto signal is on; the pipeline feeds it and retunes the encoders
+    pub(super) content_classifier: Arc<Mutex<Option<ContentClassifier>>>,
+    pub(super) broadcast_tx: broadcast::Sender<Vec<u8>>,
+    pub(super) frames_tx: broadcast::Sender<EncodedFrame>,
+    pub(super) sink_format: Arc<Mutex<SinkFormat>>,
+    pub(super) packet_loss: Arc<Mutex<PacketLossConfig>>,
+    pub(super) stream_stats: Arc<Mutex<StatsTracker>>,
+    pub(super) effects_chain: Arc<Mutex<EffectsChain>>,
+    pub(super) is_paused: Arc<Mutex<bool>>,
+    pub(super) current_levels: Arc<Mutex<AudioLevels>>,
+    pub(super) level_meter: Arc<Mutex<LevelMeter>>,
+    // Same ballistics as level_meter, ahead of the chain
+    pub(super) input_meter: Arc<Mutex<LevelMeter>>,
+    pub(super) spectrum: Arc<Mutex<SpectrumAnalyzer>>,
+    pub(super) monitor: Arc<Mutex<MonitorBuffer>>,
+    pub(super) recorder: Arc<Mutex<Option<Recorder>>>,
+    pub(super) sample_capture: Arc<Mutex<Option<SampleCapture>>>,
+    // The most recent capture to finish, for waveform previews
+    pub(super) last_sample_capture: Arc<Mutex<Option<CapturedSample>>>,
+    pub(super) music_bus: Arc<Mutex<Option<MusicBus>>>,
+    pub(super) stream: Arc<Mutex<Option<cpal::Stream>>>,
+    pub(super) source_worker: Option<SourceWorker>,
+    pub(super) input_stream_config: Option<cpal::StreamConfig>,
+    pub(super) capture_timing: Arc<Mutex<CaptureTiming>>,
+    pub(super) icecast_sink: Option<IcecastSink>,
+    // Extra encodes at other bitrates, each with its own Icecast sink
+    pub(super) encoder_outputs: Arc<Mutex<Vec<EncoderOutput>>>,
+    pub(super) output_sinks: HashMap<u32, IcecastSink>,
+    pub(super) next_output_id: u32,
+    pub(super) webrtc_peer: Option<WebRtcPeer>,
+    // Output stream playing what the WebRTC peer sends back
+    pub(super) webrtc_playout: Option<cpal::Stream>,
+}
+
+impl StreamSession {
+    // An idle session with an empty chain, encoding Opus at maximum bitrate
+    // until the stream asks for something else
+    pub fn new(
+        sample_rate: u32,
+        channels: u16,
+        broadcast_capacity: usize,
+        complexity: i32,
+    ) -> Result<Self, AudioError> {
+        let encoder = new_encoder(Codec::Opus, sample_rate, channels, complexity)?;
+        let (broadcast_tx, _) = broadcast::channel(broadcast_capacity);
+        let (frames_tx, _) = broadcast::channel(broadcast_capacity);
+
+        Ok(Self {
+            source: Source::default(),
+            encoder: Arc::new(Mutex::new(encoder)),
+            bitrate: None,
+            block_frames: None,
+            bitrate_controller: None,
+            content_classifier: Arc::new(Mutex::new(None)),
+            broadcast_tx,
+            frames_tx,
+            sink_format: Arc::new(Mutex::new(SinkFormat::default())),
+            packet_loss: Arc::new(Mutex::new(PacketLossConfig::default())),
+            stream_stats: Arc::new(Mutex::new(StatsTracker::default())),
+            effects_chain: Arc::new(Mutex::new(EffectsChain::new())),
+            is_paused: Arc::new(Mutex::new(false)),
+            current_levels: Arc::new(Mutex::new(AudioLevels::default())),
+            level_meter: Arc::new(Mutex::new(LevelMeter::new(MeterBallistics::default()))),
+            input_meter: Arc::new(Mutex::new(LevelMeter::new(MeterBallistics::default()))),
+            spectrum: Arc::new(Mutex::new(SpectrumAnalyzer::new(
+                sample_rate,
+                channels as usize,
+            ))),
+            monitor: Arc::new(Mutex::new(MonitorBuffer::default())),
+            recorder: Arc::new(Mutex::new(None)),
+            sample_capture: Arc::new(Mutex::new(None)),
+            last_sample_capture: Arc::new(Mutex::new(None)),
+            music_bus: Arc::new(Mutex::new(None)),
+            stream: Arc::new(Mutex::new(None)),
+            source_worker: None,
+            input_stream_config: None,
+            capture_timing: Arc::new(Mutex::new(CaptureTiming::default())),
+            icecast_sink: None,
+            encoder_outputs: Arc::new(Mutex::new(Vec::new())),
+            output_sinks: HashMap::new(),
+            next_output_id: 0,
+            webrtc_peer: None,
+            webrtc_playout: None,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.stream.lock().unwrap().is_some() || self.source_worker.is_some()
+    }
+
+    pub fn status(&self) -> StreamStatus {
+        if !self.is_running() {
+            StreamStatus::Stopped
+        } else if *self.is_paused.lock().unwrap() {
+            StreamStatus::Paused
+        } else {
+            StreamStatus::Live
+        }
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u32) -> Result<(), AudioError> {
+        self.encoder
+            .lock()
+            .unwrap()
+            .set_target_bitrate(Some(bitrate))?;
+        self.bitrate = Some(bitrate);
+        Ok(())
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.encoder.lock().unwrap().codec()
+    }
+
+    // Replaces the main encoder with one for `codec`, keeping the bitrate and
+    // content hint. Only for an idle session: a pipeline sizes its packet
+    // buffer for the encoder it starts with.
+    pub fn set_codec(
+        &mut self,
+        codec: Codec,
+        sample_rate: u32,
+        channels: u16,
+        complexity: i32,
+    ) -> Result<(), AudioError> {
+        if self.codec() == codec {
+            return Ok(());
+        }
+        let mut encoder = new_encoder(codec, sample_rate, channels, complexity)?;
+        encoder.set_target_bitrate(self.bitrate)?;
+        encoder.set_content(self.content())?;
+        *self.encoder.lock().unwrap() = encoder;
+        Ok(())
+    }
+
+    pub fn set_adaptive_bitrate(&mut self, config: Option<AdaptiveBitrateConfig>) {
+        self.bitrate_controller = config.map(BitrateController::new);
+    }
+
+    pub fn set_auto_signal(
+        &mut self,
+        enabled: bool,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<(), AudioError> {
+        let mut classifier = self.content_classifier.lock().unwrap();
+        if enabled {
+            if classifier.is_none() {
+                // The encoders are on music until the classifier says otherwise
+                *classifier = Some(ContentClassifier::new(
+                    sample_rate,
+                    channels as usize,
+                    ContentType::Music,
+                ));
+            }
+            return Ok(());
+        }
+        if classifier
+            .take()
+            .is_some_and(|c| c.content() != ContentType::Music)
+        {
+            apply_content_signal(&self.encoder, &self.encoder_outputs, ContentType::Music)?;
+        }
+        Ok(())
+    }
+
+    // The content the encoders are tuned for
+    pub(super) fn content(&self) -> ContentType {
+        self.content_classifier
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(ContentType::Music, |c| c.content())
+    }
+
+    // Runs the controller over the latest stats and applies what it decides;
+    // returns the new bitrate if it changed
+    pub(super) fn adapt_bitrate(&mut self) -> Result<Option<u32>, AudioError> {
+        if self.status() != StreamStatus::Live {
+            return Ok(None);
+        }
+        let Some(controller) = self.bitrate_controller.as_mut() else {
+            return Ok(None);
+        };
+        let stats = self.stream_stats.lock().unwrap().snapshot();
+        match controller.update(&stats, self.bitrate) {
+            Some(bitrate) => {
+                self.set_bitrate(bitrate)?;
+                Ok(Some(bitrate))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Tears down whatever is currently feeding the pipeline
+    pub(super) fn stop_sources(&mut self) {
+        *self.stream.lock().unwrap() = None;
+        self.source_worker = None;
+        self.input_stream_config = None;
+    }
+
+    pub(super) fn disconnect_icecast(&mut self) {
+        if let Some(sink) = self.icecast_sink.take() {
+            sink.disconnect();
+        }
+    }
+
+    pub(super) fn disconnect_outputs(&mut self) {
+        self.encoder_outputs.lock().unwrap().clear();
+        for (_, sink) in self.output_sinks.drain() {
+            sink.disconnect();
+        }
+    }
+
+    pub(super) async fn close_webrtc(&mut self) {
+        self.webrtc_playout = None;
+        if let Some(peer) = self.webrtc_peer.take() {
+            peer.close().await;
+        }
+    }
+
+    // Stops capture and closes the sinks, waiting for them to finish cleanly
+    pub(super) async fn shutdown(&mut self) {
+        self.stop_sources();
+        if let Some(sink) = self.icecast_sink.take() {
+            sink.close().await;
+        }
+        for (_, sink) in self.output_sinks.drain() {
+            sink.close().await;
+        }
+        self.close_webrtc().await;
+        if let Some(recorder) = self.recorder.lock().unwrap().take() {
+            if let Err(e) = recorder.stop() {
+                log::error!("Failed to finish recording: {}", e);
+            }
+        }
+    }
+}
+
+// Retunes a stream's main encoder and its extra outputs together, so every
+// bitrate of the stream carries the same hint
+pub(super) fn apply_content_signal(
+    encoder: &Mutex<Box<dyn Encoder>>,
+    outputs: &Mutex<Vec<EncoderOutput>>,
+    content: ContentType,
+) -> Result<(), AudioError> {
+    encoder.lock().unwrap().set_content(content)?;
+    let signal = content_signal(content);
+    for output in outputs.lock().unwrap().iter_mut() {
+        output.set_signal(signal)?;
+    }
+    Ok(())
+}
+
+// Sessions keyed by id, one of which is selected. Commands that don't name a
+// stream (effects, meters, stats, sinks) act on the selected one.
+pub struct Sessions {
+    sessions: HashMap<StreamId, StreamSession>,
+    selected: StreamId,
+}
+
+impl Sessions {
+    pub fn new(first: StreamSession) -> Self {
+        let selected = StreamId::new();
+        let mut sessions = HashMap::new();
+        sessions.insert(selected.clone(), first);
+        Self { sessions, selected }
+    }
+
+    pub fn selected_id(&self) -> &StreamId {
+        &self.selected
+    }
+
+    pub fn selected(&self) -> &StreamSession {
+        &self.sessions[&self.selected]
+    }
+
+    pub fn selected_mut(&mut self) -> &mut StreamSession {
+        self.sessions
+            .get_mut(&self.selected)
+            .expect("selected session exists")
+    }
+
+    pub fn select(&mut self, id: &StreamId) -> Result<(), AudioError> {
+        if !self.sessions.contains_key(id) {
+            return Err(AudioError::NoSuchStream(id.clone()));
+        }
+        self.selected = id.clone();
+        Ok(())
+    }
+
+    pub fn get_mut(&mut self, id: &StreamId) -> Result<&mut StreamSession, AudioError> {
+        self.sessions
+            .get_mut(id)
+            .ok_or_else(|| AudioError::NoSuchStream(id.clone()))
+    }
+
+    // Adds `session` and selects it
+    pub fn insert(&mut self, session: StreamSession) -> StreamId {
+        let id = StreamId::new();
+        self.sessions.insert(id.clone(), session);
+        self.selected = id.clone();
+        id
+    }
+
+    // The selected session is never removed, so there is always one to act on
+    pub fn remove(&mut self, id: &StreamId) -> Option<StreamSession> {
+        if *id == self.selected {
+            return None;
+        }
+        self.sessions.remove(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&StreamId, &StreamSession)> {
+        self.sessions.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&StreamId, &mut StreamSession)> {
+        self.sessions.iter_mut()
+    }
+}
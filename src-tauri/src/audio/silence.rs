@@ -0,0 +1,131 @@
+// Signal/silence detection for automation: the frontend can start recording
+// when the user begins speaking and trim or pause on long silences. Follows
+// the smoothed input level (after the input trim, before the chain), and
+// only reports a change once the level has stayed on the other side of the
+// threshold for the hold time, so a breath or a single word doesn't toggle it.
+use serde::{Deserialize, Serialize};
+
+pub const MIN_SILENCE_THRESHOLD_DB: f32 = -80.0;
+pub const MAX_SILENCE_THRESHOLD_DB: f32 = 0.0;
+pub const MAX_SILENCE_HOLD_MS: f32 = 10_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SilenceDetectionConfig {
+    pub enabled: bool,
+    pub threshold_db: f32,
+    pub hold_ms: f32,
+}
+
+impl Default for SilenceDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -45.0,
+            hold_ms: 500.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalState {
+    Signal,
+    Silence,
+}
+
+pub struct SilenceDetector {
+    config: SilenceDetectionConfig,
+    // Starts out silent, so the first speech fires signal-detected
+    state: SignalState,
+    // How long the level has been on the other side of the threshold
+    pending_ms: f32,
+}
+
+impl SilenceDetector {
+    pub fn new(config: SilenceDetectionConfig) -> Self {
+        Self {
+            config,
+            state: SignalState::Silence,
+            pending_ms: 0.0,
+        }
+    }
+
+    // Feeds one block's level; returns the new state once it has held for
+    // `hold_ms`. A changed config starts over from silence.
+    pub fn process(
+        &mut self,
+        config: SilenceDetectionConfig,
+        level_db: f32,
+        block_ms: f32,
+    ) -> Option<SignalState> {
+        if config != self.config {
+            *self = Self::new(config);
+        }
+        if !config.enabled {
+            return None;
+        }
+
+        let observed = if level_db >= config.threshold_db {
+            SignalState::Signal
+        } else {
+            SignalState::Silence
+        };
+        if observed == self.state {
+            self.pending_ms = 0.0;
+            return None;
+        }
+        self.pending_ms += block_ms;
+        if self.pending_ms < config.hold_ms {
+            return None;
+        }
+        self.state = observed;
+        self.pending_ms = 0.0;
+        Some(observed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Events for `levels`, each one a 10 ms block
+    fn events(
+        detector: &mut SilenceDetector,
+        config: SilenceDetectionConfig,
+        levels: &[f32],
+    ) -> Vec<SignalState> {
+        levels
+            .iter()
+            .filter_map(|&level_db| detector.process(config, level_db, 10.0))
+            .collect()
+    }
+
+    #[test]
+    fn signal_held_past_the_hold_time_fires_once() {
+        let config = SilenceDetectionConfig {
+            enabled: true,
+            threshold_db: -40.0,
+            hold_ms: 200.0,
+        };
+        let mut detector = SilenceDetector::new(config);
+        // Silence, a 50 ms blip, silence again, then a second of signal
+        let mut levels = vec![-70.0; 10];
+        levels.extend([-20.0; 5]);
+        levels.extend([-70.0; 5]);
+        levels.extend([-20.0; 100]);
+        assert_eq!(
+            events(&mut detector, config, &levels),
+            [SignalState::Signal]
+        );
+        assert_eq!(
+            events(&mut detector, config, &[-70.0; 30]),
+            [SignalState::Silence]
+        );
+
+        let disabled = SilenceDetectionConfig {
+            enabled: false,
+            ..config
+        };
+        assert!(events(&mut detector, disabled, &[-10.0; 100]).is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+// A point-in-time dump of the selected stream for support tickets and tuning
+// logs: meters, the latest spectrum frame, the effects chain with every
+// parameter, and the device and encoder setup, in one JSON file a user can
+// attach to "my audio sounds wrong".
+use super::{AudioLevels, EffectParameter, Source, Spectrogram, StreamLatency, StreamStats};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioSnapshot {
+    // Milliseconds since the Unix epoch
+    pub captured_at_ms: u64,
+    pub config: SnapshotConfig,
+    pub levels: AudioLevels,
+    // Just the most recent frame
+    pub spectrum: Spectrogram,
+    pub effects: Vec<EffectSnapshot>,
+    pub latency: StreamLatency,
+    pub stats: StreamStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    pub host: String,
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub buffer_size: usize,
+    pub source: Source,
+    // None while the encoder runs at its maximum
+    pub bitrate: Option<u32>,
+    pub complexity: i32,
+    pub input_gain_db: f32,
+    pub output_gain_db: f32,
+    pub muted: bool,
+    pub paused: bool,
+    pub dc_block: bool,
+    pub chain_bypassed: bool,
+    pub monitoring: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectSnapshot {
+    pub index: usize,
+    // Registry key, e.g. "reverb"
+    pub effect_type: String,
+    pub name: String,
+    pub bypassed: bool,
+    pub mix: f32,
+    pub parameters: Vec<EffectParameter>,
+}
+
+impl AudioSnapshot {
+    // Pretty-printed, so it reads fine pasted into a ticket
+    pub fn write_to(&self, path: &Path) -> Result<(), serde_json::Error> {
+        let mut writer = BufWriter::new(File::create(path).map_err(serde_json::Error::io)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush().map_err(serde_json::Error::io)
+    }
+}
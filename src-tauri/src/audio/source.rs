@@ -0,0 +1,263 @@
+use super::channels::convert_channels;
+use super::codec::{CodecError, OpusDecoder};
+use super::db_to_linear;
+use super::ogg::{self, ChannelMapping, OggError};
+use super::pipeline::CapturePipeline;
+use super::resample::{resample_interleaved, ResampleError, ResampleQuality};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// Where the capture pipeline gets its audio
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Source {
+    // The selected input device
+    #[default]
+    Device,
+    // Internally generated sine; `level` is in dBFS
+    TestTone {
+        freq: f32,
+        level: f32,
+    },
+    // Prerecorded WAV or Ogg Opus file, played at real-time rate
+    File {
+        path: String,
+        #[serde(default)]
+        looping: bool,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SourceError {
+    #[error("Unsupported file type: {0}")]
+    UnsupportedFormat(String),
+    #[error("Invalid file: {0}")]
+    InvalidFile(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("WAV error: {0}")]
+    Wav(#[from] hound::Error),
+    #[error("Ogg error: {0}")]
+    Ogg(#[from] OggError),
+    #[error("Opus error: {0}")]
+    Opus(#[from] CodecError),
+    #[error("Resample error: {0}")]
+    Resample(#[from] ResampleError),
+    #[error("Decoding stopped: {0}")]
+    Decode(#[from] tokio::task::JoinError),
+}
+
+// What a file is converted to when it is decoded: the engine's rate and
+// channel count, through its resampler setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeFormat {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub quality: ResampleQuality,
+}
+
+// A file source decoded ahead of starting it
+#[derive(Debug, Clone)]
+pub struct DecodedFile {
+    pub path: String,
+    pub format: DecodeFormat,
+    pub samples: Arc<Vec<f32>>,
+}
+
+// Decodes `path` on tokio's blocking pool, so neither the async runtime nor
+// the engine lock is held while a long file is read and resampled
+pub async fn decode_file(path: String, format: DecodeFormat) -> Result<DecodedFile, SourceError> {
+    tokio::task::spawn_blocking(move || {
+        let samples = load_file(
+            Path::new(&path),
+            format.sample_rate,
+            format.channels,
+            format.quality,
+        )?;
+        Ok(DecodedFile {
+            path,
+            format,
+            samples: Arc::new(samples),
+        })
+    })
+    .await?
+}
+
+// Fills interleaved blocks; returns false once the source has nothing more
+pub type BlockGenerator = Box<dyn FnMut(&mut [f32]) -> bool + Send>;
+
+pub fn test_tone(freq: f32, level_db: f32, sample_rate: f32, channels: usize) -> BlockGenerator {
+    let amplitude = db_to_linear(level_db.min(0.0));
+    let phase_inc = 2.0 * PI * freq.clamp(1.0, sample_rate * 0.49) / sample_rate;
+    let mut phase = 0.0f32;
+
+    Box::new(move |block: &mut [f32]| {
+        for frame in block.chunks_mut(channels.max(1)) {
+            frame.fill(phase.sin() * amplitude);
+            phase = (phase + phase_inc) % (2.0 * PI);
+        }
+        true
+    })
+}
+
+// Plays decoded interleaved audio from the start, wrapping around when
+// looping and padding the final block with silence otherwise. The samples are
+// shared, so a restart reuses a decoded file instead of copying it.
+pub fn file_playback(samples: Arc<Vec<f32>>, looping: bool) -> BlockGenerator {
+    let mut pos = 0;
+
+    Box::new(move |block: &mut [f32]| {
+        if samples.is_empty() || (pos >= samples.len() && !looping) {
+            return false;
+        }
+
+        let mut filled = 0;
+        while filled < block.len() {
+            if pos >= samples.len() {
+                if !looping {
+                    block[filled..].fill(0.0);
+                    break;
+                }
+                pos = 0;
+            }
+            let n = (block.len() - filled).min(samples.len() - pos);
+            block[filled..filled + n].copy_from_slice(&samples[pos..pos + n]);
+            filled += n;
+            pos += n;
+        }
+        true
+    })
+}
+
+// Decodes a whole file and converts it to the engine's rate and channel count
+pub fn load_file(
+    path: &Path,
+    sample_rate: u32,
+    channels: usize,
+    quality: ResampleQuality,
+) -> Result<Vec<f32>, SourceError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let (samples, file_channels, file_rate) = match extension.as_str() {
+        "wav" => decode_wav(path)?,
+        "opus" | "ogg" => decode_ogg_opus(path)?,
+        other => return Err(SourceError::UnsupportedFormat(other.to_string())),
+    };
+
+    let samples = convert_channels(&samples, file_channels, channels.max(1));
+    Ok(resample_interleaved(
+        &samples,
+        channels.max(1),
+        file_rate,
+        sample_rate,
+        quality,
+    )?)
+}
+
+fn decode_wav(path: &Path) -> Result<(Vec<f32>, usize, u32), SourceError> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    Ok((samples, spec.channels.max(1) as usize, spec.sample_rate))
+}
+
+fn decode_ogg_opus(path: &Path) -> Result<(Vec<f32>, usize, u32), SourceError> {
+    let data = std::fs::read(path)?;
+    let mut packets = ogg::read_packets(&data)?.into_iter();
+
+    let head = packets
+        .next()
+        .filter(|p| p.len() >= 19 && p.starts_with(b"OpusHead"))
+        .ok_or_else(|| SourceError::InvalidFile("missing OpusHead".to_string()))?;
+    let mapping = ChannelMapping::from_opus_head(&head).ok_or_else(|| {
+        SourceError::InvalidFile("unsupported OpusHead channel mapping".to_string())
+    })?;
+    let channel_count = mapping.channels() as usize;
+    let pre_skip = u16::from_le_bytes([head[10], head[11]]) as usize;
+
+    // Second packet is OpusTags; everything after it is audio
+    packets.next();
+
+    let mut decoder = OpusDecoder::new(48000, &mapping)?;
+    let mut buffer = vec![0.0f32; 5760 * channel_count];
+    let mut samples = Vec::new();
+    for packet in packets {
+        let frames = decoder.decode_float(&packet, &mut buffer, false)?;
+        samples.extend_from_slice(&buffer[..frames * channel_count]);
+    }
+    samples.drain(..(pre_skip * channel_count).min(samples.len()));
+
+    Ok((samples, channel_count, 48000))
+}
+
+// Drives a generator through the capture pipeline at real-time rate on its
+// own thread, standing in for the cpal input callback
+pub struct SourceWorker {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SourceWorker {
+    pub fn spawn(
+        mut generator: BlockGenerator,
+        mut pipeline: CapturePipeline,
+        sample_rate: f32,
+        channels: usize,
+        block_frames: usize,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+
+        let thread = std::thread::spawn(move || {
+            let block_duration = Duration::from_secs_f64(block_frames as f64 / sample_rate as f64);
+            let mut block = vec![0.0f32; block_frames * channels];
+            let mut deadline = Instant::now();
+
+            while !stop_flag.load(Ordering::Relaxed) {
+                if !generator(&mut block) {
+                    break;
+                }
+                pipeline.process(&block);
+
+                deadline += block_duration;
+                if let Some(wait) = deadline.checked_duration_since(Instant::now()) {
+                    std::thread::sleep(wait);
+                }
+            }
+        });
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for SourceWorker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
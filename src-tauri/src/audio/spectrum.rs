@@ -0,0 +1,183 @@
+// FFT analysis of the outgoing signal for spectrum and spectrogram displays.
+// Blocks are downmixed to mono and cut into non-overlapping Hann-windowed
+// frames; each frame's magnitudes are kept in a fixed-size ring so the UI can
+// draw a scrolling waterfall of the last few seconds.
+use super::{linear_to_db, METER_FLOOR_DB};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// 1024 points gives ~47 Hz bins and ~21 ms frames at 48 kHz
+pub const SPECTRUM_FFT_SIZE: usize = 1024;
+
+// Frames kept for the spectrogram; ~5.5 s at 48 kHz, about 0.5 MB
+pub const MAX_SPECTROGRAM_FRAMES: usize = 256;
+
+// Most recent frames, oldest first. `first_frame` numbers the first one
+// since the stream started, so a poller can tell how far the ring moved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spectrogram {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    // Width of one bin; bin i is centred on i * bin_hz
+    pub bin_hz: f32,
+    pub first_frame: u64,
+    // Magnitudes in dBFS (a full-scale sine reads 0), SPECTRUM_FFT_SIZE / 2 + 1 bins each
+    pub frames: Vec<Vec<f32>>,
+}
+
+pub struct SpectrumAnalyzer {
+    sample_rate: u32,
+    channels: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    // Scales a windowed bin so a full-scale sine comes out at 1.0
+    normalization: f32,
+    pending: Vec<f32>,
+    buffer: Vec<Complex<f32>>,
+    frames: VecDeque<Vec<f32>>,
+    total_frames: u64,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        let window: Vec<f32> = (0..SPECTRUM_FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / SPECTRUM_FFT_SIZE as f32).cos())
+            .collect();
+        let normalization = 2.0 / window.iter().sum::<f32>();
+        Self {
+            sample_rate,
+            channels: channels.max(1),
+            fft: FftPlanner::new().plan_fft_forward(SPECTRUM_FFT_SIZE),
+            window,
+            normalization,
+            pending: Vec::with_capacity(SPECTRUM_FFT_SIZE * 2),
+            buffer: vec![Complex::default(); SPECTRUM_FFT_SIZE],
+            frames: VecDeque::with_capacity(MAX_SPECTROGRAM_FRAMES),
+            total_frames: 0,
+        }
+    }
+
+    // Takes an interleaved block; analyzes every full frame it completes
+    pub fn process(&mut self, samples: &[f32]) {
+        let scale = 1.0 / self.channels as f32;
+        self.pending.extend(
+            samples
+                .chunks_exact(self.channels)
+                .map(|frame| frame.iter().sum::<f32>() * scale),
+        );
+
+        let mut start = 0;
+        while self.pending.len() - start >= SPECTRUM_FFT_SIZE {
+            self.analyze(start);
+            start += SPECTRUM_FFT_SIZE;
+        }
+        self.pending.drain(..start);
+    }
+
+    fn analyze(&mut self, start: usize) {
+        let frame = &self.pending[start..start + SPECTRUM_FFT_SIZE];
+        for ((bin, &sample), &w) in self.buffer.iter_mut().zip(frame).zip(&self.window) {
+            *bin = Complex::new(sample * w, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+
+        let magnitudes = self.buffer[..=SPECTRUM_FFT_SIZE / 2]
+            .iter()
+            .map(|bin| linear_to_db(bin.norm() * self.normalization).max(METER_FLOOR_DB))
+            .collect();
+        if self.frames.len() == MAX_SPECTROGRAM_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(magnitudes);
+        self.total_frames += 1;
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.frames.clear();
+        self.total_frames = 0;
+    }
+
+    // Up to the last `frames` frames, oldest first
+    pub fn spectrogram(&self, frames: usize) -> Spectrogram {
+        let count = frames.min(self.frames.len());
+        let skip = self.frames.len() - count;
+        Spectrogram {
+            sample_rate: self.sample_rate,
+            fft_size: SPECTRUM_FFT_SIZE,
+            bin_hz: self.sample_rate as f32 / SPECTRUM_FFT_SIZE as f32,
+            first_frame: self.total_frames - count as u64,
+            frames: self.frames.iter().skip(skip).cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `frames` FFT frames of a full-scale stereo sine on bin 32 (1500 Hz)
+    fn tone(frames: usize) -> Vec<f32> {
+        (0..frames * SPECTRUM_FFT_SIZE)
+            .flat_map(|i| {
+                let s = (2.0 * PI * 1_500.0 * i as f32 / 48_000.0).sin();
+                [s, s]
+            })
+            .collect()
+    }
+
+    fn loudest_bin(frame: &[f32]) -> (usize, f32) {
+        frame
+            .iter()
+            .copied()
+            .enumerate()
+            .fold((0, f32::MIN), |loudest, (bin, level)| {
+                if level > loudest.1 {
+                    (bin, level)
+                } else {
+                    loudest
+                }
+            })
+    }
+
+    #[test]
+    fn new_frames_shift_in_at_the_end() {
+        let mut analyzer = SpectrumAnalyzer::new(48_000, 2);
+        analyzer.process(&vec![0.0; 4 * SPECTRUM_FFT_SIZE * 2]);
+        let before = analyzer.spectrogram(3);
+        assert_eq!((before.first_frame, before.frames.len()), (1, 3));
+        assert!(before
+            .frames
+            .iter()
+            .flatten()
+            .all(|&level| level == METER_FLOOR_DB));
+
+        // Two frames of tone, plus half a frame that waits for the rest
+        analyzer.process(&tone(2));
+        analyzer.process(&vec![0.0; SPECTRUM_FFT_SIZE]);
+        let after = analyzer.spectrogram(3);
+        assert_eq!(after.first_frame, 3);
+        assert!(after.frames[0].iter().all(|&level| level == METER_FLOOR_DB));
+        for frame in &after.frames[1..] {
+            let (bin, level) = loudest_bin(frame);
+            assert_eq!(bin, 32);
+            assert!(level.abs() < 0.5, "full-scale tone read {} dB", level);
+        }
+    }
+
+    #[test]
+    fn spectrogram_keeps_only_the_newest_frames() {
+        let mut analyzer = SpectrumAnalyzer::new(48_000, 2);
+        analyzer.process(&vec![
+            0.0;
+            (MAX_SPECTROGRAM_FRAMES + 10) * SPECTRUM_FFT_SIZE * 2
+        ]);
+        let spectrogram = analyzer.spectrogram(1_000);
+        assert_eq!(spectrogram.frames.len(), MAX_SPECTROGRAM_FRAMES);
+        assert_eq!(spectrogram.first_frame, 10);
+    }
+}
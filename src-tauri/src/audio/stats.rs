@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Window for the rolling bitrate average
+const BITRATE_WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamStats {
+    pub frames_encoded: u64,
+    pub bytes_encoded: u64,
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub encode_errors: u64,
+    pub dropped_frames: u64,
+    // Packets a consumer (the Icecast sink, an app subscriber) skipped
+    // because it fell more than the broadcast capacity behind
+    pub lagged_packets: u64,
+    // Source chunks that didn't fit the capture ring buffer and lost samples
+    pub capture_overruns: u64,
+    pub avg_bitrate_kbps: f32,
+    pub uptime_secs: f64,
+}
+
+// Accumulates counters from the capture path; rates are derived on read
+#[derive(Debug, Default)]
+pub struct StatsTracker {
+    stats: StreamStats,
+    started: Option<Instant>,
+    recent_packets: VecDeque<(Instant, usize)>,
+}
+
+impl StatsTracker {
+    pub fn start(&mut self) {
+        *self = Self {
+            started: Some(Instant::now()),
+            ..Self::default()
+        };
+    }
+
+    pub fn record_encoded(&mut self, bytes: usize) {
+        let now = Instant::now();
+        self.stats.frames_encoded += 1;
+        self.stats.bytes_encoded += bytes as u64;
+        self.recent_packets.push_back((now, bytes));
+        self.prune(now);
+    }
+
+    pub fn record_sent(&mut self, bytes: usize) {
+        self.stats.packets_sent += 1;
+        self.stats.bytes_sent += bytes as u64;
+    }
+
+    pub fn record_dropped(&mut self) {
+        self.stats.dropped_frames += 1;
+    }
+
+    pub fn record_lagged(&mut self, packets: u64) {
+        self.stats.lagged_packets += packets;
+    }
+
+    pub fn record_capture_overruns(&mut self, overruns: u64) {
+        self.stats.capture_overruns += overruns;
+    }
+
+    // Returns the running error total
+    pub fn record_encode_error(&mut self) -> u64 {
+        self.stats.encode_errors += 1;
+        self.stats.dropped_frames += 1;
+        self.stats.encode_errors
+    }
+
+    pub fn snapshot(&mut self) -> StreamStats {
+        let now = Instant::now();
+        self.prune(now);
+
+        let mut stats = self.stats.clone();
+        if let Some(started) = self.started {
+            let elapsed = now.duration_since(started);
+            stats.uptime_secs = elapsed.as_secs_f64();
+
+            // Average over the window, or over the session if it's younger than that
+            let span = elapsed.min(BITRATE_WINDOW).as_secs_f32();
+            if span > 0.0 {
+                let bytes: usize = self.recent_packets.iter().map(|(_, b)| b).sum();
+                stats.avg_bitrate_kbps = bytes as f32 * 8.0 / 1000.0 / span;
+            }
+        }
+        stats
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(&(at, _)) = self.recent_packets.front() {
+            if now.duration_since(at) > BITRATE_WINDOW {
+                self.recent_packets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
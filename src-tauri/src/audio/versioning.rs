@@ -0,0 +1,34 @@
+// Schema versions of what gets saved outside the engine: chain presets and
+// the engine config. Files written before versioning have no `version` field
+// and read as version 1. Loading upgrades a file one version at a time up to
+// the current one; a file written by a newer app is refused instead of being
+// half understood.
+use super::AudioError;
+
+// 2: every effect carries all of its parameters, defaults included, and the
+// noise gate threshold is in dBFS
+// 3: the compressor threshold is in dBFS too
+pub const PRESET_VERSION: u32 = 3;
+
+// 2: the version field itself; fields added since 1 are filled by serde defaults
+pub const CONFIG_VERSION: u32 = 2;
+
+// What a file without a `version` field is read as
+pub(super) fn unversioned() -> u32 {
+    1
+}
+
+pub(super) fn check_version(
+    kind: &'static str,
+    found: u32,
+    supported: u32,
+) -> Result<(), AudioError> {
+    if found > supported {
+        return Err(AudioError::UnsupportedVersion {
+            kind,
+            found,
+            supported,
+        });
+    }
+    Ok(())
+}
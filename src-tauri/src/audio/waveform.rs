@@ -0,0 +1,116 @@
+// Waveform data small enough to send to the UI. A display only has so many
+// pixels across, so each one gets the lowest and highest sample of the stretch
+// it covers: drawing a bar between them shows every peak the raw samples
+// would, for a few kilobytes instead of megabytes.
+use super::CapturedSample;
+use serde::{Deserialize, Serialize};
+
+// More points than any waveform view is wide
+pub const MAX_WAVEFORM_POINTS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WaveformPoint {
+    pub min: f32,
+    pub max: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaveformPreview {
+    pub sample_rate: u32,
+    pub duration_ms: f32,
+    // One envelope per channel, each `points` long (or the capture's length,
+    // if that is shorter)
+    pub channels: Vec<Vec<WaveformPoint>>,
+}
+
+impl WaveformPreview {
+    pub fn from_capture(capture: &CapturedSample, points: usize) -> Self {
+        let channels = capture.channels.max(1) as usize;
+        let frames = capture.samples.len() / channels;
+        Self {
+            sample_rate: capture.sample_rate,
+            duration_ms: frames as f32 / capture.sample_rate.max(1) as f32 * 1000.0,
+            channels: (0..channels)
+                .map(|ch| {
+                    let channel: Vec<f32> = capture
+                        .samples
+                        .iter()
+                        .skip(ch)
+                        .step_by(channels)
+                        .copied()
+                        .collect();
+                    downsample_waveform(&channel, points)
+                })
+                .collect(),
+        }
+    }
+}
+
+// Splits `samples` into `target_points` near-equal buckets, in order, and
+// returns each bucket's min and max. Never returns more points than samples.
+pub fn downsample_waveform(samples: &[f32], target_points: usize) -> Vec<WaveformPoint> {
+    let points = target_points.min(samples.len());
+    (0..points)
+        .map(|i| {
+            let bucket = &samples[i * samples.len() / points..(i + 1) * samples.len() / points];
+            bucket.iter().fold(
+                WaveformPoint {
+                    min: f32::INFINITY,
+                    max: f32::NEG_INFINITY,
+                },
+                |point, &s| WaveformPoint {
+                    min: point.min.min(s),
+                    max: point.max.max(s),
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_point_bounds_its_bucket() {
+        let samples: Vec<f32> = (0..10_007)
+            .map(|n| (n as f32 * 0.37).sin() * (n as f32 * 0.001).cos())
+            .collect();
+        for target in [1, 7, 100, 999, 10_007, 20_000] {
+            let points = downsample_waveform(&samples, target);
+            assert_eq!(points.len(), target.min(samples.len()));
+            for (i, point) in points.iter().enumerate() {
+                let bucket = &samples
+                    [i * samples.len() / points.len()..(i + 1) * samples.len() / points.len()];
+                assert!(bucket.iter().all(|&s| s >= point.min && s <= point.max));
+                // Tight: the extremes are samples of the bucket
+                assert!(bucket.contains(&point.min) && bucket.contains(&point.max));
+            }
+        }
+        assert!(downsample_waveform(&[], 10).is_empty());
+        assert!(downsample_waveform(&samples, 0).is_empty());
+    }
+
+    #[test]
+    fn preview_splits_the_capture_by_channel() {
+        let capture = CapturedSample {
+            sample_rate: 48_000,
+            channels: 2,
+            samples: (0..9_600)
+                .map(|n| if n % 2 == 0 { 0.5 } else { -0.25 })
+                .collect(),
+        };
+        let preview = WaveformPreview::from_capture(&capture, 64);
+        assert_eq!(preview.channels.len(), 2);
+        assert_eq!(preview.channels[0].len(), 64);
+        assert_eq!(preview.channels[0][3], WaveformPoint { min: 0.5, max: 0.5 });
+        assert_eq!(
+            preview.channels[1][3],
+            WaveformPoint {
+                min: -0.25,
+                max: -0.25
+            }
+        );
+        assert!((preview.duration_ms - 100.0).abs() < 1e-3);
+    }
+}
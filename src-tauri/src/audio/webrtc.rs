@@ -0,0 +1,388 @@
+// Peer-to-peer voice over WebRTC. The engine is always the offerer: the
+// frontend carries the offer, the answer and ICE candidates to the remote peer
+// over its own signaling channel, the stream's Opus frames go out on an audio
+// track, and whatever the peer sends back is decoded into a playout queue.
+use super::fanout::StreamReceiver;
+use super::monitor::MonitorBuffer;
+use super::{
+    AudioEvent, ChannelMapping, EncodedFrame, OpusDecoder, RtpPacketizer, RTP_OPUS_CLOCK_RATE,
+    RTP_OPUS_PAYLOAD_TYPE,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use webrtc::api::interceptor_registry::register_default_interceptors;
+use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_OPUS};
+use webrtc::api::APIBuilder;
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::interceptor::registry::Registry;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+use webrtc::track::track_local::{TrackLocal, TrackLocalWriter};
+use webrtc::track::track_remote::TrackRemote;
+
+// Used when the caller doesn't name any ICE servers
+pub const DEFAULT_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+// WebRTC negotiates Opus as opus/48000/2 only, so multistream layouts can't be sent
+pub const WEBRTC_MAX_CHANNELS: u16 = 2;
+
+// Most received audio queued for playout before the oldest is dropped
+const PLAYOUT_QUEUE_SECS: f32 = 0.2;
+
+// Longest Opus frame (120 ms) at 48 kHz
+const MAX_DECODED_FRAMES: usize = 5760;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WebRtcError {
+    #[error("{0}")]
+    Connection(#[from] webrtc::Error),
+    #[error("No peer connection; create an offer first")]
+    NoPeer,
+    #[error("WebRTC carries mono or stereo only, not {0} channels")]
+    UnsupportedChannels(u16),
+}
+
+// One ICE candidate as exchanged over signaling, in the browser's
+// RTCIceCandidateInit shape
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidate {
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_line_index: Option<u16>,
+}
+
+// Layout of the audio both directions use: the local stream's encoder
+// settings, and what received audio is decoded to for playout
+#[derive(Debug, Clone, Copy)]
+pub struct PeerAudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+pub struct WebRtcPeer {
+    connection: Arc<RTCPeerConnection>,
+    send_task: JoinHandle<()>,
+    playout: Arc<Mutex<MonitorBuffer>>,
+}
+
+impl WebRtcPeer {
+    // Builds the connection with an Opus track fed from `frames` and returns
+    // it with the SDP offer. Local ICE candidates are trickled out as
+    // `ice-candidate` events.
+    pub async fn offer(
+        ice_servers: Vec<String>,
+        format: PeerAudioFormat,
+        frames: StreamReceiver<EncodedFrame>,
+        events: broadcast::Sender<AudioEvent>,
+    ) -> Result<(Self, String), WebRtcError> {
+        if format.channels > WEBRTC_MAX_CHANNELS {
+            return Err(WebRtcError::UnsupportedChannels(format.channels));
+        }
+
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs()?;
+        let registry = register_default_interceptors(Registry::new(), &mut media_engine)?;
+        let api = APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_interceptor_registry(registry)
+            .build();
+
+        let urls = if ice_servers.is_empty() {
+            vec![DEFAULT_STUN_SERVER.to_string()]
+        } else {
+            ice_servers
+        };
+        let config = RTCConfiguration {
+            ice_servers: vec![RTCIceServer {
+                urls,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let connection = Arc::new(api.new_peer_connection(config).await?);
+
+        let track = Arc::new(TrackLocalStaticRTP::new(
+            RTCRtpCodecCapability {
+                mime_type: MIME_TYPE_OPUS.to_string(),
+                clock_rate: RTP_OPUS_CLOCK_RATE,
+                channels: WEBRTC_MAX_CHANNELS,
+                ..Default::default()
+            },
+            "audio".to_string(),
+            "voicecast".to_string(),
+        ));
+        let sender = connection
+            .add_track(Arc::clone(&track) as Arc<dyn TrackLocal + Send + Sync>)
+            .await?;
+
+        // RTCP has to be read for the interceptors (NACK, reports) to run
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 1500];
+            while sender.read(&mut buf).await.is_ok() {}
+        });
+
+        let mut playout = MonitorBuffer::default();
+        playout.attach(
+            (PLAYOUT_QUEUE_SECS * format.sample_rate as f32) as usize * format.channels as usize,
+        );
+        let playout = Arc::new(Mutex::new(playout));
+
+        let remote_playout = playout.clone();
+        connection.on_track(Box::new(move |track, _, _| {
+            tokio::spawn(play_remote_track(track, remote_playout.clone(), format));
+            Box::pin(async {})
+        }));
+
+        let candidate_events = events.clone();
+        connection.on_ice_candidate(Box::new(move |candidate: Option<RTCIceCandidate>| {
+            let events = candidate_events.clone();
+            Box::pin(async move {
+                // None marks the end of gathering
+                let Some(candidate) = candidate else { return };
+                match candidate.to_json() {
+                    Ok(init) => {
+                        let _ = events.send(AudioEvent::IceCandidate {
+                            candidate: init.candidate,
+                            sdp_mid: init.sdp_mid,
+                            sdp_m_line_index: init.sdp_mline_index,
+                        });
+                    }
+                    Err(e) => log::warn!("Cannot serialize local ICE candidate: {}", e),
+                }
+            })
+        }));
+
+        connection.on_peer_connection_state_change(Box::new(
+            move |state: RTCPeerConnectionState| {
+                let event = match state {
+                    RTCPeerConnectionState::Connected => Some(AudioEvent::SinkConnected {
+                        sink: "webrtc".to_string(),
+                    }),
+                    RTCPeerConnectionState::Failed => Some(AudioEvent::SinkFailed {
+                        sink: "webrtc".to_string(),
+                        reason: "Peer connection failed".to_string(),
+                    }),
+                    _ => None,
+                };
+                if let Some(event) = event {
+                    let _ = events.send(event);
+                }
+                Box::pin(async {})
+            },
+        ));
+
+        let offer = connection.create_offer(None).await?;
+        connection.set_local_description(offer.clone()).await?;
+
+        let send_task = tokio::spawn(send_frames(frames, track));
+        Ok((
+            Self {
+                connection,
+                send_task,
+                playout,
+            },
+            offer.sdp,
+        ))
+    }
+
+    // Received audio at the format passed to offer(), for an output stream
+    pub fn playout(&self) -> Arc<Mutex<MonitorBuffer>> {
+        self.playout.clone()
+    }
+
+    pub async fn accept_answer(&self, sdp: String) -> Result<(), WebRtcError> {
+        let answer = RTCSessionDescription::answer(sdp)?;
+        self.connection.set_remote_description(answer).await?;
+        Ok(())
+    }
+
+    pub async fn add_ice_candidate(&self, candidate: IceCandidate) -> Result<(), WebRtcError> {
+        self.connection
+            .add_ice_candidate(RTCIceCandidateInit {
+                candidate: candidate.candidate,
+                sdp_mid: candidate.sdp_mid,
+                sdp_mline_index: candidate.sdp_m_line_index,
+                username_fragment: None,
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn close(self) {
+        self.send_task.abort();
+        self.playout.lock().unwrap().detach();
+        if let Err(e) = self.connection.close().await {
+            log::warn!("Error closing peer connection: {}", e);
+        }
+    }
+}
+
+// Wraps each frame in RTP with the same packetizer the RTP sink format uses;
+// the track rewrites SSRC and payload type to what was negotiated
+async fn send_frames(mut frames: StreamReceiver<EncodedFrame>, track: Arc<TrackLocalStaticRTP>) {
+    let mut packetizer = RtpPacketizer::new(RTP_OPUS_PAYLOAD_TYPE);
+    while let Some(frame) = frames.recv().await {
+        let packet = packetizer.packetize(&frame.data, frame.duration_48k);
+        // Writes before the peer connects are discarded by the track
+        if let Err(e) = track.write(&packet).await {
+            if matches!(e, webrtc::Error::ErrClosedPipe) {
+                break;
+            }
+            log::warn!("WebRTC send failed: {}", e);
+        }
+    }
+}
+
+async fn play_remote_track(
+    track: Arc<TrackRemote>,
+    playout: Arc<Mutex<MonitorBuffer>>,
+    format: PeerAudioFormat,
+) {
+    let channels = format.channels as usize;
+    let mut decoder = match OpusDecoder::new(
+        format.sample_rate,
+        &ChannelMapping::single_stream(format.channels as u8),
+    ) {
+        Ok(decoder) => decoder,
+        Err(e) => {
+            log::error!("Cannot create decoder for remote audio: {}", e);
+            return;
+        }
+    };
+
+    let mut buffer = vec![0.0f32; MAX_DECODED_FRAMES * channels];
+    while let Ok((packet, _)) = track.read_rtp().await {
+        match decoder.decode_float(&packet.payload, &mut buffer, false) {
+            Ok(frames) => playout.lock().unwrap().push(&buffer[..frames * channels]),
+            Err(e) => log::warn!("Dropping undecodable remote packet: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::stats::StatsTracker;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    // A plain webrtc-rs peer standing in for the browser on the far side
+    async fn answering_peer() -> RTCPeerConnection {
+        let mut media_engine = MediaEngine::default();
+        media_engine.register_default_codecs().unwrap();
+        let registry = register_default_interceptors(Registry::new(), &mut media_engine).unwrap();
+        let api = APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_interceptor_registry(registry)
+            .build();
+        api.new_peer_connection(RTCConfiguration::default())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn offer_answer_round_trip_carries_frames() {
+        let (frames_tx, _) = broadcast::channel(64);
+        let (events_tx, mut events) = broadcast::channel(64);
+        let frames = StreamReceiver::new(
+            frames_tx.subscribe(),
+            Arc::new(Mutex::new(StatsTracker::default())),
+            "test peer",
+        );
+        let format = PeerAudioFormat {
+            sample_rate: 48_000,
+            channels: 2,
+        };
+        // A local STUN address keeps the test off the network; host candidates
+        // are all a same-machine connection needs
+        let (peer, offer) = WebRtcPeer::offer(
+            vec!["stun:127.0.0.1:3478".to_string()],
+            format,
+            frames,
+            events_tx,
+        )
+        .await
+        .unwrap();
+        assert!(
+            offer.contains("opus/48000/2"),
+            "offer without Opus:\n{}",
+            offer
+        );
+
+        let remote = answering_peer().await;
+        let (payload_tx, mut payload_rx) = mpsc::channel(1);
+        remote.on_track(Box::new(move |track, _, _| {
+            let payload_tx = payload_tx.clone();
+            tokio::spawn(async move {
+                if let Ok((packet, _)) = track.read_rtp().await {
+                    let _ = payload_tx.send(packet.payload.to_vec()).await;
+                }
+            });
+            Box::pin(async {})
+        }));
+        remote
+            .set_remote_description(RTCSessionDescription::offer(offer).unwrap())
+            .await
+            .unwrap();
+        let answer = remote.create_answer(None).await.unwrap();
+        let mut gathered = remote.gathering_complete_promise().await;
+        remote.set_local_description(answer).await.unwrap();
+        let _ = gathered.recv().await;
+        peer.accept_answer(remote.local_description().await.unwrap().sdp)
+            .await
+            .unwrap();
+
+        // Trickle the engine's candidates across until the connection comes up
+        let connected = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match events.recv().await.unwrap() {
+                    AudioEvent::IceCandidate {
+                        candidate,
+                        sdp_mid,
+                        sdp_m_line_index,
+                    } => {
+                        let init = RTCIceCandidateInit {
+                            candidate,
+                            sdp_mid,
+                            sdp_mline_index: sdp_m_line_index,
+                            username_fragment: None,
+                        };
+                        remote.add_ice_candidate(init).await.unwrap();
+                    }
+                    AudioEvent::SinkConnected { sink } if sink == "webrtc" => break,
+                    _ => {}
+                }
+            }
+        })
+        .await;
+        assert!(connected.is_ok(), "peers never connected");
+
+        // A 20 ms Opus silence frame
+        let frame = EncodedFrame {
+            data: vec![0xf8, 0xff, 0xfe],
+            duration_48k: 960,
+        };
+        let received = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let _ = frames_tx.send(frame.clone());
+                tokio::select! {
+                    payload = payload_rx.recv() => return payload,
+                    _ = tokio::time::sleep(Duration::from_millis(20)) => {}
+                }
+            }
+        })
+        .await;
+        assert_eq!(received.ok().flatten(), Some(frame.data.clone()));
+
+        peer.close().await;
+        remote.close().await.unwrap();
+    }
+}
@@ -1,46 +1,100 @@
-use crate::audio::{AudioEngine, AudioLevels, EffectParams};
-use crate::audio::effects::{CompressorEffect, EqualizerEffect, NoiseGateEffect, ReverbEffect};
+use crate::audio::{
+    available_host_names, device_capabilities, factory_preset_names, source, AdaptiveBitrateConfig,
+    AudioEngine, AudioEvent, AudioLevels, BandLevel, CapturedSample, ChainPreset, Codec,
+    CodecQuality, ContentType, DeviceCapabilities, DitherMode, DuckingConfig, EffectCpuUsage,
+    EffectParameter, EffectParams, EffectPreset, EffectRegistry, EncoderOutputSummary,
+    IceCandidate, IcecastConfig, MeterBallistics, MonitorLimiterConfig, MonitoringAlignment,
+    PacketLossConfig, ParameterChange, ParameterChangeResult, ReconnectPolicy, RecordingSummary,
+    ResampleQuality, SelfTestReport, SilenceDetectionConfig, SinkFormat, Source, Spectrogram,
+    StreamId, StreamLatency, StreamStats, StreamStatus, StreamSummary, StreamValidation,
+    TestSignal, WaveformPreview, BITRATE_ADAPT_INTERVAL, DEFAULT_QUALITY_BITRATES,
+    MAX_SAMPLE_CAPTURE_MS,
+};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::Arc;
-use tauri::State;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::{broadcast, Mutex};
+
+// Bounds for pushed level updates: ~60 fps at the fast end, 1 Hz at the slow end
+const MIN_LEVEL_EVENT_INTERVAL_MS: u64 = 16;
+const MAX_LEVEL_EVENT_INTERVAL_MS: u64 = 1000;
+
+// How much longer than the requested length capture_sample waits for audio
+const SAMPLE_CAPTURE_GRACE: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamConfig {
     pub quality: String,
     pub bitrate: u32,
+    #[serde(default = "default_stream_sample_rate")]
     pub sample_rate: u32,
+    #[serde(default = "default_stream_channels")]
     pub channels: u16,
+    // Where this stream's audio comes from; the selected stream's source if omitted
+    #[serde(default)]
+    pub source: Option<Source>,
+    // Opus packet duration: 2.5, 5, 10, 20, 40 or 60 ms. Omitted uses the
+    // engine's buffer size (20 ms by default).
+    #[serde(default)]
+    pub frame_size_ms: Option<f32>,
+    // "opus" (default) or "aac"; Icecast, WebRTC and RTP framing need Opus
+    #[serde(default)]
+    pub codec: Codec,
+}
+
+fn default_stream_sample_rate() -> u32 {
+    48000
+}
+
+fn default_stream_channels() -> u16 {
+    2
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamInfo {
-    pub id: String,
+    pub id: StreamId,
     pub status: StreamStatus,
     pub quality: String,
     pub bitrate: u32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum StreamStatus {
-    Live,
-    Stopped,
-    Paused,
+    pub codec: Codec,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioDevices {
     pub inputs: Vec<String>,
     pub outputs: Vec<String>,
+    // Whether the engine has a default input/output to use
+    pub has_input_device: bool,
+    pub has_output_device: bool,
+    // Nothing to capture from or play to, so the UI can explain rather than
+    // show empty dropdowns
+    pub no_devices_available: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum EffectType {
-    Eq,
-    Compressor,
-    Reverb,
-    NoiseGate,
+// Decodes a file source on a blocking thread with the engine unlocked, so a
+// long file doesn't stall every other command; the start that follows finds
+// it already decoded. None means the selected stream's own source.
+async fn preload_source(
+    audio_engine: &Mutex<AudioEngine>,
+    source: Option<&Source>,
+) -> Result<(), String> {
+    let (path, format) = {
+        let engine = audio_engine.lock().await;
+        let Source::File { path, .. } = source.unwrap_or(engine.source()) else {
+            return Ok(());
+        };
+        if engine.decoded_file(path).is_some() {
+            return Ok(());
+        }
+        (path.clone(), engine.decode_format())
+    };
+    let file = source::decode_file(path, format)
+        .await
+        .map_err(|e| e.to_string())?;
+    audio_engine.lock().await.store_decoded_file(file);
+    Ok(())
 }
 
 #[tauri::command]
@@ -48,111 +102,1189 @@ pub async fn start_streaming(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
     config: StreamConfig,
 ) -> Result<StreamInfo, String> {
+    preload_source(&audio_engine, config.source.as_ref()).await?;
     let mut engine = audio_engine.lock().await;
-    engine.start_capture().await.map_err(|e| e.to_string())?;
+    let id = engine
+        .start_stream(
+            config.source,
+            config.codec,
+            config.bitrate,
+            config.frame_size_ms,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
 
     Ok(StreamInfo {
-        id: generate_stream_id(),
+        id,
         status: StreamStatus::Live,
         quality: config.quality,
         bitrate: config.bitrate,
+        codec: config.codec,
     })
 }
 
+// Dry run of start_streaming: opens the input and builds the stream without
+// playing it, and checks the encoder takes the bitrate. Nothing is broadcast.
+#[tauri::command]
+pub async fn validate_stream_setup(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    config: StreamConfig,
+) -> Result<StreamValidation, String> {
+    // A file that won't decode is reported by the validation itself
+    let _ = preload_source(&audio_engine, None).await;
+    let engine = audio_engine.lock().await;
+    Ok(engine.validate_stream_setup(
+        config.codec,
+        config.bitrate,
+        config.sample_rate,
+        config.channels,
+        config.frame_size_ms,
+    ))
+}
+
+// Encode/decode round trip of a reference signal at each bitrate (a default
+// sweep if none are given), so the UI can show where quality stops improving
+#[tauri::command]
+pub async fn measure_codec_quality(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    test_signal: TestSignal,
+    bitrates: Option<Vec<u32>>,
+) -> Result<Vec<CodecQuality>, String> {
+    let bitrates = bitrates.unwrap_or_else(|| DEFAULT_QUALITY_BITRATES.to_vec());
+    let engine = audio_engine.lock().await;
+    engine
+        .measure_codec_quality(test_signal, &bitrates)
+        .map_err(|e| e.to_string())
+}
+
+// "Is my audio working?" without a mic: a test tone through a fixed chain, the
+// codec and the meters, with a pass/fail per stage
+#[tauri::command]
+pub async fn run_audio_selftest(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<SelfTestReport, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.run_selftest())
+}
+
+// Lets the selected stream's bitrate follow network conditions between
+// `min_bitrate` and `max_bitrate`; disabling keeps the current bitrate
+#[tauri::command]
+pub async fn set_adaptive_bitrate(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+    min_bitrate: u32,
+    max_bitrate: u32,
+) -> Result<AdaptiveBitrateConfig, String> {
+    let config = AdaptiveBitrateConfig::new(min_bitrate, max_bitrate);
+    let mut engine = audio_engine.lock().await;
+    engine.set_adaptive_bitrate(enabled.then_some(config));
+
+    if engine.needs_bitrate_task() {
+        let engine_state = audio_engine.inner().clone();
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(BITRATE_ADAPT_INTERVAL);
+            loop {
+                ticker.tick().await;
+                engine_state.lock().await.adapt_bitrates();
+            }
+        });
+        engine.set_bitrate_task(task);
+    }
+    Ok(config)
+}
+
+// None while the selected stream's bitrate is fixed
+#[tauri::command]
+pub async fn get_adaptive_bitrate(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Option<AdaptiveBitrateConfig>, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_adaptive_bitrate())
+}
+
+// Lets the selected stream's encoder switch between voice and music tuning as
+// its content changes; each switch emits `signal-changed`
+#[tauri::command]
+pub async fn set_auto_signal(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_auto_signal(enabled).map_err(|e| e.to_string())
+}
+
+// The content the selected stream's encoder is tuned for; None while auto
+// signal is off
+#[tauri::command]
+pub async fn get_auto_signal(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Option<ContentType>, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_auto_signal())
+}
+
+// Without a `stream_id` these act on the selected stream
 #[tauri::command]
 pub async fn stop_streaming(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    stream_id: Option<StreamId>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    let id = stream_id.unwrap_or_else(|| engine.selected_stream().clone());
+    engine.stop_stream(&id).await.map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn pause_streaming(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    stream_id: Option<StreamId>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    let id = stream_id.unwrap_or_else(|| engine.selected_stream().clone());
+    engine
+        .set_stream_paused(&id, true)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn resume_streaming(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    stream_id: Option<StreamId>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    let id = stream_id.unwrap_or_else(|| engine.selected_stream().clone());
+    engine
+        .set_stream_paused(&id, false)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_streams(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Vec<StreamSummary>, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.streams())
+}
+
+// Effect, meter, stats and sink commands target the selected stream
+#[tauri::command]
+pub async fn select_stream(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    stream_id: StreamId,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.select_stream(&stream_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_source(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    source: Source,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_source(source);
+    Ok(())
+}
+
+// Music bed under the mic: a file or tone source mixed in at `level_db`
+// (0 dB if omitted) and ducked while the mic is active. No source removes it.
+#[tauri::command]
+pub async fn set_music_bus(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    source: Option<Source>,
+    level_db: Option<f32>,
+    ducking: Option<DuckingConfig>,
+) -> Result<(), String> {
+    preload_source(&audio_engine, source.as_ref()).await?;
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_music_bus(source, level_db.unwrap_or(0.0), ducking.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_music_level(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    level_db: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_music_level(level_db).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_music_ducking(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    ducking: DuckingConfig,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_music_ducking(ducking).map_err(|e| e.to_string())
+}
+
+// Switches capture to a generated sine so the chain, meters and sinks can be
+// exercised without a microphone
+#[tauri::command]
+pub async fn start_test_tone(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    freq: f32,
+    level: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_source(Source::TestTone { freq, level });
+    engine.start_capture().await.map_err(|e| e.to_string())
+}
+
+// Plays a sine burst on the output device (up to 10 s) to check headphones or
+// monitors; capture and streaming are unaffected
+#[tauri::command]
+pub async fn play_test_tone_output(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    freq: f32,
+    level: f32,
+    duration_ms: u64,
+) -> Result<(), String> {
+    let engine = audio_engine.lock().await;
+    engine
+        .play_test_tone_output(freq, level, duration_ms)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Streams a prerecorded WAV or Ogg Opus file through the effects chain
+#[tauri::command]
+pub async fn start_file_source(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    path: String,
+    looping: bool,
+) -> Result<(), String> {
+    let source = Source::File { path, looping };
+    preload_source(&audio_engine, Some(&source)).await?;
+    let mut engine = audio_engine.lock().await;
+    engine.set_source(source);
+    engine.start_capture().await.map_err(|e| e.to_string())
+}
+
+// 0-10; lower values cut encode CPU (useful on phones and old laptops) for a
+// small loss in quality
+#[tauri::command]
+pub async fn set_opus_complexity(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    complexity: i32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_complexity(complexity).map_err(|e| e.to_string())
+}
+
+// 0-based input device channels to capture, e.g. [0, 1] for the first pair
+// of a multi-input interface; an empty list captures every channel. Applies
+// from the next start.
+#[tauri::command]
+pub async fn set_capture_channels(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    channels: Vec<u16>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_capture_channels(channels)
+        .map_err(|e| e.to_string())
+}
+
+// "fast", "balanced" or "high"; applies to the next file source decode
+#[tauri::command]
+pub async fn set_file_resample_quality(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    quality: ResampleQuality,
 ) -> Result<(), String> {
     let mut engine = audio_engine.lock().await;
-    engine.stop_capture().await.map_err(|e| e.to_string())?;
+    engine.set_file_resample_quality(quality);
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_audio_devices() -> Result<AudioDevices, String> {
-    use cpal::traits::HostTrait;
+pub async fn get_audio_devices(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<AudioDevices, String> {
+    use cpal::traits::{DeviceTrait, HostTrait};
 
-    let host = cpal::default_host();
+    // List devices from the backend the engine is actually using
+    let (host_id, has_input_device, has_output_device) = {
+        let engine = audio_engine.lock().await;
+        (
+            engine.host_id(),
+            engine.has_input_device(),
+            engine.has_output_device(),
+        )
+    };
+    let host = cpal::host_from_id(host_id).map_err(|e| e.to_string())?;
 
-    let input_devices: Vec<String> = host
-        .input_devices()
-        .map_err(|e| e.to_string())?
-        .filter_map(|d| d.name().ok())
-        .collect();
+    // A backend with audio disabled can fail to enumerate at all; that's
+    // reported as no devices rather than an error
+    let input_devices: Vec<String> = match host.input_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            log::warn!("Cannot list input devices: {}", e);
+            Vec::new()
+        }
+    };
 
-    let output_devices: Vec<String> = host
-        .output_devices()
-        .map_err(|e| e.to_string())?
-        .filter_map(|d| d.name().ok())
-        .collect();
+    let output_devices: Vec<String> = match host.output_devices() {
+        Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+        Err(e) => {
+            log::warn!("Cannot list output devices: {}", e);
+            Vec::new()
+        }
+    };
 
     Ok(AudioDevices {
+        no_devices_available: input_devices.is_empty() && output_devices.is_empty(),
         inputs: input_devices,
         outputs: output_devices,
+        has_input_device,
+        has_output_device,
     })
 }
 
+#[tauri::command]
+pub async fn set_output_device(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    name: String,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_output_device(&name).map_err(|e| e.to_string())
+}
+
+// Sample rate ranges, channel counts and sample formats `name` supports for
+// input and output. A device that fails to report returns what could be read.
+#[tauri::command]
+pub async fn get_device_capabilities(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    name: String,
+) -> Result<DeviceCapabilities, String> {
+    let host_id = audio_engine.lock().await.host_id();
+    let host = cpal::host_from_id(host_id).map_err(|e| e.to_string())?;
+    device_capabilities(&host, &name).ok_or_else(|| format!("No audio device named {}", name))
+}
+
+#[tauri::command]
+pub async fn get_available_hosts() -> Result<Vec<String>, String> {
+    Ok(available_host_names())
+}
+
 #[tauri::command]
 pub async fn apply_audio_effect(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
-    effect_type: EffectType,
+    registry: State<'_, EffectRegistry>,
+    effect_type: String,
     params: EffectParams,
 ) -> Result<(), String> {
     let mut engine = audio_engine.lock().await;
 
-    match effect_type {
-        EffectType::Eq => {
-            engine.add_effect(Box::new(EqualizerEffect::new(params)));
-        }
-        EffectType::Compressor => {
-            engine.add_effect(Box::new(CompressorEffect::new(params)));
-        }
-        EffectType::Reverb => {
-            engine.add_effect(Box::new(ReverbEffect::new(params)));
-        }
-        EffectType::NoiseGate => {
-            engine.add_effect(Box::new(NoiseGateEffect::new(params)));
-        }
+    // Time-based effects need the engine rate to convert ms/Hz into samples
+    let mut params = params;
+    if params.get("sample_rate").is_none() {
+        params.set("sample_rate".to_string(), engine.sample_rate() as f32);
     }
 
+    let effect = registry
+        .create(&effect_type, params)
+        .ok_or_else(|| format!("Unknown effect type: {}", effect_type))?;
+    engine.add_effect(&effect_type, effect);
+
     Ok(())
 }
 
 #[tauri::command]
-pub async fn clear_audio_effects(
+pub async fn describe_effect(
+    registry: State<'_, EffectRegistry>,
+    effect_type: String,
+) -> Result<Vec<EffectParameter>, String> {
+    // A throwaway instance built from defaults; the engine's chain is untouched
+    registry
+        .create(&effect_type, EffectParams::default())
+        .map(|effect| effect.get_parameters())
+        .ok_or_else(|| format!("Unknown effect type: {}", effect_type))
+}
+
+#[tauri::command]
+pub async fn list_available_effects(
+    registry: State<'_, EffectRegistry>,
+) -> Result<Vec<String>, String> {
+    Ok(registry.keys())
+}
+
+// Held peak and clip light of each band of the equalizer at `effect_index`
+#[tauri::command]
+pub async fn get_eq_band_levels(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+) -> Result<Vec<BandLevel>, String> {
+    let engine = audio_engine.lock().await;
+    engine
+        .get_eq_band_levels(effect_index)
+        .map_err(|e| e.to_string())
+}
+
+// Fades one effect out of the chain (or back in) over about 10 ms
+#[tauri::command]
+pub async fn set_effect_bypass(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+    bypassed: bool,
 ) -> Result<(), String> {
     let mut engine = audio_engine.lock().await;
-    engine.clear_effects();
+    engine
+        .set_effect_bypass(effect_index, bypassed)
+        .map_err(|e| e.to_string())
+}
+
+// Dry/wet balance of one effect for parallel processing (0 input only, 1
+// effect only); the input is delayed by the effect's latency to stay in phase
+#[tauri::command]
+pub async fn set_effect_mix(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+    mix: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_effect_mix(effect_index, mix)
+        .map_err(|e| e.to_string())
+}
+
+// Silences an effect's tail (reverb, delay, envelopes) without touching its settings
+#[tauri::command]
+pub async fn reset_effect(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.reset_effect(effect_index).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_effect_parameter(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+    name: String,
+    value: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_effect_parameter(effect_index, &name, value)
+        .map_err(|e| e.to_string())
+}
+
+// Applies many parameter changes at once, e.g. when loading a scene. The
+// audio thread sees either none or all of them; the result for each change
+// comes back in the same order.
+#[tauri::command]
+pub async fn apply_parameter_batch(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    changes: Vec<ParameterChange>,
+) -> Result<Vec<ParameterChangeResult>, String> {
+    let mut engine = audio_engine.lock().await;
+    Ok(engine.apply_parameter_batch(&changes))
+}
+
+// Ramp time for gain-like parameters (makeup, mixes, levels) changed while
+// streaming; 0 makes changes instant. Defaults to 20 ms.
+#[tauri::command]
+pub async fn set_parameter_smoothing(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    ms: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_parameter_smoothing(ms);
     Ok(())
 }
 
+// Per-effect timing costs a couple of clock reads per block, so it only runs
+// while the UI asks for it
 #[tauri::command]
-pub async fn get_audio_levels(
+pub async fn set_effect_profiling(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
-) -> Result<AudioLevels, String> {
+    enabled: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_effect_profiling(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_effect_cpu_usage(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Vec<EffectCpuUsage>, String> {
     let engine = audio_engine.lock().await;
-    Ok(engine.get_current_levels())
+    Ok(engine.effect_cpu_usage())
 }
 
+// Bypasses the most expensive effect, with an `effects-overrun` event, when a
+// stream's chain keeps running past its real-time budget
 #[tauri::command]
-pub async fn set_monitoring(
+pub async fn set_overrun_protection(
     audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
     enabled: bool,
 ) -> Result<(), String> {
     let mut engine = audio_engine.lock().await;
-    engine.set_monitoring(enabled);
+    engine.set_overrun_protection(enabled);
     Ok(())
 }
 
-// Helper function to generate stream ID
-fn generate_stream_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
+// Builds the whole chain before swapping it in, so audio never passes through
+// a half-loaded preset. Nothing changes if any effect type is unknown.
+// `version` is the preset's schema version; presets saved without one are
+// read as version 1 and upgraded.
+#[tauri::command]
+pub async fn set_effects_chain(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    registry: State<'_, EffectRegistry>,
+    effects: Vec<EffectPreset>,
+    version: Option<u32>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    let preset = ChainPreset {
+        version: version.unwrap_or(1),
+        effects,
+    };
+    engine
+        .load_chain(&preset, &registry)
+        .map_err(|e| e.to_string())
+}
+
+// Names of the chains that ship with the app, for apply_factory_preset
+#[tauri::command]
+pub async fn list_factory_presets() -> Result<Vec<String>, String> {
+    Ok(factory_preset_names())
+}
+
+// Crossfades to a built-in chain, replacing the current one
+#[tauri::command]
+pub async fn apply_factory_preset(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    registry: State<'_, EffectRegistry>,
+    name: String,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .apply_factory_preset(&name, &registry)
+        .map_err(|e| e.to_string())
+}
+
+// The chain as a versioned preset, for saving to disk
+#[tauri::command]
+pub async fn get_chain_preset(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<ChainPreset, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.chain_preset())
+}
+
+#[tauri::command]
+pub async fn get_effects_chain_spec(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Vec<EffectPreset>, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.chain_preset().effects)
+}
+
+// `normalized` is a 0-1 slider position; returns the parameter value it maps to
+#[tauri::command]
+pub async fn set_effect_parameter_normalized(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+    name: String,
+    normalized: f32,
+) -> Result<f32, String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_effect_parameter_normalized(effect_index, &name, normalized)
+        .map_err(|e| e.to_string())
+}
+
+// In-memory chain snapshots for live A/B comparison (not saved to disk)
+#[tauri::command]
+pub async fn snapshot_chain_to(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    slot: u8,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.snapshot_chain_to(slot);
+    Ok(())
+}
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
+#[tauri::command]
+pub async fn recall_chain_snapshot(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    registry: State<'_, EffectRegistry>,
+    slot: u8,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .recall_chain_snapshot(slot, &registry)
+        .map_err(|e| e.to_string())
+}
 
-    format!("stream_{}", timestamp)
+// Flips between snapshot slots A (0) and B (1); returns the slot now live
+#[tauri::command]
+pub async fn toggle_ab(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    registry: State<'_, EffectRegistry>,
+) -> Result<u8, String> {
+    let mut engine = audio_engine.lock().await;
+    engine.toggle_ab(&registry).map_err(|e| e.to_string())
+}
+
+// Auditions one effect in isolation: only the effect at `effect_index` runs
+// until clear_effect_solo
+#[tauri::command]
+pub async fn set_effect_solo(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    effect_index: usize,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .set_effect_solo(effect_index)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn clear_effect_solo(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.clear_effect_solo();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn clear_audio_effects(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.clear_effects();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_audio_levels(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<AudioLevels, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_current_levels())
+}
+
+#[tauri::command]
+pub async fn start_level_events(
+    app: AppHandle,
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    interval_ms: u64,
+) -> Result<(), String> {
+    let interval_ms = interval_ms.clamp(MIN_LEVEL_EVENT_INTERVAL_MS, MAX_LEVEL_EVENT_INTERVAL_MS);
+    let engine_state = audio_engine.inner().clone();
+
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+        loop {
+            ticker.tick().await;
+            let levels = engine_state.lock().await.get_current_levels();
+            if let Err(e) = app.emit_all("audio-levels", levels) {
+                log::warn!("Stopping level events: {}", e);
+                break;
+            }
+        }
+    });
+
+    let mut engine = audio_engine.lock().await;
+    engine.set_level_events_task(task);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_level_events(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.stop_level_events();
+    Ok(())
+}
+
+// Last `frames` FFT magnitude frames of the outgoing signal for a scrolling
+// spectrogram, oldest first; `first_frame` tells a poller how far it moved
+#[tauri::command]
+pub async fn get_spectrogram(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    frames: usize,
+) -> Result<Spectrogram, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_spectrogram(frames))
+}
+
+// Writes the selected stream's levels, latest spectrum frame, effects with
+// their parameters, and device/encoder setup to `path` as JSON, for attaching
+// to a support ticket
+#[tauri::command]
+pub async fn export_audio_snapshot(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    path: String,
+) -> Result<(), String> {
+    let engine = audio_engine.lock().await;
+    engine
+        .export_audio_snapshot(Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_meter_ballistics(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    attack_ms: f32,
+    release_ms: f32,
+    peak_hold_ms: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_meter_ballistics(MeterBallistics {
+        attack_ms,
+        release_ms,
+        peak_hold_ms,
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_stream_latency(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<StreamLatency, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_stream_latency())
+}
+
+#[tauri::command]
+pub async fn get_stream_stats(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<StreamStats, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.get_stream_stats())
+}
+
+#[tauri::command]
+pub async fn set_sink_format(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    format: SinkFormat,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_sink_format(format);
+    Ok(())
+}
+
+// Development only: randomly drops (and optionally reorders) outgoing packets
+// to exercise receiver resilience. Pass 0 to turn it off.
+#[tauri::command]
+pub async fn set_packet_loss_simulation(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    percent: f32,
+    reorder_percent: Option<f32>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_packet_loss_simulation(PacketLossConfig {
+        loss_percent: percent,
+        reorder_percent: reorder_percent.unwrap_or(0.0),
+    });
+    Ok(())
+}
+
+// The engine is unlocked while the server answers, so a slow or unreachable
+// server doesn't hold up every other command
+#[tauri::command]
+pub async fn connect_icecast(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    url: String,
+    mount: String,
+    user: String,
+    password: String,
+    reconnect: Option<ReconnectPolicy>,
+) -> Result<(), String> {
+    let pending = audio_engine
+        .lock()
+        .await
+        .prepare_icecast(IcecastConfig {
+            url,
+            mount,
+            user,
+            password,
+            reconnect: reconnect.unwrap_or_default(),
+        })
+        .map_err(|e| e.to_string())?;
+    let stream = pending.stream().clone();
+    let sink = pending.connect().await.map_err(|e| e.to_string())?;
+
+    let mut engine = audio_engine.lock().await;
+    engine
+        .attach_icecast(&stream, sink)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn disconnect_icecast(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.disconnect_icecast();
+    Ok(())
+}
+
+// Encodes the selected stream again at `bitrate` for a second Icecast mount;
+// returns the output id. Connects with the engine unlocked, like connect_icecast.
+#[tauri::command]
+pub async fn add_encoder_output(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    bitrate: u32,
+    sink: IcecastConfig,
+) -> Result<u32, String> {
+    let (pending, output) = audio_engine
+        .lock()
+        .await
+        .prepare_encoder_output(bitrate, sink)
+        .map_err(|e| e.to_string())?;
+    let stream = pending.stream().clone();
+    let sink = pending.connect().await.map_err(|e| e.to_string())?;
+
+    let mut engine = audio_engine.lock().await;
+    engine
+        .attach_encoder_output(&stream, output, sink)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn remove_encoder_output(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    output_id: u32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .remove_encoder_output(output_id)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_encoder_outputs(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<Vec<EncoderOutputSummary>, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.encoder_outputs())
+}
+
+// Records the selected stream (post effects, as broadcast) to a FLAC file
+#[tauri::command]
+pub async fn start_flac_recording(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    path: String,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .start_flac_recording(Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+// FLAC recording scaled on stop so the file's integrated loudness hits
+// `target_lufs` (e.g. -16); stop with stop_flac_recording
+#[tauri::command]
+pub async fn start_recording_normalized(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    path: String,
+    target_lufs: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .start_recording_normalized(Path::new(&path), target_lufs)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn stop_flac_recording(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<RecordingSummary, String> {
+    let mut engine = audio_engine.lock().await;
+    engine.stop_recording().map_err(|e| e.to_string())
+}
+
+// Records the selected stream to a WAV file at `bit_depth` (8-24), e.g. 16 for
+// CD-style archives; `dither` defaults to none
+#[tauri::command]
+pub async fn start_wav_recording(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    path: String,
+    bit_depth: u16,
+    dither: Option<DitherMode>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .start_wav_recording(Path::new(&path), bit_depth, dither.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn stop_wav_recording(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<RecordingSummary, String> {
+    let mut engine = audio_engine.lock().await;
+    engine.stop_recording().map_err(|e| e.to_string())
+}
+
+// The selected stream's next `duration_ms` (up to 10 s) of processed audio,
+// interleaved, for a quick waveform preview. The engine isn't held while waiting.
+#[tauri::command]
+pub async fn capture_sample(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    duration_ms: u32,
+) -> Result<CapturedSample, String> {
+    let pending = {
+        let engine = audio_engine.lock().await;
+        engine
+            .capture_sample(duration_ms)
+            .map_err(|e| e.to_string())?
+    };
+
+    // Pausing or stopping the stream would otherwise leave the capture hanging
+    let wait =
+        Duration::from_millis(duration_ms.min(MAX_SAMPLE_CAPTURE_MS) as u64) + SAMPLE_CAPTURE_GRACE;
+    match tokio::time::timeout(wait, pending).await {
+        Ok(Ok(sample)) => Ok(sample),
+        Ok(Err(_)) => Err("Sample capture was cancelled".to_string()),
+        Err(_) => Err("Sample capture timed out; the stream stopped delivering audio".to_string()),
+    }
+}
+
+// The last finished capture_sample as `points` min/max pairs per channel, so
+// a waveform can be drawn with every peak without sending the raw samples
+#[tauri::command]
+pub async fn get_waveform_preview(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    points: usize,
+) -> Result<WaveformPreview, String> {
+    let engine = audio_engine.lock().await;
+    engine.waveform_preview(points).map_err(|e| e.to_string())
+}
+
+// Starts a WebRTC call carrying the selected stream and returns the SDP offer.
+// Local ICE candidates follow as `ice-candidate` events. `ice_servers` are
+// STUN/TURN URLs; a public STUN server is used if none are given.
+#[tauri::command]
+pub async fn create_offer(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    ice_servers: Option<Vec<String>>,
+) -> Result<String, String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .create_webrtc_offer(ice_servers.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn accept_answer(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    sdp: String,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .accept_webrtc_answer(sdp)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn add_ice_candidate(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    candidate: IceCandidate,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine
+        .add_webrtc_ice_candidate(candidate)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn close_peer_connection(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.close_webrtc().await;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_monitoring(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_monitoring(enabled).map_err(|e| e.to_string())
+}
+
+// 0-1 level of the unprocessed input in the monitor, delayed to line up with
+// the chain output; 0 (the default) turns the dry bus off
+#[tauri::command]
+pub async fn set_dry_monitor(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    level: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_dry_monitor(level);
+    Ok(())
+}
+
+// 0-1 levels of the performer's own input and of the WebRTC peer's return
+// in what the output device plays; both default to 1
+#[tauri::command]
+pub async fn set_monitor_mix(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    input_level: f32,
+    return_level: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_monitor_mix(input_level, return_level);
+    Ok(())
+}
+
+// Latency of the software monitor path and the delay that lines it up with a
+// hardware monitor path of `hardware_path_ms` (0, a direct monitor, if omitted)
+#[tauri::command]
+pub async fn get_monitoring_alignment(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    hardware_path_ms: Option<f32>,
+) -> Result<MonitoringAlignment, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.monitoring_alignment(hardware_path_ms.unwrap_or(0.0)))
+}
+
+// Extra delay on the monitor feed, 0-500 ms
+#[tauri::command]
+pub async fn set_monitor_delay(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    ms: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_monitor_delay(ms);
+    Ok(())
+}
+
+// Hearing protection: limits what the monitor outputs play to `ceiling_db`
+// (-30 to 0 dBFS) without touching the stream; returns the applied settings
+#[tauri::command]
+pub async fn set_monitor_limiter(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+    ceiling_db: f32,
+) -> Result<MonitorLimiterConfig, String> {
+    let mut engine = audio_engine.lock().await;
+    Ok(engine.set_monitor_limiter(enabled, ceiling_db))
+}
+
+// Fires signal-detected / silence-detected when the smoothed input level
+// stays above / below `threshold_db` (-80 to 0 dBFS) for `hold_ms` (up to
+// 10 s); returns the applied settings
+#[tauri::command]
+pub async fn configure_silence_detection(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    threshold_db: f32,
+    hold_ms: f32,
+    enabled: bool,
+) -> Result<SilenceDetectionConfig, String> {
+    let mut engine = audio_engine.lock().await;
+    Ok(engine.configure_silence_detection(threshold_db, hold_ms, enabled))
+}
+
+#[tauri::command]
+pub async fn get_silence_detection(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+) -> Result<SilenceDetectionConfig, String> {
+    let engine = audio_engine.lock().await;
+    Ok(engine.silence_detection())
+}
+
+#[tauri::command]
+pub async fn set_input_gain(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    gain_db: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_input_gain(gain_db).map_err(|e| e.to_string())
+}
+
+// Final level after the effects chain, -24 to +24 dB
+#[tauri::command]
+pub async fn set_output_gain(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    gain_db: f32,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_output_gain(gain_db).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_dc_block(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_dc_block(enabled);
+    Ok(())
+}
+
+// Passes the input straight to metering and encoding, skipping every effect
+#[tauri::command]
+pub async fn set_chain_bypass(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    bypassed: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_chain_bypass(bypassed);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_muted(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    muted: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_muted(muted);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_ptt_enabled(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_ptt_enabled(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_ptt_active(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    active: bool,
+) -> Result<(), String> {
+    let mut engine = audio_engine.lock().await;
+    engine.set_ptt_active(active);
+    Ok(())
+}
+
+// Re-emits engine events to the frontend until the engine goes away
+pub async fn forward_audio_events(app: AppHandle, mut events: broadcast::Receiver<AudioEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if let Err(e) = app.emit_all(event.event_name(), event.clone()) {
+                    log::warn!("Failed to emit {}: {}", event.event_name(), e);
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                log::warn!("Dropped {} audio events", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
 }
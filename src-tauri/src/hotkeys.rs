@@ -0,0 +1,312 @@
+use crate::audio::AudioEngine;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, GlobalShortcutManager, Manager, State};
+use tokio::sync::Mutex;
+
+// What a global hotkey does. Tauri only reports key presses, not releases, so
+// both toggle: a PTT hotkey opens the mic on one press and closes it on the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    Mute,
+    Ptt,
+}
+
+// Payload of the `hotkey-triggered` event, so the UI can follow state changed
+// while another app had focus
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyTriggered {
+    pub action: HotkeyAction,
+    // Muted, or PTT open, after the press
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyBinding {
+    pub action: HotkeyAction,
+    pub accelerator: String,
+}
+
+// Where hotkeys are registered with the OS: Tauri's global shortcut manager
+// in the app, a stand-in in the tests
+pub trait ShortcutRegistry {
+    fn is_registered(&self, accelerator: &str) -> Result<bool, String>;
+    // Pressing `accelerator` runs `action` from then on
+    fn register(&mut self, accelerator: &str, action: HotkeyAction) -> Result<(), String>;
+    fn unregister(&mut self, accelerator: &str) -> Result<(), String>;
+}
+
+pub struct AppShortcuts<'a>(pub &'a AppHandle);
+
+impl ShortcutRegistry for AppShortcuts<'_> {
+    fn is_registered(&self, accelerator: &str) -> Result<bool, String> {
+        self.0
+            .global_shortcut_manager()
+            .is_registered(accelerator)
+            .map_err(|e| e.to_string())
+    }
+
+    fn register(&mut self, accelerator: &str, action: HotkeyAction) -> Result<(), String> {
+        let handle = self.0.clone();
+        self.0
+            .global_shortcut_manager()
+            .register(accelerator, move || {
+                let app = handle.clone();
+                tauri::async_runtime::spawn(async move { trigger(&app, action).await });
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    fn unregister(&mut self, accelerator: &str) -> Result<(), String> {
+        self.0
+            .global_shortcut_manager()
+            .unregister(accelerator)
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Managed as Tauri state. The shortcuts are OS-wide, so they fire whichever
+// app has focus, and are released on exit.
+#[derive(Default)]
+pub struct HotkeyControl {
+    bindings: HashMap<HotkeyAction, String>,
+}
+
+impl HotkeyControl {
+    // Binds `accelerator` (e.g. "CmdOrCtrl+Shift+M") to `action`, replacing
+    // its previous hotkey. The new one is registered before the old one is
+    // released, so a failure leaves the old binding working.
+    fn bind(
+        &mut self,
+        registry: &mut impl ShortcutRegistry,
+        action: HotkeyAction,
+        accelerator: String,
+    ) -> Result<(), String> {
+        if self.bindings.get(&action) == Some(&accelerator) {
+            return Ok(());
+        }
+        if let Some((other, _)) = self
+            .bindings
+            .iter()
+            .find(|(_, bound)| **bound == accelerator)
+        {
+            return Err(format!("{} is already the {:?} hotkey", accelerator, other));
+        }
+
+        // Registered elsewhere in this app, e.g. by the frontend's globalShortcut
+        // API. One held by another app makes register() fail on most platforms.
+        if registry.is_registered(&accelerator)? {
+            return Err(format!("{} is already in use", accelerator));
+        }
+        registry
+            .register(&accelerator, action)
+            .map_err(|e| format!("Cannot register {}: {}", accelerator, e))?;
+
+        if let Some(previous) = self.bindings.insert(action, accelerator) {
+            if let Err(e) = registry.unregister(&previous) {
+                log::warn!("Cannot release hotkey {}: {}", previous, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn unbind(&mut self, registry: &mut impl ShortcutRegistry, action: HotkeyAction) {
+        if let Some(accelerator) = self.bindings.remove(&action) {
+            if let Err(e) = registry.unregister(&accelerator) {
+                log::warn!("Cannot release hotkey {}: {}", accelerator, e);
+            }
+        }
+    }
+
+    pub fn release_all(&mut self, registry: &mut impl ShortcutRegistry) {
+        let actions: Vec<HotkeyAction> = self.bindings.keys().copied().collect();
+        for action in actions {
+            self.unbind(registry, action);
+        }
+    }
+
+    pub fn bindings(&self) -> Vec<HotkeyBinding> {
+        let mut bindings: Vec<HotkeyBinding> = self
+            .bindings
+            .iter()
+            .map(|(&action, accelerator)| HotkeyBinding {
+                action,
+                accelerator: accelerator.clone(),
+            })
+            .collect();
+        bindings.sort_by_key(|b| b.action);
+        bindings
+    }
+}
+
+async fn trigger(app: &AppHandle, action: HotkeyAction) {
+    let engine = app.state::<Arc<Mutex<AudioEngine>>>().inner().clone();
+    let mut engine = engine.lock().await;
+    let active = match action {
+        HotkeyAction::Mute => {
+            let muted = !engine.is_muted();
+            engine.set_muted(muted);
+            muted
+        }
+        HotkeyAction::Ptt => {
+            let open = !engine.is_ptt_active();
+            engine.set_ptt_active(open);
+            open
+        }
+    };
+    let _ = app.emit_all("hotkey-triggered", HotkeyTriggered { action, active });
+}
+
+// Toggles mute from anywhere on the system
+#[tauri::command]
+pub async fn register_mute_hotkey(
+    app: AppHandle,
+    hotkeys: State<'_, Arc<Mutex<HotkeyControl>>>,
+    accelerator: String,
+) -> Result<(), String> {
+    hotkeys
+        .lock()
+        .await
+        .bind(&mut AppShortcuts(&app), HotkeyAction::Mute, accelerator)
+}
+
+// Toggles push-to-talk open and closed from anywhere on the system; PTT itself
+// still has to be enabled with set_ptt_enabled
+#[tauri::command]
+pub async fn register_ptt_hotkey(
+    app: AppHandle,
+    hotkeys: State<'_, Arc<Mutex<HotkeyControl>>>,
+    accelerator: String,
+) -> Result<(), String> {
+    hotkeys
+        .lock()
+        .await
+        .bind(&mut AppShortcuts(&app), HotkeyAction::Ptt, accelerator)
+}
+
+// Releases the hotkey for one action, or every hotkey if `action` is omitted
+#[tauri::command]
+pub async fn clear_hotkey(
+    app: AppHandle,
+    hotkeys: State<'_, Arc<Mutex<HotkeyControl>>>,
+    action: Option<HotkeyAction>,
+) -> Result<(), String> {
+    let mut hotkeys = hotkeys.lock().await;
+    let mut registry = AppShortcuts(&app);
+    match action {
+        Some(action) => hotkeys.unbind(&mut registry, action),
+        None => hotkeys.release_all(&mut registry),
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_hotkeys(
+    hotkeys: State<'_, Arc<Mutex<HotkeyControl>>>,
+) -> Result<Vec<HotkeyBinding>, String> {
+    Ok(hotkeys.lock().await.bindings())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The OS side: what is registered, plus accelerators another app holds
+    #[derive(Default)]
+    struct FakeShortcuts {
+        registered: HashMap<String, HotkeyAction>,
+        held_elsewhere: Vec<String>,
+    }
+
+    impl ShortcutRegistry for FakeShortcuts {
+        fn is_registered(&self, accelerator: &str) -> Result<bool, String> {
+            Ok(self.registered.contains_key(accelerator))
+        }
+
+        fn register(&mut self, accelerator: &str, action: HotkeyAction) -> Result<(), String> {
+            if self.held_elsewhere.iter().any(|held| held == accelerator) {
+                return Err("held by another application".to_string());
+            }
+            self.registered.insert(accelerator.to_string(), action);
+            Ok(())
+        }
+
+        fn unregister(&mut self, accelerator: &str) -> Result<(), String> {
+            self.registered
+                .remove(accelerator)
+                .map(|_| ())
+                .ok_or_else(|| "not registered".to_string())
+        }
+    }
+
+    fn bound(hotkeys: &HotkeyControl) -> Vec<(HotkeyAction, &str)> {
+        hotkeys
+            .bindings
+            .iter()
+            .map(|(&action, accelerator)| (action, accelerator.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn rebinding_releases_the_old_hotkey_and_release_all_clears_them() {
+        let mut os = FakeShortcuts::default();
+        let mut hotkeys = HotkeyControl::default();
+        hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+M".to_string())
+            .unwrap();
+        hotkeys
+            .bind(&mut os, HotkeyAction::Ptt, "Ctrl+T".to_string())
+            .unwrap();
+        assert_eq!(os.registered.len(), 2);
+
+        hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+Shift+M".to_string())
+            .unwrap();
+        assert!(!os.registered.contains_key("Ctrl+M"));
+        assert_eq!(os.registered.get("Ctrl+Shift+M"), Some(&HotkeyAction::Mute));
+        // Binding the same hotkey again is a no-op
+        hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+Shift+M".to_string())
+            .unwrap();
+
+        hotkeys.unbind(&mut os, HotkeyAction::Ptt);
+        assert_eq!(bound(&hotkeys), [(HotkeyAction::Mute, "Ctrl+Shift+M")]);
+        assert!(!os.registered.contains_key("Ctrl+T"));
+
+        hotkeys.release_all(&mut os);
+        assert!(hotkeys.bindings().is_empty());
+        assert!(os.registered.is_empty());
+    }
+
+    #[test]
+    fn conflicting_hotkeys_leave_the_old_binding_working() {
+        let mut os = FakeShortcuts {
+            held_elsewhere: vec!["Ctrl+X".to_string()],
+            ..FakeShortcuts::default()
+        };
+        let mut hotkeys = HotkeyControl::default();
+        hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+M".to_string())
+            .unwrap();
+
+        // Already the other action's hotkey
+        assert!(hotkeys
+            .bind(&mut os, HotkeyAction::Ptt, "Ctrl+M".to_string())
+            .is_err());
+        // Held by another app
+        assert!(hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+X".to_string())
+            .is_err());
+        // Registered outside HotkeyControl, e.g. by the frontend
+        os.registered
+            .insert("Ctrl+F".to_string(), HotkeyAction::Ptt);
+        assert!(hotkeys
+            .bind(&mut os, HotkeyAction::Mute, "Ctrl+F".to_string())
+            .is_err());
+
+        assert_eq!(bound(&hotkeys), [(HotkeyAction::Mute, "Ctrl+M")]);
+        assert_eq!(os.registered.get("Ctrl+M"), Some(&HotkeyAction::Mute));
+    }
+}
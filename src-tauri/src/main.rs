@@ -3,20 +3,34 @@
 
 mod audio;
 mod commands;
+mod hotkeys;
+mod midi;
+mod osc;
 
-use audio::{AudioConfig, AudioEngine};
+use audio::{AudioConfig, AudioEngine, EffectRegistry};
 use commands::*;
+use hotkeys::{AppShortcuts, HotkeyControl};
+use midi::MidiControl;
+use osc::OscControl;
 use std::sync::Arc;
+use std::time::Duration;
+use tauri::Manager;
 use tokio::sync::Mutex;
 
+// Upper bound on waiting for a busy engine when the app closes
+const SHUTDOWN_LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn main() {
     // Initialize logger
     env_logger::init();
 
     // Create audio engine with default config
     let audio_config = AudioConfig::default();
-    let audio_engine = match AudioEngine::new(audio_config) {
-        Ok(engine) => Arc::new(Mutex::new(engine)),
+    let (audio_engine, audio_events) = match AudioEngine::new(audio_config) {
+        Ok(engine) => {
+            let events = engine.subscribe_to_events();
+            (Arc::new(Mutex::new(engine)), events)
+        }
         Err(e) => {
             log::error!("Failed to initialize audio engine: {}", e);
             // Create a placeholder - in production, handle this more gracefully
@@ -26,15 +40,135 @@ fn main() {
 
     tauri::Builder::default()
         .manage(audio_engine)
+        .manage(EffectRegistry::with_builtin_effects())
+        .manage(Arc::new(Mutex::new(MidiControl::default())))
+        .manage(Arc::new(Mutex::new(OscControl::default())))
+        .manage(Arc::new(Mutex::new(HotkeyControl::default())))
+        .setup(move |app| {
+            tauri::async_runtime::spawn(forward_audio_events(app.handle(), audio_events));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             start_streaming,
+            validate_stream_setup,
+            measure_codec_quality,
+            run_audio_selftest,
+            set_adaptive_bitrate,
+            get_adaptive_bitrate,
+            set_auto_signal,
+            get_auto_signal,
             stop_streaming,
+            pause_streaming,
+            resume_streaming,
+            list_streams,
+            select_stream,
+            set_source,
+            set_music_bus,
+            set_music_level,
+            set_music_ducking,
+            start_test_tone,
+            play_test_tone_output,
+            start_file_source,
+            set_file_resample_quality,
+            set_opus_complexity,
+            set_capture_channels,
             get_audio_devices,
+            get_device_capabilities,
+            set_output_device,
+            get_available_hosts,
             apply_audio_effect,
+            describe_effect,
+            list_available_effects,
+            snapshot_chain_to,
+            recall_chain_snapshot,
+            toggle_ab,
+            set_effects_chain,
+            set_effect_profiling,
+            set_overrun_protection,
+            get_effect_cpu_usage,
+            get_effects_chain_spec,
+            get_chain_preset,
+            list_factory_presets,
+            apply_factory_preset,
             clear_audio_effects,
+            set_effect_solo,
+            clear_effect_solo,
+            reset_effect,
+            set_effect_bypass,
+            set_effect_mix,
+            set_effect_parameter,
+            apply_parameter_batch,
+            set_effect_parameter_normalized,
+            set_parameter_smoothing,
+            midi::list_midi_inputs,
+            midi::connect_midi_input,
+            midi::disconnect_midi_input,
+            midi::learn_midi_mapping,
+            midi::clear_midi_mapping,
+            midi::get_midi_mappings,
+            osc::start_osc_server,
+            osc::stop_osc_server,
+            hotkeys::register_mute_hotkey,
+            hotkeys::register_ptt_hotkey,
+            hotkeys::clear_hotkey,
+            hotkeys::get_hotkeys,
             get_audio_levels,
+            set_meter_ballistics,
+            get_spectrogram,
+            export_audio_snapshot,
+            get_eq_band_levels,
+            start_level_events,
+            stop_level_events,
             set_monitoring,
+            set_dry_monitor,
+            set_monitor_mix,
+            get_monitoring_alignment,
+            set_monitor_delay,
+            set_monitor_limiter,
+            configure_silence_detection,
+            get_silence_detection,
+            get_stream_latency,
+            get_stream_stats,
+            set_sink_format,
+            set_packet_loss_simulation,
+            connect_icecast,
+            disconnect_icecast,
+            add_encoder_output,
+            remove_encoder_output,
+            get_encoder_outputs,
+            start_flac_recording,
+            start_recording_normalized,
+            stop_flac_recording,
+            start_wav_recording,
+            stop_wav_recording,
+            capture_sample,
+            get_waveform_preview,
+            create_offer,
+            accept_answer,
+            add_ice_candidate,
+            close_peer_connection,
+            set_input_gain,
+            set_output_gain,
+            set_dc_block,
+            set_chain_bypass,
+            set_muted,
+            set_ptt_enabled,
+            set_ptt_active,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::ExitRequested { .. } = event {
+                let engine = app.state::<Arc<Mutex<AudioEngine>>>().inner().clone();
+                let hotkeys = app.state::<Arc<Mutex<HotkeyControl>>>().inner().clone();
+                tauri::async_runtime::block_on(async move {
+                    // Global hotkeys would otherwise stay taken until the process is gone
+                    hotkeys.lock().await.release_all(&mut AppShortcuts(app));
+                    match tokio::time::timeout(SHUTDOWN_LOCK_TIMEOUT, engine.lock()).await {
+                        Ok(mut engine) => engine.shutdown().await,
+                        Err(_) => log::warn!("Audio engine busy at exit, skipping shutdown"),
+                    }
+                });
+            }
+        });
 }
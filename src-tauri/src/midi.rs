@@ -0,0 +1,240 @@
+use crate::audio::AudioEngine;
+use midir::{MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::{mpsc, Mutex};
+
+const MIDI_CLIENT_NAME: &str = "VoiceCast";
+
+// Status nibble of a Control Change message; the low nibble is the channel
+const CONTROL_CHANGE: u8 = 0xB0;
+const CC_MAX: f32 = 127.0;
+
+// The effect parameter a controller knob drives
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MidiTarget {
+    pub effect_index: usize,
+    pub parameter: String,
+}
+
+// Payload of the `midi-mapping-learned` event. `channel` is 0-15 as sent on
+// the wire (hardware usually labels it 1-16).
+#[derive(Debug, Clone, Serialize)]
+pub struct MidiMapping {
+    pub channel: u8,
+    pub cc: u8,
+    pub effect_index: usize,
+    pub parameter: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ControlChange {
+    channel: u8,
+    cc: u8,
+    value: u8,
+}
+
+// Managed as Tauri state. Messages arrive on midir's thread and are handed to
+// an async task, which applies them through the engine like any other command.
+#[derive(Default)]
+pub struct MidiControl {
+    connection: Option<MidiInputConnection<()>>,
+    mappings: HashMap<(u8, u8), MidiTarget>,
+    // Set by learn_midi_mapping; the next CC received is bound to it
+    learning: Option<MidiTarget>,
+}
+
+impl MidiControl {
+    pub fn mappings(&self) -> Vec<MidiMapping> {
+        let mut mappings: Vec<MidiMapping> = self
+            .mappings
+            .iter()
+            .map(|(&(channel, cc), target)| MidiMapping {
+                channel,
+                cc,
+                effect_index: target.effect_index,
+                parameter: target.parameter.clone(),
+            })
+            .collect();
+        mappings.sort_by_key(|m| (m.channel, m.cc));
+        mappings
+    }
+}
+
+fn parse_control_change(message: &[u8]) -> Option<ControlChange> {
+    match message {
+        [status, cc, value, ..] if status & 0xF0 == CONTROL_CHANGE => Some(ControlChange {
+            channel: status & 0x0F,
+            cc: cc & 0x7F,
+            value: value & 0x7F,
+        }),
+        _ => None,
+    }
+}
+
+async fn handle_control_change(app: &AppHandle, change: ControlChange) {
+    let midi = app.state::<Arc<Mutex<MidiControl>>>().inner().clone();
+    let target = {
+        let mut midi = midi.lock().await;
+        if let Some(target) = midi.learning.take() {
+            let mapping = MidiMapping {
+                channel: change.channel,
+                cc: change.cc,
+                effect_index: target.effect_index,
+                parameter: target.parameter.clone(),
+            };
+            midi.mappings.insert((change.channel, change.cc), target);
+            let _ = app.emit_all("midi-mapping-learned", mapping);
+            return;
+        }
+        match midi.mappings.get(&(change.channel, change.cc)) {
+            Some(target) => target.clone(),
+            None => return,
+        }
+    };
+
+    let engine = app.state::<Arc<Mutex<AudioEngine>>>().inner().clone();
+    let mut engine = engine.lock().await;
+    let parameter = engine
+        .effect_parameters(target.effect_index)
+        .ok()
+        .and_then(|params| params.into_iter().find(|p| p.name == target.parameter));
+
+    // The chain may have changed since the mapping was made
+    let Some(parameter) = parameter else {
+        log::warn!(
+            "MIDI CC {} on channel {} is mapped to a missing parameter {}/{}",
+            change.cc,
+            change.channel,
+            target.effect_index,
+            target.parameter
+        );
+        return;
+    };
+
+    // 0-127 across the parameter's range, snapped to its step
+    let value = parameter.from_normalized(change.value as f32 / CC_MAX);
+    if let Err(e) = engine.set_effect_parameter(target.effect_index, &target.parameter, value) {
+        log::warn!("MIDI parameter change failed: {}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn list_midi_inputs() -> Result<Vec<String>, String> {
+    let input = MidiInput::new(MIDI_CLIENT_NAME).map_err(|e| e.to_string())?;
+    Ok(input
+        .ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect())
+}
+
+// Opens the named input, or the first one available; returns the port name
+#[tauri::command]
+pub async fn connect_midi_input(
+    app: AppHandle,
+    midi: State<'_, Arc<Mutex<MidiControl>>>,
+    port_name: Option<String>,
+) -> Result<String, String> {
+    let input = MidiInput::new(MIDI_CLIENT_NAME).map_err(|e| e.to_string())?;
+    let ports = input.ports();
+    let port = match &port_name {
+        Some(name) => ports
+            .iter()
+            .find(|port| input.port_name(port).ok().as_deref() == Some(name.as_str())),
+        None => ports.first(),
+    }
+    .ok_or_else(|| "No MIDI input found".to_string())?;
+    let name = input.port_name(port).map_err(|e| e.to_string())?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let connection = input
+        .connect(
+            port,
+            "voicecast-control",
+            move |_timestamp, message, _| {
+                if let Some(change) = parse_control_change(message) {
+                    let _ = tx.send(change);
+                }
+            },
+            (),
+        )
+        .map_err(|e| e.to_string())?;
+
+    // Ends on its own once the connection (and with it the sender) is dropped
+    tauri::async_runtime::spawn(async move {
+        while let Some(change) = rx.recv().await {
+            handle_control_change(&app, change).await;
+        }
+    });
+
+    midi.lock().await.connection = Some(connection);
+    log::info!("Listening for MIDI on {}", name);
+    Ok(name)
+}
+
+#[tauri::command]
+pub async fn disconnect_midi_input(midi: State<'_, Arc<Mutex<MidiControl>>>) -> Result<(), String> {
+    if let Some(connection) = midi.lock().await.connection.take() {
+        connection.close();
+    }
+    Ok(())
+}
+
+// Binds the next CC received to `param` of the effect at `effect_index`
+#[tauri::command]
+pub async fn learn_midi_mapping(
+    audio_engine: State<'_, Arc<Mutex<AudioEngine>>>,
+    midi: State<'_, Arc<Mutex<MidiControl>>>,
+    effect_index: usize,
+    param: String,
+) -> Result<(), String> {
+    let parameters = audio_engine
+        .lock()
+        .await
+        .effect_parameters(effect_index)
+        .map_err(|e| e.to_string())?;
+    if !parameters.iter().any(|p| p.name == param) {
+        return Err(format!(
+            "Effect {} has no parameter {}",
+            effect_index, param
+        ));
+    }
+
+    midi.lock().await.learning = Some(MidiTarget {
+        effect_index,
+        parameter: param,
+    });
+    Ok(())
+}
+
+// Removes the mapping for one channel/CC, every mapping on a channel (or of a
+// CC) when only that is given, or every mapping when neither is. Also cancels
+// a pending learn.
+#[tauri::command]
+pub async fn clear_midi_mapping(
+    midi: State<'_, Arc<Mutex<MidiControl>>>,
+    channel: Option<u8>,
+    cc: Option<u8>,
+) -> Result<(), String> {
+    let mut midi = midi.lock().await;
+    midi.learning = None;
+    match (channel, cc) {
+        (Some(channel), Some(cc)) => {
+            midi.mappings.remove(&(channel, cc));
+        }
+        (Some(channel), None) => midi.mappings.retain(|(c, _), _| *c != channel),
+        (None, Some(cc)) => midi.mappings.retain(|(_, n), _| *n != cc),
+        (None, None) => midi.mappings.clear(),
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_midi_mappings(
+    midi: State<'_, Arc<Mutex<MidiControl>>>,
+) -> Result<Vec<MidiMapping>, String> {
+    Ok(midi.lock().await.mappings())
+}
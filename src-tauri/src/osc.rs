@@ -0,0 +1,190 @@
+use crate::audio::AudioEngine;
+use rosc::{OscMessage, OscPacket, OscType};
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+
+const OSC_PREFIX: &str = "/voicecast";
+
+// Largest datagram we accept; OSC control messages are tiny
+const OSC_BUFFER_SIZE: usize = 4096;
+
+// Only this machine by default; pass "0.0.0.0" to take commands from a
+// tablet or show-control rig on the network
+const DEFAULT_OSC_HOST: &str = "127.0.0.1";
+
+// Managed as Tauri state; holds the listener task while the server runs
+#[derive(Default)]
+pub struct OscControl {
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl OscControl {
+    fn stop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+// What an address asks the engine to do, once its argument has been checked
+#[derive(Debug, Clone, PartialEq)]
+enum OscAction {
+    StartStream,
+    StopStream,
+    PauseStream,
+    ResumeStream,
+    Mute(bool),
+    InputGain(f32),
+    PttActive(bool),
+    EffectParameter {
+        index: usize,
+        name: String,
+        value: f32,
+    },
+}
+
+fn arg_f32(args: &[OscType]) -> Option<f32> {
+    match args.first()? {
+        OscType::Float(v) => Some(*v),
+        OscType::Double(v) => Some(*v as f32),
+        OscType::Int(v) => Some(*v as f32),
+        OscType::Long(v) => Some(*v as f32),
+        _ => None,
+    }
+    .filter(|v| v.is_finite())
+}
+
+// Accepts OSC booleans and the 0/1 numbers most controllers send instead
+fn arg_bool(args: &[OscType]) -> Option<bool> {
+    match args.first()? {
+        OscType::Bool(v) => Some(*v),
+        _ => arg_f32(args).map(|v| v >= 0.5),
+    }
+}
+
+fn parse_message(message: &OscMessage) -> Option<OscAction> {
+    let path = message.addr.strip_prefix(OSC_PREFIX)?;
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let args = &message.args;
+
+    match segments.as_slice() {
+        ["stream", "start"] => Some(OscAction::StartStream),
+        ["stream", "stop"] => Some(OscAction::StopStream),
+        ["stream", "pause"] => Some(OscAction::PauseStream),
+        ["stream", "resume"] => Some(OscAction::ResumeStream),
+        ["mute"] => arg_bool(args).map(OscAction::Mute),
+        ["gain"] => arg_f32(args).map(OscAction::InputGain),
+        ["ptt"] => arg_bool(args).map(OscAction::PttActive),
+        ["effect", index, name] => Some(OscAction::EffectParameter {
+            index: index.parse().ok()?,
+            name: name.to_string(),
+            value: arg_f32(args)?,
+        }),
+        _ => None,
+    }
+}
+
+async fn apply_action(engine: &mut AudioEngine, action: OscAction) -> Result<(), String> {
+    match action {
+        OscAction::StartStream => engine.start_capture().await.map_err(|e| e.to_string()),
+        OscAction::StopStream => engine.stop_capture().await.map_err(|e| e.to_string()),
+        OscAction::PauseStream => {
+            engine.set_paused(true);
+            Ok(())
+        }
+        OscAction::ResumeStream => {
+            engine.set_paused(false);
+            Ok(())
+        }
+        OscAction::Mute(muted) => {
+            engine.set_muted(muted);
+            Ok(())
+        }
+        // The engine clamps to the trim range and refuses NaN
+        OscAction::InputGain(gain_db) => engine.set_input_gain(gain_db).map_err(|e| e.to_string()),
+        OscAction::PttActive(active) => {
+            engine.set_ptt_active(active);
+            Ok(())
+        }
+        OscAction::EffectParameter { index, name, value } => {
+            let parameters = engine.effect_parameters(index).map_err(|e| e.to_string())?;
+            let parameter = parameters
+                .iter()
+                .find(|p| p.name == name)
+                .ok_or_else(|| format!("Effect {} has no parameter {}", index, name))?;
+            let value = parameter.constrain(value);
+            engine
+                .set_effect_parameter(index, &name, value)
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+async fn handle_packet(app: &AppHandle, packet: OscPacket) {
+    let mut messages = vec![packet];
+    while let Some(packet) = messages.pop() {
+        let message = match packet {
+            OscPacket::Message(message) => message,
+            // Bundle timetags are ignored; everything applies on arrival
+            OscPacket::Bundle(bundle) => {
+                messages.extend(bundle.content.into_iter().rev());
+                continue;
+            }
+        };
+
+        let Some(action) = parse_message(&message) else {
+            log::warn!("Ignoring OSC message {} {:?}", message.addr, message.args);
+            continue;
+        };
+
+        let engine = app.state::<Arc<Mutex<AudioEngine>>>().inner().clone();
+        let mut engine = engine.lock().await;
+        if let Err(e) = apply_action(&mut engine, action).await {
+            log::warn!("OSC {} failed: {}", message.addr, e);
+        }
+    }
+}
+
+// Listens for OSC on UDP `port`, replacing any server already running
+#[tauri::command]
+pub async fn start_osc_server(
+    app: AppHandle,
+    osc: State<'_, Arc<Mutex<OscControl>>>,
+    port: u16,
+    host: Option<String>,
+) -> Result<(), String> {
+    let mut osc = osc.lock().await;
+    osc.stop();
+
+    let host = host.unwrap_or_else(|| DEFAULT_OSC_HOST.to_string());
+    let socket = UdpSocket::bind((host.as_str(), port))
+        .await
+        .map_err(|e| e.to_string())?;
+    log::info!("OSC server listening on {}:{}", host, port);
+
+    osc.task = Some(tokio::spawn(async move {
+        let mut buffer = vec![0u8; OSC_BUFFER_SIZE];
+        loop {
+            let len = match socket.recv_from(&mut buffer).await {
+                Ok((len, _)) => len,
+                Err(e) => {
+                    log::warn!("OSC receive failed: {}", e);
+                    continue;
+                }
+            };
+            match rosc::decoder::decode_udp(&buffer[..len]) {
+                Ok((_, packet)) => handle_packet(&app, packet).await,
+                Err(e) => log::warn!("Malformed OSC packet: {:?}", e),
+            }
+        }
+    }));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_osc_server(osc: State<'_, Arc<Mutex<OscControl>>>) -> Result<(), String> {
+    osc.lock().await.stop();
+    Ok(())
+}